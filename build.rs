@@ -0,0 +1,55 @@
+/*
+ * build.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+// Generates the list of migrations embedded by src/migration.rs,
+// one entry for each directory in migrations/, in the order they are applied.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    let mut names = fs::read_dir("migrations")
+        .expect("Unable to read migrations directory")
+        .map(|entry| entry.expect("Unable to read directory entry"))
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| {
+            entry
+                .file_name()
+                .into_string()
+                .expect("Migration name isn't UTF-8")
+        })
+        .collect::<Vec<_>>();
+
+    names.sort();
+
+    let mut output = String::from("vec![\n");
+    for name in &names {
+        writeln!(output, "    embed!({:?}),", name).unwrap();
+    }
+    output.push_str("]\n");
+
+    let out_dir = env::var("OUT_DIR").expect("No OUT_DIR set");
+    let path = Path::new(&out_dir).join("migrations.rs");
+    fs::write(path, output).expect("Unable to write migration list");
+}
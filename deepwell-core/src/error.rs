@@ -20,6 +20,7 @@
 
 use crate::roles::Role;
 use crate::types::UserId;
+use diesel::r2d2::PoolError;
 use diesel::result::{ConnectionError, Error as DieselError};
 use std::fmt::{self, Display};
 use std::io;
@@ -47,33 +48,79 @@ pub enum Error {
     #[error("error connecting to database: {0}")]
     DatabaseConnection(#[from] ConnectionError),
 
+    #[error("error getting database connection from pool: {0}")]
+    DatabasePool(#[from] PoolError),
+
     #[error("error running subprocess: {0}")]
     Subprocess(#[from] PopenError),
 
     #[error("command failed: {0}")]
     CommandFailed(String),
 
+    #[error("{command}command failed ({}): {stderr}", display_exit_code(*.code))]
+    Revision {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+
     #[error("unable to communicate with service: {0}")]
     ServiceTransport(io::Error),
 
+    #[error("error running migrations: {0}")]
+    Migration(String),
+
+    #[error("database schema is newer than expected, has unknown migration {0}")]
+    DatabaseSchemaNewer(String),
+
+    #[error("invalid credential kind stored in database: {0}")]
+    InvalidCredentialKind(String),
+
     #[error("request was too large, {0} > {1}")]
     RequestTooLarge(usize, usize),
 
+    #[error("invalid argument: {0}")]
+    InvalidArgument(&'static str),
+
+    #[error("too many requests, try again later")]
+    RateLimited,
+
+    #[error("the commit message is too long")]
+    MessageTooLong,
+
     #[error("invalid username or password")]
     AuthenticationFailed,
 
     #[error("not logged in, expired session, or invalid token")]
     InvalidSession,
 
-    #[error("invalid password: {0}")]
-    NewPasswordInvalid(&'static str),
+    #[error("too many failed login attempts, try again later")]
+    AccountLocked,
+
+    #[error("the account's email has not been verified")]
+    AccountNotVerified(Option<String>),
+
+    #[error("password is too weak: {0}")]
+    PasswordTooWeak(WeakPasswordReason),
+
+    #[error("password was used too recently")]
+    PasswordReused,
 
     #[error("invalid verification token")]
     InvalidVerificationToken,
 
+    #[error("invalid, expired, or already used password reset token")]
+    InvalidResetToken,
+
     #[error("insufficient permissions, can only be done at {1} or higher, not {0}")]
     InsufficientPermissions(Role, Role),
 
+    #[error("cannot impersonate the given user from this session")]
+    ImpersonationNotAllowed,
+
+    #[error("a two-factor authentication code is required")]
+    TwoFactorRequired(Option<String>),
+
     #[error("the given wiki was not found")]
     WikiNotFound,
 
@@ -98,11 +145,32 @@ pub enum Error {
     #[error("a user with the given email already exists")]
     UserEmailExists,
 
+    #[error("invalid timezone: {0}")]
+    InvalidTimezone(String),
+
+    #[error("invalid locale: {0}")]
+    InvalidLocale(String),
+
+    #[error("invalid user name: {0}")]
+    InvalidUserName(String),
+
+    #[error("invalid email: {0}")]
+    InvalidEmail(String),
+
+    #[error("the user cannot be deleted, they are still referenced by: {0}")]
+    UserReferenced(String),
+
     #[error("the given revision was not found")]
     RevisionNotFound,
 
     #[error("the given revision does not correspond to the specified page")]
     RevisionPageMismatch,
+
+    #[error("invalid revision tag name: {0}")]
+    InvalidRevisionTag(String),
+
+    #[error("the given revision tag was not found")]
+    RevisionTagNotFound,
 }
 
 impl Error {
@@ -115,15 +183,29 @@ impl Error {
             Utf8(_) => "utf-8",
             Database(_) => "database",
             DatabaseConnection(_) => "database-connection",
+            DatabasePool(_) => "database-pool",
             Subprocess(_) => "subprocess",
             CommandFailed(_) => "command-failed",
+            Revision { .. } => "revision-command",
             ServiceTransport(_) => "service-transport",
+            Migration(_) => "migration",
+            DatabaseSchemaNewer(_) => "database-schema-newer",
+            InvalidCredentialKind(_) => "invalid-credential-kind",
             RequestTooLarge(_, _) => "request-too-large",
+            InvalidArgument(_) => "invalid-argument",
+            RateLimited => "rate-limited",
+            MessageTooLong => "message-too-long",
             AuthenticationFailed => "authentication-failed",
             InvalidSession => "invalid-session",
-            NewPasswordInvalid(_) => "invalid-password",
+            AccountLocked => "account-locked",
+            AccountNotVerified(_) => "account-not-verified",
+            PasswordTooWeak(_) => "password-too-weak",
+            PasswordReused => "password-reused",
             InvalidVerificationToken => "invalid-verification-token",
+            InvalidResetToken => "invalid-reset-token",
             InsufficientPermissions(_, _) => "insufficient-permissions",
+            ImpersonationNotAllowed => "impersonation-not-allowed",
+            TwoFactorRequired(_) => "two-factor-required",
             WikiNotFound => "wiki-not-found",
             PageNotFound => "page-not-found",
             PageExists => "page-exists",
@@ -132,27 +214,159 @@ impl Error {
             UserNotFound => "user-not-found",
             UserNameExists => "user-name-exists",
             UserEmailExists => "user-email-exists",
+            InvalidTimezone(_) => "invalid-timezone",
+            InvalidLocale(_) => "invalid-locale",
+            UserReferenced(_) => "user-referenced",
+            InvalidUserName(_) => "invalid-user-name",
+            InvalidEmail(_) => "invalid-email",
             RevisionNotFound => "revision-not-found",
             RevisionPageMismatch => "revision-page-mismatch",
+            InvalidRevisionTag(_) => "invalid-revision-tag",
+            RevisionTagNotFound => "revision-tag-not-found",
+        }
+    }
+
+    /// Gets the numeric error code for this variant.
+    ///
+    /// These are stable, so existing codes must not be changed or reused.
+    /// They are grouped by the hundreds according to the area of the error.
+    pub fn code(&self) -> u16 {
+        use self::Error::*;
+
+        match *self {
+            // Internal errors
+            StaticMsg(_) => 1,
+            Io(_) => 2,
+            Utf8(_) => 3,
+            Database(_) => 4,
+            DatabaseConnection(_) => 5,
+            DatabasePool(_) => 6,
+            Subprocess(_) => 7,
+            CommandFailed(_) => 8,
+            ServiceTransport(_) => 9,
+            Migration(_) => 10,
+            DatabaseSchemaNewer(_) => 11,
+            Revision { .. } => 12,
+            InvalidCredentialKind(_) => 13,
+
+            // Request errors
+            RequestTooLarge(_, _) => 100,
+            InvalidArgument(_) => 101,
+            RateLimited => 102,
+            MessageTooLong => 103,
+
+            // Authentication errors
+            AuthenticationFailed => 200,
+            InvalidSession => 201,
+            AccountLocked => 202,
+            AccountNotVerified(_) => 203,
+            PasswordTooWeak(_) => 204,
+            InvalidVerificationToken => 205,
+            InvalidResetToken => 206,
+            InsufficientPermissions(_, _) => 207,
+            ImpersonationNotAllowed => 208,
+            PasswordReused => 209,
+            TwoFactorRequired(_) => 210,
+
+            // Wiki and page errors
+            WikiNotFound => 300,
+            PageNotFound => 301,
+            PageExists => 302,
+            PageLocked(_) => 303,
+            PageLockNotFound => 304,
+
+            // User errors
+            UserNotFound => 400,
+            UserNameExists => 401,
+            UserEmailExists => 402,
+            InvalidTimezone(_) => 403,
+            InvalidLocale(_) => 404,
+            UserReferenced(_) => 405,
+            InvalidUserName(_) => 406,
+            InvalidEmail(_) => 407,
+
+            // Revision errors
+            RevisionNotFound => 500,
+            RevisionPageMismatch => 501,
+            InvalidRevisionTag(_) => 502,
+            RevisionTagNotFound => 503,
         }
     }
 
     #[inline]
     pub fn to_sendable(&self) -> SendableError {
         SendableError {
+            code: self.code(),
             name: self.fixed_name().into(),
             message: self.to_string(),
         }
     }
 }
 
+fn display_exit_code(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("exit status {}", code),
+        None => String::from("killed by signal"),
+    }
+}
+
+/// Which requirement a new password failed to meet.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WeakPasswordReason {
+    TooShort(usize),
+    TooLong(usize),
+    MissingLowercase,
+    MissingUppercase,
+    MissingDigit,
+    MissingSymbol,
+    TooCommon,
+}
+
+impl WeakPasswordReason {
+    pub fn fixed_name(self) -> &'static str {
+        use self::WeakPasswordReason::*;
+
+        match self {
+            TooShort(_) => "too-short",
+            TooLong(_) => "too-long",
+            MissingLowercase => "missing-lowercase",
+            MissingUppercase => "missing-uppercase",
+            MissingDigit => "missing-digit",
+            MissingSymbol => "missing-symbol",
+            TooCommon => "too-common",
+        }
+    }
+}
+
+impl Display for WeakPasswordReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::WeakPasswordReason::*;
+
+        match *self {
+            TooShort(length) => write!(f, "must be at least {} characters", length),
+            TooLong(length) => write!(f, "must be at most {} bytes", length),
+            MissingLowercase => write!(f, "must contain a lowercase letter"),
+            MissingUppercase => write!(f, "must contain an uppercase letter"),
+            MissingDigit => write!(f, "must contain a digit"),
+            MissingSymbol => write!(f, "must contain a symbol"),
+            TooCommon => write!(f, "is too commonly used"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct SendableError {
+    code: u16,
     name: String,
     message: String,
 }
 
 impl SendableError {
+    #[inline]
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
     #[inline]
     pub fn name(&self) -> &str {
         &self.name
@@ -173,8 +387,20 @@ impl Display for SendableError {
 impl Into<(String, String)> for SendableError {
     #[inline]
     fn into(self) -> (String, String) {
-        let Self { name, message } = self;
+        let Self { name, message, .. } = self;
 
         (name, message)
     }
 }
+
+#[test]
+fn test_sendable_error() {
+    let error = Error::PageNotFound.to_sendable();
+    assert_eq!(error.code(), 301);
+    assert_eq!(error.name(), "page-not-found");
+
+    let json = serde_json::to_string(&error).unwrap();
+    let error: SendableError = serde_json::from_str(&json).unwrap();
+    assert_eq!(error.code(), 301);
+    assert_eq!(error.message(), "the given page was not found");
+}
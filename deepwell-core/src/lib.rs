@@ -26,6 +26,7 @@ extern crate chrono;
 
 #[macro_use]
 extern crate diesel;
+extern crate ipnetwork;
 extern crate subprocess;
 
 #[macro_use]
@@ -39,6 +40,7 @@ extern crate regex;
 
 #[macro_use]
 extern crate serde;
+extern crate serde_json;
 
 #[macro_use]
 extern crate thiserror;
@@ -54,9 +56,10 @@ pub mod scoring;
 pub mod types;
 
 pub mod prelude {
-    pub use super::error::{Error, SendableError};
+    pub use super::error::{Error, SendableError, WeakPasswordReason};
     pub use super::models::*;
     pub use super::roles::Role;
     pub use super::scoring::*;
     pub use super::types::*;
+    pub use ipnetwork::IpNetwork;
 }
@@ -0,0 +1,64 @@
+/*
+ * models/audit_log.rs
+ *
+ * deepwell-core - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use serde_json::Value as JsonValue;
+
+#[derive(Serialize, Deserialize, Queryable, Debug)]
+pub struct AuditLogEntry {
+    id: AuditLogEntryId,
+    entry_type: String,
+    created_at: DateTime<Utc>,
+    wiki_id: Option<WikiId>,
+    user_id: Option<UserId>,
+    data: JsonValue,
+}
+
+impl AuditLogEntry {
+    #[inline]
+    pub fn audit_log_entry_id(&self) -> AuditLogEntryId {
+        self.id
+    }
+
+    #[inline]
+    pub fn entry_type(&self) -> &str {
+        &self.entry_type
+    }
+
+    #[inline]
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    #[inline]
+    pub fn wiki_id(&self) -> Option<WikiId> {
+        self.wiki_id
+    }
+
+    #[inline]
+    pub fn user_id(&self) -> Option<UserId> {
+        self.user_id
+    }
+
+    #[inline]
+    pub fn data(&self) -> &JsonValue {
+        &self.data
+    }
+}
@@ -18,7 +18,9 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use crate::models::revision_info::parse_user_id;
 use crate::models::GitHash;
+use crate::types::UserId;
 use chrono::{DateTime, FixedOffset};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -28,6 +30,17 @@ pub struct BlameAuthor {
     pub time: DateTime<FixedOffset>,
 }
 
+impl BlameAuthor {
+    /// Gets the user ID encoded in the email, if this change was made by a user.
+    #[inline]
+    pub fn user_id(&self) -> Option<UserId> {
+        // Porcelain output keeps the angle brackets around the email
+        let email = self.email.trim_start_matches('<').trim_end_matches('>');
+
+        parse_user_id(email)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlameLine {
     pub commit: GitHash,
@@ -49,3 +62,12 @@ pub struct BlameGroup {
 pub struct Blame {
     pub groups: Vec<BlameGroup>,
 }
+
+impl Blame {
+    /// Iterates over each line in the blame in order, alongside the group it belongs to.
+    pub fn lines(&self) -> impl Iterator<Item = (&BlameGroup, &BlameLine)> {
+        self.groups
+            .iter()
+            .flat_map(|group| group.lines.iter().map(move |line| (group, line)))
+    }
+}
@@ -23,6 +23,7 @@ use crate::error::{Error, Result};
 use crate::models::GitHash;
 use chrono::{DateTime, FixedOffset, NaiveDateTime};
 use regex::bytes::Regex;
+use std::convert::TryFrom;
 use std::{mem, str};
 
 lazy_static! {
@@ -98,7 +99,25 @@ impl Blame {
 
         macro_rules! utf {
             ($captures:expr, $name:expr) => {
-                str::from_utf8(&$captures[$name]).unwrap()
+                str::from_utf8(&$captures[$name]).map_err(|_| BLAME_ERROR)?
+            };
+        }
+
+        macro_rules! parse {
+            ($value:expr) => {
+                $value.parse().map_err(|_| BLAME_ERROR)?
+            };
+        }
+
+        macro_rules! require {
+            ($value:expr, $key:expr) => {
+                match $value {
+                    Some(value) => value,
+                    None => {
+                        warn!("No value for blame key {}", $key);
+                        return Err(BLAME_ERROR);
+                    }
+                }
             };
         }
 
@@ -114,7 +133,7 @@ impl Blame {
         // FSM state
         let lines = raw_bytes.split(|&b| b == b'\n');
         let mut state = State::Commit;
-        let mut new_group = false;
+        let mut has_headers = false;
 
         // Temporary state to build next item
         let mut author = Author::default();
@@ -124,8 +143,7 @@ impl Blame {
         let mut commit_info = None;
 
         // In-progress result data
-        let mut blame_groups = Vec::new();
-        let mut blame_lines = Vec::new();
+        let mut blame_groups: Vec<BlameGroup> = Vec::new();
 
         for line in lines {
             if line.is_empty() {
@@ -147,13 +165,13 @@ impl Blame {
                         None => return Err(BLAME_ERROR),
                     };
 
-                    // Unwraps are safe because the values are regex-verified
-                    let old_lineno = utf!(captures, "old_line").parse().unwrap();
-                    let new_lineno = utf!(captures, "new_line").parse().unwrap();
+                    // The values are regex-verified, but can still overflow
+                    let old_lineno = parse!(utf!(captures, "old_line"));
+                    let new_lineno = parse!(utf!(captures, "new_line"));
                     let commit = {
                         let sha1 = utf!(captures, "sha1");
 
-                        GitHash::from_checked(sha1)
+                        GitHash::try_from(sha1).map_err(|_| BLAME_ERROR)?
                     };
 
                     trace!(
@@ -169,7 +187,6 @@ impl Blame {
                     let captures = match METADATA_REGEX.captures(line) {
                         Some(captures) => captures,
                         None => {
-                            new_group = true;
                             state = State::Content;
                             continue;
                         }
@@ -178,50 +195,57 @@ impl Blame {
                     let key = utf!(captures, "key");
                     let value = captures
                         .name("value")
-                        .map(|mtch| str::from_utf8(mtch.as_bytes()).unwrap());
+                        .map(|mtch| str::from_utf8(mtch.as_bytes()))
+                        .transpose()
+                        .map_err(|_| BLAME_ERROR)?;
 
                     trace!("Got blame key '{}' -> {:?}", key, value);
 
                     match key {
                         "author" => {
-                            let value = value.expect("No value for key author");
+                            let value = require!(value, "author");
                             set_string!(&mut author.name, value);
+                            has_headers = true;
                         }
                         "author-mail" => {
-                            let value = value.expect("No value for key author-mail");
+                            let value = require!(value, "author-mail");
                             set_string!(&mut author.email, value);
                         }
                         "author-time" => {
-                            let value = value.expect("No value for key author-time");
-                            author.timestamp = value.parse().unwrap();
+                            let value = require!(value, "author-time");
+                            author.timestamp = parse!(value);
                         }
                         "author-tz" => {
-                            let value = value.expect("No value for key author-tz");
-                            author.tz = value.parse().unwrap();
+                            let value = require!(value, "author-tz");
+                            author.tz = parse!(value);
                         }
                         "committer" => {
-                            let value = value.expect("No value for key author");
+                            let value = require!(value, "committer");
                             set_string!(&mut committer.name, value);
                         }
                         "committer-mail" => {
-                            let value = value.expect("No value for key author-mail");
+                            let value = require!(value, "committer-mail");
                             set_string!(&mut committer.email, value);
                         }
                         "committer-time" => {
-                            let value = value.expect("No value for key author-time");
-                            committer.timestamp = value.parse().unwrap();
+                            let value = require!(value, "committer-time");
+                            committer.timestamp = parse!(value);
                         }
                         "committer-tz" => {
-                            let value = value.expect("No value for key author-tz");
-                            committer.tz = value.parse().unwrap();
+                            let value = require!(value, "committer-tz");
+                            committer.tz = parse!(value);
                         }
                         "summary" => {
-                            let value = value.expect("No value for key summary");
+                            let value = require!(value, "summary");
                             set_string!(summary, value);
                         }
                         "previous" => {
-                            let (value, _) = value.expect("No value for key previous").split_at(40);
-                            let hash = GitHash::from_checked(value);
+                            let value = require!(value, "previous");
+                            let hash = value
+                                .get(..40)
+                                .and_then(|value| GitHash::try_from(value).ok())
+                                .ok_or(BLAME_ERROR)?;
+
                             previous_commit = Some(hash);
                         }
                         "boundary" => trace!("Hit metadata boundary"),
@@ -234,9 +258,12 @@ impl Blame {
                 }
                 State::Content => {
                     let (first, line) = line.split_at(1);
-                    assert_eq!(first, b"\t", "In content state but doesn't start with tab");
+                    if first != b"\t" {
+                        warn!("In content state but line doesn't start with tab");
+                        return Err(BLAME_ERROR);
+                    }
 
-                    // Push new blame line
+                    // Build new blame line
                     let line = line.into();
                     let (commit, old_lineno, new_lineno) = match commit_info.take() {
                         Some(values) => values,
@@ -245,30 +272,58 @@ impl Blame {
 
                     trace!("Creating new blame line");
 
-                    blame_lines.push(BlameLine {
+                    let blame_line = BlameLine {
                         commit,
                         old_lineno,
                         new_lineno,
                         line,
-                    });
+                    };
 
-                    // Push new blame group
-                    if new_group {
+                    // Headers are only given the first time a commit appears,
+                    // so later lines copy them from the earlier group.
+                    if has_headers {
                         trace!("Creating new blame group");
 
                         let author = mem::replace(&mut author, Author::default());
                         let committer = mem::replace(&mut committer, Author::default());
                         let summary = mem::replace(&mut summary, String::new());
                         let previous = previous_commit.take();
-                        let blame_lines = mem::replace(&mut blame_lines, Vec::new());
 
                         blame_groups.push(BlameGroup {
                             author: author.into(),
                             committer: committer.into(),
                             summary,
                             previous,
-                            lines: blame_lines,
+                            lines: vec![blame_line],
                         });
+
+                        has_headers = false;
+                    } else {
+                        let last_commit = blame_groups.last().map(|group| &group.lines[0].commit);
+
+                        if last_commit == Some(&blame_line.commit) {
+                            trace!("Adding line to current blame group");
+
+                            let group = blame_groups.last_mut().unwrap();
+                            group.lines.push(blame_line);
+                        } else {
+                            trace!("Creating new blame group from earlier commit");
+
+                            let earlier = blame_groups
+                                .iter()
+                                .find(|group| group.lines[0].commit == blame_line.commit)
+                                .ok_or(BLAME_ERROR)?;
+
+                            let group = BlameGroup {
+                                author: earlier.author.clone(),
+                                committer: earlier.committer.clone(),
+                                summary: earlier.summary.clone(),
+                                previous: earlier.previous.clone(),
+                                lines: vec![blame_line],
+                            };
+
+                            blame_groups.push(group);
+                        }
                     }
 
                     state = State::Commit;
@@ -276,19 +331,6 @@ impl Blame {
             }
         }
 
-        // Final blame group
-        if !blame_lines.is_empty() {
-            trace!("Moving remaining blame lines to final blame group");
-
-            blame_groups.push(BlameGroup {
-                author: author.into(),
-                committer: committer.into(),
-                summary,
-                previous: previous_commit,
-                lines: blame_lines,
-            });
-        }
-
         Ok(Blame {
             groups: blame_groups,
         })
@@ -28,7 +28,7 @@ use std::fmt::{self, Debug, Display};
 use std::str;
 
 lazy_static! {
-    static ref GIT_HASH_REGEX: Regex = Regex::new(r"[a-f0-9]{40}").unwrap();
+    static ref GIT_HASH_REGEX: Regex = Regex::new(r"^[a-f0-9]{40}$").unwrap();
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -91,3 +91,14 @@ impl Display for GitHash {
         write!(f, "{}", &self.0)
     }
 }
+
+#[test]
+fn test_git_hash() {
+    let hash = "0123456789abcdef0123456789abcdef01234567";
+
+    assert_eq!(GitHash::try_from(hash).unwrap().as_str(), hash);
+    assert!(GitHash::try_from(&hash[..39]).is_err());
+    assert!(GitHash::try_from("0123456789abcdef0123456789abcdef012345678").is_err());
+    assert!(GitHash::try_from("0123456789ABCDEF0123456789ABCDEF01234567").is_err());
+    assert!(GitHash::try_from("").is_err());
+}
@@ -19,15 +19,22 @@
  */
 
 use super::prelude::*;
+use ipnetwork::IpNetwork;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::net::IpAddr;
 
-#[derive(Debug, Queryable)]
+#[derive(Serialize, Deserialize, Debug, Queryable)]
 pub struct LoginAttempt {
     id: LoginAttemptId,
     user_id: Option<UserId>,
     username_or_email: Option<String>,
-    remote_address: Option<String>,
+    remote_address: Option<IpNetwork>,
     success: bool,
     attempted_at: DateTime<Utc>,
+    credential_kind: String,
+    idempotency_key: Option<String>,
+    user_agent: Option<String>,
 }
 
 impl LoginAttempt {
@@ -47,8 +54,13 @@ impl LoginAttempt {
     }
 
     #[inline]
-    pub fn remote_address(&self) -> Option<&str> {
-        self.remote_address.ref_map(|s| s.as_str())
+    pub fn remote_address(&self) -> Option<IpAddr> {
+        self.remote_address.map(|network| network.ip())
+    }
+
+    #[inline]
+    pub fn remote_network(&self) -> Option<IpNetwork> {
+        self.remote_address
     }
 
     #[inline]
@@ -60,4 +72,254 @@ impl LoginAttempt {
     pub fn attempted_at(&self) -> DateTime<Utc> {
         self.attempted_at
     }
+
+    /// How the user identified themselves when logging in.
+    ///
+    /// Returns `InvalidCredentialKind` if the stored value isn't recognized.
+    #[inline]
+    pub fn credential_kind(&self) -> Result<CredentialKind> {
+        CredentialKind::from_stored(&self.credential_kind)
+    }
+
+    /// The user agent of the client, if known. Long values are truncated.
+    #[inline]
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.ref_map(|s| s.as_str())
+    }
+
+    /// The key the client sent to avoid recording this attempt twice, if any.
+    #[inline]
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.ref_map(|s| s.as_str())
+    }
+
+    /// The position of this attempt, to continue listing from it.
+    #[inline]
+    pub fn cursor(&self) -> LoginAttemptCursor {
+        LoginAttemptCursor::new(self.attempted_at, self.id)
+    }
+}
+
+/// What kind of identifier a login attempt was made with.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum CredentialKind {
+    Username,
+    Email,
+    Id,
+}
+
+impl From<CredentialKind> for &'static str {
+    fn from(kind: CredentialKind) -> Self {
+        use self::CredentialKind::*;
+
+        match kind {
+            Username => "username",
+            Email => "email",
+            Id => "id",
+        }
+    }
+}
+
+impl CredentialKind {
+    /// Converts the value from the database, which should always be valid.
+    pub fn from_stored(value: &str) -> Result<Self> {
+        CredentialKind::try_from(value).map_err(|_| {
+            error!("Credential kind in database invalid: '{}'", value);
+
+            Error::InvalidCredentialKind(String::from(value))
+        })
+    }
+}
+
+impl TryFrom<&'_ str> for CredentialKind {
+    type Error = ();
+
+    fn try_from(value: &str) -> StdResult<Self, ()> {
+        let case = match value {
+            "username" => CredentialKind::Username,
+            "email" => CredentialKind::Email,
+            "id" => CredentialKind::Id,
+            _ => return Err(()),
+        };
+
+        Ok(case)
+    }
+}
+
+/// A position in the list of login attempts, which are ordered by time and then ID.
+///
+/// Clients receive this as an opaque string, from its `Display` implementation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoginAttemptCursor {
+    attempted_at: DateTime<Utc>,
+    id: LoginAttemptId,
+}
+
+impl LoginAttemptCursor {
+    #[inline]
+    pub fn new(attempted_at: DateTime<Utc>, id: LoginAttemptId) -> Self {
+        LoginAttemptCursor { attempted_at, id }
+    }
+
+    #[inline]
+    pub fn attempted_at(&self) -> DateTime<Utc> {
+        self.attempted_at
+    }
+
+    #[inline]
+    pub fn login_attempt_id(&self) -> LoginAttemptId {
+        self.id
+    }
+}
+
+impl TryFrom<&str> for LoginAttemptCursor {
+    type Error = ();
+
+    fn try_from(cursor: &str) -> StdResult<Self, ()> {
+        let mut parts = cursor.splitn(2, '.');
+        let micros = parts.next().and_then(|part| part.parse::<i64>().ok());
+        let id = parts.next().and_then(|part| part.parse::<i64>().ok());
+
+        match (micros, id) {
+            (Some(micros), Some(id)) => {
+                let secs = micros.div_euclid(1_000_000);
+                let nanos = micros.rem_euclid(1_000_000) as u32 * 1000;
+                let attempted_at = Utc.timestamp_opt(secs, nanos).single().ok_or(())?;
+
+                Ok(LoginAttemptCursor::new(
+                    attempted_at,
+                    LoginAttemptId::from_raw(id),
+                ))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for LoginAttemptCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Postgres stores timestamps to the microsecond
+        let micros = self.attempted_at.timestamp() * 1_000_000
+            + i64::from(self.attempted_at.timestamp_subsec_micros());
+
+        write!(f, "{}.{}", micros, self.id)
+    }
+}
+
+/// Aggregate counts of login attempts over some period.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoginStats {
+    successful: i64,
+    failed: i64,
+    distinct_users: i64,
+}
+
+impl LoginStats {
+    #[inline]
+    pub fn new(successful: i64, failed: i64, distinct_users: i64) -> Self {
+        LoginStats {
+            successful,
+            failed,
+            distinct_users,
+        }
+    }
+
+    #[inline]
+    pub fn successful(&self) -> i64 {
+        self.successful
+    }
+
+    #[inline]
+    pub fn failed(&self) -> i64 {
+        self.failed
+    }
+
+    /// The number of different known users which attempted to log in.
+    /// Attempts with a name or email that wasn't found are not included.
+    #[inline]
+    pub fn distinct_users(&self) -> i64 {
+        self.distinct_users
+    }
+}
+
+/// Failed logins for a single account, grouped by the networks they came from.
+///
+/// Many failures spread across many networks suggests a distributed attack,
+/// such as credential stuffing, which per-address limits would not catch.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SuspicionReport {
+    failures: i64,
+    distinct_networks: i64,
+    suspicious: bool,
+}
+
+impl SuspicionReport {
+    #[inline]
+    pub fn new(failures: i64, distinct_networks: i64, suspicious: bool) -> Self {
+        SuspicionReport {
+            failures,
+            distinct_networks,
+            suspicious,
+        }
+    }
+
+    #[inline]
+    pub fn failures(&self) -> i64 {
+        self.failures
+    }
+
+    /// The number of different networks the failures came from.
+    /// Attempts without a recorded address are not included.
+    #[inline]
+    pub fn distinct_networks(&self) -> i64 {
+        self.distinct_networks
+    }
+
+    /// Whether the number of networks is over the configured threshold.
+    #[inline]
+    pub fn suspicious(&self) -> bool {
+        self.suspicious
+    }
+}
+
+#[test]
+fn test_credential_kind() {
+    use self::CredentialKind::*;
+
+    for &kind in &[Username, Email, Id] {
+        let name: &str = kind.into();
+
+        assert_eq!(CredentialKind::try_from(name), Ok(kind));
+    }
+
+    assert!(CredentialKind::try_from("").is_err());
+    assert!(CredentialKind::try_from("Email").is_err());
+
+    match CredentialKind::from_stored("password") {
+        Err(Error::InvalidCredentialKind(value)) => assert_eq!(value, "password"),
+        result => panic!("Unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_login_attempt_cursor() {
+    let attempted_at = Utc.timestamp(1_583_000_000, 123_456_000);
+    let cursor = LoginAttemptCursor::new(attempted_at, LoginAttemptId::from_raw(42));
+    let encoded = cursor.to_string();
+
+    assert_eq!(encoded, "1583000000123456.42");
+    assert_eq!(LoginAttemptCursor::try_from(encoded.as_str()), Ok(cursor));
+
+    let before_epoch =
+        LoginAttemptCursor::new(Utc.timestamp(-10, 500_000_000), LoginAttemptId::from_raw(7));
+    let encoded = before_epoch.to_string();
+    assert_eq!(
+        LoginAttemptCursor::try_from(encoded.as_str()),
+        Ok(before_epoch)
+    );
+
+    assert!(LoginAttemptCursor::try_from("").is_err());
+    assert!(LoginAttemptCursor::try_from("1583000000123456").is_err());
+    assert!(LoginAttemptCursor::try_from("abc.42").is_err());
+    assert!(LoginAttemptCursor::try_from("1583000000123456.42.1").is_err());
 }
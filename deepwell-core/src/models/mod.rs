@@ -18,12 +18,17 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+mod audit_log;
 mod blame;
 mod git_hash;
 mod login_attempt;
 mod page;
+mod revision_info;
+mod search_hit;
 mod session;
 mod user;
+mod user_audit;
+mod user_export;
 mod votes;
 mod wiki;
 
@@ -34,11 +39,18 @@ mod prelude {
     pub use ref_map::*;
 }
 
-pub use self::blame::Blame;
+pub use self::audit_log::AuditLogEntry;
+pub use self::blame::{Blame, BlameAuthor, BlameGroup, BlameLine};
 pub use self::git_hash::GitHash;
-pub use self::login_attempt::LoginAttempt;
+pub use self::login_attempt::{
+    CredentialKind, LoginAttempt, LoginAttemptCursor, LoginStats, SuspicionReport,
+};
 pub use self::page::Page;
-pub use self::session::Session;
-pub use self::user::{User, UserMetadata, UserMetadataOwned};
+pub use self::revision_info::{clean_message, RevisionInfo, REVISION_LOG_FORMAT};
+pub use self::search_hit::SearchHit;
+pub use self::session::{IssuedSession, Session};
+pub use self::user::{User, UserMetadata, UserMetadataOwned, UserView};
+pub use self::user_audit::AuditEntry;
+pub use self::user_export::UserExport;
 pub use self::votes::Votes;
 pub use self::wiki::{Wiki, WikiSettings};
@@ -0,0 +1,259 @@
+/*
+ * models/revision_info.rs
+ *
+ * deepwell-core - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::models::GitHash;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::str;
+
+/// The `git log` format string expected by `RevisionInfo::from_log()`.
+///
+/// Fields are separated by the unit separator, with the freeform message last.
+/// The trailing null byte lets the message be told apart from the file list,
+/// since it cannot appear in commit messages.
+pub const REVISION_LOG_FORMAT: &str = "--format=format:%H%x1f%an%x1f%ae%x1f%at%x1f%B%x00";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevisionInfo {
+    hash: GitHash,
+    slugs: Vec<String>,
+    user_id: Option<UserId>,
+    username: String,
+    time: DateTime<Utc>,
+    message: String,
+}
+
+impl RevisionInfo {
+    #[inline]
+    pub fn new(
+        hash: GitHash,
+        slugs: Vec<String>,
+        user_id: Option<UserId>,
+        username: String,
+        time: DateTime<Utc>,
+        message: String,
+    ) -> Self {
+        RevisionInfo {
+            hash,
+            slugs,
+            user_id,
+            username,
+            time,
+            message,
+        }
+    }
+
+    /// Parses the output of `git log -z --name-status` using `REVISION_LOG_FORMAT`.
+    pub fn from_log(raw_bytes: &[u8]) -> Result<Vec<Self>> {
+        const LOG_ERROR: Error = Error::StaticMsg("unexpected or mismatched input in git log data");
+
+        debug!("Parsing git log output ({} bytes)", raw_bytes.len());
+
+        let mut fields = raw_bytes.split(|&b| b == b'\0');
+        let mut revisions = Vec::new();
+
+        while let Some(header) = fields.next() {
+            // Separator between commits, or the end of the output
+            if header.is_empty() {
+                continue;
+            }
+
+            let header = str::from_utf8(header).map_err(|_| LOG_ERROR)?;
+            let mut parts = header.trim_start_matches('\n').splitn(5, '\x1f');
+
+            macro_rules! next {
+                () => {
+                    parts.next().ok_or(LOG_ERROR)?
+                };
+            }
+
+            let hash = GitHash::try_from(next!()).map_err(|_| LOG_ERROR)?;
+            let username = next!().to_string();
+            let user_id = parse_user_id(next!());
+            let timestamp = next!().parse().map_err(|_| LOG_ERROR)?;
+            let message = clean_message(next!().trim_end()).into_owned();
+            let time = Utc.timestamp_opt(timestamp, 0).single().ok_or(LOG_ERROR)?;
+
+            trace!("Got commit {} by '{}' ({:?})", hash, username, user_id);
+
+            // Changed files, until the empty field ending this commit
+            let mut slugs = Vec::new();
+
+            while let Some(status) = fields.next() {
+                let status = str::from_utf8(status).map_err(|_| LOG_ERROR)?;
+                let count = match status.trim_start_matches('\n').chars().next() {
+                    None => break,
+                    Some('R') | Some('C') => 2,
+                    Some(_) => 1,
+                };
+
+                // Renames and copies list both the old and new paths
+                for _ in 0..count {
+                    let path = fields.next().ok_or(LOG_ERROR)?;
+                    let path = str::from_utf8(path).map_err(|_| LOG_ERROR)?;
+
+                    slugs.push(path_to_slug(path));
+                }
+            }
+
+            revisions.push(RevisionInfo {
+                hash,
+                slugs,
+                user_id,
+                username,
+                time,
+                message,
+            });
+        }
+
+        Ok(revisions)
+    }
+
+    #[inline]
+    pub fn hash(&self) -> &GitHash {
+        &self.hash
+    }
+
+    #[inline]
+    pub fn slugs(&self) -> &[String] {
+        &self.slugs
+    }
+
+    #[inline]
+    pub fn user_id(&self) -> Option<UserId> {
+        self.user_id
+    }
+
+    #[inline]
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    #[inline]
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Removes any control characters from a commit message, except for newlines.
+///
+/// Messages come from users, so they must be cleaned before being committed or displayed.
+pub fn clean_message(message: &str) -> Cow<'_, str> {
+    fn is_disallowed(c: char) -> bool {
+        c.is_control() && c != '\n'
+    }
+
+    if message.contains(is_disallowed) {
+        Cow::Owned(message.chars().filter(|&c| !is_disallowed(c)).collect())
+    } else {
+        Cow::Borrowed(message)
+    }
+}
+
+/// Extracts the user ID from a commit author email of the form `user-<id>@<domain>`.
+pub(crate) fn parse_user_id(email: &str) -> Option<UserId> {
+    const PREFIX: &str = "user-";
+
+    let local = email.split('@').next()?;
+    if !local.starts_with(PREFIX) {
+        return None;
+    }
+
+    let id = local[PREFIX.len()..].parse().ok()?;
+
+    Some(UserId::from_raw(id))
+}
+
+/// Reverses the conversion done by the revision store, `scp-001.ftml` -> `scp-001`.
+pub(crate) fn path_to_slug(path: &str) -> String {
+    let filename = path.trim_end_matches(".ftml");
+
+    filename.replace('$', ":")
+}
+
+#[test]
+fn test_from_log() {
+    let raw = b"\
+        039d50d60b03f3d3b594c9a6b62566bf5dab2aa9\x1fAlice\x1fuser-7@example.com\x1f1582000000\x1fdel\n\0\
+        \nD\0b.ftml\0\0\
+        a842fe61788e73283a44ffffffa0f5f63ddcf17e\x1fBob\x1fuser-6@example.com\x1f1581999000\x1fempty\n\0\0\
+        52536aef20ea0812a0468b34c1c4093d712d5450\x1fBob\x1fuser-6@example.com\x1f1581998000\x1fren\n\0\
+        \nR100\0a.ftml\0fragment$c.ftml\0\0\
+        ace64dbb358c295f1cdbd82fe8ba1b6b993e09dc\x1fDEEPWELL\x1fnoreply@example.com\x1f1581997000\x1ftwo\nlines\n\0\
+        \nA\0a.ftml\0A\0b.ftml\0";
+
+    let revisions = RevisionInfo::from_log(raw).expect("Unable to parse log");
+    assert_eq!(revisions.len(), 4);
+
+    let first = &revisions[0];
+    assert_eq!(
+        first.hash().as_str(),
+        "039d50d60b03f3d3b594c9a6b62566bf5dab2aa9"
+    );
+    assert_eq!(first.slugs(), &["b"]);
+    assert_eq!(first.user_id(), Some(UserId::from_raw(7)));
+    assert_eq!(first.username(), "Alice");
+    assert_eq!(first.time().timestamp(), 1582000000);
+    assert_eq!(first.message(), "del");
+
+    let second = &revisions[1];
+    assert!(second.slugs().is_empty());
+    assert_eq!(second.message(), "empty");
+
+    let third = &revisions[2];
+    assert_eq!(third.slugs(), &["a", "fragment:c"]);
+
+    let fourth = &revisions[3];
+    assert_eq!(fourth.slugs(), &["a", "b"]);
+    assert_eq!(fourth.user_id(), None);
+    assert_eq!(fourth.message(), "two\nlines");
+
+    assert!(RevisionInfo::from_log(b"").unwrap().is_empty());
+    assert!(RevisionInfo::from_log(b"not a commit\x1f\0").is_err());
+
+    // Timestamps out of range are rejected instead of panicking
+    let raw = b"\
+        039d50d60b03f3d3b594c9a6b62566bf5dab2aa9\x1fAlice\x1fuser-7@example.com\
+        \x1f9223372036854775807\x1fbig\n\0\0";
+    assert!(RevisionInfo::from_log(raw).is_err());
+}
+
+#[test]
+fn test_clean_message() {
+    macro_rules! check {
+        ($input:expr, $expected:expr) => {
+            assert_eq!(clean_message($input), $expected);
+        };
+    }
+
+    check!("", "");
+    check!("fixed typo", "fixed typo");
+    check!("two\nlines", "two\nlines");
+    check!("ring\x07 the\x1b[31m bell", "ring the[31m bell");
+    check!("tab\tand\r\nreturn\0", "taband\nreturn");
+    check!("\u{85}next line\u{9f}", "next line");
+    check!("ユニコード\x00", "ユニコード");
+}
@@ -0,0 +1,141 @@
+/*
+ * models/search_hit.rs
+ *
+ * deepwell-core - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use super::revision_info::path_to_slug;
+use std::collections::HashMap;
+use std::str;
+
+/// A line in the current version of a page which matched a content search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    slug: String,
+    line_number: u32,
+    line: String,
+}
+
+impl SearchHit {
+    #[inline]
+    pub fn new(slug: String, line_number: u32, line: String) -> Self {
+        SearchHit {
+            slug,
+            line_number,
+            line,
+        }
+    }
+
+    /// Parses the output of `git grep -z -n HEAD`.
+    ///
+    /// At most `max_per_file` hits are kept from each page, and at most `limit` overall.
+    pub fn from_grep(raw_bytes: &[u8], max_per_file: usize, limit: usize) -> Result<Vec<Self>> {
+        const GREP_ERROR: Error =
+            Error::StaticMsg("unexpected or mismatched input in git grep data");
+
+        debug!("Parsing git grep output ({} bytes)", raw_bytes.len());
+
+        let mut counts = HashMap::new();
+        let mut hits = Vec::new();
+
+        for record in raw_bytes.split(|&b| b == b'\n') {
+            if hits.len() >= limit {
+                break;
+            }
+
+            if record.is_empty() {
+                continue;
+            }
+
+            let mut parts = record.splitn(3, |&b| b == b'\0');
+
+            macro_rules! next {
+                () => {
+                    str::from_utf8(parts.next().ok_or(GREP_ERROR)?).map_err(|_| GREP_ERROR)?
+                };
+            }
+
+            let path = next!().trim_start_matches("HEAD:");
+            let line_number = next!().parse().map_err(|_| GREP_ERROR)?;
+            let line = next!();
+
+            // So one large page doesn't crowd out the rest
+            let count = counts.entry(path).or_insert(0);
+            if *count >= max_per_file {
+                continue;
+            }
+
+            *count += 1;
+
+            hits.push(SearchHit {
+                slug: path_to_slug(path),
+                line_number,
+                line: line.to_string(),
+            });
+        }
+
+        Ok(hits)
+    }
+
+    #[inline]
+    pub fn slug(&self) -> &str {
+        &self.slug
+    }
+
+    /// The line's position in the page, starting from 1.
+    #[inline]
+    pub fn line_number(&self) -> u32 {
+        self.line_number
+    }
+
+    #[inline]
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+}
+
+#[test]
+fn test_from_grep() {
+    let raw = b"\
+        HEAD:scp-001.ftml\x001\x00the anomaly is contained\n\
+        HEAD:scp-001.ftml\x004\x00anomaly: yes\n\
+        HEAD:scp-001.ftml\x009\x00another anomaly\n\
+        HEAD:fragment$scp-002.ftml\x002\x00no anomaly\n";
+
+    let hits = SearchHit::from_grep(raw, 10, 10).expect("Unable to parse grep");
+    assert_eq!(hits.len(), 4);
+    assert_eq!(hits[0].slug(), "scp-001");
+    assert_eq!(hits[0].line_number(), 1);
+    assert_eq!(hits[0].line(), "the anomaly is contained");
+    assert_eq!(hits[1].line(), "anomaly: yes");
+    assert_eq!(hits[3].slug(), "fragment:scp-002");
+    assert_eq!(hits[3].line_number(), 2);
+
+    // Per-page cap
+    let hits = SearchHit::from_grep(raw, 2, 10).expect("Unable to parse grep");
+    let slugs: Vec<_> = hits.iter().map(|hit| hit.slug()).collect();
+    assert_eq!(slugs, ["scp-001", "scp-001", "fragment:scp-002"]);
+
+    // Overall limit
+    let hits = SearchHit::from_grep(raw, 10, 3).expect("Unable to parse grep");
+    assert_eq!(hits.len(), 3);
+
+    assert!(SearchHit::from_grep(b"", 10, 10).unwrap().is_empty());
+    assert!(SearchHit::from_grep(b"HEAD:a.ftml\x00x\x00line\n", 10, 10).is_err());
+    assert!(SearchHit::from_grep(b"HEAD:a.ftml\n", 10, 10).is_err());
+}
@@ -19,27 +19,125 @@
  */
 
 use super::prelude::*;
+use ipnetwork::IpNetwork;
+use std::fmt::{self, Debug};
+use std::net::IpAddr;
+use std::ops::Deref;
 
 #[derive(Serialize, Deserialize, Queryable, Debug)]
 pub struct Session {
-    id: SessionId,
     user_id: UserId,
-    login_attempt_id: LoginAttemptId,
+    login_attempt_id: Option<LoginAttemptId>,
+    impersonator_id: Option<UserId>,
+    remote_address: Option<IpNetwork>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    user_agent: Option<String>,
 }
 
 impl Session {
-    #[inline]
-    pub fn session_id(&self) -> SessionId {
-        self.id
-    }
-
     #[inline]
     pub fn user_id(&self) -> UserId {
         self.user_id
     }
 
+    /// The login attempt which created this session.
+    /// Impersonation sessions do not have one.
     #[inline]
-    pub fn login_attempt_id(&self) -> LoginAttemptId {
+    pub fn login_attempt_id(&self) -> Option<LoginAttemptId> {
         self.login_attempt_id
     }
+
+    /// The staff user acting as this session's user, if any.
+    #[inline]
+    pub fn impersonator_id(&self) -> Option<UserId> {
+        self.impersonator_id
+    }
+
+    #[inline]
+    pub fn is_impersonation(&self) -> bool {
+        self.impersonator_id.is_some()
+    }
+
+    /// The address this session was logged in from, if known.
+    #[inline]
+    pub fn remote_address(&self) -> Option<IpAddr> {
+        self.remote_address.map(|network| network.ip())
+    }
+
+    /// The user agent of the client this session was logged in from, if known.
+    #[inline]
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.ref_map(|s| s.as_str())
+    }
+
+    #[inline]
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    #[inline]
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+
+    /// Whether this session is no longer valid at the given time.
+    #[inline]
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now >= expires_at,
+            None => false,
+        }
+    }
+}
+
+/// A newly created session, along with the token clients use to refer to it.
+///
+/// The raw token is only available here, since only its hash is stored.
+#[derive(Serialize, Deserialize)]
+pub struct IssuedSession {
+    session: Session,
+    token: String,
+}
+
+impl IssuedSession {
+    #[inline]
+    pub fn new(session: Session, token: String) -> Self {
+        IssuedSession { session, token }
+    }
+
+    #[inline]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    #[inline]
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> (Session, String) {
+        let IssuedSession { session, token } = self;
+
+        (session, token)
+    }
+}
+
+impl Deref for IssuedSession {
+    type Target = Session;
+
+    #[inline]
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+
+impl Debug for IssuedSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IssuedSession")
+            .field("session", &self.session)
+            .field("token", &"<secret>")
+            .finish()
+    }
 }
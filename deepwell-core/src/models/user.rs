@@ -30,6 +30,8 @@ pub struct UserMetadata<'a> {
     pub about: Option<&'a str>,
     pub gender: Option<&'a str>,
     pub location: Option<&'a str>,
+    pub timezone: Option<&'a str>,
+    pub locale: Option<&'a str>,
 }
 
 impl UserMetadata<'_> {
@@ -48,6 +50,8 @@ impl UserMetadata<'_> {
             about: clone!(about),
             gender: clone!(gender),
             location: clone!(location),
+            timezone: clone!(timezone),
+            locale: clone!(locale),
         }
     }
 }
@@ -62,6 +66,8 @@ pub struct UserMetadataOwned {
     pub about: Option<String>,
     pub gender: Option<String>,
     pub location: Option<String>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
 }
 
 impl UserMetadataOwned {
@@ -80,6 +86,8 @@ impl UserMetadataOwned {
             about: borrow!(about),
             gender: borrow!(gender),
             location: borrow!(location),
+            timezone: borrow!(timezone),
+            locale: borrow!(locale),
         }
     }
 }
@@ -88,6 +96,7 @@ impl UserMetadataOwned {
 pub struct User {
     user_id: UserId,
     name: String,
+    slug: String,
     email: String,
     is_verified: bool,
     is_special: bool,
@@ -97,8 +106,11 @@ pub struct User {
     about: String,
     gender: String,
     location: String,
+    timezone: String,
+    locale: String,
     created_at: DateTime<Utc>,
     deleted_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
 }
 
 impl User {
@@ -112,6 +124,12 @@ impl User {
         &self.name
     }
 
+    /// The normalized form of the user's name, used for lookups.
+    #[inline]
+    pub fn slug(&self) -> &str {
+        &self.slug
+    }
+
     #[inline]
     pub fn email(&self) -> &str {
         &self.email
@@ -157,11 +175,29 @@ impl User {
         &self.location
     }
 
+    /// The IANA name of the user's preferred timezone, or empty if not set.
+    #[inline]
+    pub fn timezone(&self) -> &str {
+        &self.timezone
+    }
+
+    /// The BCP 47 tag of the user's preferred locale, or empty if not set.
+    #[inline]
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
     #[inline]
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
 
+    /// When the user's account was last changed, or their creation time if never.
+    #[inline]
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
     #[inline]
     pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
         self.deleted_at
@@ -172,3 +208,67 @@ impl User {
         self.deleted_at.is_none()
     }
 }
+
+/// The public view of a user, suitable for sending to clients.
+///
+/// Only the fields listed here are exposed, so columns added to `User`
+/// later are not sent unless they are also added here.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserView {
+    pub id: UserId,
+    pub name: String,
+    pub is_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<User> for UserView {
+    #[inline]
+    fn from(user: User) -> Self {
+        UserView {
+            id: user.user_id,
+            name: user.name,
+            is_verified: user.is_verified,
+            created_at: user.created_at,
+        }
+    }
+}
+
+#[test]
+fn test_user_view() {
+    let user = User {
+        user_id: UserId::from_raw(42),
+        name: String::from("Jenny Person"),
+        slug: String::from("jenny-person"),
+        email: String::from("jenny@example.com"),
+        is_verified: true,
+        is_special: false,
+        is_bot: false,
+        user_page: String::from(""),
+        website: String::from("https://example.com"),
+        about: String::from("about"),
+        gender: String::from("female"),
+        location: String::from("Earth"),
+        timezone: String::from("UTC"),
+        locale: String::from("en"),
+        created_at: Utc.timestamp(1_583_000_000, 0),
+        deleted_at: None,
+        updated_at: Utc.timestamp(1_583_000_000, 0),
+    };
+
+    let view = UserView::from(user);
+    let json = serde_json::to_value(&view).unwrap();
+
+    // Clients rely on this shape, don't change it without versioning
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "id": 42,
+            "name": "Jenny Person",
+            "is_verified": true,
+            "created_at": "2020-02-29T18:13:20Z",
+        }),
+    );
+
+    let parsed: UserView = serde_json::from_value(json).unwrap();
+    assert_eq!(parsed, view);
+}
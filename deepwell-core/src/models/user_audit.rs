@@ -0,0 +1,66 @@
+/*
+ * models/user_audit.rs
+ *
+ * deepwell-core - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use serde_json::Value as JsonValue;
+
+/// A single field changed in an edit to a user's metadata.
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    user_id: UserId,
+    field: String,
+    old_value: JsonValue,
+    new_value: JsonValue,
+    changed_by: UserId,
+    changed_at: DateTime<Utc>,
+}
+
+impl AuditEntry {
+    #[inline]
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    #[inline]
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    #[inline]
+    pub fn old_value(&self) -> &JsonValue {
+        &self.old_value
+    }
+
+    #[inline]
+    pub fn new_value(&self) -> &JsonValue {
+        &self.new_value
+    }
+
+    /// The user who made the change.
+    #[inline]
+    pub fn changed_by(&self) -> UserId {
+        self.changed_by
+    }
+
+    #[inline]
+    pub fn changed_at(&self) -> DateTime<Utc> {
+        self.changed_at
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * models/user_export.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use super::{AuditEntry, LoginAttempt, Session, User};
+
+/// Everything stored about a user, for answering data access requests.
+///
+/// Serialized as a JSON object with these keys, which tools may rely on:
+///
+/// * `exported_at` — when the export was made, as an RFC 3339 timestamp.
+/// * `user` — the user's account and profile fields.
+/// * `login_attempts` — every recorded login attempt for the user, most recent first.
+/// * `sessions` — the user's active sessions, oldest first.
+/// * `audit_log` — changes made to the user's profile, oldest first.
+///
+/// Passwords, session tokens, and two-factor secrets are never included.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserExport {
+    exported_at: DateTime<Utc>,
+    user: User,
+    login_attempts: Vec<LoginAttempt>,
+    sessions: Vec<Session>,
+    audit_log: Vec<AuditEntry>,
+}
+
+impl UserExport {
+    #[inline]
+    pub fn new(
+        exported_at: DateTime<Utc>,
+        user: User,
+        login_attempts: Vec<LoginAttempt>,
+        sessions: Vec<Session>,
+        audit_log: Vec<AuditEntry>,
+    ) -> Self {
+        UserExport {
+            exported_at,
+            user,
+            login_attempts,
+            sessions,
+            audit_log,
+        }
+    }
+
+    #[inline]
+    pub fn exported_at(&self) -> DateTime<Utc> {
+        self.exported_at
+    }
+
+    #[inline]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    #[inline]
+    pub fn login_attempts(&self) -> &[LoginAttempt] {
+        &self.login_attempts
+    }
+
+    #[inline]
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    #[inline]
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit_log
+    }
+}
@@ -18,6 +18,7 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+make_id_type!(audit_log_entry, AuditLogEntryId);
 make_id_type!(login_attempt, LoginAttemptId);
 make_id_type!(page, PageId);
 make_id_type!(rating, RatingId);
@@ -0,0 +1,25 @@
+DELETE FROM audit_log WHERE wiki_id IS NULL;
+
+ALTER TABLE audit_log
+    DROP CONSTRAINT audit_log_audit_log_entry_type_check,
+    ADD CONSTRAINT audit_log_audit_log_entry_type_check CHECK (
+        audit_log_entry_type IN (
+            'view_page',
+            'add_page',
+            'edit_page_content',
+            'edit_page_tags',
+            'remove_page'
+            -- TODO
+        )
+    ),
+    ALTER COLUMN wiki_id SET NOT NULL;
+
+DELETE FROM sessions WHERE impersonator_id IS NOT NULL;
+
+ALTER TABLE sessions
+    DROP CONSTRAINT sessions_impersonation_expiry_check,
+    DROP CONSTRAINT sessions_origin_check,
+    ALTER COLUMN login_attempt_id SET NOT NULL,
+    DROP COLUMN expires_at,
+    DROP COLUMN created_at,
+    DROP COLUMN impersonator_id;
@@ -0,0 +1,27 @@
+-- Impersonation sessions, used by staff to act as another user.
+-- These have no associated login attempt, and always expire.
+ALTER TABLE sessions
+    ADD COLUMN impersonator_id BIGINT REFERENCES users(user_id),
+    ADD COLUMN created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE,
+    ALTER COLUMN login_attempt_id DROP NOT NULL,
+    ADD CONSTRAINT sessions_origin_check
+        CHECK ((login_attempt_id IS NULL) != (impersonator_id IS NULL)),
+    ADD CONSTRAINT sessions_impersonation_expiry_check
+        CHECK (impersonator_id IS NULL OR expires_at IS NOT NULL);
+
+-- Allow audit log entries which aren't tied to a particular wiki
+ALTER TABLE audit_log
+    ALTER COLUMN wiki_id DROP NOT NULL,
+    DROP CONSTRAINT audit_log_audit_log_entry_type_check,
+    ADD CONSTRAINT audit_log_audit_log_entry_type_check CHECK (
+        audit_log_entry_type IN (
+            'view_page',
+            'add_page',
+            'edit_page_content',
+            'edit_page_tags',
+            'remove_page',
+            'impersonate_user'
+            -- TODO
+        )
+    );
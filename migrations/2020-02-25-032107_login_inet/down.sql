@@ -0,0 +1,2 @@
+ALTER TABLE login_attempts
+    ALTER COLUMN remote_address TYPE TEXT USING HOST(remote_address);
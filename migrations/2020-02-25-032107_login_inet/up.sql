@@ -0,0 +1,14 @@
+-- Addresses which aren't valid IPs cannot be kept
+CREATE FUNCTION pg_temp.try_inet(address TEXT) RETURNS INET AS $$
+BEGIN
+    RETURN address::INET;
+EXCEPTION
+    WHEN invalid_text_representation THEN
+        RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE login_attempts
+    ALTER COLUMN remote_address TYPE INET USING pg_temp.try_inet(remote_address);
+
+DROP FUNCTION pg_temp.try_inet(TEXT);
@@ -0,0 +1,8 @@
+CREATE TABLE password_resets (
+    selector TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    token_hash BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    redeemed_at TIMESTAMP WITH TIME ZONE
+);
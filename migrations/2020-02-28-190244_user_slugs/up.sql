@@ -0,0 +1,19 @@
+ALTER TABLE users ADD COLUMN slug TEXT;
+
+-- Approximates wikidot_normalize for existing users
+UPDATE users SET slug = TRIM(BOTH '-' FROM
+    REGEXP_REPLACE(LOWER(name), '[^a-z0-9_:/-]+|-{2,}', '-', 'g'));
+
+-- Disambiguate any existing names which now collide
+UPDATE users SET slug = slug || '-' || user_id
+    WHERE user_id IN (
+        SELECT user_id FROM (
+            SELECT user_id, ROW_NUMBER() OVER (PARTITION BY slug ORDER BY user_id) AS row
+            FROM users
+        ) AS duplicates
+        WHERE row > 1
+    );
+
+ALTER TABLE users
+    ALTER COLUMN slug SET NOT NULL,
+    ADD UNIQUE (slug);
@@ -0,0 +1,3 @@
+ALTER TABLE sessions
+    DROP COLUMN token_selector,
+    DROP COLUMN token_hash;
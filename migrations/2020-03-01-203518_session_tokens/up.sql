@@ -0,0 +1,6 @@
+-- Existing sessions have no token to refer to them by, so they cannot be kept
+DELETE FROM sessions;
+
+ALTER TABLE sessions
+    ADD COLUMN token_selector TEXT NOT NULL UNIQUE,
+    ADD COLUMN token_hash BYTEA NOT NULL CHECK (LENGTH(token_hash) * 8 = 256);
@@ -0,0 +1,6 @@
+-- Copied from the login attempt, so it can be shown alongside the session
+ALTER TABLE sessions ADD COLUMN remote_address INET;
+
+UPDATE sessions SET remote_address = login_attempts.remote_address
+    FROM login_attempts
+    WHERE sessions.login_attempt_id = login_attempts.login_attempt_id;
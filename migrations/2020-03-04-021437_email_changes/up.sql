@@ -0,0 +1,9 @@
+CREATE TABLE email_changes (
+    selector TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    email TEXT NOT NULL CHECK (email = LOWER(email)),
+    token_hash BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    redeemed_at TIMESTAMP WITH TIME ZONE
+);
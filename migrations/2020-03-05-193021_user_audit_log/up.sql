@@ -0,0 +1,14 @@
+CREATE TABLE user_audit_log (
+    user_audit_log_entry_id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    field TEXT NOT NULL,
+    old_value JSONB NOT NULL,
+    new_value JSONB NOT NULL,
+    changed_by BIGINT NOT NULL REFERENCES users(user_id),
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX user_audit_log_user_id_idx ON user_audit_log (user_id);
+
+-- make table append-only
+REVOKE UPDATE, DELETE, TRUNCATE ON TABLE user_audit_log FROM public;
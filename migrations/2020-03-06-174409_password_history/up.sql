@@ -0,0 +1,12 @@
+CREATE TABLE password_history (
+    password_history_id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    hash BYTEA NOT NULL,
+    salt BYTEA NOT NULL,
+    logn SMALLINT NOT NULL,
+    param_r INTEGER NOT NULL,
+    param_p INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX password_history_user_id_idx ON password_history (user_id);
@@ -0,0 +1,2 @@
+DROP TABLE totp_logins;
+DROP TABLE user_totp;
@@ -0,0 +1,16 @@
+CREATE TABLE user_totp (
+    user_id BIGINT PRIMARY KEY REFERENCES users(user_id),
+    totp_secret BYTEA NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT false,
+    last_used_step BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE totp_logins (
+    selector TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    login_attempt_id BIGINT NOT NULL REFERENCES login_attempts(login_attempt_id) ON DELETE CASCADE,
+    token_hash BYTEA NOT NULL,
+    failures SMALLINT NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
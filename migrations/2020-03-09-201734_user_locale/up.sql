@@ -0,0 +1,3 @@
+ALTER TABLE users
+    ADD COLUMN timezone TEXT NOT NULL DEFAULT '',
+    ADD COLUMN locale TEXT NOT NULL DEFAULT '';
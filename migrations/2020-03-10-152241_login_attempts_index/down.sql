@@ -0,0 +1 @@
+DROP INDEX login_attempts_attempted_at_idx;
@@ -0,0 +1 @@
+CREATE INDEX login_attempts_attempted_at_idx ON login_attempts (attempted_at DESC, login_attempt_id DESC);
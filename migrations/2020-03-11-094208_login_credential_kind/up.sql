@@ -0,0 +1,18 @@
+-- Older attempts didn't record this, so guess from the value
+ALTER TABLE login_attempts
+    ADD COLUMN credential_kind TEXT CHECK (
+        credential_kind IN (
+            'username',
+            'email',
+            'id'
+        )
+    );
+
+UPDATE login_attempts SET credential_kind =
+    CASE
+        WHEN username_or_email IS NULL THEN 'id'
+        WHEN username_or_email LIKE '%@%' THEN 'email'
+        ELSE 'username'
+    END;
+
+ALTER TABLE login_attempts ALTER COLUMN credential_kind SET NOT NULL;
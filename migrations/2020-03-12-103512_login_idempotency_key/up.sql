@@ -0,0 +1,2 @@
+-- Supplied by clients so retried requests don't record an attempt twice
+ALTER TABLE login_attempts ADD COLUMN idempotency_key TEXT UNIQUE;
@@ -0,0 +1,4 @@
+-- Older users haven't been tracked, so start from when they were created
+ALTER TABLE users ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+UPDATE users SET updated_at = created_at;
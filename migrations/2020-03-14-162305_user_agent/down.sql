@@ -0,0 +1,2 @@
+ALTER TABLE sessions DROP COLUMN user_agent;
+ALTER TABLE login_attempts DROP COLUMN user_agent;
@@ -0,0 +1,3 @@
+-- Truncated by the application before being stored
+ALTER TABLE login_attempts ADD COLUMN user_agent TEXT;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
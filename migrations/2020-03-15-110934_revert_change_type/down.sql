@@ -0,0 +1,15 @@
+-- Reverts were recorded as undos before this
+UPDATE revisions SET change_type = 'undo' WHERE change_type = 'revert';
+
+ALTER TABLE revisions DROP CONSTRAINT revisions_change_type_check;
+ALTER TABLE revisions ADD CONSTRAINT revisions_change_type_check CHECK (
+    change_type IN (
+        'create',
+        'modify',
+        'delete',
+        'restore',
+        'rename',
+        'undo',
+        'tags'
+    )
+);
@@ -0,0 +1,13 @@
+ALTER TABLE revisions DROP CONSTRAINT revisions_change_type_check;
+ALTER TABLE revisions ADD CONSTRAINT revisions_change_type_check CHECK (
+    change_type IN (
+        'create',
+        'modify',
+        'delete',
+        'restore',
+        'rename',
+        'undo',
+        'revert',
+        'tags'
+    )
+);
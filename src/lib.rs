@@ -22,13 +22,16 @@
 #![forbid(unsafe_code)]
 
 extern crate async_std;
+extern crate async_trait;
 extern crate chrono;
+extern crate chrono_tz;
 extern crate cow_utils;
 extern crate crypto;
 extern crate deepwell_core;
 
 #[macro_use]
 extern crate diesel;
+extern crate diesel_migrations;
 extern crate either;
 
 #[macro_use]
@@ -45,6 +48,9 @@ extern crate ref_map;
 
 #[macro_use]
 extern crate serde;
+
+#[macro_use]
+extern crate serde_json;
 extern crate subprocess;
 
 #[macro_use]
@@ -54,22 +60,36 @@ extern crate wikidot_normalize;
 #[macro_use]
 mod macros;
 
+mod migration;
 mod package;
+mod pool;
 mod schema;
 mod server;
+mod token;
 mod utils;
 
 #[cfg(test)]
 mod test;
 
 pub mod prelude {
+    pub use crate::migration::MigrationStatus;
     pub use crate::package::page::PageCommit;
-    pub use crate::server::{Config, Server};
+    pub use crate::package::password::{HashCost, PasswordPolicy};
+    pub use crate::package::session::{
+        LockoutPolicy, LoginEvent, LoginEventListener, RateLimitPolicy, SessionExpiry,
+    };
+    pub use crate::package::totp::{TotpEnrollment, TotpKey, TotpPolicy};
+    pub use crate::package::user::VerificationPolicy;
+    pub use crate::server::{
+        Actor, Config, HealthStatus, LoginResponse, NewUserSpec, SelfTestReport, SelfTestResult,
+        Server,
+    };
     pub use crate::{Error, Result, StdResult};
     pub use deepwell_core::prelude::*;
 }
 
 mod manager_prelude {
+    pub use crate::pool::ConnectionPool;
     pub use crate::prelude::*;
     pub use crate::schema::*;
     pub use async_std::prelude::*;
@@ -81,7 +101,6 @@ mod manager_prelude {
     pub use std::collections::HashMap;
     pub use std::convert::TryFrom;
     pub use std::fmt::{self, Debug};
-    pub use std::sync::Arc;
 
     // For Option<Option<T>>, updating nullable columns
     pub type Nullable<T> = Option<T>;
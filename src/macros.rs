@@ -31,6 +31,29 @@ macro_rules! impl_async_transaction {
 
                 self.conn.transaction(|| task::block_on(f))
             }
+
+            #[inline]
+            #[allow(dead_code)]
+            async fn snapshot_transaction<F, T>(&self, f: F) -> Result<T>
+            where
+                F: Future<Output = Result<T>>,
+            {
+                use async_std::task;
+
+                self.conn.snapshot_transaction(|| task::block_on(f))
+            }
+
+            #[inline]
+            #[allow(dead_code)]
+            async fn retry_transaction<F, Fut, T>(&self, mut f: F) -> Result<T>
+            where
+                F: FnMut() -> Fut,
+                Fut: Future<Output = Result<T>>,
+            {
+                use async_std::task;
+
+                self.conn.retry_transaction(|| task::block_on(f()))
+            }
         }
     };
 }
@@ -0,0 +1,196 @@
+/*
+ * migration.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+// Running the database migrations from within the service.
+//
+// The migrations are embedded in the binary so it can bring a database
+// up to date on its own. These are tracked in the same table the diesel
+// CLI uses, so the two can be used interchangeably.
+
+use crate::{Error, Result, StdResult};
+use diesel::connection::SimpleConnection;
+use diesel::migration::{Migration, RunMigrationsError};
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel_migrations::{run_migrations, setup_database, MigrationConnection};
+use std::collections::HashSet;
+use std::io;
+
+/// The advisory lock key held while running migrations.
+const MIGRATION_LOCK_ID: i64 = 0x6465_6570_7765_6c6c; // "deepwell"
+
+macro_rules! embed {
+    ($name:expr) => {
+        EmbeddedMigration::new(
+            $name,
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/migrations/",
+                $name,
+                "/up.sql",
+            )),
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/migrations/",
+                $name,
+                "/down.sql",
+            )),
+        )
+    };
+}
+
+lazy_static! {
+    /// All migrations, in the order they are applied.
+    ///
+    /// The list is generated by `build.rs` from the directories in `migrations/`,
+    /// sorted by name.
+    pub static ref MIGRATIONS: Vec<EmbeddedMigration> =
+        include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+}
+
+#[derive(Debug)]
+pub struct EmbeddedMigration {
+    name: &'static str,
+    version: String,
+    up_sql: &'static str,
+    down_sql: &'static str,
+}
+
+impl EmbeddedMigration {
+    fn new(name: &'static str, up_sql: &'static str, down_sql: &'static str) -> Self {
+        // Diesel's version is the timestamp prefix without dashes
+        let prefix = name.split('_').next().unwrap_or(name);
+        let version = prefix.replace('-', "");
+
+        EmbeddedMigration {
+            name,
+            version,
+            up_sql,
+            down_sql,
+        }
+    }
+
+    /// The name of the migration's directory.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Migration for EmbeddedMigration {
+    #[inline]
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn run(&self, conn: &dyn SimpleConnection) -> StdResult<(), RunMigrationsError> {
+        conn.batch_execute(self.up_sql)?;
+        Ok(())
+    }
+
+    fn revert(&self, conn: &dyn SimpleConnection) -> StdResult<(), RunMigrationsError> {
+        conn.batch_execute(self.down_sql)?;
+        Ok(())
+    }
+}
+
+/// Whether a particular migration has been applied to the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    name: &'static str,
+    applied: bool,
+}
+
+impl MigrationStatus {
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn applied(&self) -> bool {
+        self.applied
+    }
+}
+
+/// Gets the versions which have been applied, failing if any are unknown.
+fn applied_versions(conn: &PgConnection) -> Result<HashSet<String>> {
+    setup_database(conn)?;
+
+    let applied = conn.previously_run_migration_versions()?;
+    for version in &applied {
+        if !MIGRATIONS
+            .iter()
+            .any(|migration| &migration.version == version)
+        {
+            error!("Database has unknown migration {} applied", version);
+            return Err(Error::DatabaseSchemaNewer(version.clone()));
+        }
+    }
+
+    Ok(applied)
+}
+
+pub fn status(conn: &PgConnection) -> Result<Vec<MigrationStatus>> {
+    let applied = applied_versions(conn)?;
+    let statuses = MIGRATIONS
+        .iter()
+        .map(|migration| MigrationStatus {
+            name: migration.name(),
+            applied: applied.contains(&migration.version),
+        })
+        .collect();
+
+    Ok(statuses)
+}
+
+/// Runs all pending migrations, returning the names of the ones applied.
+///
+/// This takes a transaction-level advisory lock, so concurrent
+/// instances starting up wait for each other instead of racing.
+pub fn run_pending(conn: &PgConnection) -> Result<Vec<&'static str>> {
+    conn.transaction(|| {
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+            .bind::<BigInt, _>(MIGRATION_LOCK_ID)
+            .execute(conn)?;
+
+        let applied = applied_versions(conn)?;
+        let pending = MIGRATIONS
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .collect::<Vec<_>>();
+
+        for migration in &pending {
+            info!("Running migration {}", migration.name());
+        }
+
+        let migrations = pending.iter().map(|&migration| migration as &dyn Migration);
+        run_migrations(conn, migrations, &mut io::sink()).map_err(migration_error)?;
+
+        Ok(pending.iter().map(|migration| migration.name()).collect())
+    })
+}
+
+fn migration_error(error: RunMigrationsError) -> Error {
+    match error {
+        RunMigrationsError::QueryError(error) => Error::Database(error),
+        _ => Error::Migration(error.to_string()),
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * audit/manager.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::{AuditLogEntryType, NewAuditLogEntry};
+use crate::manager_prelude::*;
+use crate::schema::audit_log;
+use diesel::sql_types::{Integer, Text};
+use serde_json::Value as JsonValue;
+
+/// The first half of the advisory lock keys taken by `AuditLogManager::lock()`.
+const AUDIT_LOCK_CLASS: i32 = 0x6175_6469; // "audi"
+
+pub struct AuditLogManager {
+    conn: ConnectionPool,
+}
+
+impl AuditLogManager {
+    #[inline]
+    pub fn new(conn: &ConnectionPool) -> Self {
+        debug!("Creating audit-log-manager service");
+
+        let conn = conn.clone();
+        AuditLogManager { conn }
+    }
+
+    pub async fn add(
+        &self,
+        entry_type: AuditLogEntryType,
+        wiki_id: Option<WikiId>,
+        user_id: Option<UserId>,
+        data: &JsonValue,
+    ) -> Result<AuditLogEntryId> {
+        info!(
+            "Adding audit log entry '{}' (wiki ID {:?}, user ID {:?})",
+            entry_type.fixed_name(),
+            wiki_id,
+            user_id,
+        );
+
+        let model = NewAuditLogEntry {
+            audit_log_entry_type: entry_type.fixed_name(),
+            wiki_id: wiki_id.map(|id| id.into()),
+            user_id: user_id.map(|id| id.into()),
+            data,
+        };
+
+        let id = diesel::insert_into(audit_log::table)
+            .values(&model)
+            .returning(audit_log::dsl::audit_log_entry_id)
+            .get_result::<AuditLogEntryId>(&*self.conn.get()?)?;
+
+        Ok(id)
+    }
+
+    /// Blocks other transactions from taking this lock for the same entry type
+    /// and user until the current transaction ends.
+    ///
+    /// This lets a `count()` followed by an `add()` act as a single step,
+    /// so concurrent requests can't all pass a limit before any are logged.
+    pub async fn lock(&self, entry_type: AuditLogEntryType, user_id: UserId) -> Result<()> {
+        debug!(
+            "Locking audit log entries '{}' for user ID {}",
+            entry_type.fixed_name(),
+            user_id,
+        );
+
+        let key = format!("{}/{}", entry_type.fixed_name(), user_id);
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+            .bind::<Integer, _>(AUDIT_LOCK_CLASS)
+            .bind::<Text, _>(key)
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
+
+    pub async fn count<Tz: TimeZone>(
+        &self,
+        entry_type: AuditLogEntryType,
+        user_id: UserId,
+        since: DateTime<Tz>,
+    ) -> Result<i64> {
+        debug!(
+            "Counting audit log entries '{}' for user ID {} since {}",
+            entry_type.fixed_name(),
+            user_id,
+            since.time(),
+        );
+
+        let id: i64 = user_id.into();
+        let count = audit_log::table
+            .filter(audit_log::audit_log_entry_type.eq(entry_type.fixed_name()))
+            .filter(audit_log::user_id.eq(id))
+            .filter(audit_log::created_at.gt(since))
+            .count()
+            .get_result::<i64>(&*self.conn.get()?)?;
+
+        Ok(count)
+    }
+
+    pub async fn get_entries<Tz: TimeZone>(
+        &self,
+        user_id: UserId,
+        since: DateTime<Tz>,
+    ) -> Result<Vec<AuditLogEntry>> {
+        debug!(
+            "Getting audit log entries for user ID {} since {}",
+            user_id,
+            since.time(),
+        );
+
+        let id: i64 = user_id.into();
+        let entries = audit_log::table
+            .filter(audit_log::user_id.eq(id))
+            .filter(audit_log::created_at.gt(since))
+            .order_by(audit_log::created_at.desc())
+            .limit(100)
+            .get_results::<AuditLogEntry>(&*self.conn.get()?)?;
+
+        Ok(entries)
+    }
+
+    /// Detaches the user from their entries, so the user can be deleted.
+    /// The entries themselves are kept.
+    pub async fn anonymize_user(&self, user_id: UserId) -> Result<()> {
+        info!("Removing user ID {} from audit log entries", user_id);
+
+        let id: i64 = user_id.into();
+        diesel::update(audit_log::table.filter(audit_log::user_id.eq(id)))
+            .set(audit_log::user_id.eq(None::<i64>))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
+}
+
+impl_async_transaction!(AuditLogManager);
+
+impl Debug for AuditLogManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AuditLogManager")
+            .field("conn", &"PgConnection { .. }")
+            .finish()
+    }
+}
@@ -0,0 +1,46 @@
+/*
+ * audit/models.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::schema::audit_log;
+use serde_json::Value as JsonValue;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AuditLogEntryType {
+    ImpersonateUser,
+}
+
+impl AuditLogEntryType {
+    pub fn fixed_name(self) -> &'static str {
+        use self::AuditLogEntryType::*;
+
+        match self {
+            ImpersonateUser => "impersonate_user",
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "audit_log"]
+pub struct NewAuditLogEntry<'a> {
+    pub audit_log_entry_type: &'a str,
+    pub wiki_id: Option<i64>,
+    pub user_id: Option<i64>,
+    pub data: &'a JsonValue,
+}
@@ -57,14 +57,14 @@ impl Author {
 }
 
 pub struct AuthorManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
 }
 
 impl AuthorManager {
-    pub fn new(conn: &Arc<PgConnection>) -> Self {
+    pub fn new(conn: &ConnectionPool) -> Self {
         debug!("Creating author-manager service");
 
-        let conn = Arc::clone(conn);
+        let conn = conn.clone();
         AuthorManager { conn }
     }
 
@@ -76,7 +76,7 @@ impl AuthorManager {
             .filter(authors::dsl::page_id.eq(id))
             .order_by(authors::dsl::written_at.asc())
             .order_by(authors::dsl::user_id.asc())
-            .load::<Author>(&*self.conn)?;
+            .load::<Author>(&*self.conn.get()?)?;
 
         Ok(result)
     }
@@ -109,7 +109,7 @@ impl AuthorManager {
             ))
             .do_update()
             .set(&model)
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
         Ok(())
     }
@@ -132,9 +132,9 @@ impl AuthorManager {
             .filter(authors::dsl::page_id.eq(page_id))
             .filter(authors::dsl::user_id.eq(user_id))
             .filter(authors::dsl::author_type.eq(author_type))
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
-        Ok(rows_to_result(rows))
+        rows_to_result(rows)
     }
 }
 
@@ -23,15 +23,15 @@ use crate::manager_prelude::*;
 use crate::schema::page_locks;
 
 pub struct LockManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
 }
 
 impl LockManager {
     #[inline]
-    pub fn new(conn: &Arc<PgConnection>) -> Self {
+    pub fn new(conn: &ConnectionPool) -> Self {
         debug!("Creating lock-manager service");
 
-        let conn = Arc::clone(conn);
+        let conn = conn.clone();
         LockManager { conn }
     }
 
@@ -42,7 +42,7 @@ impl LockManager {
 
         let rows = diesel::delete(page_locks::table)
             .filter(page_locks::dsl::locked_until.lt(now))
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
         Ok(rows)
     }
@@ -57,7 +57,7 @@ impl LockManager {
         let result = page_locks::table
             .filter(page_locks::dsl::page_id.eq(id))
             .select(page_locks::dsl::user_id)
-            .first::<UserId>(&*self.conn)
+            .first::<UserId>(&*self.conn.get()?)
             .optional()?;
 
         match result {
@@ -86,7 +86,7 @@ impl LockManager {
 
         diesel::insert_into(page_locks::table)
             .values(&model)
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
         Ok(())
     }
@@ -105,7 +105,7 @@ impl LockManager {
         let locked_until = Utc::now() + lock_duration;
         let rows = diesel::update(page_locks::table)
             .set(page_locks::dsl::locked_until.eq(locked_until))
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
         row_check(rows)
     }
@@ -116,7 +116,7 @@ impl LockManager {
         let id: i64 = page_id.into();
         let rows = diesel::delete(page_locks::table)
             .filter(page_locks::dsl::page_id.eq(id))
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
         row_check(rows)
     }
@@ -135,7 +135,7 @@ impl Debug for LockManager {
 fn row_check(rows: usize) -> Result<()> {
     use crate::utils::rows_to_result;
 
-    if rows_to_result(rows) {
+    if rows_to_result(rows)? {
         Ok(())
     } else {
         Err(Error::PageLockNotFound)
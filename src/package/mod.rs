@@ -18,6 +18,7 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+pub mod audit;
 pub mod author;
 pub mod lock;
 pub mod page;
@@ -25,5 +26,6 @@ pub mod password;
 pub mod rating;
 pub mod revision;
 pub mod session;
+pub mod totp;
 pub mod user;
 pub mod wiki;
@@ -20,7 +20,7 @@
 
 use super::{ChangeType, NewPage, NewRevision, NewTagChange, UpdatePage};
 use crate::manager_prelude::*;
-use crate::package::revision::{CommitInfo, RevisionStore};
+use crate::package::revision::{CommitInfo, GitStore, RevisionStore};
 use crate::schema::{pages, revisions, tag_history};
 use async_std::fs;
 use async_std::sync::RwLockReadGuard;
@@ -29,24 +29,32 @@ use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
+/// Commit messages longer than this are rejected rather than truncated.
+const MESSAGE_LENGTH_LIMIT: usize = 4096;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct PageCommit<'a> {
     pub wiki_id: WikiId,
     pub slug: &'a str,
+
+    /// The editor's summary of the change, such as "fixed typo".
+    ///
+    /// This is added to the commit message after a generated line describing the change.
+    /// It may be empty, in which case the commit message is only that line.
     pub message: &'a str,
     pub user: &'a User,
 }
 
 #[derive(Debug)]
 struct ReadGuard<'a> {
-    guard: RwLockReadGuard<'a, HashMap<WikiId, RevisionStore>>,
+    guard: RwLockReadGuard<'a, HashMap<WikiId, Box<dyn RevisionStore>>>,
     wiki_id: WikiId,
 }
 
 impl ReadGuard<'_> {
-    fn get(&self) -> Result<&RevisionStore> {
+    fn get(&self) -> Result<&dyn RevisionStore> {
         match self.guard.get(&self.wiki_id) {
-            Some(store) => Ok(store),
+            Some(store) => Ok(store.as_ref()),
             None => {
                 error!("No revision store found for wiki ID {}", self.wiki_id);
                 Err(Error::WikiNotFound)
@@ -56,21 +64,23 @@ impl ReadGuard<'_> {
 }
 
 pub struct PageManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
     directory: PathBuf,
-    stores: RwLock<HashMap<WikiId, RevisionStore>>,
+    max_message_length: usize,
+    stores: RwLock<HashMap<WikiId, Box<dyn RevisionStore>>>,
 }
 
 impl PageManager {
     #[inline]
-    pub fn new(conn: &Arc<PgConnection>, directory: PathBuf) -> Self {
+    pub fn new(conn: &ConnectionPool, directory: PathBuf, max_message_length: usize) -> Self {
         debug!("Creating page-manager service");
 
-        let conn = Arc::clone(conn);
+        let conn = conn.clone();
 
         PageManager {
             conn,
             directory,
+            max_message_length,
             stores: RwLock::new(HashMap::new()),
         }
     }
@@ -81,25 +91,59 @@ impl PageManager {
         page_id: PageId,
         user_id: UserId,
         change_type: ChangeType,
+        message: &str,
     ) -> String {
-        format!(
+        let mut commit = format!(
             "User ID {} {} page ID {} on wiki ID {}",
             user_id,
             change_type.verb(),
             page_id,
             wiki_id,
-        )
+        );
+
+        if !message.is_empty() {
+            commit.push_str("\n\n");
+            commit.push_str(message);
+        }
+
+        commit
+    }
+
+    /// Cleans a user-provided commit message, truncating it if it's too long.
+    fn prepare_message<'a>(&self, message: &'a str) -> Result<Cow<'a, str>> {
+        let mut message = clean_message(message);
+        let length = message.chars().count();
+
+        if length > MESSAGE_LENGTH_LIMIT {
+            warn!(
+                "Commit message is too long ({} > {})",
+                length, MESSAGE_LENGTH_LIMIT,
+            );
+
+            return Err(Error::MessageTooLong);
+        }
+
+        if let Some((index, _)) = message.char_indices().nth(self.max_message_length) {
+            debug!(
+                "Truncating commit message to {} characters",
+                self.max_message_length,
+            );
+
+            message.to_mut().truncate(index);
+        }
+
+        Ok(message)
     }
 
     pub async fn add_store(&self, wiki: &Wiki) -> Result<()> {
         let repo = self.directory.join(wiki.slug());
         fs::create_dir(&repo).await?;
 
-        let store = RevisionStore::new(repo, wiki.domain());
+        let store = GitStore::new(repo, wiki.domain());
         store.initial_commit().await?;
 
         let mut guard = self.stores.write().await;
-        guard.insert(wiki.id(), store);
+        guard.insert(wiki.id(), Box::new(store));
 
         Ok(())
     }
@@ -135,7 +179,7 @@ impl PageManager {
             .filter(pages::dsl::wiki_id.eq(wiki_id))
             .filter(pages::dsl::slug.eq(slug))
             .select(pages::dsl::page_id)
-            .first::<PageId>(&*self.conn)
+            .first::<PageId>(&*self.conn.get()?)
             .optional()?;
 
         Ok(page_id)
@@ -157,6 +201,8 @@ impl PageManager {
             user,
         } = commit;
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             let model = NewPage {
                 wiki_id: wiki_id.into(),
@@ -174,13 +220,14 @@ impl PageManager {
             let page_id = diesel::insert_into(pages::table)
                 .values(&model)
                 .returning(pages::dsl::page_id)
-                .get_result::<PageId>(&*self.conn)?;
+                .get_result::<PageId>(&*self.conn.get()?)?;
 
             let user_id = user.id();
             let change_type = ChangeType::Create;
 
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -189,7 +236,7 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -198,7 +245,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             Ok((page_id, revision_id))
         })
@@ -222,6 +269,8 @@ impl PageManager {
             user,
         } = commit;
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             let model = UpdatePage {
                 slug: None,
@@ -237,14 +286,15 @@ impl PageManager {
                 let id: i64 = page_id.into();
                 diesel::update(dsl::pages.filter(dsl::page_id.eq(id)))
                     .set(&model)
-                    .execute(&*self.conn)?;
+                    .execute(&*self.conn.get()?)?;
             }
 
             let user_id = user.id();
             let change_type = ChangeType::Modify;
 
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -253,7 +303,7 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -262,7 +312,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             Ok(revision_id)
         })
@@ -283,6 +333,8 @@ impl PageManager {
             old_slug, new_slug, wiki_id
         );
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             let model = UpdatePage {
                 slug: Some(new_slug),
@@ -297,14 +349,15 @@ impl PageManager {
                 let id: i64 = page_id.into();
                 diesel::update(dsl::pages.filter(dsl::page_id.eq(id)))
                     .set(&model)
-                    .execute(&*self.conn)?;
+                    .execute(&*self.conn.get()?)?;
             }
 
             let user_id = user.id();
             let change_type = ChangeType::Rename;
 
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -317,7 +370,7 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -326,7 +379,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             Ok(revision_id)
         })
@@ -343,6 +396,8 @@ impl PageManager {
             user,
         } = commit;
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             use diesel::dsl::now;
 
@@ -353,14 +408,15 @@ impl PageManager {
                 let id: i64 = page_id.into();
                 diesel::update(dsl::pages.filter(dsl::page_id.eq(id)))
                     .set(pages::dsl::deleted_at.eq(now))
-                    .execute(&*self.conn)?;
+                    .execute(&*self.conn.get()?)?;
             }
 
             let user_id = user.id();
             let change_type = ChangeType::Delete;
 
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -377,7 +433,7 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -386,7 +442,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             Ok(revision_id)
         })
@@ -407,6 +463,8 @@ impl PageManager {
             user,
         } = commit;
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             if self.check_page(wiki_id, slug).await? {
                 return Err(Error::PageExists);
@@ -425,7 +483,7 @@ impl PageManager {
                         .filter(pages::deleted_at.is_not_null())
                         .order_by(pages::created_at.desc())
                         .select(pages::dsl::page_id)
-                        .first::<PageId>(&*self.conn)
+                        .first::<PageId>(&*self.conn.get()?)
                         .optional()?;
 
                     // If none, found nothing to restore
@@ -444,7 +502,7 @@ impl PageManager {
                 let result = pages::table
                     .find(id)
                     .select((pages::dsl::wiki_id, pages::dsl::slug))
-                    .first::<(WikiId, String)>(&*self.conn)
+                    .first::<(WikiId, String)>(&*self.conn.get()?)
                     .optional()?;
 
                 let (wiki_id, old_slug) = match result {
@@ -462,7 +520,7 @@ impl PageManager {
                     .filter(revisions::dsl::change_type.ne(change_type))
                     .order_by(revisions::dsl::revision_id.desc())
                     .select(revisions::dsl::git_commit)
-                    .first::<String>(&*self.conn)?;
+                    .first::<String>(&*self.conn.get()?)?;
 
                 let hash = GitHash::from_checked(raw_hash);
 
@@ -470,8 +528,9 @@ impl PageManager {
             };
 
             let change_type = ChangeType::Restore;
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -485,7 +544,7 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -494,7 +553,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             trace!("Removing deletion marker from pages table");
             {
@@ -505,7 +564,7 @@ impl PageManager {
 
                 diesel::update(dsl::pages.filter(dsl::page_id.eq(id)))
                     .set(dsl::deleted_at.eq(null))
-                    .execute(&*self.conn)?;
+                    .execute(&*self.conn.get()?)?;
             }
 
             Ok(revision_id)
@@ -527,6 +586,8 @@ impl PageManager {
             user,
         } = commit;
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             // Get page ID and revision ID
             let page_id = self
@@ -543,7 +604,7 @@ impl PageManager {
                 let result = revisions::table
                     .filter(revisions::dsl::git_commit.eq(hash))
                     .select(revisions::dsl::page_id)
-                    .first::<i64>(&*self.conn)
+                    .first::<i64>(&*self.conn.get()?)
                     .optional()?;
 
                 let page_id: i64 = page_id.into();
@@ -556,8 +617,9 @@ impl PageManager {
 
             // Run undo method in RevisionStore
             let change_type = ChangeType::Undo;
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -570,7 +632,67 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
+                git_commit: hash.as_ref(),
+                change_type: change_type.into(),
+            };
+
+            trace!("Inserting revision {:?} into revisions table", &model);
+            let revision_id = diesel::insert_into(revisions::table)
+                .values(&model)
+                .returning(revisions::dsl::revision_id)
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
+
+            Ok(revision_id)
+        })
+        .await
+    }
+
+    pub async fn revert(
+        &self,
+        commit: PageCommit<'_>,
+        revision: Either<RevisionId, &GitHash>,
+    ) -> Result<RevisionId> {
+        info!("Reverting to revision {:?} for {:?}", revision, commit);
+
+        let PageCommit {
+            wiki_id,
+            slug,
+            message,
+            user,
+        } = commit;
+
+        let message = self.prepare_message(message)?;
+
+        self.transaction(async {
+            let page_id = self
+                .get_page_id(wiki_id, slug)
+                .await?
+                .ok_or(Error::PageNotFound)?;
+
+            let hash = self.commit_hash(revision).await?;
+            let user_id = user.id();
+
+            // Run revert method in RevisionStore
+            //
+            // This checks that the commit is in the page's history.
+            let change_type = ChangeType::Revert;
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
+            let info = CommitInfo {
+                user_id: user.id(),
+                username: user.name(),
+                message: &commit,
+            };
+
+            let guard = self.store(wiki_id).await;
+            let store = guard.get()?;
+            let hash = store.revert(slug, &hash, info).await?;
+
+            // Insert new revision into database
+            let model = NewRevision {
+                page_id: page_id.into(),
+                user_id: user_id.into(),
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -579,7 +701,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             Ok(revision_id)
         })
@@ -601,6 +723,8 @@ impl PageManager {
             ..
         } = commit;
 
+        let message = self.prepare_message(message)?;
+
         self.transaction(async {
             trace!("Getting tag difference");
             let current_tags = {
@@ -608,7 +732,7 @@ impl PageManager {
                 pages::table
                     .find(id)
                     .select(pages::dsl::tags)
-                    .first::<Vec<String>>(&*self.conn)?
+                    .first::<Vec<String>>(&*self.conn.get()?)?
             };
 
             let (added_tags, removed_tags) = tag_diff(&current_tags, tags);
@@ -622,8 +746,9 @@ impl PageManager {
             let user_id = user.id();
             let change_type = ChangeType::Tags;
 
-            let commit = self.commit_data(wiki_id, page_id, user_id, change_type);
+            let commit = self.commit_data(wiki_id, page_id, user_id, change_type, &message);
             let info = CommitInfo {
+                user_id: user.id(),
                 username: user.name(),
                 message: &commit,
             };
@@ -635,7 +760,7 @@ impl PageManager {
             let model = NewRevision {
                 page_id: page_id.into(),
                 user_id: user_id.into(),
-                message,
+                message: &message,
                 git_commit: hash.as_ref(),
                 change_type: change_type.into(),
             };
@@ -644,7 +769,7 @@ impl PageManager {
             let revision_id = diesel::insert_into(revisions::table)
                 .values(&model)
                 .returning(revisions::dsl::revision_id)
-                .get_result::<RevisionId>(&*self.conn)?;
+                .get_result::<RevisionId>(&*self.conn.get()?)?;
 
             let model = NewTagChange {
                 revision_id: revision_id.into(),
@@ -655,14 +780,14 @@ impl PageManager {
             trace!("Inserting tag change {:?} into tag history table", &model);
             diesel::insert_into(tag_history::table)
                 .values(&model)
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
 
             tags.sort();
 
             trace!("Updating tags for page");
             diesel::update(pages::table)
                 .set(pages::dsl::tags.eq(&*tags))
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
 
             Ok(Some(revision_id))
         })
@@ -682,7 +807,7 @@ impl PageManager {
             .filter(pages::wiki_id.eq(id))
             .filter(pages::tags.contains(tags))
             .filter(pages::deleted_at.is_null())
-            .get_results::<Page>(&*self.conn)?;
+            .get_results::<Page>(&*self.conn.get()?)?;
 
         Ok(pages)
     }
@@ -699,7 +824,7 @@ impl PageManager {
             .filter(pages::slug.eq(slug))
             .filter(pages::deleted_at.is_null())
             .select(pages::page_id)
-            .first::<PageId>(&*self.conn)
+            .first::<PageId>(&*self.conn.get()?)
             .optional()?;
 
         Ok(result.is_some())
@@ -713,7 +838,7 @@ impl PageManager {
             .filter(pages::wiki_id.eq(id))
             .filter(pages::slug.eq(slug))
             .filter(pages::deleted_at.is_null())
-            .first::<Page>(&*self.conn)
+            .first::<Page>(&*self.conn.get()?)
             .optional()?;
 
         Ok(page)
@@ -725,7 +850,7 @@ impl PageManager {
         let id: i64 = page_id.into();
         let page = pages::table
             .find(id)
-            .first::<Page>(&*self.conn)
+            .first::<Page>(&*self.conn.get()?)
             .optional()?;
 
         Ok(page)
@@ -748,7 +873,7 @@ impl PageManager {
         let result = pages::table
             .find(id)
             .select((pages::dsl::wiki_id, pages::dsl::slug))
-            .first::<(WikiId, String)>(&*self.conn)
+            .first::<(WikiId, String)>(&*self.conn.get()?)
             .optional()?;
 
         let (wiki_id, slug) = match result {
@@ -760,7 +885,7 @@ impl PageManager {
             .filter(revisions::dsl::page_id.eq(id))
             .order_by(revisions::dsl::revision_id.desc())
             .select(revisions::dsl::git_commit)
-            .first::<String>(&*self.conn)?;
+            .first::<String>(&*self.conn.get()?)?;
 
         let hash = GitHash::from_checked(raw_hash);
 
@@ -819,7 +944,7 @@ impl PageManager {
         let result = revisions::table
             .find(id)
             .select(revisions::dsl::git_commit)
-            .first::<String>(&*self.conn)
+            .first::<String>(&*self.conn.get()?)
             .optional()?;
 
         match result {
@@ -880,19 +1005,120 @@ impl PageManager {
         Ok(diff)
     }
 
+    pub async fn get_revision(
+        &self,
+        wiki_id: WikiId,
+        slug: &str,
+        revision: Either<RevisionId, &GitHash>,
+    ) -> Result<RevisionInfo> {
+        info!("Getting revision for wiki ID {}, slug {}", wiki_id, slug);
+
+        let hash = self.commit_hash(revision).await?;
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+        store.get_revision(slug, &hash).await
+    }
+
+    pub async fn tag_revision(
+        &self,
+        wiki_id: WikiId,
+        slug: &str,
+        revision: Either<RevisionId, &GitHash>,
+        tag: &str,
+    ) -> Result<()> {
+        info!(
+            "Tagging revision for wiki ID {}, slug {} as '{}'",
+            wiki_id, slug, tag,
+        );
+
+        let hash = self.commit_hash(revision).await?;
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+        store.tag_revision(slug, &hash, tag).await
+    }
+
+    pub async fn get_tagged_revision(
+        &self,
+        wiki_id: WikiId,
+        slug: &str,
+        tag: &str,
+    ) -> Result<RevisionInfo> {
+        info!(
+            "Getting revision tagged '{}' for wiki ID {}, slug {}",
+            tag, wiki_id, slug,
+        );
+
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+        store.get_tagged_revision(slug, tag).await
+    }
+
     pub async fn edit_revision(&self, revision_id: RevisionId, message: &str) -> Result<()> {
         use self::revisions::dsl;
 
         info!("Editing revision message for ID {}", revision_id);
 
+        let message = self.prepare_message(message)?;
         let id: i64 = revision_id.into();
         diesel::update(dsl::revisions.filter(dsl::revision_id.eq(id)))
-            .set(dsl::message.eq(message))
-            .execute(&*self.conn)?;
+            .set(dsl::message.eq(&*message))
+            .execute(&*self.conn.get()?)?;
 
         Ok(())
     }
 
+    pub async fn get_recent_changes(
+        &self,
+        wiki_id: WikiId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+        store.recent_changes(limit, offset).await
+    }
+
+    pub async fn search_content(
+        &self,
+        wiki_id: WikiId,
+        query: &str,
+        regex: bool,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+
+        if regex {
+            store.search_content_regex(query, limit).await
+        } else {
+            store.search_content(query, limit).await
+        }
+    }
+
+    pub async fn get_contributions(
+        &self,
+        wiki_id: WikiId,
+        user_id: UserId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+        store.contributions(user_id, limit, offset).await
+    }
+
+    pub async fn get_revisions(
+        &self,
+        wiki_id: WikiId,
+        slug: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        let guard = self.store(wiki_id).await;
+        let store = guard.get()?;
+        store.list_revisions(slug, limit, offset).await
+    }
+
     pub async fn set_domain(&self, wiki_id: WikiId, new_domain: &str) -> Result<()> {
         let guard = self.store(wiki_id).await;
         let store = guard.get()?;
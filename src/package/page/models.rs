@@ -32,6 +32,7 @@ pub enum ChangeType {
     Restore,
     Rename,
     Undo,
+    Revert,
     Tags,
 }
 
@@ -46,7 +47,8 @@ impl ChangeType {
             Delete => "deleted",
             Restore => "restored",
             Rename => "renamed",
-            Undo => "reverted",
+            Undo => "undid",
+            Revert => "reverted",
             Tags => "tags",
         }
     }
@@ -65,6 +67,7 @@ impl Into<&'static str> for ChangeType {
             Restore => "restore",
             Rename => "rename",
             Undo => "undo",
+            Revert => "revert",
             Tags => "tags",
         }
     }
@@ -80,6 +83,8 @@ impl TryFrom<&'_ str> for ChangeType {
             "delete" => ChangeType::Delete,
             "restore" => ChangeType::Restore,
             "rename" => ChangeType::Rename,
+            "undo" => ChangeType::Undo,
+            "revert" => ChangeType::Revert,
             "tags" => ChangeType::Tags,
             _ => return Err(()),
         };
@@ -26,26 +26,90 @@ use crypto::util::fixed_time_eq;
 use deepwell_core::types::UserId;
 use rand::{rngs::OsRng, RngCore};
 
-const PARAM_LOGN: u8 = 6;
-const PARAM_R: u32 = 8;
-const PARAM_P: u32 = 16;
-
 type Hash = [u8; 32];
 type Salt = [u8; 16];
 
-lazy_static! {
-    static ref PARAMS: ScryptParams = ScryptParams::new(PARAM_LOGN, PARAM_R, PARAM_P);
+/// The scrypt cost used to hash new passwords.
+///
+/// Each stored hash records the parameters it was created with,
+/// so changing this does not invalidate existing passwords.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HashCost {
+    logn: u8,
+    r: u32,
+    p: u32,
+}
+
+impl HashCost {
+    /// Mirrors the assertions in `ScryptParams::new()`, returning `None`
+    /// instead of panicking if the parameters are invalid.
+    pub fn new(logn: u8, r: u32, p: u32) -> Option<Self> {
+        if logn == 0 || r == 0 || p == 0 {
+            return None;
+        }
+
+        let r128 = (r as usize).checked_mul(128)?;
+        let n = 1usize.checked_shl(u32::from(logn))?;
+        r128.checked_mul(n)?;
+        r128.checked_mul(p as usize)?;
+
+        if (logn as usize) >= (r as usize) * 16 {
+            return None;
+        }
+
+        if (r as usize).checked_mul(p as usize)? >= 0x4000_0000 {
+            return None;
+        }
+
+        Some(HashCost { logn, r, p })
+    }
+
+    #[inline]
+    pub fn logn(self) -> u8 {
+        self.logn
+    }
+
+    #[inline]
+    pub fn r(self) -> u32 {
+        self.r
+    }
+
+    #[inline]
+    pub fn p(self) -> u32 {
+        self.p
+    }
+
+    #[inline]
+    fn params(self) -> ScryptParams {
+        ScryptParams::new(self.logn, self.r, self.p)
+    }
+}
+
+impl Default for HashCost {
+    #[inline]
+    fn default() -> Self {
+        HashCost {
+            logn: 6,
+            r: 8,
+            p: 16,
+        }
+    }
 }
 
 #[inline]
-fn make_model<'a>(user_id: UserId, hash: &'a [u8], salt: &'a [u8]) -> NewPassword<'a> {
+fn make_model<'a>(
+    user_id: UserId,
+    hash: &'a [u8],
+    salt: &'a [u8],
+    cost: HashCost,
+) -> NewPassword<'a> {
     NewPassword {
         user_id: user_id.into(),
         hash,
         salt,
-        logn: PARAM_LOGN.into(),
-        param_r: PARAM_R as i32,
-        param_p: PARAM_P as i32,
+        logn: cost.logn.into(),
+        param_r: cost.r as i32,
+        param_p: cost.p as i32,
     }
 }
 
@@ -60,32 +124,73 @@ fn new_hash() -> Hash {
     [0; 32]
 }
 
-pub async fn new_password<F>(user_id: UserId, password: &[u8], f: F) -> Result<()>
+/// A salted password hash which has not been stored yet.
+pub struct PasswordHash {
+    hash: Hash,
+    salt: Salt,
+    cost: HashCost,
+}
+
+impl PasswordHash {
+    #[inline]
+    pub fn model(&self, user_id: UserId) -> NewPassword<'_> {
+        make_model(user_id, &self.hash, &self.salt, self.cost)
+    }
+}
+
+pub fn hash_password(password: &[u8], cost: HashCost) -> PasswordHash {
+    let salt = random_salt();
+    let mut hash = new_hash();
+
+    scrypt(password, &salt, &cost.params(), &mut hash);
+
+    PasswordHash { hash, salt, cost }
+}
+
+pub async fn new_password<F>(user_id: UserId, password: &[u8], cost: HashCost, f: F) -> Result<()>
 where
     F: FnOnce(NewPassword<'_>) -> Result<()>,
 {
     debug!("Creating new password for user ID {}", user_id);
 
-    let salt = random_salt();
-    let mut hash = new_hash();
-
-    scrypt(password, &salt, &*PARAMS, &mut hash);
+    let hash = hash_password(password, cost);
 
     trace!("Handing password model to consumer");
-    let model = make_model(user_id, &hash, &salt);
-    f(model)
+    f(hash.model(user_id))
+}
+
+/// Gets the cost a stored password was hashed with.
+/// Returns `None` if the stored values are invalid.
+fn record_cost(record: &Password) -> Option<HashCost> {
+    HashCost::new(record.logn()?, record.param_r()?, record.param_p()?)
+}
+
+/// Whether a stored password was hashed with a cost other than the current one.
+#[inline]
+pub fn needs_rehash(record: &Password, cost: HashCost) -> bool {
+    record_cost(record) != Some(cost)
 }
 
 pub async fn check_password(record: &Password, password: &[u8]) -> bool {
-    let params = ScryptParams::new(record.logn(), record.param_r(), record.param_p());
+    let params = match record_cost(record) {
+        Some(cost) => cost.params(),
+        None => {
+            error!("Stored scrypt parameters are invalid, failing password check");
+            return false;
+        }
+    };
+
     let mut calculated = new_hash();
 
     // If the hash length ever changes we'll need to use a dynamically-allocated Vec instead.
-    assert_eq!(
-        record.hash().len(),
-        calculated.as_ref().len(),
-        "Hash length mismatch (stored vs runtime)",
-    );
+    if record.hash().len() != calculated.as_ref().len() {
+        error!(
+            "Hash length mismatch (stored {} vs runtime {}), failing password check",
+            record.hash().len(),
+            calculated.as_ref().len(),
+        );
+        return false;
+    }
 
     debug!("Checking password validity");
     scrypt(password, record.salt(), &params, &mut calculated);
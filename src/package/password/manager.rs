@@ -18,14 +18,21 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use super::{build_blacklist, check_password, new_password};
+use super::models::{NewPassword, NewPasswordHistory, NewPasswordReset, PasswordReset};
+use super::policy::MAX_PASSWORD_LEN;
+use super::{
+    check_password, hash_password, needs_rehash, new_password, HashCost, PasswordHash,
+    PasswordPolicy,
+};
 use crate::manager_prelude::*;
-use crate::schema::passwords;
-use std::collections::HashSet;
+use crate::schema::{password_history, password_resets, passwords};
+use crate::token::{check_verifier, split_token, NewToken};
+use chrono::Duration;
+use rand::{rngs::OsRng, RngCore};
 use std::convert::TryInto;
-use std::path::Path;
 
-const MAX_PASSWORD_LEN: usize = 8192;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug, Queryable)]
 pub struct Password {
@@ -68,84 +75,176 @@ impl Password {
         &self.salt
     }
 
+    /// A stand-in for a missing password, hashed from random bytes at the given cost.
+    ///
+    /// Checking against it takes as long as a real check, but never succeeds.
+    fn decoy(cost: HashCost) -> Self {
+        let mut password = [0; 32];
+        OsRng.fill_bytes(&mut password);
+
+        let user_id = UserId::from_raw(0);
+        let hash = hash_password(&password, cost);
+        let model = hash.model(user_id);
+
+        Password {
+            user_id,
+            hash: model.hash.to_vec(),
+            salt: model.salt.to_vec(),
+            logn: model.logn,
+            param_r: model.param_r,
+            param_p: model.param_p,
+        }
+    }
+
+    /// Returns `None` if the stored field is out of bounds.
     #[inline]
-    pub fn logn(&self) -> u8 {
-        self.logn
-            .try_into()
-            .expect("Stored log_n field is out of bounds")
+    pub fn logn(&self) -> Option<u8> {
+        self.logn.try_into().ok()
     }
 
+    /// Returns `None` if the stored field is out of bounds.
     #[inline]
-    pub fn param_r(&self) -> u32 {
-        self.param_r
-            .try_into()
-            .expect("Stored param_r field is out of bounds")
+    pub fn param_r(&self) -> Option<u32> {
+        self.param_r.try_into().ok()
     }
 
+    /// Returns `None` if the stored field is out of bounds.
     #[inline]
-    pub fn param_p(&self) -> u32 {
-        self.param_p
-            .try_into()
-            .expect("Stored param_r field is out of bounds")
+    pub fn param_p(&self) -> Option<u32> {
+        self.param_p.try_into().ok()
     }
 }
 
 pub struct PasswordManager {
-    conn: Arc<PgConnection>,
-    blacklist: HashSet<String>,
+    conn: ConnectionPool,
+    policy: PasswordPolicy,
+    cost: HashCost,
+    reset_ttl: Duration,
+    decoy: Password,
+
+    #[cfg(test)]
+    decoy_checks: AtomicUsize,
 }
 
 impl PasswordManager {
-    pub fn new(conn: &Arc<PgConnection>, blacklist: Option<&Path>) -> Result<Self> {
+    #[inline]
+    pub fn new(
+        conn: &ConnectionPool,
+        policy: PasswordPolicy,
+        cost: HashCost,
+        reset_ttl: Duration,
+    ) -> Self {
         debug!("Creating password-manager service");
 
-        let conn = Arc::clone(conn);
+        let conn = conn.clone();
+        let decoy = Password::decoy(cost);
+        PasswordManager {
+            conn,
+            policy,
+            cost,
+            reset_ttl,
+            decoy,
+
+            #[cfg(test)]
+            decoy_checks: AtomicUsize::new(0),
+        }
+    }
 
-        let blacklist = match blacklist {
-            None => HashSet::new(),
-            Some(path) => {
-                debug!("Loading password blacklist from {}", path.display());
+    pub async fn set(&self, user_id: UserId, password: &str) -> Result<()> {
+        self.policy.check_error(password)?;
+        self.check_history(user_id, password).await?;
 
-                build_blacklist(path)?
-            }
-        };
+        new_password(user_id, password.as_bytes(), self.cost, |model| {
+            self.store(&model)
+        })
+        .await?;
 
-        Ok(PasswordManager { conn, blacklist })
+        Ok(())
     }
 
-    fn verify_password(&self, password: &str) -> Result<()> {
-        // To avoid computation-based DOS attacks
-        if password.len() > MAX_PASSWORD_LEN {
-            return Err(Error::NewPasswordInvalid("password too long"));
-        }
+    /// Checks the password against the policy and hashes it, without storing it.
+    ///
+    /// This allows the slow hashing to be done before starting a transaction.
+    pub fn hash(&self, password: &str) -> Result<PasswordHash> {
+        self.policy.check_error(password)?;
+
+        Ok(hash_password(password.as_bytes(), self.cost))
+    }
 
-        if password.chars().count() < 8 {
-            return Err(Error::NewPasswordInvalid(
-                "password must be at least 8 characters",
-            ));
+    /// Stores a password which was hashed earlier with `hash()`.
+    pub async fn set_hash(&self, user_id: UserId, hash: &PasswordHash) -> Result<()> {
+        debug!("Setting prepared password for user ID {}", user_id);
+
+        self.store(&hash.model(user_id))
+    }
+
+    /// Writes the password, and adds it to the user's history if that is being kept.
+    fn store(&self, model: &NewPassword) -> Result<()> {
+        use self::password_history::dsl;
+
+        let conn = &*self.conn.get()?;
+
+        diesel::insert_into(passwords::table)
+            .values(model)
+            .on_conflict(passwords::dsl::user_id)
+            .do_update()
+            .set(model)
+            .execute(conn)?;
+
+        if self.policy.history_size == 0 {
+            return Ok(());
         }
 
-        if self.blacklist.contains(password) {
-            return Err(Error::NewPasswordInvalid("password is too common"));
+        diesel::insert_into(password_history::table)
+            .values(&NewPasswordHistory::from(model))
+            .execute(conn)?;
+
+        // Only the most recent passwords need to be kept
+        let stale = password_history::table
+            .filter(dsl::user_id.eq(model.user_id))
+            .select(dsl::password_history_id)
+            .order_by(dsl::password_history_id.desc())
+            .offset(self.policy.history_size as i64)
+            .load::<i64>(conn)?;
+
+        if !stale.is_empty() {
+            diesel::delete(password_history::table)
+                .filter(dsl::password_history_id.eq_any(stale))
+                .execute(conn)?;
         }
 
         Ok(())
     }
 
-    pub async fn set(&self, user_id: UserId, password: &str) -> Result<()> {
-        self.verify_password(password)?;
+    /// Returns `PasswordReused` if the password matches any in the user's history.
+    async fn check_history(&self, user_id: UserId, password: &str) -> Result<()> {
+        use self::password_history::dsl;
 
-        new_password(user_id, password.as_bytes(), |model| {
-            diesel::insert_into(passwords::table)
-                .values(&model)
-                .on_conflict(passwords::dsl::user_id)
-                .do_update()
-                .set(&model)
-                .execute(&*self.conn)?;
+        if self.policy.history_size == 0 {
+            return Ok(());
+        }
 
-            Ok(())
-        })
-        .await?;
+        let id: i64 = user_id.into();
+        let records = password_history::table
+            .filter(dsl::user_id.eq(id))
+            .select((
+                dsl::user_id,
+                dsl::hash,
+                dsl::salt,
+                dsl::logn,
+                dsl::param_r,
+                dsl::param_p,
+            ))
+            .order_by(dsl::password_history_id.desc())
+            .limit(self.policy.history_size as i64)
+            .get_results::<Password>(&*self.conn.get()?)?;
+
+        for record in &records {
+            if check_password(record, password.as_bytes()).await {
+                warn!("User ID {} attempted to reuse a recent password", user_id);
+                return Err(Error::PasswordReused);
+            }
+        }
 
         Ok(())
     }
@@ -174,6 +273,122 @@ impl PasswordManager {
         }
     }
 
+    /// Does the same work as checking a password, for when there is no user to check.
+    ///
+    /// This way failing to log in as someone who doesn't exist takes as long as
+    /// getting a real user's password wrong, so it doesn't reveal which accounts exist.
+    pub async fn check_decoy(&self, password: &str) {
+        if password.len() > MAX_PASSWORD_LEN {
+            return;
+        }
+
+        debug!("Checking password against decoy");
+
+        #[cfg(test)]
+        self.decoy_checks.fetch_add(1, Ordering::SeqCst);
+
+        check_password(&self.decoy, password.as_bytes()).await;
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub fn decoy_checks(&self) -> usize {
+        self.decoy_checks.load(Ordering::SeqCst)
+    }
+
+    /// Creates a single-use password reset token for the given user.
+    /// Only a hash of the token is stored.
+    pub async fn create_reset_token(&self, user_id: UserId) -> Result<String> {
+        info!("Creating password reset token for user ID {}", user_id);
+
+        let token = NewToken::generate();
+        let model = NewPasswordReset {
+            selector: token.selector(),
+            user_id: user_id.into(),
+            token_hash: token.hash(),
+            expires_at: Utc::now() + self.reset_ttl,
+        };
+
+        diesel::insert_into(password_resets::table)
+            .values(&model)
+            .execute(&*self.conn.get()?)?;
+
+        Ok(token.into_token())
+    }
+
+    /// Sets a new password using the given reset token, then marks it as used.
+    /// Returns the ID of the user whose password was changed.
+    pub async fn redeem_reset_token(&self, token: &str, new_password: &str) -> Result<UserId> {
+        debug!("Redeeming password reset token");
+
+        let (selector, verifier) = split_token(token).ok_or(Error::InvalidResetToken)?;
+
+        self.transaction(async {
+            use self::password_resets::dsl;
+
+            let record = password_resets::table
+                .find(selector)
+                .select((
+                    dsl::user_id,
+                    dsl::token_hash,
+                    dsl::expires_at,
+                    dsl::redeemed_at,
+                ))
+                .for_update()
+                .first::<PasswordReset>(&*self.conn.get()?)
+                .optional()?
+                .ok_or(Error::InvalidResetToken)?;
+
+            if !check_verifier(&record.token_hash, verifier) {
+                warn!("Password reset token mismatch");
+                return Err(Error::InvalidResetToken);
+            }
+
+            if record.redeemed_at.is_some() {
+                warn!(
+                    "Password reset token for user ID {} was already used",
+                    record.user_id,
+                );
+                return Err(Error::InvalidResetToken);
+            }
+
+            let now = Utc::now();
+            if record.expires_at <= now {
+                warn!(
+                    "Password reset token for user ID {} has expired",
+                    record.user_id,
+                );
+                return Err(Error::InvalidResetToken);
+            }
+
+            info!("Resetting password for user ID {}", record.user_id);
+            self.set(record.user_id, new_password).await?;
+
+            diesel::update(password_resets::table.find(selector))
+                .set(dsl::redeemed_at.eq(now))
+                .execute(&*self.conn.get()?)?;
+
+            Ok(record.user_id)
+        })
+        .await
+    }
+
+    /// Removes the user's password, along with its history and any reset tokens.
+    pub async fn remove_all(&self, user_id: UserId) -> Result<()> {
+        info!("Removing all password data for user ID {}", user_id);
+
+        let id: i64 = user_id.into();
+        let conn = &*self.conn.get()?;
+
+        diesel::delete(passwords::table.find(id)).execute(conn)?;
+        diesel::delete(password_history::table.filter(password_history::user_id.eq(id)))
+            .execute(conn)?;
+        diesel::delete(password_resets::table.filter(password_resets::user_id.eq(id)))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
     async fn check_internal(&self, user_id: UserId, password: &str) -> Result<()> {
         // To avoid computation-based DOS attacks
         if password.len() > MAX_PASSWORD_LEN {
@@ -183,16 +398,61 @@ impl PasswordManager {
         let id: i64 = user_id.into();
         let record = passwords::table
             .find(id)
-            .first::<Password>(&*self.conn)
+            .first::<Password>(&*self.conn.get()?)
             .optional()?;
 
-        let record = record.ok_or(Error::AuthenticationFailed)?;
+        let record = match record {
+            Some(record) => record,
+            None => {
+                self.check_decoy(password).await;
+                return Err(Error::AuthenticationFailed);
+            }
+        };
+
         let password = password.as_bytes();
-        if check_password(&record, password).await {
-            Ok(())
-        } else {
-            Err(Error::AuthenticationFailed)
+        if !check_password(&record, password).await {
+            return Err(Error::AuthenticationFailed);
         }
+
+        if needs_rehash(&record, self.cost) {
+            self.rehash(user_id, password)?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-hashes a verified password with the current cost.
+    ///
+    /// The password itself is unchanged, so the user's history is left alone.
+    fn rehash(&self, user_id: UserId, password: &[u8]) -> Result<()> {
+        info!("Upgrading password hash cost for user ID {}", user_id);
+
+        let hash = hash_password(password, self.cost);
+        let id: i64 = user_id.into();
+
+        diesel::update(passwords::table.find(id))
+            .set(&hash.model(user_id))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub async fn get_cost(&self, user_id: UserId) -> Result<Option<HashCost>> {
+        let id: i64 = user_id.into();
+        let record = passwords::table
+            .find(id)
+            .first::<Password>(&*self.conn.get()?)
+            .optional()?;
+
+        Ok(record.map(|record| {
+            HashCost::new(
+                record.logn().unwrap(),
+                record.param_r().unwrap(),
+                record.param_p().unwrap(),
+            )
+            .expect("Stored hash cost is invalid")
+        }))
     }
 }
 
@@ -202,6 +462,9 @@ impl Debug for PasswordManager {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("PasswordManager")
             .field("conn", &"PgConnection { .. }")
+            .field("policy", &self.policy)
+            .field("cost", &self.cost)
+            .field("reset_ttl", &self.reset_ttl)
             .finish()
     }
 }
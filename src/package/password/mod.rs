@@ -22,11 +22,14 @@ mod blacklist;
 mod crypto;
 mod manager;
 mod models;
+mod policy;
 
 #[cfg(test)]
 mod test;
 
+pub use self::crypto::HashCost;
 pub use self::manager::*;
+pub use self::policy::PasswordPolicy;
 
 use self::blacklist::build_blacklist;
 use self::crypto::*;
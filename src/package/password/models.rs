@@ -18,7 +18,9 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use crate::schema::passwords;
+use crate::schema::{password_history, password_resets, passwords};
+use chrono::prelude::*;
+use deepwell_core::types::UserId;
 
 #[derive(Debug, Insertable, AsChangeset)]
 #[table_name = "passwords"]
@@ -30,3 +32,44 @@ pub struct NewPassword<'a> {
     pub param_r: i32,
     pub param_p: i32,
 }
+
+#[derive(Debug, Insertable)]
+#[table_name = "password_history"]
+pub struct NewPasswordHistory<'a> {
+    pub user_id: i64,
+    pub hash: &'a [u8],
+    pub salt: &'a [u8],
+    pub logn: i16,
+    pub param_r: i32,
+    pub param_p: i32,
+}
+
+impl<'a> From<&NewPassword<'a>> for NewPasswordHistory<'a> {
+    fn from(model: &NewPassword<'a>) -> Self {
+        NewPasswordHistory {
+            user_id: model.user_id,
+            hash: model.hash,
+            salt: model.salt,
+            logn: model.logn,
+            param_r: model.param_r,
+            param_p: model.param_p,
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "password_resets"]
+pub struct NewPasswordReset<'a> {
+    pub selector: &'a str,
+    pub user_id: i64,
+    pub token_hash: &'a [u8],
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct PasswordReset {
+    pub user_id: UserId,
+    pub token_hash: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+    pub redeemed_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,145 @@
+/*
+ * password/policy.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::build_blacklist;
+use crate::{Error, Result, StdResult};
+use deepwell_core::error::WeakPasswordReason;
+use std::collections::HashSet;
+use std::fmt::{self, Debug};
+use std::path::Path;
+use std::sync::Arc;
+
+/// To avoid computation-based DOS attacks, in bytes.
+pub const MAX_PASSWORD_LEN: usize = 8192;
+
+/// Requirements for new passwords.
+///
+/// Cloning this is cheap, as the blacklist is shared.
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    /// The minimum number of characters. Empty passwords are always rejected.
+    pub min_length: usize,
+
+    /// Whether a lowercase letter is required.
+    pub require_lowercase: bool,
+
+    /// Whether an uppercase letter is required.
+    pub require_uppercase: bool,
+
+    /// Whether a digit is required.
+    pub require_digit: bool,
+
+    /// Whether a character which is not a letter or digit is required.
+    pub require_symbol: bool,
+
+    /// How many of the user's most recent passwords cannot be reused.
+    /// Zero disables this check.
+    pub history_size: usize,
+
+    blacklist: Arc<HashSet<String>>,
+}
+
+impl PasswordPolicy {
+    /// Loads a list of disallowed passwords, one per line.
+    pub fn load_blacklist(&mut self, path: &Path) -> Result<()> {
+        debug!("Loading password blacklist from {}", path.display());
+
+        self.blacklist = Arc::new(build_blacklist(path)?);
+        Ok(())
+    }
+
+    /// Checks a new password, returning the first rule it fails.
+    pub fn check(&self, password: &str) -> StdResult<(), WeakPasswordReason> {
+        use self::WeakPasswordReason::*;
+
+        if password.len() > MAX_PASSWORD_LEN {
+            return Err(TooLong(MAX_PASSWORD_LEN));
+        }
+
+        let min_length = self.min_length.max(1);
+        if password.chars().count() < min_length {
+            return Err(TooShort(min_length));
+        }
+
+        macro_rules! require {
+            ($enabled:expr, $predicate:expr, $reason:expr) => {
+                if $enabled && !password.chars().any($predicate) {
+                    return Err($reason);
+                }
+            };
+        }
+
+        require!(self.require_lowercase, char::is_lowercase, MissingLowercase);
+        require!(self.require_uppercase, char::is_uppercase, MissingUppercase);
+        require!(
+            self.require_digit,
+            |c: char| c.is_ascii_digit(),
+            MissingDigit
+        );
+        require!(
+            self.require_symbol,
+            |c: char| !c.is_alphanumeric(),
+            MissingSymbol
+        );
+
+        if self.blacklist.contains(password) {
+            return Err(TooCommon);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn check_error(&self, password: &str) -> Result<()> {
+        self.check(password).map_err(Error::PasswordTooWeak)
+    }
+}
+
+impl Default for PasswordPolicy {
+    #[inline]
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            history_size: 0,
+            blacklist: Arc::new(HashSet::new()),
+        }
+    }
+}
+
+impl Debug for PasswordPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PasswordPolicy")
+            .field("min_length", &self.min_length)
+            .field("require_lowercase", &self.require_lowercase)
+            .field("require_uppercase", &self.require_uppercase)
+            .field("require_digit", &self.require_digit)
+            .field("require_symbol", &self.require_symbol)
+            .field("history_size", &self.history_size)
+            .field(
+                "blacklist",
+                &format_args!("[{} entries]", self.blacklist.len()),
+            )
+            .finish()
+    }
+}
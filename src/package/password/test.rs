@@ -18,8 +18,9 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use super::{check_password, new_password, Password};
+use super::{check_password, needs_rehash, new_password, HashCost, Password, PasswordPolicy};
 use async_std::task;
+use deepwell_core::error::WeakPasswordReason;
 use deepwell_core::types::UserId;
 
 #[test]
@@ -39,7 +40,7 @@ async fn crypto_inner() {
     let mut salt = Vec::new();
     let (mut logn, mut param_r, mut param_p) = (0, 0, 0);
 
-    new_password(user, b"apples and bananas", |model| {
+    new_password(user, b"apples and bananas", HashCost::default(), |model| {
         hash.extend_from_slice(model.hash);
         salt.extend_from_slice(model.salt);
 
@@ -65,4 +66,85 @@ async fn crypto_inner() {
     check!("", false);
     check!("apples and bananas", true);
     check!("apples and banana", false);
+
+    // Only hashes with a different cost need upgrading
+    assert!(!needs_rehash(&record, HashCost::default()));
+    assert!(needs_rehash(&record, HashCost::new(4, 8, 1).unwrap()));
+
+    // Corrupted records fail instead of panicking
+    let hash = record.hash();
+    let salt = record.salt();
+
+    macro_rules! check_corrupted {
+        ($hash:expr, $logn:expr, $param_r:expr, $param_p:expr) => {{
+            let record = Password::new(
+                user,
+                $hash.to_vec(),
+                salt.to_vec(),
+                $logn,
+                $param_r,
+                $param_p,
+            );
+            let actual = check_password(&record, b"apples and bananas").await;
+            assert!(!actual, "Corrupted password record passed check");
+        }};
+    }
+
+    check_corrupted!(&hash[..16], logn, param_r, param_p);
+    check_corrupted!(hash, 300, param_r, param_p);
+    check_corrupted!(hash, 0, param_r, param_p);
+    check_corrupted!(hash, logn, -1, param_p);
+    check_corrupted!(hash, logn, param_r, 0);
+}
+
+#[test]
+fn hash_cost() {
+    let cost = HashCost::default();
+    assert_eq!(HashCost::new(cost.logn(), cost.r(), cost.p()), Some(cost));
+    assert!(HashCost::new(14, 8, 1).is_some());
+
+    assert_eq!(HashCost::new(0, 8, 1), None);
+    assert_eq!(HashCost::new(6, 0, 1), None);
+    assert_eq!(HashCost::new(6, 8, 0), None);
+    assert_eq!(HashCost::new(16, 1, 1), None);
+    assert_eq!(HashCost::new(200, 8, 1), None);
+}
+
+#[test]
+fn policy() {
+    use self::WeakPasswordReason::*;
+
+    let mut policy = PasswordPolicy::default();
+
+    macro_rules! check {
+        ($password:expr, $expected:expr) => {{
+            let password: &str = $password;
+            assert_eq!(policy.check(password), $expected, "{:?}", password);
+        }};
+    }
+
+    // Default policy
+    check!("", Err(TooShort(8)));
+    check!("letmein", Err(TooShort(8)));
+    check!("ユニコードのパスワード", Ok(()));
+    check!("blackmoonhowls", Ok(()));
+    check!(&"a".repeat(8193), Err(TooLong(8192)));
+
+    // Empty passwords are always rejected
+    policy.min_length = 0;
+    check!("", Err(TooShort(1)));
+    check!("a", Ok(()));
+
+    // Character classes
+    policy.min_length = 8;
+    policy.require_lowercase = true;
+    policy.require_uppercase = true;
+    policy.require_digit = true;
+    policy.require_symbol = true;
+
+    check!("BLACKMOON", Err(MissingLowercase));
+    check!("blackmoon", Err(MissingUppercase));
+    check!("BlackMoon", Err(MissingDigit));
+    check!("BlackMoon9", Err(MissingSymbol));
+    check!("BlackMoon-9", Ok(()));
 }
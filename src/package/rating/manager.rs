@@ -60,15 +60,15 @@ impl RatingHistory {
 }
 
 pub struct RatingManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
 }
 
 impl RatingManager {
     #[inline]
-    pub fn new(conn: &Arc<PgConnection>) -> Self {
+    pub fn new(conn: &ConnectionPool) -> Self {
         debug!("Creating rating-manager service");
 
-        let conn = Arc::clone(conn);
+        let conn = conn.clone();
         RatingManager { conn }
     }
 
@@ -83,7 +83,7 @@ impl RatingManager {
         // let rows = ratings::table
         //     .filter(ratings::page_id.eq(id))
         //     .select((ratings::rating, count(ratings::user_id)))
-        //     .get_results::<(i16, i64)>(&*self.conn)?;
+        //     .get_results::<(i16, i64)>(&*self.conn.get()?)?;
         // ```
         //
         // However diesel does not currently support queries across
@@ -104,7 +104,7 @@ impl RatingManager {
         let ratings = ratings::table
             .filter(ratings::page_id.eq(id))
             .select(ratings::rating)
-            .get_results::<i16>(&*self.conn)?;
+            .get_results::<i16>(&*self.conn.get()?)?;
 
         // Increment each rating for each occurrence
         let mut votes = Map::new();
@@ -129,14 +129,14 @@ impl RatingManager {
                 .on_conflict((ratings::dsl::page_id, ratings::dsl::user_id))
                 .do_update()
                 .set(ratings::dsl::rating.eq(rating))
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
 
             trace!("Inserting rating into rating history");
             let model = NewRatingHistory::from(model);
             let rating_id = diesel::insert_into(ratings_history::table)
                 .values(&model)
                 .returning(ratings_history::dsl::rating_id)
-                .get_result::<RatingId>(&*self.conn)?;
+                .get_result::<RatingId>(&*self.conn.get()?)?;
 
             Ok(rating_id)
         })
@@ -152,9 +152,9 @@ impl RatingManager {
             let rows = diesel::delete(ratings::table)
                 .filter(ratings::page_id.eq(page_id))
                 .filter(ratings::user_id.eq(user_id))
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
 
-            if !rows_to_result(rows) {
+            if !rows_to_result(rows)? {
                 return Ok(None);
             }
 
@@ -167,7 +167,7 @@ impl RatingManager {
             let rating_id = diesel::insert_into(ratings_history::table)
                 .values(&model)
                 .returning(ratings_history::dsl::rating_id)
-                .get_result::<RatingId>(&*self.conn)?;
+                .get_result::<RatingId>(&*self.conn.get()?)?;
 
             Ok(Some(rating_id))
         })
@@ -191,7 +191,7 @@ impl RatingManager {
             .filter(ratings_history::page_id.eq(page_id))
             .filter(ratings_history::user_id.eq(user_id))
             .order_by(ratings_history::created_at.asc())
-            .load::<RatingHistory>(&*self.conn)?;
+            .load::<RatingHistory>(&*self.conn.get()?)?;
 
         Ok(result)
     }
@@ -213,7 +213,7 @@ impl RatingManager {
             .filter(ratings_history::page_id.eq(page_id))
             .filter(ratings_history::user_id.eq(user_id))
             .order_by(ratings_history::created_at.asc())
-            .first::<RatingHistory>(&*self.conn)
+            .first::<RatingHistory>(&*self.conn.get()?)
             .optional()?;
 
         Ok(result)
@@ -225,7 +225,7 @@ impl RatingManager {
         let id: i64 = rating_id.into();
         let result = ratings_history::table
             .find(id)
-            .first::<RatingHistory>(&*self.conn)
+            .first::<RatingHistory>(&*self.conn.get()?)
             .optional()?;
 
         Ok(result)
@@ -0,0 +1,954 @@
+/*
+ * revision/git.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::store::{check_normal, check_tag, MAX_MATCHES_PER_FILE};
+use super::{CommitInfo, OwnedBytes, RevisionStore};
+use crate::{Error, Result};
+use async_std::fs::{self, File};
+use async_std::prelude::*;
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use deepwell_core::models::{Blame, GitHash, RevisionInfo, SearchHit, REVISION_LOG_FORMAT};
+use deepwell_core::types::UserId;
+use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::str;
+
+macro_rules! arguments {
+    ($($x:expr), *) => {{
+        let mut arguments = array_vec!([&OsStr; 16]);
+
+        $(
+            arguments.push(OsStr::new($x));
+        )*
+
+        arguments
+    }};
+    ($($x:expr,)*) => (arguments![$($x),*]);
+}
+
+macro_rules! convert_utf8 {
+    ($bytes:expr) => {
+        String::from_utf8($bytes.into_vec())?
+    };
+}
+
+// The lock covers the whole repository rather than each page, since every
+// commit moves HEAD and uses the shared index. Reads only look at objects
+// which are already committed, so any number of them can run at once,
+// while a write waits for them to finish and excludes everything else.
+//
+// Note: this only maintains a lock per DEEPWELL process.
+// If there are multiple processes working on the same repositories,
+// an external locking mechanism (probably based on the exclusive
+// creation of a lock file) will need to be implemented.
+macro_rules! read_lock {
+    ($self:expr) => {
+        &$self.lock.read().await
+    };
+}
+
+macro_rules! write_lock {
+    ($self:expr) => {
+        &mut $self.lock.write().await
+    };
+}
+
+/// The identity git records as the author and committer of a change.
+#[derive(Debug)]
+struct CommitAuthor {
+    name: String,
+    email: String,
+}
+
+/// An object that can't be copied or cloned for the `RwLock`.
+///
+/// Helpers take a reference to it to show that the lock is held.
+/// The ones which write files or commits take `&mut`, so they can
+/// only be called while holding the write lock.
+#[derive(Debug)]
+struct RevisionBlock;
+
+/// Stores page contents and their histories in a git repository.
+#[derive(Debug)]
+pub struct GitStore {
+    lock: RwLock<RevisionBlock>,
+    repo: PathBuf,
+    domain: RwLock<String>,
+}
+
+impl GitStore {
+    /// Creates a new revision store using the given repository and domain name.
+    ///
+    /// The domain name should not have a protocol but allows permit subdomains.
+    #[inline]
+    pub fn new<P, S>(repo: P, domain: S) -> Self
+    where
+        P: Into<PathBuf>,
+        S: Into<String>,
+    {
+        let lock = RwLock::new(RevisionBlock);
+        let repo = repo.into();
+        let domain = domain.into();
+
+        info!(
+            "Creating new revision store for repository {}, domain {}",
+            repo.display(),
+            domain,
+        );
+
+        let domain = RwLock::new(domain);
+
+        GitStore { lock, repo, domain }
+    }
+
+    // Filesystem helpers
+    fn get_path(&self, slug: &str, absolute: bool) -> PathBuf {
+        trace!(
+            "Converting slug '{}' to path (absolute: {})",
+            slug,
+            absolute,
+        );
+
+        let filename = {
+            let mut filename = String::new();
+
+            for part in slug.split(':') {
+                filename.push_str(part);
+                filename.push('$');
+            }
+
+            filename.pop();
+            filename
+        };
+
+        let mut path = PathBuf::new();
+
+        if absolute {
+            path.push(&self.repo);
+        }
+
+        path.push(&filename);
+        path.set_extension("ftml");
+        path
+    }
+
+    async fn read_file(&self, _guard: &RevisionBlock, slug: &str) -> Result<Option<String>> {
+        let path = self.get_path(slug, true);
+
+        debug!("Reading file from {}", path.display());
+
+        let mut file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(error) => {
+                use std::io::ErrorKind;
+
+                return match error.kind() {
+                    ErrorKind::NotFound => Ok(None),
+                    _ => Err(Error::from(error)),
+                };
+            }
+        };
+
+        let mut content = String::new();
+        file.read_to_string(&mut content).await?;
+        Ok(Some(content))
+    }
+
+    async fn write_file(
+        &self,
+        _guard: &mut RevisionBlock,
+        slug: &str,
+        content: &str,
+    ) -> Result<()> {
+        let path = self.get_path(slug, true);
+
+        debug!("Writing {} bytes to {}", content.len(), path.display());
+
+        let mut file = File::create(path).await?;
+        let bytes = content.as_bytes();
+        file.write_all(bytes).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, _guard: &mut RevisionBlock, slug: &str) -> Result<Option<()>> {
+        let path = self.get_path(slug, true);
+
+        debug!("Removing file {}", path.display());
+
+        match fs::remove_file(path).await {
+            Ok(_) => (),
+            Err(error) => {
+                use std::io::ErrorKind;
+
+                return match error.kind() {
+                    ErrorKind::NotFound => Ok(None),
+                    _ => Err(Error::from(error)),
+                };
+            }
+        }
+
+        Ok(Some(()))
+    }
+
+    /// Gets the name of the git tag for a page, such as `page/fragment$scp-002/published`.
+    ///
+    /// Tags are kept under the page's filename so that the same tag name
+    /// can be used on different pages.
+    fn tag_name(&self, slug: &str, tag: &str) -> String {
+        let path = self.get_path(slug, false);
+        let stem = path
+            .file_stem()
+            .expect("Page path has no filename")
+            .to_string_lossy();
+
+        format!("page/{}/{}", stem, tag)
+    }
+
+    // Argument helpers
+    async fn author(&self, name: &str, user_id: Option<UserId>) -> CommitAuthor {
+        let domain = self.domain.read().await;
+
+        // The user ID is encoded in the email so it can be read back from the history
+        let email = match user_id {
+            Some(id) => format!("user-{}@{}", id, domain),
+            None => format!("noreply@{}", domain),
+        };
+
+        CommitAuthor {
+            name: name.to_string(),
+            email,
+        }
+    }
+
+    fn arg_message(&self, message: &str) -> String {
+        format!("--message={}", message)
+    }
+
+    // Process helpers
+    fn repo(&self) -> OsString {
+        self.repo.as_os_str().to_os_string()
+    }
+
+    async fn spawn(&self, _guard: &RevisionBlock, arguments: &[&OsStr]) -> Result<()> {
+        super::spawn(self.repo(), arguments).await
+    }
+
+    async fn spawn_as(
+        &self,
+        _guard: &mut RevisionBlock,
+        arguments: &[&OsStr],
+        author: &CommitAuthor,
+    ) -> Result<()> {
+        // Passed through the environment so git sees them as-is
+        let environment = [
+            (OsStr::new("GIT_AUTHOR_NAME"), OsStr::new(&author.name)),
+            (OsStr::new("GIT_AUTHOR_EMAIL"), OsStr::new(&author.email)),
+            (OsStr::new("GIT_COMMITTER_NAME"), OsStr::new(&author.name)),
+            (OsStr::new("GIT_COMMITTER_EMAIL"), OsStr::new(&author.email)),
+        ];
+
+        super::spawn_env(self.repo(), arguments, &environment).await
+    }
+
+    async fn spawn_output(
+        &self,
+        _guard: &RevisionBlock,
+        arguments: &[&OsStr],
+    ) -> Result<OwnedBytes> {
+        super::spawn_output(self.repo(), arguments).await
+    }
+
+    async fn spawn_search(
+        &self,
+        _guard: &RevisionBlock,
+        arguments: &[&OsStr],
+    ) -> Result<OwnedBytes> {
+        super::spawn_search(self.repo(), arguments).await
+    }
+
+    // Git helper
+    async fn get_commit(&self, guard: &RevisionBlock) -> Result<GitHash> {
+        debug!("Getting current HEAD commit");
+
+        let args = arguments!["git", "rev-parse", "--verify", "HEAD"];
+
+        let digest_bytes = self.spawn_output(guard, &args).await?;
+        let digest = str::from_utf8(&digest_bytes)
+            .map_err(|_| Error::StaticMsg("git hash wasn't valid UTF-8"))?;
+
+        let hash = GitHash::try_from(digest)
+            .map_err(|_| Error::StaticMsg("unable to parse git hash from output"))?;
+
+        Ok(hash)
+    }
+
+    /// Ensures the commit exists and modified the file at the given path.
+    async fn check_page_commit(
+        &self,
+        guard: &RevisionBlock,
+        path: &Path,
+        hash: &GitHash,
+    ) -> Result<()> {
+        debug!("Checking that commit {} changed {}", hash, path.display());
+
+        let spec = format!("{}^{{commit}}", hash);
+        let args = arguments!["git", "cat-file", "-e", &spec];
+        match self.spawn(guard, &args).await {
+            Ok(_) => (),
+            Err(Error::Revision { .. }) => return Err(Error::RevisionNotFound),
+            Err(error) => return Err(error),
+        }
+
+        // Finds the latest commit for the page as of this one
+        let args = arguments![
+            "git",
+            "log",
+            "--max-count=1",
+            "--format=%H",
+            hash,
+            "--",
+            path
+        ];
+        let output = self.spawn_output(guard, &args).await?;
+        let output =
+            str::from_utf8(&output).map_err(|_| Error::StaticMsg("git hash wasn't valid UTF-8"))?;
+
+        if output.trim() == hash.as_str() {
+            Ok(())
+        } else {
+            Err(Error::RevisionPageMismatch)
+        }
+    }
+
+    #[cfg(test)]
+    async fn check_clean(&self, guard: &RevisionBlock) {
+        debug!("Checking if repository is clean");
+
+        let args = arguments!["git", "status", "--porcelain"];
+        let output = self
+            .spawn_output(guard, &args)
+            .await
+            .expect("Unable to get git status");
+
+        if !output.is_empty() {
+            panic!(
+                "Git repository is not clean:\n{}",
+                String::from_utf8_lossy(&output),
+            );
+        }
+    }
+
+    #[cfg(not(test))]
+    async fn check_clean(&self, _guard: &RevisionBlock) {}
+
+    /// Gets the information for a commit which is known to have changed the given path.
+    async fn revision_info(
+        &self,
+        guard: &RevisionBlock,
+        path: &Path,
+        hash: &GitHash,
+    ) -> Result<RevisionInfo> {
+        let mut revisions = self.log(guard, &arguments![hash, "--", path], 1, 0).await?;
+
+        // The initial commit is skipped by log(), but never changes a page
+        revisions.pop().ok_or(Error::RevisionNotFound)
+    }
+
+    async fn search(&self, query: &str, regex: bool, limit: usize) -> Result<Vec<SearchHit>> {
+        info!(
+            "Searching page contents for '{}' (regex: {}, limit {})",
+            query, regex, limit,
+        );
+
+        // Would match every line
+        if query.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mode = if regex {
+            "--extended-regexp"
+        } else {
+            "--fixed-strings"
+        };
+
+        // Git stops reading each file once it has enough lines for it,
+        // though the total limit can only be applied to the output.
+        let max_count = format!("--max-count={}", MAX_MATCHES_PER_FILE);
+
+        // The query is passed with -e so it can't be read as an option
+        let guard = read_lock!(self);
+        let args = arguments![
+            "git",
+            "grep",
+            "-z",
+            "--line-number",
+            "-I",
+            &max_count,
+            mode,
+            "-e",
+            query,
+            "HEAD",
+            "--",
+            "*.ftml",
+        ];
+
+        let output = self.spawn_search(guard, &args).await?;
+        let hits = SearchHit::from_grep(&output, MAX_MATCHES_PER_FILE, limit)?;
+        self.check_clean(guard).await;
+
+        Ok(hits)
+    }
+
+    async fn log(
+        &self,
+        guard: &RevisionBlock,
+        filter: &[&OsStr],
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        let max_count = format!("--max-count={}", limit);
+        let skip = format!("--skip={}", offset);
+        let mut args = arguments![
+            "git",
+            "log",
+            "-z",
+            "--name-status",
+            "-M",
+            "--min-parents=1",
+            REVISION_LOG_FORMAT,
+            &max_count,
+            &skip,
+        ];
+
+        args.extend(filter.iter().copied());
+
+        let raw_log = self.spawn_output(guard, &args).await?;
+        let revisions = RevisionInfo::from_log(&raw_log)?;
+
+        Ok(revisions)
+    }
+
+    async fn vacuum_internal(&self, gc_argument: &str) -> Result<usize> {
+        // Doesn't obtain the lock since this is intended to run in the background
+        macro_rules! run {
+            ($call:ident, $arguments:expr) => {
+                super::$call(self.repo(), &$arguments).await?
+            };
+        }
+
+        let args = arguments!["git", "gc", gc_argument];
+        run!(spawn, args);
+
+        let args = arguments!["git", "prune", "-v"];
+        let output = run!(spawn_output, args);
+        let pruned = if output.is_empty() {
+            0
+        } else {
+            output.split(|&c| c == b'\n').count()
+        };
+
+        Ok(pruned)
+    }
+}
+
+#[async_trait]
+impl RevisionStore for GitStore {
+    #[cold]
+    async fn initial_commit(&self) -> Result<()> {
+        info!("Initializing new git repository");
+
+        let guard = write_lock!(self);
+        let args = arguments!["git", "init"];
+        self.spawn(guard, &args).await?;
+
+        let author = self.author("DEEPWELL", None).await;
+        let message = self.arg_message("Initial commit");
+        let args = arguments!["git", "commit", "--allow-empty", &message];
+
+        self.spawn_as(guard, &args, &author).await?;
+        self.check_clean(guard).await;
+
+        Ok(())
+    }
+
+    async fn commit(
+        &self,
+        slug: &str,
+        content: Option<&str>,
+        info: CommitInfo<'_>,
+    ) -> Result<GitHash> {
+        info!(
+            "Committing file changes for slug '{}' ({} bytes)",
+            slug,
+            content.map(|b| b.len()).unwrap_or(0),
+        );
+
+        check_normal(slug)?;
+        let guard = write_lock!(self);
+
+        if let Some(content) = content {
+            self.write_file(guard, slug, content).await?;
+        }
+
+        let path = self.get_path(slug, false);
+        let args = arguments!["git", "add", &path];
+        self.spawn(guard, &args).await?;
+
+        let author = self.author(info.username, Some(info.user_id)).await;
+        let message = self.arg_message(info.message);
+        let args = arguments!["git", "commit", "--allow-empty", &message, "--", &path,];
+        self.spawn_as(guard, &args, &author).await?;
+
+        let commit = self.get_commit(guard).await?;
+        self.check_clean(guard).await;
+
+        Ok(commit)
+    }
+
+    async fn empty_commit(&self, info: CommitInfo<'_>) -> Result<GitHash> {
+        info!("Creating empty commit");
+
+        let guard = write_lock!(self);
+        let author = self.author(info.username, Some(info.user_id)).await;
+        let message = self.arg_message(info.message);
+
+        let args = arguments!["git", "commit", "--allow-empty", &message];
+        self.spawn_as(guard, &args, &author).await?;
+
+        let commit = self.get_commit(guard).await?;
+        self.check_clean(guard).await;
+
+        Ok(commit)
+    }
+
+    async fn rename(
+        &self,
+        old_slug: &str,
+        new_slug: &str,
+        info: CommitInfo<'_>,
+    ) -> Result<GitHash> {
+        info!("Renaming file for slug '{}' -> '{}'", old_slug, new_slug);
+
+        check_normal(old_slug)?;
+        check_normal(new_slug)?;
+        let guard = write_lock!(self);
+
+        let new_path = self.get_path(new_slug, true);
+        if new_path.exists() {
+            return Err(Error::PageExists);
+        }
+
+        let old_path = self.get_path(old_slug, false);
+        let new_path = self.get_path(new_slug, false);
+        let args = arguments!["git", "mv", "--", &old_path, &new_path];
+        self.spawn(guard, &args).await?;
+
+        let author = self.author(info.username, Some(info.user_id)).await;
+        let message = self.arg_message(info.message);
+        let args = arguments!["git", "commit", &message, "--", &old_path, &new_path];
+        self.spawn_as(guard, &args, &author).await?;
+
+        let commit = self.get_commit(guard).await?;
+        self.check_clean(guard).await;
+
+        Ok(commit)
+    }
+
+    async fn remove(&self, slug: &str, info: CommitInfo<'_>) -> Result<Option<GitHash>> {
+        info!("Removing file for slug '{}' (info: {:?})", slug, info);
+
+        check_normal(slug)?;
+        let guard = write_lock!(self);
+
+        let removed = self.remove_file(guard, slug).await?;
+        if removed.is_none() {
+            return Ok(None);
+        }
+
+        let author = self.author(info.username, Some(info.user_id)).await;
+        let message = self.arg_message(info.message);
+        let path = self.get_path(slug, false);
+        let args = arguments!["git", "commit", &message, "--", &path];
+
+        self.spawn_as(guard, &args, &author).await?;
+
+        let commit = self.get_commit(guard).await.map(Some)?;
+        self.check_clean(guard).await;
+
+        Ok(commit)
+    }
+
+    async fn restore(
+        &self,
+        slug: &str,
+        old_slug: &str,
+        hash: &GitHash,
+        info: CommitInfo<'_>,
+    ) -> Result<GitHash> {
+        info!(
+            "Restoring file '{}' from {} onto '{}' (info: {:?})",
+            old_slug, hash, slug, info,
+        );
+
+        check_normal(slug)?;
+        check_normal(old_slug)?;
+
+        let guard = write_lock!(self);
+
+        // Get old page content
+        let content = {
+            let path = self.get_path(old_slug, false);
+            let spec = format!("{}:{}", hash, path.display());
+            let args = arguments!["git", "show", "--format=%B", &spec];
+
+            match self.spawn_output(guard, &args).await {
+                Ok(bytes) => Ok(convert_utf8!(bytes)),
+                Err(Error::Revision { .. }) => Err(Error::PageNotFound),
+                Err(error) => Err(error),
+            }
+        }?;
+
+        // Write and commit contents
+        self.write_file(guard, slug, &content).await?;
+
+        let path = self.get_path(slug, false);
+        let args = arguments!["git", "add", &path];
+        self.spawn(guard, &args).await?;
+
+        let author = self.author(info.username, Some(info.user_id)).await;
+        let message = self.arg_message(info.message);
+        let args = arguments!["git", "commit", "--allow-empty", &message, "--", &path,];
+        self.spawn_as(guard, &args, &author).await?;
+
+        let commit = self.get_commit(guard).await?;
+        self.check_clean(guard).await;
+
+        Ok(commit)
+    }
+
+    async fn revert(&self, slug: &str, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash> {
+        info!("Reverting file '{}' to {} (info: {:?})", slug, hash, info);
+
+        check_normal(slug)?;
+        let guard = write_lock!(self);
+        let path = self.get_path(slug, false);
+
+        self.check_page_commit(guard, &path, hash).await?;
+
+        // Get old page content
+        let content = {
+            let spec = format!("{}:{}", hash, path.display());
+            let args = arguments!["git", "show", "--format=%B", &spec];
+
+            match self.spawn_output(guard, &args).await {
+                Ok(bytes) => Ok(convert_utf8!(bytes)),
+                Err(Error::Revision { .. }) => Err(Error::PageNotFound),
+                Err(error) => Err(error),
+            }
+        }?;
+
+        // Write and commit contents
+        let result = async {
+            self.write_file(guard, slug, &content).await?;
+
+            let args = arguments!["git", "add", &path];
+            self.spawn(guard, &args).await?;
+
+            let author = self.author(info.username, Some(info.user_id)).await;
+            let message = self.arg_message(info.message);
+            let args = arguments!["git", "commit", "--allow-empty", &message, "--", &path,];
+            self.spawn_as(guard, &args, &author).await?;
+
+            self.get_commit(guard).await
+        }
+        .await;
+
+        // Discard the partial change if anything failed
+        if let Err(ref error) = result {
+            warn!("Revert failed, resetting working tree: {}", error);
+
+            let args = arguments!["git", "reset", "--hard", "--quiet", "HEAD"];
+            self.spawn(guard, &args).await?;
+        }
+
+        self.check_clean(guard).await;
+        result
+    }
+
+    /// Performs a standard `git revert`, then edits the message.
+    async fn undo(&self, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash> {
+        info!("Undoing commit {} (info: {:?})", hash, info);
+
+        let guard = write_lock!(self);
+        let author = self.author(info.username, Some(info.user_id)).await;
+
+        // Perform the revert
+        let args = arguments!["git", "revert", "--no-edit", hash];
+        self.spawn_as(guard, &args, &author).await?;
+
+        // Edit the commit message
+        let message = self.arg_message(info.message);
+        let args = arguments!["git", "commit", "--amend", "--reset-author", &message];
+        self.spawn_as(guard, &args, &author).await?;
+
+        let commit = self.get_commit(guard).await?;
+        self.check_clean(guard).await;
+
+        Ok(commit)
+    }
+
+    async fn get_page(&self, slug: &str) -> Result<Option<String>> {
+        info!("Getting page content for slug '{}'", slug);
+
+        check_normal(slug)?;
+        let guard = read_lock!(self);
+
+        let contents = self.read_file(guard, slug).await?;
+        self.check_clean(guard).await;
+
+        Ok(contents)
+    }
+
+    async fn get_page_version(&self, slug: &str, hash: &GitHash) -> Result<Option<String>> {
+        info!(
+            "Getting page content for slug '{}' at commit {}",
+            slug, hash,
+        );
+
+        check_normal(slug)?;
+        let guard = read_lock!(self);
+
+        let path = self.get_path(slug, false);
+        let spec = format!("{}:{}", hash, path.display());
+        let args = arguments!["git", "show", "--format=%B", &spec];
+
+        let result = match self.spawn_output(guard, &args).await {
+            Ok(bytes) => Ok(Some(convert_utf8!(bytes))),
+            Err(Error::Revision { .. }) => Ok(None),
+            Err(error) => Err(error),
+        };
+
+        self.check_clean(guard).await;
+        result
+    }
+
+    async fn get_revision(&self, slug: &str, hash: &GitHash) -> Result<RevisionInfo> {
+        info!("Getting revision for slug '{}' at commit {}", slug, hash);
+
+        check_normal(slug)?;
+        let guard = read_lock!(self);
+        let path = self.get_path(slug, false);
+
+        self.check_page_commit(guard, &path, hash).await?;
+
+        let revision = self.revision_info(guard, &path, hash).await?;
+        self.check_clean(guard).await;
+
+        Ok(revision)
+    }
+
+    async fn tag_revision(&self, slug: &str, hash: &GitHash, tag: &str) -> Result<()> {
+        info!("Tagging commit {} for slug '{}' as '{}'", hash, slug, tag,);
+
+        check_normal(slug)?;
+        check_tag(tag)?;
+
+        let guard = write_lock!(self);
+        let path = self.get_path(slug, false);
+
+        self.check_page_commit(guard, &path, hash).await?;
+
+        let name = self.tag_name(slug, tag);
+        let args = arguments!["git", "tag", "--force", &name, hash];
+        self.spawn(guard, &args).await?;
+        self.check_clean(guard).await;
+
+        Ok(())
+    }
+
+    async fn get_tagged_revision(&self, slug: &str, tag: &str) -> Result<RevisionInfo> {
+        info!("Getting revision tagged '{}' for slug '{}'", tag, slug);
+
+        check_normal(slug)?;
+        check_tag(tag)?;
+
+        let guard = read_lock!(self);
+        let path = self.get_path(slug, false);
+
+        let spec = format!("refs/tags/{}^{{commit}}", self.tag_name(slug, tag));
+        let args = arguments!["git", "rev-parse", "--verify", "--quiet", &spec];
+        let output = match self.spawn_output(guard, &args).await {
+            Ok(output) => output,
+            Err(Error::Revision { .. }) => return Err(Error::RevisionTagNotFound),
+            Err(error) => return Err(error),
+        };
+
+        let hash = str::from_utf8(&output)
+            .ok()
+            .and_then(|output| GitHash::try_from(output.trim()).ok())
+            .ok_or(Error::StaticMsg("unable to parse git hash from output"))?;
+
+        let revision = self.revision_info(guard, &path, &hash).await?;
+        self.check_clean(guard).await;
+
+        Ok(revision)
+    }
+
+    async fn get_diff(&self, slug: &str, first: &GitHash, second: &GitHash) -> Result<String> {
+        info!(
+            "Getting diff for slug '{}' between {}..{}",
+            slug, first, second,
+        );
+
+        check_normal(slug)?;
+        let guard = read_lock!(self);
+        let path = self.get_path(slug, false);
+
+        self.check_page_commit(guard, &path, first).await?;
+        self.check_page_commit(guard, &path, second).await?;
+
+        let args = arguments!["git", "diff", &first, &second, "--", &path];
+
+        let diff = self.spawn_output(guard, &args).await?;
+        self.check_clean(guard).await;
+
+        Ok(convert_utf8!(diff))
+    }
+
+    async fn get_blame(&self, slug: &str, hash: Option<&GitHash>) -> Result<Option<Blame>> {
+        info!("Getting blame for slug '{}'", slug);
+
+        check_normal(slug)?;
+        let guard = read_lock!(self);
+        let path = self.get_path(slug, false);
+
+        let args = match hash {
+            Some(ref hash) => arguments!["git", "blame", "--porcelain", hash, "--", &path],
+            None => arguments!["git", "blame", "--porcelain", "--", &path],
+        };
+
+        let raw_blame = match self.spawn_output(guard, &args).await {
+            Ok(bytes) => bytes,
+            Err(Error::Revision { .. }) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let blame = Blame::from_porcelain(&raw_blame)?;
+        self.check_clean(guard).await;
+
+        Ok(Some(blame))
+    }
+
+    async fn search_content(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.search(query, false, limit).await
+    }
+
+    async fn search_content_regex(&self, pattern: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.search(pattern, true, limit).await
+    }
+
+    async fn recent_changes(&self, limit: usize, offset: usize) -> Result<Vec<RevisionInfo>> {
+        info!(
+            "Getting recent changes (limit {}, offset {})",
+            limit, offset,
+        );
+
+        let guard = read_lock!(self);
+        let revisions = self.log(guard, &[], limit, offset).await?;
+        self.check_clean(guard).await;
+
+        Ok(revisions)
+    }
+
+    async fn contributions(
+        &self,
+        user_id: UserId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        info!(
+            "Getting contributions for user ID {} (limit {}, offset {})",
+            user_id, limit, offset,
+        );
+
+        // Matches the email set by author()
+        let author = format!("--author=<user-{}@", user_id);
+
+        let guard = read_lock!(self);
+        let revisions = self.log(guard, &arguments![&author], limit, offset).await?;
+        self.check_clean(guard).await;
+
+        Ok(revisions)
+    }
+
+    async fn list_revisions(
+        &self,
+        slug: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        info!(
+            "Getting revisions for slug '{}' (limit {}, offset {})",
+            slug, limit, offset,
+        );
+
+        check_normal(slug)?;
+        let guard = read_lock!(self);
+        let path = self.get_path(slug, false);
+
+        let revisions = self
+            .log(guard, &arguments!["--", &path], limit, offset)
+            .await?;
+
+        // Could also be past the end of the history
+        if revisions.is_empty() {
+            let args = arguments!["git", "log", "--max-count=1", "--format=%H", "--", &path];
+            let output = self.spawn_output(guard, &args).await?;
+
+            if output.is_empty() {
+                return Err(Error::PageNotFound);
+            }
+        }
+
+        self.check_clean(guard).await;
+
+        Ok(revisions)
+    }
+
+    async fn set_domain(&self, new_domain: &str) {
+        trace!("Acquiring domain write lock to change: {}", new_domain);
+
+        let mut guard = self.domain.write().await;
+        guard.clear();
+        guard.push_str(new_domain);
+    }
+
+    /// Runs `git gc` and `git prune` on the repository.
+    async fn vacuum(&self) -> Result<usize> {
+        self.vacuum_internal("--auto").await
+    }
+
+    /// Runs a full `git gc` and `git prune` on the repository.
+    async fn vacuum_deep(&self) -> Result<usize> {
+        self.vacuum_internal("--aggressive").await
+    }
+}
@@ -18,8 +18,11 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use deepwell_core::types::UserId;
+
 #[derive(Debug, Copy, Clone)]
 pub struct CommitInfo<'a> {
+    pub user_id: UserId,
     pub username: &'a str,
     pub message: &'a str,
 }
@@ -0,0 +1,872 @@
+/*
+ * revision/memory.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+extern crate regex;
+
+use super::store::{check_normal, check_tag, MAX_MATCHES_PER_FILE};
+use super::{CommitInfo, RevisionStore};
+use crate::{Error, Result};
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use deepwell_core::models::{
+    Blame, BlameAuthor, BlameGroup, BlameLine, GitHash, RevisionInfo, SearchHit,
+};
+use deepwell_core::types::UserId;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// A change to a page between two versions, from `diff_lines()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum LineChange {
+    Keep(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Splits page contents into lines the way git does, without the newlines.
+fn split_lines(content: &str) -> Vec<&str> {
+    let mut lines: Vec<_> = content.split('\n').collect();
+
+    // A final newline ends the last line rather than starting another
+    if lines.last() == Some(&"") {
+        lines.pop();
+    }
+
+    lines
+}
+
+/// Finds the smallest set of line changes from `old` to `new`.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<LineChange> {
+    // Length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            changes.push(LineChange::Keep(i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            changes.push(LineChange::Delete(i));
+            i += 1;
+        } else {
+            changes.push(LineChange::Insert(j));
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+/// Gets the filename git would use for a page, `fragment:scp-002` -> `fragment$scp-002.ftml`.
+fn page_path(slug: &str) -> String {
+    format!("{}.ftml", slug.replace(':', "$"))
+}
+
+#[derive(Debug)]
+struct MemoryCommit {
+    hash: GitHash,
+    pages: BTreeMap<String, String>,
+    slugs: Vec<String>,
+    user_id: Option<UserId>,
+    username: String,
+    email: String,
+    time: DateTime<Utc>,
+    message: String,
+}
+
+impl MemoryCommit {
+    fn changed(&self, slug: &str) -> bool {
+        self.slugs.iter().any(|changed| changed == slug)
+    }
+
+    fn revision_info(&self) -> RevisionInfo {
+        RevisionInfo::new(
+            self.hash.clone(),
+            self.slugs.clone(),
+            self.user_id,
+            self.username.clone(),
+            self.time,
+            self.message.clone(),
+        )
+    }
+
+    fn blame_author(&self) -> BlameAuthor {
+        BlameAuthor {
+            name: self.username.clone(),
+            email: format!("<{}>", self.email),
+            time: self.time.with_timezone(&FixedOffset::east(0)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct MemoryHistory {
+    commits: Vec<MemoryCommit>,
+    tags: HashMap<(String, String), GitHash>,
+}
+
+impl MemoryHistory {
+    fn head(&self) -> Option<&BTreeMap<String, String>> {
+        self.commits.last().map(|commit| &commit.pages)
+    }
+
+    fn find(&self, hash: &GitHash) -> Option<usize> {
+        self.commits.iter().position(|commit| &commit.hash == hash)
+    }
+
+    /// Ensures the commit exists and modified the given page.
+    fn check_page_commit(&self, slug: &str, hash: &GitHash) -> Result<&MemoryCommit> {
+        let index = self.find(hash).ok_or(Error::RevisionNotFound)?;
+        let commit = &self.commits[index];
+
+        if commit.changed(slug) {
+            Ok(commit)
+        } else {
+            Err(Error::RevisionPageMismatch)
+        }
+    }
+
+    /// Gets the commits which aren't the initial commit, newest first.
+    fn log(&self) -> impl Iterator<Item = &MemoryCommit> {
+        self.commits.iter().skip(1).rev()
+    }
+}
+
+/// Stores page contents and their histories in memory, for tests.
+///
+/// Each commit keeps a full copy of every page, so this isn't meant for large histories.
+/// Unlike `GitStore`, blames do not follow pages across renames.
+#[derive(Debug)]
+pub struct MemoryStore {
+    history: RwLock<MemoryHistory>,
+    domain: RwLock<String>,
+}
+
+impl MemoryStore {
+    /// Creates a new, empty revision store using the given domain name.
+    #[inline]
+    pub fn new<S: Into<String>>(domain: S) -> Self {
+        let domain = domain.into();
+
+        info!("Creating new in-memory revision store, domain {}", domain);
+
+        MemoryStore {
+            history: RwLock::new(MemoryHistory::default()),
+            domain: RwLock::new(domain),
+        }
+    }
+
+    async fn email(&self, user_id: Option<UserId>) -> String {
+        let domain = self.domain.read().await;
+
+        // Same as GitStore, so the addresses look the same in blames
+        match user_id {
+            Some(id) => format!("user-{}@{}", id, domain),
+            None => format!("noreply@{}", domain),
+        }
+    }
+
+    /// Adds a commit with the given pages on top of the history.
+    ///
+    /// The changed slugs are the ones given in `slugs` which differ from the last commit.
+    async fn push(
+        &self,
+        history: &mut MemoryHistory,
+        pages: BTreeMap<String, String>,
+        slugs: &[&str],
+        user_id: Option<UserId>,
+        username: &str,
+        message: &str,
+    ) -> GitHash {
+        let email = self.email(user_id).await;
+        let time = Utc.timestamp(Utc::now().timestamp(), 0);
+
+        let slugs = slugs
+            .iter()
+            .filter(|slug| history.head().and_then(|head| head.get(**slug)) != pages.get(**slug))
+            .map(|slug| slug.to_string())
+            .collect();
+
+        // Only needs to be unique, the position keeps it from repeating
+        let hash = {
+            let mut hasher = Sha1::new();
+            hasher.input_str(&format!(
+                "{}\0{}\0{}\0{}",
+                history.commits.len(),
+                username,
+                time.timestamp(),
+                message,
+            ));
+
+            GitHash::from_checked(hasher.result_str())
+        };
+
+        history.commits.push(MemoryCommit {
+            hash: hash.clone(),
+            pages,
+            slugs,
+            user_id,
+            username: username.to_string(),
+            email,
+            time,
+            message: message.to_string(),
+        });
+
+        hash
+    }
+
+    async fn search(&self, query: &str, regex: bool, limit: usize) -> Result<Vec<SearchHit>> {
+        info!(
+            "Searching page contents for '{}' (regex: {}, limit {})",
+            query, regex, limit,
+        );
+
+        // Would match every line
+        if query.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let pattern = if regex {
+            Some(Regex::new(query).map_err(|_| Error::StaticMsg("invalid search pattern"))?)
+        } else {
+            None
+        };
+
+        let history = self.history.read().await;
+        let head = match history.head() {
+            Some(head) => head,
+            None => return Ok(Vec::new()),
+        };
+
+        // In the order git lists files
+        let mut pages: Vec<_> = head.iter().collect();
+        pages.sort_by_key(|(slug, _)| page_path(slug));
+
+        let mut hits = Vec::new();
+        for (slug, content) in pages {
+            let matches = split_lines(content)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, line)| match pattern {
+                    Some(ref pattern) => pattern.is_match(line),
+                    None => line.contains(query),
+                })
+                .take(MAX_MATCHES_PER_FILE);
+
+            for (index, line) in matches {
+                if hits.len() >= limit {
+                    return Ok(hits);
+                }
+
+                hits.push(SearchHit::new(
+                    slug.clone(),
+                    index as u32 + 1,
+                    line.to_string(),
+                ));
+            }
+        }
+
+        Ok(hits)
+    }
+}
+
+#[async_trait]
+impl RevisionStore for MemoryStore {
+    async fn initial_commit(&self) -> Result<()> {
+        info!("Initializing new in-memory history");
+
+        let mut history = self.history.write().await;
+        self.push(
+            &mut history,
+            BTreeMap::new(),
+            &[],
+            None,
+            "DEEPWELL",
+            "Initial commit",
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn commit(
+        &self,
+        slug: &str,
+        content: Option<&str>,
+        info: CommitInfo<'_>,
+    ) -> Result<GitHash> {
+        info!(
+            "Committing changes for slug '{}' ({} bytes)",
+            slug,
+            content.map(|b| b.len()).unwrap_or(0),
+        );
+
+        check_normal(slug)?;
+        let mut history = self.history.write().await;
+        let mut pages = history.head().cloned().unwrap_or_default();
+
+        match content {
+            Some(content) => {
+                pages.insert(String::from(slug), String::from(content));
+            }
+            None if !pages.contains_key(slug) => return Err(Error::PageNotFound),
+            None => (),
+        }
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &[slug],
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(hash)
+    }
+
+    async fn empty_commit(&self, info: CommitInfo<'_>) -> Result<GitHash> {
+        info!("Creating empty commit");
+
+        let mut history = self.history.write().await;
+        let pages = history.head().cloned().unwrap_or_default();
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &[],
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(hash)
+    }
+
+    async fn rename(
+        &self,
+        old_slug: &str,
+        new_slug: &str,
+        info: CommitInfo<'_>,
+    ) -> Result<GitHash> {
+        info!("Renaming page for slug '{}' -> '{}'", old_slug, new_slug);
+
+        check_normal(old_slug)?;
+        check_normal(new_slug)?;
+
+        let mut history = self.history.write().await;
+        let mut pages = history.head().cloned().unwrap_or_default();
+
+        if pages.contains_key(new_slug) {
+            return Err(Error::PageExists);
+        }
+
+        let content = pages.remove(old_slug).ok_or(Error::PageNotFound)?;
+        pages.insert(String::from(new_slug), content);
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &[old_slug, new_slug],
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(hash)
+    }
+
+    async fn remove(&self, slug: &str, info: CommitInfo<'_>) -> Result<Option<GitHash>> {
+        info!("Removing page for slug '{}' (info: {:?})", slug, info);
+
+        check_normal(slug)?;
+        let mut history = self.history.write().await;
+        let mut pages = history.head().cloned().unwrap_or_default();
+
+        if pages.remove(slug).is_none() {
+            return Ok(None);
+        }
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &[slug],
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(Some(hash))
+    }
+
+    async fn restore(
+        &self,
+        slug: &str,
+        old_slug: &str,
+        hash: &GitHash,
+        info: CommitInfo<'_>,
+    ) -> Result<GitHash> {
+        info!(
+            "Restoring page '{}' from {} onto '{}' (info: {:?})",
+            old_slug, hash, slug, info,
+        );
+
+        check_normal(slug)?;
+        check_normal(old_slug)?;
+
+        let mut history = self.history.write().await;
+        let content = history
+            .find(hash)
+            .and_then(|index| history.commits[index].pages.get(old_slug))
+            .cloned()
+            .ok_or(Error::PageNotFound)?;
+
+        let mut pages = history.head().cloned().unwrap_or_default();
+        pages.insert(String::from(slug), content);
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &[slug],
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(hash)
+    }
+
+    async fn revert(&self, slug: &str, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash> {
+        info!("Reverting page '{}' to {} (info: {:?})", slug, hash, info);
+
+        check_normal(slug)?;
+        let mut history = self.history.write().await;
+        let content = history
+            .check_page_commit(slug, hash)?
+            .pages
+            .get(slug)
+            .cloned()
+            .ok_or(Error::PageNotFound)?;
+
+        let mut pages = history.head().cloned().unwrap_or_default();
+        pages.insert(String::from(slug), content);
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &[slug],
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(hash)
+    }
+
+    async fn undo(&self, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash> {
+        info!("Undoing commit {} (info: {:?})", hash, info);
+
+        let mut history = self.history.write().await;
+        let index = history.find(hash).ok_or(Error::RevisionNotFound)?;
+
+        // The initial commit has no parent to go back to
+        if index == 0 {
+            return Err(Error::StaticMsg("cannot undo the initial commit"));
+        }
+
+        let commit = &history.commits[index];
+        let parent = &history.commits[index - 1];
+        let mut pages = history.head().cloned().unwrap_or_default();
+
+        for slug in &commit.slugs {
+            // Where git would have a conflict
+            if pages.get(slug) != commit.pages.get(slug) {
+                return Err(Error::StaticMsg("undo conflicts with later changes"));
+            }
+
+            match parent.pages.get(slug) {
+                Some(content) => pages.insert(slug.clone(), content.clone()),
+                None => pages.remove(slug),
+            };
+        }
+
+        let slugs = commit.slugs.clone();
+        let slugs: Vec<&str> = slugs.iter().map(String::as_str).collect();
+
+        let hash = self
+            .push(
+                &mut history,
+                pages,
+                &slugs,
+                Some(info.user_id),
+                info.username,
+                info.message,
+            )
+            .await;
+
+        Ok(hash)
+    }
+
+    async fn get_page(&self, slug: &str) -> Result<Option<String>> {
+        info!("Getting page content for slug '{}'", slug);
+
+        check_normal(slug)?;
+        let history = self.history.read().await;
+        let content = history.head().and_then(|head| head.get(slug)).cloned();
+
+        Ok(content)
+    }
+
+    async fn get_page_version(&self, slug: &str, hash: &GitHash) -> Result<Option<String>> {
+        info!(
+            "Getting page content for slug '{}' at commit {}",
+            slug, hash,
+        );
+
+        check_normal(slug)?;
+        let history = self.history.read().await;
+        let content = history
+            .find(hash)
+            .and_then(|index| history.commits[index].pages.get(slug))
+            .cloned();
+
+        Ok(content)
+    }
+
+    async fn get_revision(&self, slug: &str, hash: &GitHash) -> Result<RevisionInfo> {
+        info!("Getting revision for slug '{}' at commit {}", slug, hash);
+
+        check_normal(slug)?;
+        let history = self.history.read().await;
+        let commit = history.check_page_commit(slug, hash)?;
+
+        Ok(commit.revision_info())
+    }
+
+    async fn tag_revision(&self, slug: &str, hash: &GitHash, tag: &str) -> Result<()> {
+        info!("Tagging commit {} for slug '{}' as '{}'", hash, slug, tag);
+
+        check_normal(slug)?;
+        check_tag(tag)?;
+
+        let mut history = self.history.write().await;
+        history.check_page_commit(slug, hash)?;
+        history
+            .tags
+            .insert((String::from(slug), String::from(tag)), hash.clone());
+
+        Ok(())
+    }
+
+    async fn get_tagged_revision(&self, slug: &str, tag: &str) -> Result<RevisionInfo> {
+        info!("Getting revision tagged '{}' for slug '{}'", tag, slug);
+
+        check_normal(slug)?;
+        check_tag(tag)?;
+
+        let history = self.history.read().await;
+        let hash = history
+            .tags
+            .get(&(String::from(slug), String::from(tag)))
+            .ok_or(Error::RevisionTagNotFound)?;
+
+        let commit = history.check_page_commit(slug, hash)?;
+
+        Ok(commit.revision_info())
+    }
+
+    async fn get_diff(&self, slug: &str, first: &GitHash, second: &GitHash) -> Result<String> {
+        info!(
+            "Getting diff for slug '{}' between {}..{}",
+            slug, first, second,
+        );
+
+        check_normal(slug)?;
+        let history = self.history.read().await;
+        let old = history.check_page_commit(slug, first)?.pages.get(slug);
+        let new = history.check_page_commit(slug, second)?.pages.get(slug);
+
+        // Same as git, no output if nothing changed
+        if old == new {
+            return Ok(String::new());
+        }
+
+        let path = page_path(slug);
+        let old_lines = old.map(|content| split_lines(content)).unwrap_or_default();
+        let new_lines = new.map(|content| split_lines(content)).unwrap_or_default();
+
+        // The diff is a single hunk with the whole page as context
+        let mut diff = String::new();
+        writeln!(diff, "diff --git a/{0} b/{0}", path).unwrap();
+
+        match old {
+            Some(_) => writeln!(diff, "--- a/{}", path),
+            None => writeln!(diff, "--- /dev/null"),
+        }
+        .unwrap();
+
+        match new {
+            Some(_) => writeln!(diff, "+++ b/{}", path),
+            None => writeln!(diff, "+++ /dev/null"),
+        }
+        .unwrap();
+
+        let start = |lines: &[&str]| if lines.is_empty() { 0 } else { 1 };
+        writeln!(
+            diff,
+            "@@ -{},{} +{},{} @@",
+            start(&old_lines),
+            old_lines.len(),
+            start(&new_lines),
+            new_lines.len(),
+        )
+        .unwrap();
+
+        for change in diff_lines(&old_lines, &new_lines) {
+            match change {
+                LineChange::Keep(i, _) => writeln!(diff, " {}", old_lines[i]),
+                LineChange::Delete(i) => writeln!(diff, "-{}", old_lines[i]),
+                LineChange::Insert(j) => writeln!(diff, "+{}", new_lines[j]),
+            }
+            .unwrap();
+        }
+
+        Ok(diff)
+    }
+
+    async fn get_blame(&self, slug: &str, hash: Option<&GitHash>) -> Result<Option<Blame>> {
+        info!("Getting blame for slug '{}'", slug);
+
+        check_normal(slug)?;
+        let history = self.history.read().await;
+        let last = match hash {
+            Some(hash) => match history.find(hash) {
+                Some(index) => index,
+                None => return Ok(None),
+            },
+            None if history.commits.is_empty() => return Ok(None),
+            None => history.commits.len() - 1,
+        };
+
+        if !history.commits[last].pages.contains_key(slug) {
+            return Ok(None);
+        }
+
+        // For each line, the commit which added it and its line number there
+        let mut origins: Vec<(usize, u32)> = Vec::new();
+        let mut previous = HashMap::new();
+        let mut last_change = None;
+        let mut lines = Vec::new();
+
+        for (index, commit) in history.commits[..=last].iter().enumerate() {
+            if !commit.changed(slug) {
+                continue;
+            }
+
+            let new_lines = commit
+                .pages
+                .get(slug)
+                .map(|content| split_lines(content))
+                .unwrap_or_default();
+
+            let mut new_origins = Vec::with_capacity(new_lines.len());
+            for change in diff_lines(&lines, &new_lines) {
+                match change {
+                    LineChange::Keep(i, _) => new_origins.push(origins[i]),
+                    LineChange::Insert(j) => new_origins.push((index, j as u32 + 1)),
+                    LineChange::Delete(_) => (),
+                }
+            }
+
+            if let Some(change) = last_change {
+                if !lines.is_empty() {
+                    previous.insert(index, change);
+                }
+            }
+
+            origins = new_origins;
+            lines = new_lines;
+            last_change = Some(index);
+        }
+
+        // Consecutive lines from the same commit are grouped together
+        let mut groups: Vec<(usize, BlameGroup)> = Vec::new();
+        for (number, (&(index, old_lineno), line)) in origins.iter().zip(lines).enumerate() {
+            let commit = &history.commits[index];
+            let line = BlameLine {
+                commit: commit.hash.clone(),
+                old_lineno,
+                new_lineno: number as u32 + 1,
+                line: line.as_bytes().into(),
+            };
+
+            match groups.last_mut() {
+                Some((last, group)) if *last == index => group.lines.push(line),
+                _ => {
+                    let previous = previous
+                        .get(&index)
+                        .map(|&change| history.commits[change].hash.clone());
+
+                    groups.push((
+                        index,
+                        BlameGroup {
+                            author: commit.blame_author(),
+                            committer: commit.blame_author(),
+                            summary: commit.message.lines().next().unwrap_or("").to_string(),
+                            previous,
+                            lines: vec![line],
+                        },
+                    ));
+                }
+            }
+        }
+
+        let groups = groups.into_iter().map(|(_, group)| group).collect();
+
+        Ok(Some(Blame { groups }))
+    }
+
+    #[inline]
+    async fn search_content(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.search(query, false, limit).await
+    }
+
+    /// Patterns use the syntax of the `regex` crate,
+    /// which covers the POSIX extended syntax used by `GitStore`.
+    #[inline]
+    async fn search_content_regex(&self, pattern: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.search(pattern, true, limit).await
+    }
+
+    async fn recent_changes(&self, limit: usize, offset: usize) -> Result<Vec<RevisionInfo>> {
+        info!(
+            "Getting recent changes (limit {}, offset {})",
+            limit, offset,
+        );
+
+        let history = self.history.read().await;
+        let revisions = history
+            .log()
+            .skip(offset)
+            .take(limit)
+            .map(MemoryCommit::revision_info)
+            .collect();
+
+        Ok(revisions)
+    }
+
+    async fn contributions(
+        &self,
+        user_id: UserId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        info!(
+            "Getting contributions for user ID {} (limit {}, offset {})",
+            user_id, limit, offset,
+        );
+
+        let history = self.history.read().await;
+        let revisions = history
+            .log()
+            .filter(|commit| commit.user_id == Some(user_id))
+            .skip(offset)
+            .take(limit)
+            .map(MemoryCommit::revision_info)
+            .collect();
+
+        Ok(revisions)
+    }
+
+    async fn list_revisions(
+        &self,
+        slug: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        info!(
+            "Getting revisions for slug '{}' (limit {}, offset {})",
+            slug, limit, offset,
+        );
+
+        check_normal(slug)?;
+        let history = self.history.read().await;
+
+        if !history.log().any(|commit| commit.changed(slug)) {
+            return Err(Error::PageNotFound);
+        }
+
+        let revisions = history
+            .log()
+            .filter(|commit| commit.changed(slug))
+            .skip(offset)
+            .take(limit)
+            .map(MemoryCommit::revision_info)
+            .collect();
+
+        Ok(revisions)
+    }
+
+    async fn set_domain(&self, new_domain: &str) {
+        trace!("Acquiring domain write lock to change: {}", new_domain);
+
+        let mut guard = self.domain.write().await;
+        guard.clear();
+        guard.push_str(new_domain);
+    }
+
+    /// Nothing is ever unreachable, so there is nothing to prune.
+    async fn vacuum(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    async fn vacuum_deep(&self) -> Result<usize> {
+        Ok(0)
+    }
+}
@@ -18,13 +18,21 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+mod git;
 mod info;
 mod process;
 mod store;
 
+#[cfg(test)]
+mod memory;
+
 #[cfg(test)]
 mod test;
 
+pub use self::git::GitStore;
 pub use self::info::CommitInfo;
-pub use self::process::{spawn, spawn_output, OwnedBytes};
+pub use self::process::{spawn, spawn_env, spawn_output, spawn_search, OwnedBytes};
 pub use self::store::RevisionStore;
+
+#[cfg(test)]
+pub use self::memory::MemoryStore;
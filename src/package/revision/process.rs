@@ -24,27 +24,49 @@ use futures::task::{Context, Poll};
 use futures::Future;
 use std::ffi::{OsStr, OsString};
 use std::fmt::Write;
-use std::io::Read;
+use std::fs::File;
+use std::io::{self, Read};
 use std::pin::Pin;
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
 use subprocess::{ExitStatus, Popen, PopenConfig, Redirection};
 
-macro_rules! mut_borrow {
-    ($option:expr) => {
-        $option.as_mut().unwrap()
-    };
-}
-
 pub type OwnedBytes = Box<[u8]>;
 
+type PipeReader = JoinHandle<io::Result<Vec<u8>>>;
+
+/// The most bytes of `stderr` kept when a process fails.
+const MAX_STDERR_LEN: usize = 2048;
+
 /// Runs a process to completion, returning `Err` if it fails.
+///
+/// A non-zero exit status gives `Error::Revision`, with the exit code and `stderr`.
 pub async fn spawn(repo: OsString, arguments: &[&OsStr]) -> Result<()> {
     debug!(
         "Running process: (in {:?}) {:?} (no capture)",
         repo, arguments,
     );
 
-    spawn_inner(repo, arguments, false).await.map(|_| ())
+    spawn_inner(repo, arguments, &[], false, false)
+        .await
+        .map(|_| ())
+}
+
+/// Runs a process to completion with additional environment variables,
+/// returning `Err` if it fails.
+pub async fn spawn_env(
+    repo: OsString,
+    arguments: &[&OsStr],
+    environment: &[(&OsStr, &OsStr)],
+) -> Result<()> {
+    debug!(
+        "Running process: (in {:?}) {:?} with environment {:?} (no capture)",
+        repo, arguments, environment,
+    );
+
+    spawn_inner(repo, arguments, environment, false, false)
+        .await
+        .map(|_| ())
 }
 
 /// Runs a process to completion, returning its `stdout`, or `Err` if it fails.
@@ -54,23 +76,54 @@ pub async fn spawn_output(repo: OsString, arguments: &[&OsStr]) -> Result<OwnedB
         repo, arguments,
     );
 
-    spawn_inner(repo, arguments, true)
+    spawn_inner(repo, arguments, &[], true, false)
+        .await
+        .map(Option::unwrap_or_default)
+}
+
+/// Runs a search process such as `git grep` to completion, returning its `stdout`.
+///
+/// Exit status 1 only means nothing matched, so it gives empty output instead of `Err`.
+pub async fn spawn_search(repo: OsString, arguments: &[&OsStr]) -> Result<OwnedBytes> {
+    debug!(
+        "Running process: (in {:?}) {:?} (capturing stdout, search)",
+        repo, arguments,
+    );
+
+    spawn_inner(repo, arguments, &[], true, true)
         .await
-        .map(|out| out.unwrap())
+        .map(Option::unwrap_or_default)
 }
 
 async fn spawn_inner(
     repo: OsString,
     arguments: &[&OsStr],
+    environment: &[(&OsStr, &OsStr)],
     output: bool,
+    search: bool,
 ) -> Result<Option<OwnedBytes>> {
     const TIMEOUT: Duration = Duration::from_millis(1800);
 
+    // Only override the inherited environment if needed
+    let env = if environment.is_empty() {
+        None
+    } else {
+        let mut env = PopenConfig::current_env();
+        env.retain(|(key, _)| environment.iter().all(|(new_key, _)| key != new_key));
+
+        for (key, value) in environment {
+            env.push((key.into(), value.into()));
+        }
+
+        Some(env)
+    };
+
     let config = PopenConfig {
         stdin: Redirection::Pipe,
         stdout: Redirection::Pipe,
         stderr: Redirection::Pipe,
         cwd: Some(repo),
+        env,
         ..PopenConfig::default()
     };
 
@@ -89,6 +142,11 @@ async fn spawn_inner(
         TIMEOUT.as_millis(),
     );
 
+    // Both pipes are read while the process runs, since it
+    // blocks once it has filled either, until the timeout.
+    let stdout = read_pipe(popen.stdout.take());
+    let stderr = read_pipe(popen.stderr.take());
+
     macro_rules! await_exit {
         ($popen:expr, $timeout:expr) => {
             timeout($timeout, PopenAsync::from(&mut $popen)).await
@@ -100,9 +158,7 @@ async fn spawn_inner(
             trace!("Command succeeded, gathering stdout");
 
             if output {
-                let stdout = mut_borrow!(popen.stdout);
-                let mut buffer = Vec::new();
-                stdout.read_to_end(&mut buffer)?;
+                let buffer = join_pipe(stdout)?;
                 trace!("Gathered {} bytes of stdout", buffer.len());
                 let bytes = buffer.into_boxed_slice();
 
@@ -111,35 +167,41 @@ async fn spawn_inner(
                 Ok(None)
             }
         }
+        Ok(ExitStatus::Exited(1)) if search => {
+            trace!("Search found no matches");
+
+            Ok(Some(OwnedBytes::default()))
+        }
         Ok(status) => {
             trace!("Command failed, status {:?}", status);
 
-            let mut buffer = String::new();
-            for argument in &arguments[..2] {
-                write!(&mut buffer, "{} ", argument.to_string_lossy()).unwrap();
+            let mut command = String::new();
+            for argument in arguments.iter().take(2) {
+                write!(&mut command, "{} ", argument.to_string_lossy()).unwrap();
             }
 
-            buffer.push_str("command failed: ");
-
-            let stderr = mut_borrow!(popen.stderr);
-            stderr.read_to_string(&mut buffer)?;
-
-            match status {
+            let code = match status {
                 ExitStatus::Exited(code) => {
                     warn!("Process exited with non-zero status code {}", code);
-                    write!(&mut buffer, "(exit status {})", code).unwrap();
+                    Some(code as i32)
                 }
                 ExitStatus::Signaled(code) => {
                     warn!("Process was killed by signal {}", code);
-                    write!(&mut buffer, "(killed by signal {})", code).unwrap();
+                    None
                 }
                 _ => {
                     warn!("Process was killed by unknown source ({:?})", status);
-                    write!(&mut buffer, "(unknown cause)").unwrap();
+                    None
                 }
-            }
+            };
+
+            let stderr = truncate_stderr(&join_pipe(stderr)?);
 
-            Err(Error::CommandFailed(buffer))
+            Err(Error::Revision {
+                command,
+                code,
+                stderr,
+            })
         }
         Err(_) => {
             const KILL_TIMEOUT: Duration = Duration::from_millis(2000);
@@ -165,6 +227,43 @@ async fn spawn_inner(
     }
 }
 
+/// Reads everything from the pipe on another thread, until the process closes it.
+fn read_pipe(pipe: Option<File>) -> PipeReader {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            pipe.read_to_end(&mut buffer)?;
+        }
+
+        Ok(buffer)
+    })
+}
+
+/// Waits for the pipe to be read completely, returning its contents.
+fn join_pipe(reader: PipeReader) -> Result<Vec<u8>> {
+    match reader.join() {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(Error::StaticMsg("thread reading process output panicked")),
+    }
+}
+
+/// Converts `stderr` for an error, cutting it short if it's too long.
+fn truncate_stderr(bytes: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(bytes);
+    let stderr = stderr.trim_end();
+
+    if stderr.len() <= MAX_STDERR_LEN {
+        return String::from(stderr);
+    }
+
+    let mut end = MAX_STDERR_LEN;
+    while !stderr.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}...", &stderr[..end])
+}
+
 #[derive(Debug)]
 struct PopenAsync<'p> {
     inner: &'p mut Popen,
@@ -19,57 +19,20 @@
  */
 
 use super::CommitInfo;
-use super::OwnedBytes;
 use crate::{Error, Result};
-use async_std::fs::{self, File};
-use async_std::prelude::*;
-use async_std::sync::{Mutex, RwLock};
-use deepwell_core::models::{Blame, GitHash};
-use std::convert::TryFrom;
-use std::ffi::{OsStr, OsString};
-use std::path::PathBuf;
-use std::str;
+use async_trait::async_trait;
+use deepwell_core::models::{Blame, GitHash, RevisionInfo, SearchHit};
+use deepwell_core::types::UserId;
+use std::fmt::Debug;
 use wikidot_normalize::is_normal;
 
-macro_rules! arguments {
-    ($($x:expr), *) => {{
-        let mut arguments = array_vec!([&OsStr; 16]);
+/// The most lines which can match in any one page during a content search.
+pub const MAX_MATCHES_PER_FILE: usize = 5;
 
-        $(
-            arguments.push(OsStr::new($x));
-        )*
+/// The longest name a revision tag can have.
+pub const MAX_TAG_LENGTH: usize = 64;
 
-        arguments
-    }};
-    ($($x:expr,)*) => (arguments![$($x),*]);
-}
-
-macro_rules! check_normal {
-    ($slug:expr) => {
-        match check_normal($slug) {
-            Ok(_) => (),
-            Err(error) => return Err(error),
-        }
-    };
-}
-
-macro_rules! convert_utf8 {
-    ($bytes:expr) => {
-        String::from_utf8($bytes.into_vec())?
-    };
-}
-
-// Note: this only maintains a lock per DEEPWELL process.
-// If there are multiple processes working on the same repositories,
-// an external locking mechanism (probably based on the exclusive
-// creation of a lock file) will need to be implemented.
-macro_rules! lock {
-    ($self:expr) => {
-        &mut $self.mutex.lock().await
-    };
-}
-
-fn check_normal(slug: &str) -> Result<()> {
+pub fn check_normal(slug: &str) -> Result<()> {
     trace!("Checking slug for normal form: {}", slug);
 
     if is_normal(slug, false) {
@@ -79,340 +42,62 @@ fn check_normal(slug: &str) -> Result<()> {
     }
 }
 
-/// An object that can't be copied or cloned for the `Mutex`.
-#[derive(Debug)]
-struct RevisionBlock;
-
-/// Represents a git repository to store page contents and their histories.
-#[derive(Debug)]
-pub struct RevisionStore {
-    mutex: Mutex<RevisionBlock>,
-    repo: PathBuf,
-    domain: RwLock<String>,
-}
-
-impl RevisionStore {
-    /// Creates a new revision store using the given repository and domain name.
-    ///
-    /// The domain name should not have a protocol but allows permit subdomains.
-    #[inline]
-    pub fn new<P, S>(repo: P, domain: S) -> Self
-    where
-        P: Into<PathBuf>,
-        S: Into<String>,
-    {
-        let mutex = Mutex::new(RevisionBlock);
-        let repo = repo.into();
-        let domain = domain.into();
-
-        info!(
-            "Creating new revision store for repository {}, domain {}",
-            repo.display(),
-            domain,
-        );
-
-        let domain = RwLock::new(domain);
-
-        RevisionStore {
-            mutex,
-            repo,
-            domain,
-        }
-    }
-
-    // Filesystem helpers
-    fn get_path(&self, slug: &str, absolute: bool) -> PathBuf {
-        trace!(
-            "Converting slug '{}' to path (absolute: {})",
-            slug,
-            absolute,
-        );
-
-        let filename = {
-            let mut filename = String::new();
-
-            for part in slug.split(':') {
-                filename.push_str(part);
-                filename.push('$');
-            }
-
-            filename.pop();
-            filename
-        };
-
-        let mut path = PathBuf::new();
-
-        if absolute {
-            path.push(&self.repo);
-        }
-
-        path.push(&filename);
-        path.set_extension("ftml");
-        path
-    }
-
-    async fn read_file(&self, _guard: &mut RevisionBlock, slug: &str) -> Result<Option<String>> {
-        let path = self.get_path(slug, true);
-
-        debug!("Reading file from {}", path.display());
-
-        let mut file = match File::open(&path).await {
-            Ok(file) => file,
-            Err(error) => {
-                use std::io::ErrorKind;
-
-                return match error.kind() {
-                    ErrorKind::NotFound => Ok(None),
-                    _ => Err(Error::from(error)),
-                };
-            }
-        };
-
-        let mut content = String::new();
-        file.read_to_string(&mut content).await?;
-        Ok(Some(content))
-    }
-
-    async fn write_file(
-        &self,
-        _guard: &mut RevisionBlock,
-        slug: &str,
-        content: &str,
-    ) -> Result<()> {
-        let path = self.get_path(slug, true);
-
-        debug!("Writing {} bytes to {}", content.len(), path.display());
-
-        let mut file = File::create(path).await?;
-        let bytes = content.as_bytes();
-        file.write_all(bytes).await?;
+/// Checks that a revision tag name is safe to use as part of a git ref.
+///
+/// Only ASCII letters, digits, `-`, `_`, and `.` are allowed, and the name cannot
+/// start with `-` or `.`, contain `..`, or end with `.` or `.lock`.
+pub fn check_tag(tag: &str) -> Result<()> {
+    trace!("Checking revision tag name: {}", tag);
+
+    let valid_chars = tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LENGTH
+        && valid_chars
+        && !tag.starts_with('-')
+        && !tag.starts_with('.')
+        && !tag.ends_with('.')
+        && !tag.ends_with(".lock")
+        && !tag.contains("..");
+
+    if valid {
         Ok(())
+    } else {
+        warn!("Invalid revision tag name '{}'", tag);
+        Err(Error::InvalidRevisionTag(String::from(tag)))
     }
+}
 
-    async fn remove_file(&self, _guard: &mut RevisionBlock, slug: &str) -> Result<Option<()>> {
-        let path = self.get_path(slug, true);
-
-        debug!("Removing file {}", path.display());
-
-        match fs::remove_file(path).await {
-            Ok(_) => (),
-            Err(error) => {
-                use std::io::ErrorKind;
-
-                return match error.kind() {
-                    ErrorKind::NotFound => Ok(None),
-                    _ => Err(Error::from(error)),
-                };
-            }
-        }
-
-        Ok(Some(()))
-    }
-
-    // Argument helpers
-    async fn arg_author(&self, name: &str) -> String {
-        let domain = self.domain.read().await;
-
-        format!("--author={} <noreply@{}>", name, domain)
-    }
-
-    fn arg_message(&self, message: &str) -> String {
-        format!("--message={}", message)
-    }
-
-    // Process helpers
-    fn repo(&self) -> OsString {
-        self.repo.as_os_str().to_os_string()
-    }
-
-    async fn spawn(&self, _guard: &mut RevisionBlock, arguments: &[&OsStr]) -> Result<()> {
-        super::spawn(self.repo(), arguments).await
-    }
-
-    async fn spawn_output(
-        &self,
-        _guard: &mut RevisionBlock,
-        arguments: &[&OsStr],
-    ) -> Result<OwnedBytes> {
-        super::spawn_output(self.repo(), arguments).await
-    }
-
-    // Git helper
-    async fn get_commit(&self, guard: &mut RevisionBlock) -> Result<GitHash> {
-        debug!("Getting current HEAD commit");
-
-        let args = arguments!["git", "rev-parse", "--verify", "HEAD"];
-
-        let digest_bytes = self.spawn_output(guard, &args).await?;
-        let digest = str::from_utf8(&digest_bytes)
-            .map_err(|_| Error::StaticMsg("git hash wasn't valid UTF-8"))?;
-
-        let hash = GitHash::try_from(digest)
-            .map_err(|_| Error::StaticMsg("unable to parse git hash from output"))?;
-
-        Ok(hash)
-    }
-
-    #[cfg(test)]
-    async fn check_clean(&self, guard: &mut RevisionBlock) {
-        debug!("Checking if repository is clean");
-
-        let args = arguments!["git", "status", "--porcelain"];
-        let output = self
-            .spawn_output(guard, &args)
-            .await
-            .expect("Unable to get git status");
-
-        if !output.is_empty() {
-            panic!(
-                "Git repository is not clean:\n{}",
-                String::from_utf8_lossy(&output),
-            );
-        }
-    }
-
-    #[cfg(not(test))]
-    async fn check_clean(&self, _guard: &mut RevisionBlock) {}
-
-    /// Create the first commit of the repo.
-    /// Should only be called on empty repositories.
-    #[cold]
-    pub async fn initial_commit(&self) -> Result<()> {
-        info!("Initializing new git repository");
-
-        let guard = &mut self.mutex.lock().await;
-        let args = arguments!["git", "init"];
-        self.spawn(guard, &args).await?;
-
-        let author = self.arg_author("DEEPWELL").await;
-        let message = self.arg_message("Initial commit");
-        let args = arguments!["git", "commit", "--allow-empty", &author, &message];
-
-        self.spawn(guard, &args).await?;
-        self.check_clean(guard).await;
-
-        Ok(())
-    }
+/// Stores page contents and their histories.
+///
+/// Revisions are identified by git hashes. `GitStore` is the implementation
+/// used by the server, and `MemoryStore` keeps everything in memory for tests.
+#[async_trait]
+pub trait RevisionStore: Debug + Send + Sync {
+    /// Create the first commit of the store.
+    /// Should only be called on empty stores.
+    async fn initial_commit(&self) -> Result<()>;
 
     /// For the given slug, create or edit a page to have the specified contents.
-    pub async fn commit(
+    async fn commit(
         &self,
         slug: &str,
         content: Option<&str>,
         info: CommitInfo<'_>,
-    ) -> Result<GitHash> {
-        info!(
-            "Committing file changes for slug '{}' ({} bytes)",
-            slug,
-            content.map(|b| b.len()).unwrap_or(0),
-        );
-
-        check_normal!(slug);
-        let guard = &mut self.mutex.lock().await;
-
-        if let Some(content) = content {
-            self.write_file(guard, slug, content).await?;
-        }
-
-        let path = self.get_path(slug, false);
-        let args = arguments!["git", "add", &path];
-        self.spawn(guard, &args).await?;
-
-        let author = self.arg_author(info.username).await;
-        let message = self.arg_message(info.message);
-        let args = arguments![
-            "git",
-            "commit",
-            "--allow-empty",
-            &author,
-            &message,
-            "--",
-            &path,
-        ];
-        self.spawn(guard, &args).await?;
-
-        let commit = self.get_commit(guard).await?;
-        self.check_clean(guard).await;
-
-        Ok(commit)
-    }
+    ) -> Result<GitHash>;
 
     /// Creates an empty commit.
-    pub async fn empty_commit(&self, info: CommitInfo<'_>) -> Result<GitHash> {
-        info!("Creating empty commit");
-
-        let guard = &mut self.mutex.lock().await;
-        let author = self.arg_author(info.username).await;
-        let message = self.arg_message(info.message);
-
-        let args = arguments!["git", "commit", "--allow-empty", &author, &message];
-        self.spawn(guard, &args).await?;
-
-        let commit = self.get_commit(guard).await?;
-        self.check_clean(guard).await;
-
-        Ok(commit)
-    }
-
-    /// Renames the given page in the repository.
-    pub async fn rename(
-        &self,
-        old_slug: &str,
-        new_slug: &str,
-        info: CommitInfo<'_>,
-    ) -> Result<GitHash> {
-        info!("Renaming file for slug '{}' -> '{}'", old_slug, new_slug);
-
-        check_normal!(old_slug);
-        check_normal!(new_slug);
-        let guard = &mut self.mutex.lock().await;
+    async fn empty_commit(&self, info: CommitInfo<'_>) -> Result<GitHash>;
 
-        let new_path = self.get_path(new_slug, true);
-        if new_path.exists() {
-            return Err(Error::PageExists);
-        }
+    /// Renames the given page.
+    async fn rename(&self, old_slug: &str, new_slug: &str, info: CommitInfo<'_>)
+        -> Result<GitHash>;
 
-        let old_path = self.get_path(old_slug, false);
-        let new_path = self.get_path(new_slug, false);
-        let args = arguments!["git", "mv", "--", &old_path, &new_path];
-        self.spawn(guard, &args).await?;
-
-        let author = self.arg_author(info.username).await;
-        let message = self.arg_message(info.message);
-        let args = arguments!["git", "commit", &author, &message, "--", &old_path, &new_path];
-        self.spawn(guard, &args).await?;
-
-        let commit = self.get_commit(guard).await?;
-        self.check_clean(guard).await;
-
-        Ok(commit)
-    }
-
-    /// Remove the given page from the repository.
+    /// Remove the given page.
     /// Returns `None` if the page does not exist.
-    pub async fn remove(&self, slug: &str, info: CommitInfo<'_>) -> Result<Option<GitHash>> {
-        info!("Removing file for slug '{}' (info: {:?})", slug, info);
-
-        check_normal!(slug);
-        let guard = &mut self.mutex.lock().await;
-
-        let removed = self.remove_file(guard, slug).await?;
-        if removed.is_none() {
-            return Ok(None);
-        }
-
-        let author = self.arg_author(info.username).await;
-        let message = self.arg_message(info.message);
-        let path = self.get_path(slug, false);
-        let args = arguments!["git", "commit", &author, &message, "--", &path];
-
-        self.spawn(guard, &args).await?;
-
-        let commit = self.get_commit(guard).await.map(Some)?;
-        self.check_clean(guard).await;
-
-        Ok(commit)
-    }
+    async fn remove(&self, slug: &str, info: CommitInfo<'_>) -> Result<Option<GitHash>>;
 
     /// Restores the given page from the given hash.
     /// This is performed by committing the page as it
@@ -421,226 +106,110 @@ impl RevisionStore {
     /// This is equivalent to Wikidot's "revert" functionality,
     /// but potentially across page boundaries. Not to be confused
     /// with git's notion of a "revert".
-    pub async fn restore(
+    async fn restore(
         &self,
         slug: &str,
         old_slug: &str,
         hash: &GitHash,
         info: CommitInfo<'_>,
-    ) -> Result<GitHash> {
-        info!(
-            "Restoring file '{}' from {} onto '{}' (info: {:?})",
-            old_slug, hash, slug, info,
-        );
-
-        check_normal!(slug);
-        check_normal!(old_slug);
-
-        let guard = &mut self.mutex.lock().await;
-
-        // Get old page content
-        let content = {
-            let path = self.get_path(old_slug, false);
-            let spec = format!("{}:{}", hash, path.display());
-            let args = arguments!["git", "show", "--format=%B", &spec];
-
-            match self.spawn_output(guard, &args).await {
-                Ok(bytes) => Ok(convert_utf8!(bytes)),
-                Err(Error::CommandFailed(_)) => Err(Error::PageNotFound),
-                Err(error) => Err(error),
-            }
-        }?;
-
-        // Write and commit contents
-        self.write_file(guard, slug, &content).await?;
-
-        let path = self.get_path(slug, false);
-        let args = arguments!["git", "add", &path];
-        self.spawn(guard, &args).await?;
-
-        let author = self.arg_author(info.username).await;
-        let message = self.arg_message(info.message);
-        let args = arguments![
-            "git",
-            "commit",
-            "--allow-empty",
-            &author,
-            &message,
-            "--",
-            &path,
-        ];
-        self.spawn(guard, &args).await?;
-
-        let commit = self.get_commit(guard).await?;
-        self.check_clean(guard).await;
-
-        Ok(commit)
-    }
+    ) -> Result<GitHash>;
+
+    /// Reverts the given page to its contents as of the given hash.
+    /// The page is committed on top of the history, which is not rewritten.
+    ///
+    /// Returns `RevisionNotFound` if the commit does not exist,
+    /// or `RevisionPageMismatch` if it did not change the page.
+    async fn revert(&self, slug: &str, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash>;
 
-    /// Reverts the given commit.
-    /// This performs a standard `git revert` but edits the message.
+    /// Reverts the given commit, like `git revert`, with the given message.
     ///
     /// This is distinct from Wikidot's notion of a "revert", which is
     /// why it is called "undo" throughout the code.
-    pub async fn undo(&self, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash> {
-        info!("Undoing commit {} (info: {:?})", hash, info);
-
-        let guard = &mut self.mutex.lock().await;
-
-        // Perform the revert
-        let args = arguments!["git", "revert", "--no-edit", hash];
-        self.spawn(guard, &args).await?;
-
-        // Edit the commit message
-        let author = self.arg_author(info.username).await;
-        let message = self.arg_message(info.message);
-        let args = arguments!["git", "commit", "--amend", &author, &message,];
-        self.spawn(guard, &args).await?;
-
-        let commit = self.get_commit(guard).await?;
-        self.check_clean(guard).await;
-
-        Ok(commit)
-    }
+    async fn undo(&self, hash: &GitHash, info: CommitInfo<'_>) -> Result<GitHash>;
 
     /// Gets the current version of a page.
     /// Returns `None` if the page does not exist.
-    pub async fn get_page(&self, slug: &str) -> Result<Option<String>> {
-        info!("Getting page content for slug '{}'", slug);
+    async fn get_page(&self, slug: &str) -> Result<Option<String>>;
 
-        check_normal!(slug);
-        let guard = lock!(self);
+    /// Gets the version of a page at the specified commit.
+    /// Returns `None` if the page did not at exist at the time.
+    async fn get_page_version(&self, slug: &str, hash: &GitHash) -> Result<Option<String>>;
 
-        let contents = self.read_file(guard, slug).await?;
-        self.check_clean(guard).await;
+    /// Gets the commit message, author, and time of a particular revision of a page.
+    ///
+    /// Returns `RevisionNotFound` if the commit does not exist,
+    /// or `RevisionPageMismatch` if it did not change the page.
+    async fn get_revision(&self, slug: &str, hash: &GitHash) -> Result<RevisionInfo>;
 
-        Ok(contents)
-    }
+    /// Marks a revision of a page with a name, such as `published` or `reviewed`.
+    ///
+    /// If the page already has a tag with this name, it is moved to the given revision.
+    /// Tags stay with the slug they were made for, so they are not carried
+    /// over if the page is renamed.
+    ///
+    /// Returns `InvalidRevisionTag` if the name isn't allowed, `RevisionNotFound`
+    /// if the commit doesn't exist, or `RevisionPageMismatch` if it didn't change the page.
+    async fn tag_revision(&self, slug: &str, hash: &GitHash, tag: &str) -> Result<()>;
 
-    /// Gets the version of a page at the specified commit.
-    /// Returns `None` if the page did not at exist at the time.
-    pub async fn get_page_version(&self, slug: &str, hash: &GitHash) -> Result<Option<String>> {
-        info!(
-            "Getting page content for slug '{}' at commit {}",
-            slug, hash,
-        );
-
-        check_normal!(slug);
-        let guard = lock!(self);
-
-        let path = self.get_path(slug, false);
-        let spec = format!("{}:{}", hash, path.display());
-        let args = arguments!["git", "show", "--format=%B", &spec];
-
-        let result = match self.spawn_output(guard, &args).await {
-            Ok(bytes) => Ok(Some(convert_utf8!(bytes))),
-            Err(Error::CommandFailed(_)) => Ok(None),
-            Err(error) => Err(error),
-        };
-
-        self.check_clean(guard).await;
-        result
-    }
+    /// Gets the revision of a page with the given tag.
+    ///
+    /// Returns `InvalidRevisionTag` if the name isn't allowed,
+    /// or `RevisionTagNotFound` if the page has no such tag.
+    async fn get_tagged_revision(&self, slug: &str, tag: &str) -> Result<RevisionInfo>;
 
-    /// Gets the diff between commits of a particular page.
-    /// Returns `None` if the page or commits do not exist.
-    pub async fn get_diff(&self, slug: &str, first: &GitHash, second: &GitHash) -> Result<String> {
-        info!(
-            "Getting diff for slug '{}' between {}..{}",
-            slug, first, second,
-        );
-
-        check_normal!(slug);
-        let guard = lock!(self);
-        let path = self.get_path(slug, false);
-
-        let args = arguments![
-            "git",
-            "diff",
-            "--word-diff=porcelain",
-            &first,
-            &second,
-            "--",
-            &path,
-        ];
-
-        let diff = self.spawn_output(guard, &args).await?;
-        self.check_clean(guard).await;
-
-        Ok(convert_utf8!(diff))
-    }
+    /// Gets the unified diff between commits of a particular page.
+    ///
+    /// Returns `RevisionNotFound` if either commit does not exist,
+    /// or `RevisionPageMismatch` if it did not change the page.
+    async fn get_diff(&self, slug: &str, first: &GitHash, second: &GitHash) -> Result<String>;
 
     /// Gets the blame for a particular page.
     /// Returns `None` if the page does not exist.
-    pub async fn get_blame(&self, slug: &str, hash: Option<&GitHash>) -> Result<Option<Blame>> {
-        info!("Getting blame for slug '{}'", slug);
-
-        check_normal!(slug);
-        let guard = lock!(self);
-        let path = self.get_path(slug, false);
+    async fn get_blame(&self, slug: &str, hash: Option<&GitHash>) -> Result<Option<Blame>>;
 
-        let args = match hash {
-            Some(ref hash) => arguments!["git", "blame", "--porcelain", hash, "--", &path],
-            None => arguments!["git", "blame", "--porcelain", "--", &path],
-        };
+    /// Finds lines in the current version of every page containing the query.
+    ///
+    /// The query is matched literally. See `search_content_regex()` for patterns.
+    async fn search_content(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>>;
 
-        let raw_blame = match self.spawn_output(guard, &args).await {
-            Ok(bytes) => bytes,
-            Err(Error::CommandFailed(_)) => return Ok(None),
-            Err(error) => return Err(error),
-        };
+    /// Finds lines in the current version of every page matching the
+    /// given POSIX extended regular expression.
+    async fn search_content_regex(&self, pattern: &str, limit: usize) -> Result<Vec<SearchHit>>;
 
-        let blame = Blame::from_porcelain(&raw_blame)?;
-        self.check_clean(guard).await;
+    /// Gets the most recent commits across the entire store, newest first.
+    /// The initial commit is excluded.
+    async fn recent_changes(&self, limit: usize, offset: usize) -> Result<Vec<RevisionInfo>>;
 
-        Ok(Some(blame))
-    }
+    /// Gets the most recent commits made by the given user, newest first.
+    async fn contributions(
+        &self,
+        user_id: UserId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>>;
+
+    /// Gets the commits which changed the given page, newest first.
+    /// Returns `PageNotFound` if the page has never been committed.
+    async fn list_revisions(
+        &self,
+        slug: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>>;
 
     /// Sets the domain to a different value.
-    pub async fn set_domain(&self, new_domain: &str) {
-        trace!("Acquiring domain write lock to change: {}", new_domain);
-
-        let mut guard = self.domain.write().await;
-        guard.clear();
-        guard.push_str(new_domain);
-    }
+    async fn set_domain(&self, new_domain: &str);
 
-    /// Runs `git gc` and `git prune` on the repository.
+    /// Cleans up unused data in the store.
     /// Returns the number of pruned objects.
-    pub async fn vacuum(&self) -> Result<usize> {
-        self.vacuum_internal("--auto").await
-    }
+    async fn vacuum(&self) -> Result<usize>;
 
-    /// Runs a full `git gc` and `git prune` on the repository.
+    /// Cleans up unused data in the store as thoroughly as possible.
     /// Will take a long time to execute, may cause performance degradatations
-    /// for other operations on the repository.
+    /// for other operations on the store.
     ///
     /// Should be run infrequently.
     ///
     /// Returns the number of pruned objects.
-    pub async fn vacuum_deep(&self) -> Result<usize> {
-        self.vacuum_internal("--aggressive").await
-    }
-
-    async fn vacuum_internal(&self, gc_argument: &str) -> Result<usize> {
-        // Doesn't obtain the lock since this is intended to run in the background
-        macro_rules! run {
-            ($call:ident, $arguments:expr) => {
-                super::$call(self.repo(), &$arguments).await?
-            };
-        }
-
-        let args = arguments!["git", "gc", gc_argument];
-        run!(spawn, args);
-
-        let args = arguments!["git", "prune", "-v"];
-        let output = run!(spawn_output, args);
-        let pruned = if output.is_empty() {
-            0
-        } else {
-            output.split(|&c| c == b'\n').count()
-        };
-
-        Ok(pruned)
-    }
+    async fn vacuum_deep(&self) -> Result<usize>;
 }
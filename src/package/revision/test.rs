@@ -18,21 +18,43 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-//! Tests the [`RevisionStore`] by creating a temporary git repository.
+//! Tests the [`RevisionStore`] implementations.
+//! Tests which depend on git itself use a [`GitStore`] in a temporary repository,
+//! and the rest use a [`MemoryStore`].
+//!
 //! Performs several actions in the same test:
 //! * Adds some files
 //! * Delete some files
 //! * Test a diff
 //! * Test a blame
-//! [`RevisionStore`]: ./struct.RevisionStore.html
+//! * Test recent changes
+//! * Test user contributions
+//! * Test page revision history
+//! * Test getting a single revision
+//! * Test diffs between page revisions
+//! * Test reverting a page
+//! * Test commit authorship
+//! * Test per-line blame authors
+//! * Test content search
+//! * Test revision tags
+//! * Test subprocess errors
+//! * Test concurrent commits
+//!
+//! [`RevisionStore`]: ./trait.RevisionStore.html
+//! [`GitStore`]: ./struct.GitStore.html
+//! [`MemoryStore`]: ./struct.MemoryStore.html
 
 extern crate color_backtrace;
 extern crate tempfile;
 
-use super::{CommitInfo, RevisionStore};
+use super::{spawn, spawn_output, spawn_search, CommitInfo, GitStore, MemoryStore, RevisionStore};
+use crate::Error;
 use async_std::task;
+use deepwell_core::models::GitHash;
+use deepwell_core::types::UserId;
 use rand::prelude::*;
 use std::cmp;
+use std::ffi::OsStr;
 use std::fmt::Write as _;
 use std::ops::{Bound, Range, RangeBounds};
 use std::str;
@@ -240,7 +262,7 @@ async fn git_internal() {
     // Create revision store
     let directory = tempdir().expect("Unable to create temporary directory");
     let repo = directory.path();
-    let store = RevisionStore::new(repo, "example.org");
+    let store = GitStore::new(repo, "example.org");
     store
         .initial_commit()
         .await
@@ -294,6 +316,7 @@ async fn git_internal() {
 
         // Commit to repo
         let info = CommitInfo {
+            user_id: UserId::from_raw(0),
             username,
             message: &message,
         };
@@ -309,7 +332,7 @@ async fn git_internal() {
                 hashes.remove(1);
             }
 
-            hashes.push(hash);
+            hashes.push((slug, hash));
         }
     }
 
@@ -326,6 +349,7 @@ async fn git_internal() {
 
         // Commit to repo
         let info = CommitInfo {
+            user_id: UserId::from_raw(0),
             username,
             message: &message,
         };
@@ -338,17 +362,23 @@ async fn git_internal() {
 
     // Get a diff
     {
-        let slug = pick(&mut rng, TEST_SLUGS.as_ref());
-        let second = hashes.pop().unwrap();
-        let first = hashes.pop().unwrap();
-        let diff = store
-            .get_diff(slug, &first, &second)
-            .await
-            .expect("Unable to get diff");
+        let (slug, second) = hashes.pop().unwrap();
+        let (first_slug, first) = hashes.pop().unwrap();
+        let result = store.get_diff(slug, &first, &second).await;
 
-        println!();
-        println!("Diff between {} and {} for {}:", first, second, slug);
-        println!("{}", diff);
+        // Both commits must have changed the page
+        if *first_slug == *slug {
+            let diff = result.expect("Unable to get diff");
+
+            println!();
+            println!("Diff between {} and {} for {}:", first, second, slug);
+            println!("{}", diff);
+        } else {
+            match result.expect_err("Got diff with commit for another page") {
+                Error::RevisionPageMismatch => (),
+                _ => panic!("Error wasn't revision page mismatch"),
+            }
+        }
     }
 
     // Get a blame
@@ -381,13 +411,14 @@ async fn thread_internal() {
     // Create revision store
     let directory = tempdir().expect("Unable to create temporary directory");
     let repo = directory.path();
-    let store = RevisionStore::new(repo, "example.org");
+    let store = GitStore::new(repo, "example.org");
     store
         .initial_commit()
         .await
         .expect("Unable to create initial commit");
 
     let info = CommitInfo {
+        user_id: UserId::from_raw(0),
         username: "username",
         message: "message",
     };
@@ -441,3 +472,1041 @@ async fn thread_internal() {
 
     assert_eq!(pruned, 0, "Pruned objects were found");
 }
+
+#[test]
+fn recent_changes() {
+    color_backtrace::install();
+
+    task::block_on(recent_changes_internal());
+}
+
+async fn recent_changes_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    let changes = store
+        .recent_changes(10, 0)
+        .await
+        .expect("Unable to get recent changes");
+
+    assert!(
+        changes.is_empty(),
+        "Initial commit included in recent changes"
+    );
+
+    macro_rules! info {
+        ($user_id:expr, $message:expr) => {
+            CommitInfo {
+                user_id: UserId::from_raw($user_id),
+                username: "username",
+                message: $message,
+            }
+        };
+    }
+
+    // Make several edits
+    store
+        .commit("scp-001", Some("alpha"), info!(10, "first"))
+        .await
+        .expect("Unable to commit");
+
+    store
+        .commit("component:theme", Some("beta"), info!(11, "second"))
+        .await
+        .expect("Unable to commit");
+
+    store
+        .commit("scp-001", Some("gamma"), info!(12, "third"))
+        .await
+        .expect("Unable to commit");
+
+    store
+        .rename("scp-001", "scp-002", info!(13, "fourth"))
+        .await
+        .expect("Unable to rename");
+
+    // Check results, newest first
+    let changes = store
+        .recent_changes(10, 0)
+        .await
+        .expect("Unable to get recent changes");
+
+    let messages: Vec<_> = changes.iter().map(|change| change.message()).collect();
+    assert_eq!(messages, ["fourth", "third", "second", "first"]);
+
+    let user_ids: Vec<_> = changes.iter().map(|change| change.user_id()).collect();
+    assert_eq!(
+        user_ids,
+        [13, 12, 11, 10]
+            .iter()
+            .map(|&id| Some(UserId::from_raw(id)))
+            .collect::<Vec<_>>(),
+    );
+
+    assert_eq!(changes[0].slugs(), ["scp-001", "scp-002"]);
+    assert_eq!(changes[1].slugs(), ["scp-001"]);
+    assert_eq!(changes[2].slugs(), ["component:theme"]);
+    assert_eq!(changes[3].slugs(), ["scp-001"]);
+
+    // Check pagination
+    let changes = store
+        .recent_changes(2, 1)
+        .await
+        .expect("Unable to get recent changes");
+
+    let messages: Vec<_> = changes.iter().map(|change| change.message()).collect();
+    assert_eq!(messages, ["third", "second"]);
+}
+
+#[test]
+fn contributions() {
+    color_backtrace::install();
+
+    task::block_on(contributions_internal());
+}
+
+async fn contributions_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    let user_1 = UserId::from_raw(1);
+    let user_2 = UserId::from_raw(2);
+    let user_12 = UserId::from_raw(12);
+
+    macro_rules! commit {
+        ($user_id:expr, $slug:expr, $message:expr) => {{
+            let info = CommitInfo {
+                user_id: $user_id,
+                username: "username",
+                message: $message,
+            };
+
+            store
+                .commit($slug, Some($message), info)
+                .await
+                .expect("Unable to commit");
+        }};
+    }
+
+    macro_rules! messages {
+        ($user_id:expr) => {{
+            let changes = store
+                .contributions($user_id, 10, 0)
+                .await
+                .expect("Unable to get contributions");
+
+            for change in &changes {
+                assert_eq!(change.user_id(), Some($user_id));
+            }
+
+            changes
+                .iter()
+                .map(|change| change.message().to_string())
+                .collect::<Vec<_>>()
+        }};
+    }
+
+    // No contributions yet
+    assert!(messages!(user_1).is_empty());
+
+    // Make edits as different users
+    commit!(user_1, "scp-001", "one-a");
+    commit!(user_2, "scp-001", "two-a");
+    commit!(user_1, "scp-002", "one-b");
+    commit!(user_12, "scp-003", "twelve-a");
+    commit!(user_2, "scp-002", "two-b");
+
+    // Each only shows their own, newest first
+    assert_eq!(messages!(user_1), ["one-b", "one-a"]);
+    assert_eq!(messages!(user_2), ["two-b", "two-a"]);
+    assert_eq!(messages!(user_12), ["twelve-a"]);
+    assert!(messages!(UserId::from_raw(3)).is_empty());
+}
+
+#[test]
+fn revisions() {
+    color_backtrace::install();
+
+    task::block_on(revisions_internal());
+}
+
+async fn revisions_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    macro_rules! commit {
+        ($slug:expr, $message:expr) => {{
+            let info = CommitInfo {
+                user_id: UserId::from_raw(1),
+                username: "username",
+                message: $message,
+            };
+
+            store
+                .commit($slug, Some($message), info)
+                .await
+                .expect("Unable to commit")
+        }};
+    }
+
+    macro_rules! messages {
+        ($slug:expr, $limit:expr, $offset:expr) => {{
+            let revisions = store
+                .list_revisions($slug, $limit, $offset)
+                .await
+                .expect("Unable to get revisions");
+
+            for revision in &revisions {
+                assert!(revision.slugs().iter().any(|slug| slug == $slug));
+            }
+
+            revisions
+                .iter()
+                .map(|revision| revision.message().to_string())
+                .collect::<Vec<_>>()
+        }};
+    }
+
+    // Never committed
+    let error = store
+        .list_revisions("scp-001", 10, 0)
+        .await
+        .expect_err("Got revisions for missing page");
+
+    match error {
+        Error::PageNotFound => (),
+        _ => panic!("Error wasn't page not found"),
+    }
+
+    // Interleave edits to different pages
+    let first = commit!("scp-001", "first");
+    commit!("scp-002", "other");
+    commit!("scp-001", "second");
+    let third = commit!("scp-001", "third");
+
+    assert_eq!(messages!("scp-001", 10, 0), ["third", "second", "first"]);
+    assert_eq!(messages!("scp-001", 2, 1), ["second", "first"]);
+    assert_eq!(messages!("scp-002", 10, 0), ["other"]);
+
+    // Past the end of the history
+    assert!(messages!("scp-001", 10, 3).is_empty());
+
+    let revisions = store
+        .list_revisions("scp-001", 10, 0)
+        .await
+        .expect("Unable to get revisions");
+
+    assert_eq!(revisions[0].hash(), &third);
+    assert_eq!(revisions[2].hash(), &first);
+
+    // Look up a single revision
+    let revision = store
+        .get_revision("scp-001", &first)
+        .await
+        .expect("Unable to get revision");
+
+    assert_eq!(revision.hash(), &first);
+    assert_eq!(revision.message(), "first");
+    assert_eq!(revision.username(), "username");
+    assert_eq!(revision.user_id(), Some(UserId::from_raw(1)));
+    assert_eq!(revision.time(), revisions[2].time());
+
+    // Only for commits which changed the page
+    match store.get_revision("scp-002", &first).await {
+        Err(Error::RevisionPageMismatch) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Got revision for a different page"),
+    }
+
+    let missing = GitHash::from_checked(String::from("0000000000000000000000000000000000000000"));
+    match store.get_revision("scp-001", &missing).await {
+        Err(Error::RevisionNotFound) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Got revision for missing commit"),
+    }
+}
+
+#[test]
+fn diff() {
+    color_backtrace::install();
+
+    task::block_on(diff_internal());
+}
+
+async fn diff_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    macro_rules! commit {
+        ($slug:expr, $content:expr) => {{
+            let info = CommitInfo {
+                user_id: UserId::from_raw(1),
+                username: "username",
+                message: "message",
+            };
+
+            store
+                .commit($slug, Some($content), info)
+                .await
+                .expect("Unable to commit")
+        }};
+    }
+
+    macro_rules! check_err {
+        ($first:expr, $second:expr, $expected:pat) => {{
+            let error = store
+                .get_diff("scp-001", $first, $second)
+                .await
+                .expect_err("Got diff for invalid commits");
+
+            match error {
+                $expected => (),
+                _ => panic!("Error doesn't match"),
+            }
+        }};
+    }
+
+    let first = commit!("scp-001", "apple\nbanana\ncherry\n");
+    let other = commit!("scp-002", "durian\n");
+    let second = commit!("scp-001", "apple\nblueberry\ncherry\n");
+
+    let diff = store
+        .get_diff("scp-001", &first, &second)
+        .await
+        .expect("Unable to get diff");
+
+    assert!(diff.contains("--- a/scp-001.ftml"));
+    assert!(diff.contains("+++ b/scp-001.ftml"));
+    assert!(diff.contains("\n-banana\n"));
+    assert!(diff.contains("\n+blueberry\n"));
+    assert!(!diff.contains("durian"));
+
+    // Commit which didn't change the page
+    check_err!(&first, &other, Error::RevisionPageMismatch);
+
+    // Commit which doesn't exist
+    let missing = GitHash::from_checked("0123456789abcdef0123456789abcdef01234567");
+    check_err!(&missing, &second, Error::RevisionNotFound);
+}
+
+#[test]
+fn revert() {
+    color_backtrace::install();
+
+    task::block_on(revert_internal());
+}
+
+async fn revert_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    macro_rules! info {
+        () => {
+            CommitInfo {
+                user_id: UserId::from_raw(1),
+                username: "username",
+                message: "message",
+            }
+        };
+    }
+
+    macro_rules! commit {
+        ($slug:expr, $content:expr) => {{
+            store
+                .commit($slug, Some($content), info!())
+                .await
+                .expect("Unable to commit")
+        }};
+    }
+
+    let original = commit!("scp-001", "original content\n");
+    let other = commit!("scp-002", "other page\n");
+    let vandalized = commit!("scp-001", "vandalism!!\n");
+
+    let reverted = store
+        .revert("scp-001", &original, info!())
+        .await
+        .expect("Unable to revert page");
+
+    let content = store.get_page("scp-001").await.unwrap();
+    assert_eq!(content.as_deref(), Some("original content\n"));
+
+    // Old versions are still present
+    let content = store
+        .get_page_version("scp-001", &vandalized)
+        .await
+        .unwrap();
+    assert_eq!(content.as_deref(), Some("vandalism!!\n"));
+
+    let revisions = store
+        .list_revisions("scp-001", 10, 0)
+        .await
+        .expect("Unable to list revisions");
+    assert_eq!(revisions.len(), 3);
+    assert_eq!(revisions[0].hash(), &reverted);
+
+    // Commit which didn't change the page
+    let error = store
+        .revert("scp-001", &other, info!())
+        .await
+        .expect_err("Reverted to commit from another page");
+
+    match error {
+        Error::RevisionPageMismatch => (),
+        _ => panic!("Error doesn't match"),
+    }
+
+    // Commit which doesn't exist
+    let missing = GitHash::from_checked("0123456789abcdef0123456789abcdef01234567");
+    let error = store
+        .revert("scp-001", &missing, info!())
+        .await
+        .expect_err("Reverted to missing commit");
+
+    match error {
+        Error::RevisionNotFound => (),
+        _ => panic!("Error doesn't match"),
+    }
+
+    let content = store.get_page("scp-001").await.unwrap();
+    assert_eq!(content.as_deref(), Some("original content\n"));
+}
+
+#[test]
+fn author() {
+    color_backtrace::install();
+
+    task::block_on(author_internal());
+}
+
+async fn author_internal() {
+    // Create revision store
+    let directory = tempdir().expect("Unable to create temporary directory");
+    let repo = directory.path();
+    let store = GitStore::new(repo, "example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    let info = CommitInfo {
+        user_id: UserId::from_raw(42),
+        username: "Dr. Jack Bright",
+        message: "message",
+    };
+
+    store
+        .commit("scp-963", Some("amulet"), info)
+        .await
+        .expect("Unable to commit");
+
+    // Read author back from the log
+    let args = [
+        OsStr::new("git"),
+        OsStr::new("log"),
+        OsStr::new("--max-count=1"),
+        OsStr::new("--format=%an%n%ae%n%cn%n%ce"),
+    ];
+
+    let output = spawn_output(repo.as_os_str().to_os_string(), &args)
+        .await
+        .expect("Unable to get git log");
+
+    let output = str::from_utf8(&output).expect("Log output wasn't UTF-8");
+    let lines: Vec<_> = output.lines().collect();
+
+    assert_eq!(
+        lines,
+        [
+            "Dr. Jack Bright",
+            "user-42@example.org",
+            "Dr. Jack Bright",
+            "user-42@example.org",
+        ],
+    );
+
+    let revisions = store
+        .list_revisions("scp-963", 1, 0)
+        .await
+        .expect("Unable to get revisions");
+
+    assert_eq!(revisions[0].user_id(), Some(UserId::from_raw(42)));
+}
+
+#[test]
+fn blame() {
+    color_backtrace::install();
+
+    let directory = tempdir().expect("Unable to create temporary directory");
+    let store = GitStore::new(directory.path(), "example.org");
+
+    task::block_on(blame_internal(&store));
+}
+
+#[test]
+fn blame_memory() {
+    color_backtrace::install();
+
+    let store = MemoryStore::new("example.org");
+
+    task::block_on(blame_internal(&store));
+}
+
+async fn blame_internal(store: &dyn RevisionStore) {
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    macro_rules! commit {
+        ($user_id:expr, $content:expr) => {{
+            let info = CommitInfo {
+                user_id: UserId::from_raw($user_id),
+                username: "username",
+                message: "message",
+            };
+
+            store
+                .commit("scp-001", Some($content), info)
+                .await
+                .expect("Unable to commit")
+        }};
+    }
+
+    let first = commit!(1, "apple\nbanana\ncherry\n");
+    let second = commit!(2, "apple\nblueberry\ncherry\n");
+
+    let blame = store
+        .get_blame("scp-001", None)
+        .await
+        .expect("Unable to get blame")
+        .expect("No blame for page");
+
+    let lines: Vec<_> = blame
+        .lines()
+        .map(|(group, line)| {
+            let content = str::from_utf8(&line.line).expect("Line wasn't UTF-8");
+
+            (content, &line.commit, group.author.user_id())
+        })
+        .collect();
+
+    assert_eq!(
+        lines,
+        [
+            ("apple", &first, Some(UserId::from_raw(1))),
+            ("blueberry", &second, Some(UserId::from_raw(2))),
+            ("cherry", &first, Some(UserId::from_raw(1))),
+        ],
+    );
+
+    // Missing pages have no blame
+    let blame = store
+        .get_blame("scp-002", None)
+        .await
+        .expect("Unable to get blame");
+
+    assert!(blame.is_none());
+}
+
+#[test]
+fn search() {
+    color_backtrace::install();
+
+    task::block_on(search_internal());
+}
+
+async fn search_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    macro_rules! commit {
+        ($slug:expr, $content:expr) => {{
+            let info = CommitInfo {
+                user_id: UserId::from_raw(1),
+                username: "username",
+                message: "edit",
+            };
+
+            store
+                .commit($slug, Some($content), info)
+                .await
+                .expect("Unable to commit");
+        }};
+    }
+
+    macro_rules! search {
+        ($method:ident, $query:expr, $limit:expr) => {{
+            store
+                .$method($query, $limit)
+                .await
+                .expect("Unable to search")
+                .iter()
+                .map(|hit| {
+                    (
+                        hit.slug().to_string(),
+                        hit.line_number(),
+                        hit.line().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        }};
+    }
+
+    // Nothing matches yet
+    assert!(search!(search_content, "keter", 10).is_empty());
+
+    commit!(
+        "scp-001",
+        "Object Class: Keter\nSee [a-z]+ for details\n-e is not an option"
+    );
+    commit!("fragment:scp-002", "Object Class: Safe\nkept in a locker");
+    commit!("scp-003", &"keter\n".repeat(20));
+
+    let keter = |slug: &str, line| (slug.to_string(), line, String::from("keter"));
+
+    // Literal matching, case-sensitive
+    assert_eq!(
+        search!(search_content, "Object Class", 10),
+        [
+            (
+                String::from("fragment:scp-002"),
+                1,
+                String::from("Object Class: Safe")
+            ),
+            (
+                String::from("scp-001"),
+                1,
+                String::from("Object Class: Keter")
+            ),
+        ],
+    );
+
+    // Regex syntax and option-like queries are taken literally
+    let hits = search!(search_content, "[a-z]+", 10);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].1, 2);
+
+    let hits = search!(search_content, "-e is", 10);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].0, "scp-001");
+
+    assert!(search!(search_content, "", 10).is_empty());
+    assert!(search!(search_content, "Euclid", 10).is_empty());
+
+    // One page can't crowd out the others
+    assert_eq!(
+        search!(search_content, "keter", 10),
+        [
+            keter("scp-003", 1),
+            keter("scp-003", 2),
+            keter("scp-003", 3),
+            keter("scp-003", 4),
+            keter("scp-003", 5),
+        ],
+    );
+
+    assert_eq!(search!(search_content, "keter", 2).len(), 2);
+
+    // Regex mode must be asked for
+    let hits = search!(search_content_regex, "^kept|Keter$", 10);
+    let slugs: Vec<_> = hits.iter().map(|(slug, _, _)| slug.as_str()).collect();
+    assert_eq!(slugs, ["fragment:scp-002", "scp-001"]);
+}
+
+#[test]
+fn search_output() {
+    color_backtrace::install();
+
+    task::block_on(search_output_internal());
+}
+
+async fn search_output_internal() {
+    // Create revision store
+    let directory = tempdir().expect("Unable to create temporary directory");
+    let repo = directory.path();
+    let store = GitStore::new(repo, "example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    let info = CommitInfo {
+        user_id: UserId::from_raw(1),
+        username: "username",
+        message: "edit",
+    };
+
+    // Output larger than a pipe can hold still finishes
+    let line = format!("apollyon {}\n", "x".repeat(4000));
+    let content = line.repeat(100);
+    for i in 0..20 {
+        let slug = format!("scp-{:03}", 100 + i);
+
+        store
+            .commit(&slug, Some(&content), info)
+            .await
+            .expect("Unable to commit");
+    }
+
+    let hits = store
+        .search_content("apollyon", 50)
+        .await
+        .expect("Unable to search");
+
+    assert_eq!(hits.len(), 50);
+    assert!(hits.iter().all(|hit| hit.line().starts_with("apollyon")));
+}
+
+#[test]
+fn tags() {
+    color_backtrace::install();
+
+    task::block_on(tags_internal());
+}
+
+async fn tags_internal() {
+    // Create revision store
+    let store = MemoryStore::new("example.org");
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    macro_rules! commit {
+        ($slug:expr, $message:expr) => {{
+            let info = CommitInfo {
+                user_id: UserId::from_raw(1),
+                username: "username",
+                message: $message,
+            };
+
+            store
+                .commit($slug, Some($message), info)
+                .await
+                .expect("Unable to commit")
+        }};
+    }
+
+    macro_rules! tagged {
+        ($slug:expr, $tag:expr) => {
+            store
+                .get_tagged_revision($slug, $tag)
+                .await
+                .expect("Unable to get tagged revision")
+        };
+    }
+
+    macro_rules! check_err {
+        ($result:expr, $error:pat) => {
+            match $result {
+                Err($error) => (),
+                Err(error) => panic!("Unexpected error: {}", error),
+                Ok(_) => panic!("Tag operation succeeded"),
+            }
+        };
+    }
+
+    let first = commit!("scp-001", "first");
+    let other = commit!("fragment:scp-001", "other");
+    let second = commit!("scp-001", "second");
+
+    // Not tagged yet
+    check_err!(
+        store.get_tagged_revision("scp-001", "published").await,
+        Error::RevisionTagNotFound
+    );
+
+    store
+        .tag_revision("scp-001", &first, "published")
+        .await
+        .expect("Unable to tag revision");
+
+    let revision = tagged!("scp-001", "published");
+    assert_eq!(revision.hash(), &first);
+    assert_eq!(revision.message(), "first");
+
+    // Tags on different pages don't collide
+    store
+        .tag_revision("fragment:scp-001", &other, "published")
+        .await
+        .expect("Unable to tag revision");
+
+    assert_eq!(tagged!("scp-001", "published").hash(), &first);
+    assert_eq!(tagged!("fragment:scp-001", "published").hash(), &other);
+    check_err!(
+        store.get_tagged_revision("scp-002", "published").await,
+        Error::RevisionTagNotFound
+    );
+
+    // Re-tagging moves the tag
+    store
+        .tag_revision("scp-001", &second, "published")
+        .await
+        .expect("Unable to move tag");
+
+    assert_eq!(tagged!("scp-001", "published").hash(), &second);
+
+    // Only commits which changed the page can be tagged
+    check_err!(
+        store.tag_revision("scp-001", &other, "reviewed").await,
+        Error::RevisionPageMismatch
+    );
+
+    // Tag names must be safe for git
+    for &tag in &[
+        "",
+        "has space",
+        "a/b",
+        "../escape",
+        ".hidden",
+        "-option",
+        "ends.",
+        "name.lock",
+        "a..b",
+        "a~1",
+        "a^",
+        "a:b",
+        "@{-1}",
+        "ユニコード",
+    ] {
+        check_err!(
+            store.tag_revision("scp-001", &first, tag).await,
+            Error::InvalidRevisionTag(_)
+        );
+        check_err!(
+            store.get_tagged_revision("scp-001", tag).await,
+            Error::InvalidRevisionTag(_)
+        );
+    }
+
+    let long_tag = "a".repeat(65);
+    check_err!(
+        store.tag_revision("scp-001", &first, &long_tag).await,
+        Error::InvalidRevisionTag(_)
+    );
+
+    store
+        .tag_revision("scp-001", &first, "v1.0_reviewed-2")
+        .await
+        .expect("Unable to tag revision");
+
+    assert_eq!(tagged!("scp-001", "v1.0_reviewed-2").hash(), &first);
+}
+
+#[test]
+fn process_errors() {
+    color_backtrace::install();
+
+    task::block_on(process_errors_internal());
+}
+
+async fn process_errors_internal() {
+    let directory = tempdir().expect("Unable to create temporary directory");
+    let repo = directory.path().as_os_str();
+
+    macro_rules! args {
+        ($($arg:expr),*) => {
+            [$(OsStr::new($arg)),*]
+        };
+    }
+
+    // A broken git invocation gives its exit status and stderr
+    let result = spawn(repo.into(), &args!["git", "not-a-subcommand"]).await;
+    match result {
+        Err(Error::Revision {
+            command,
+            code,
+            stderr,
+        }) => {
+            assert_eq!(command, "git not-a-subcommand ");
+            assert_eq!(code, Some(1));
+            assert!(
+                stderr.contains("not-a-subcommand"),
+                "stderr missing: {:?}",
+                stderr,
+            );
+        }
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Broken command succeeded"),
+    }
+
+    // Long stderr is cut short, even past what a pipe can hold
+    let script = "head -c 200000 /dev/zero | tr '\\0' x >&2; exit 3";
+    let result = spawn(repo.into(), &args!["sh", "-c", script]).await;
+    match result {
+        Err(Error::Revision { code, stderr, .. }) => {
+            assert_eq!(code, Some(3));
+            assert!(stderr.len() < 2100, "stderr not truncated");
+            assert!(stderr.starts_with("xxxx"));
+            assert!(stderr.ends_with("..."));
+        }
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Failing command succeeded"),
+    }
+
+    // Long stdout is read in full
+    let script = "head -c 200000 /dev/zero";
+    let output = spawn_output(repo.into(), &args!["sh", "-c", script])
+        .await
+        .expect("Command with long output failed");
+
+    assert_eq!(output.len(), 200_000);
+
+    // Searches exiting with 1 just found nothing
+    spawn(repo.into(), &args!["git", "init", "--quiet"])
+        .await
+        .expect("Unable to create repository");
+
+    let output = spawn_search(repo.into(), &args!["git", "grep", "-e", "nothing"])
+        .await
+        .expect("Search with no matches failed");
+
+    assert!(output.is_empty());
+
+    // But other failures are still errors
+    let result = spawn_search(repo.into(), &args!["git", "grep", "--not-an-option"]).await;
+    match result {
+        Err(Error::Revision { code, .. }) => assert_ne!(code, Some(1)),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Broken search succeeded"),
+    }
+}
+
+#[test]
+fn concurrent() {
+    color_backtrace::install();
+
+    task::block_on(concurrent_internal());
+}
+
+async fn concurrent_internal() {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    const EDITS: usize = 8;
+
+    // Create revision store
+    let directory = tempdir().expect("Unable to create temporary directory");
+    let repo = directory.path();
+    let store = Arc::new(GitStore::new(repo, "example.org"));
+    store
+        .initial_commit()
+        .await
+        .expect("Unable to create initial commit");
+
+    // Edits to the same page and other pages, with reads in between,
+    // all on separate tasks so they can run on different threads.
+    let mut handles = Vec::new();
+    for i in 0..EDITS {
+        let store = Arc::clone(&store);
+
+        handles.push(task::spawn(async move {
+            let info = CommitInfo {
+                user_id: UserId::from_raw(1),
+                username: "username",
+                message: "concurrent edit",
+            };
+
+            let shared = format!("shared edit {}\n", i);
+            let own = format!("own page {}\n", i);
+            let slug = format!("page-{}", i);
+
+            let first = store
+                .commit("shared", Some(&shared), info)
+                .await
+                .expect("Unable to commit shared page");
+
+            let second = store
+                .commit(&slug, Some(&own), info)
+                .await
+                .expect("Unable to commit own page");
+
+            let content = store
+                .get_page_version("shared", &first)
+                .await
+                .expect("Unable to get page version")
+                .expect("Committed page version not found");
+
+            assert_eq!(content, shared, "Commit has the wrong contents");
+
+            store
+                .recent_changes(5, 0)
+                .await
+                .expect("Unable to get recent changes");
+
+            (first, second)
+        }));
+    }
+
+    let mut hashes = HashSet::new();
+    for handle in handles {
+        let (first, second) = handle.await;
+        hashes.insert(first.to_string());
+        hashes.insert(second.to_string());
+    }
+
+    assert_eq!(hashes.len(), EDITS * 2, "Commits were lost or merged");
+
+    // Every edit to the shared page is in its history
+    let revisions = store
+        .list_revisions("shared", EDITS * 2, 0)
+        .await
+        .expect("Unable to list revisions");
+
+    assert_eq!(revisions.len(), EDITS);
+
+    // The last commit is the current version
+    let content = store
+        .get_page("shared")
+        .await
+        .expect("Unable to get page")
+        .expect("Shared page not found");
+
+    let latest = store
+        .get_page_version("shared", revisions[0].hash())
+        .await
+        .expect("Unable to get page version");
+
+    assert_eq!(Some(content), latest);
+
+    for i in 0..EDITS {
+        let content = store
+            .get_page(&format!("page-{}", i))
+            .await
+            .expect("Unable to get page");
+
+        assert_eq!(content, Some(format!("own page {}\n", i)));
+    }
+
+    let changes = store
+        .recent_changes(EDITS * 4, 0)
+        .await
+        .expect("Unable to get recent changes");
+
+    assert_eq!(changes.len(), EDITS * 2);
+}
@@ -0,0 +1,96 @@
+/*
+ * session/events.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::prelude::{CredentialKind, LoginAttemptId, UserId};
+use chrono::prelude::*;
+use std::fmt::Debug;
+use std::net::IpAddr;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+
+/// How many events can be waiting for the listener before new ones are dropped.
+const EVENT_QUEUE_SIZE: usize = 1024;
+
+/// A login attempt, as reported to a `LoginEventListener`.
+///
+/// Attempts are recorded as failures until the user has been fully
+/// authenticated, so a successful login is reported twice: when it is first
+/// recorded, and again with `success` set once its session is created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginEvent {
+    pub login_attempt_id: LoginAttemptId,
+    pub user_id: Option<UserId>,
+    pub username_or_email: Option<String>,
+    pub credential_kind: CredentialKind,
+    pub remote_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub success: bool,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/// Receives login attempts as they are recorded, such as to forward them
+/// to a notification service.
+///
+/// This is called from a separate thread, so it may block without
+/// delaying logins. If it falls too far behind, events are dropped.
+pub trait LoginEventListener: Debug + Send + Sync + 'static {
+    fn on_login_attempt(&self, event: LoginEvent);
+}
+
+/// A bounded queue of events, delivered to the listener by a background thread.
+#[derive(Debug, Clone)]
+pub struct LoginEventSink {
+    sender: SyncSender<LoginEvent>,
+}
+
+impl LoginEventSink {
+    pub fn new(listener: Arc<dyn LoginEventListener>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(EVENT_QUEUE_SIZE);
+
+        // Exits once the sink is dropped
+        thread::Builder::new()
+            .name(String::from("login-events"))
+            .spawn(move || {
+                for event in receiver {
+                    listener.on_login_attempt(event);
+                }
+            })
+            .expect("Unable to start login event thread");
+
+        LoginEventSink { sender }
+    }
+
+    /// Queues the event for the listener, without waiting.
+    pub fn send(&self, event: LoginEvent) {
+        match self.sender.try_send(event) {
+            Ok(()) => (),
+            Err(TrySendError::Full(event)) => {
+                warn!(
+                    "Login event queue is full, dropping event for login attempt ID {}",
+                    event.login_attempt_id,
+                );
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                error!("Login event listener has stopped, dropping event");
+            }
+        }
+    }
+}
@@ -18,66 +18,220 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use super::{NewLoginAttempt, NewSession};
+use super::events::{LoginEvent, LoginEventListener, LoginEventSink};
+use super::{LockoutPolicy, NewLoginAttempt, NewSession, RateLimitPolicy, SessionExpiry};
 use crate::manager_prelude::*;
 use crate::schema::{login_attempts, sessions};
-use crate::utils::rows_to_result;
+use crate::token::{check_verifier, split_token, NewToken};
+use crate::utils::{display_address, lower_nullable, rows_to_result};
 use chrono::prelude::*;
+use chrono::Duration;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// The most login attempts which can be fetched at once.
+const MAX_LOGIN_ATTEMPTS: u32 = 1000;
+
+/// How many login attempts to delete per statement when purging.
+const PURGE_BATCH_SIZE: i64 = 10_000;
+
+/// The longest idempotency key a client can send with a login attempt.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
+/// The longest user agent stored with a login attempt or session, in bytes.
+/// Anything past this is cut off.
+const MAX_USER_AGENT_LEN: usize = 512;
+
+/// For sliding sessions, what fraction of the TTL must pass before
+/// the expiry is pushed forward again. This avoids a write on every request.
+const REFRESH_FRACTION: i32 = 4;
+
+/// The columns which make up a `Session`, leaving out the ID and token.
+const SESSION_COLUMNS: (
+    sessions::user_id,
+    sessions::login_attempt_id,
+    sessions::impersonator_id,
+    sessions::remote_address,
+    sessions::created_at,
+    sessions::expires_at,
+    sessions::user_agent,
+) = (
+    sessions::user_id,
+    sessions::login_attempt_id,
+    sessions::impersonator_id,
+    sessions::remote_address,
+    sessions::created_at,
+    sessions::expires_at,
+    sessions::user_agent,
+);
+
+/// Converts a client's user agent into the form which is stored.
+/// Blank values are treated as unknown, and long ones are truncated.
+fn clean_user_agent(user_agent: Option<&str>) -> Option<&str> {
+    let user_agent = user_agent.map(str::trim).filter(|s| !s.is_empty())?;
+    if user_agent.len() <= MAX_USER_AGENT_LEN {
+        return Some(user_agent);
+    }
+
+    let mut end = MAX_USER_AGENT_LEN;
+    while !user_agent.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    Some(&user_agent[..end])
+}
 
 pub struct SessionManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
+    session_ttl: Duration,
+    expiry: SessionExpiry,
+    lockout: LockoutPolicy,
+    rate_limit: RateLimitPolicy,
+    events: Option<LoginEventSink>,
 }
 
 impl SessionManager {
     #[inline]
-    pub fn new(conn: &Arc<PgConnection>) -> Self {
+    pub fn new(
+        conn: &ConnectionPool,
+        session_ttl: Duration,
+        expiry: SessionExpiry,
+        lockout: LockoutPolicy,
+        rate_limit: RateLimitPolicy,
+        listener: Option<Arc<dyn LoginEventListener>>,
+    ) -> Self {
         debug!("Creating session-manager service");
 
-        let conn = Arc::clone(conn);
-        SessionManager { conn }
+        let conn = conn.clone();
+        let events = listener.map(LoginEventSink::new);
+        SessionManager {
+            conn,
+            session_ttl,
+            expiry,
+            lockout,
+            rate_limit,
+            events,
+        }
     }
 
+    /// Passes the event to the listener, if there is one.
+    ///
+    /// Within a transaction, this waits until it commits, so attempts
+    /// and sessions which are rolled back are never reported.
+    fn emit(&self, event: LoginEvent) {
+        if let Some(ref events) = self.events {
+            let events = events.clone();
+            self.conn.after_commit(move || events.send(event));
+        }
+    }
+
+    /// Records a login attempt, returning its ID.
+    ///
+    /// If an idempotency key is given and an attempt was already recorded with it,
+    /// the ID of that attempt is returned instead, and no event is emitted.
+    ///
+    /// The user agent is truncated to `MAX_USER_AGENT_LEN` bytes,
+    /// and a blank one is stored as `None`.
+    #[allow(clippy::too_many_arguments)]
     pub async fn add_login_attempt(
         &self,
         user_id: Option<UserId>,
         username_or_email: Option<&str>,
-        remote_address: Option<&str>,
+        credential_kind: CredentialKind,
+        remote_address: Option<IpAddr>,
+        user_agent: Option<&str>,
         success: bool,
+        idempotency_key: Option<&str>,
     ) -> Result<LoginAttemptId> {
         {
             // Logging call
-            let remote_address = remote_address.unwrap_or("<unknown>");
+            let remote_address = display_address(remote_address);
 
-            match user_id {
-                Some(id) => {
+            match (user_id, username_or_email) {
+                (Some(id), _) => {
                     debug!(
                         "Adding login attempt for user ID {} from {}",
                         id, remote_address,
                     );
                 }
-                None => {
-                    let name = username_or_email
-                        .expect("One of user_id or username_or_email must be Some(_)");
-
+                (None, Some(name)) => {
                     debug!(
                         "Adding login attempt for user '{}' from {}",
                         name, remote_address,
                     );
                 }
+                (None, None) => {
+                    warn!("Login attempt has neither a user ID nor a username or email");
+
+                    return Err(Error::InvalidArgument(
+                        "one of user_id or username_or_email must be specified",
+                    ));
+                }
             }
         }
 
+        if let Some(key) = idempotency_key {
+            if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+                return Err(Error::InvalidArgument(
+                    "idempotency key must be between 1 and 128 bytes",
+                ));
+            }
+        }
+
+        let user_agent = clean_user_agent(user_agent);
         let model = NewLoginAttempt {
             user_id: user_id.map(|id| id.into()),
             username_or_email,
-            remote_address,
+            remote_address: remote_address.map(IpNetwork::from),
             success,
+            credential_kind: credential_kind.into(),
+            idempotency_key,
+            user_agent,
         };
 
-        let id = diesel::insert_into(login_attempts::table)
+        // If the key was already used, nothing is inserted and we return
+        // the original attempt. Concurrent inserts with the same key wait on
+        // the unique index, so only one of them records anything.
+        let result = diesel::insert_into(login_attempts::table)
             .values(&model)
-            .returning(login_attempts::dsl::login_attempt_id)
-            .get_result::<LoginAttemptId>(&*self.conn)?;
+            .on_conflict(login_attempts::dsl::idempotency_key)
+            .do_nothing()
+            .returning((
+                login_attempts::dsl::login_attempt_id,
+                login_attempts::dsl::attempted_at,
+            ))
+            .get_result::<(LoginAttemptId, DateTime<Utc>)>(&*self.conn.get()?)
+            .optional()?;
+
+        let (id, attempted_at) = match (result, idempotency_key) {
+            (Some(row), _) => row,
+            (None, Some(key)) => {
+                debug!("Login attempt with this idempotency key already recorded");
+
+                let id = login_attempts::table
+                    .filter(login_attempts::idempotency_key.eq(key))
+                    .select(login_attempts::dsl::login_attempt_id)
+                    .first::<LoginAttemptId>(&*self.conn.get()?)?;
+
+                return Ok(id);
+            }
+            (None, None) => {
+                error!("Login attempt without an idempotency key conflicted on insert");
+
+                return Err(Error::StaticMsg("login attempt insert had a conflict"));
+            }
+        };
+
+        self.emit(LoginEvent {
+            login_attempt_id: id,
+            user_id,
+            username_or_email: username_or_email.map(String::from),
+            credential_kind,
+            remote_address,
+            user_agent: user_agent.map(String::from),
+            success,
+            attempted_at,
+        });
 
         Ok(id)
     }
@@ -86,7 +240,7 @@ impl SessionManager {
         &self,
         user_id: UserId,
         login_attempt_id: LoginAttemptId,
-    ) -> Result<Session> {
+    ) -> Result<IssuedSession> {
         use login_attempts::dsl;
 
         debug!(
@@ -94,49 +248,186 @@ impl SessionManager {
             user_id, login_attempt_id,
         );
 
-        let user_id = user_id.into();
-        let login_attempt_id = login_attempt_id.into();
-
         // Mark login attempt as successful
-        diesel::update(dsl::login_attempts.filter(dsl::login_attempt_id.eq(login_attempt_id)))
-            .set(dsl::success.eq(true))
-            .execute(&*self.conn)?;
+        let attempt_id: i64 = login_attempt_id.into();
+        let (username_or_email, credential_kind, remote_address, user_agent, attempted_at) =
+            diesel::update(dsl::login_attempts.filter(dsl::login_attempt_id.eq(attempt_id)))
+                .set(dsl::success.eq(true))
+                .returning((
+                    dsl::username_or_email,
+                    dsl::credential_kind,
+                    dsl::remote_address,
+                    dsl::user_agent,
+                    dsl::attempted_at,
+                ))
+                .get_result::<(
+                    Option<String>,
+                    String,
+                    Option<IpNetwork>,
+                    Option<String>,
+                    DateTime<Utc>,
+                )>(&*self.conn.get()?)?;
+
+        let credential_kind = CredentialKind::from_stored(&credential_kind)?;
+
+        let event = LoginEvent {
+            login_attempt_id,
+            user_id: Some(user_id),
+            username_or_email,
+            credential_kind,
+            remote_address: remote_address.map(|network| network.ip()),
+            user_agent: user_agent.clone(),
+            success: true,
+            attempted_at,
+        };
 
         // Add session
+        let token = NewToken::generate();
         let model = NewSession {
-            user_id,
-            login_attempt_id,
+            user_id: user_id.into(),
+            login_attempt_id: Some(attempt_id),
+            impersonator_id: None,
+            remote_address,
+            expires_at: Some(Utc::now() + self.session_ttl),
+            token_selector: token.selector(),
+            token_hash: token.hash(),
+            user_agent: user_agent.as_deref(),
         };
 
         let session = diesel::insert_into(sessions::table)
             .values(&model)
-            .returning((
-                sessions::dsl::session_id,
-                sessions::dsl::user_id,
-                sessions::dsl::login_attempt_id,
-            ))
-            .get_result::<Session>(&*self.conn)?;
+            .returning(SESSION_COLUMNS)
+            .get_result::<Session>(&*self.conn.get()?)?;
 
-        Ok(session)
+        self.emit(event);
+
+        Ok(IssuedSession::new(session, token.into_token()))
     }
 
-    pub async fn check_session(&self, session_id: SessionId, user_id: UserId) -> Result<()> {
-        debug!("Checking session ID {} for user ID {}", session_id, user_id);
+    pub async fn create_impersonation_session(
+        &self,
+        impersonator_id: UserId,
+        user_id: UserId,
+        expires_at: DateTime<Utc>,
+    ) -> Result<IssuedSession> {
+        debug!(
+            "Creating an impersonation session for user ID {} by user ID {} (expires {})",
+            user_id, impersonator_id, expires_at,
+        );
 
-        let session: i64 = session_id.into();
-        let user: i64 = user_id.into();
+        let token = NewToken::generate();
+        let model = NewSession {
+            user_id: user_id.into(),
+            login_attempt_id: None,
+            impersonator_id: Some(impersonator_id.into()),
+            remote_address: None,
+            expires_at: Some(expires_at),
+            token_selector: token.selector(),
+            token_hash: token.hash(),
+            user_agent: None,
+        };
+
+        let session = diesel::insert_into(sessions::table)
+            .values(&model)
+            .returning(SESSION_COLUMNS)
+            .get_result::<Session>(&*self.conn.get()?)?;
+
+        Ok(IssuedSession::new(session, token.into_token()))
+    }
+
+    /// Gets the session a client's token refers to, along with its ID.
+    /// Returns `InvalidSession` if the token is wrong or the session has expired.
+    ///
+    /// If sessions are sliding, this also extends the session.
+    ///
+    /// The ID is only for use within the server, clients always refer to sessions by token.
+    pub async fn resolve_token(&self, token: &str) -> Result<(SessionId, Session)> {
+        use diesel::dsl::now;
+
+        debug!("Resolving session token");
+
+        let (selector, verifier) = split_token(token).ok_or(Error::InvalidSession)?;
         let result = sessions::table
-            .filter(sessions::session_id.eq(session))
-            .filter(sessions::user_id.eq(user))
-            .first::<Session>(&*self.conn)
+            .filter(sessions::token_selector.eq(selector))
+            .filter(
+                sessions::expires_at
+                    .is_null()
+                    .or(sessions::expires_at.gt(now)),
+            )
+            .select((sessions::session_id, SESSION_COLUMNS, sessions::token_hash))
+            .first::<(SessionId, Session, Vec<u8>)>(&*self.conn.get()?)
             .optional()?;
 
         match result {
-            Some(_) => Ok(()),
+            Some((session_id, session, token_hash)) if check_verifier(&token_hash, verifier) => {
+                let session = self.touch_session(session_id, session)?;
+
+                Ok((session_id, session))
+            }
+            Some((session_id, _, _)) => {
+                warn!("Invalid token for session ID {}", session_id);
+                Err(Error::InvalidSession)
+            }
             None => Err(Error::InvalidSession),
         }
     }
 
+    /// Gets the session a client's token refers to.
+    /// Returns `InvalidSession` if the token is wrong or the session has expired.
+    ///
+    /// If sessions are sliding, this also extends the session.
+    pub async fn validate_token(&self, token: &str) -> Result<Session> {
+        let (_, session) = self.resolve_token(token).await?;
+
+        Ok(session)
+    }
+
+    /// Pushes a sliding session's expiry forward, returning the updated session.
+    ///
+    /// Impersonation sessions and sessions without an expiry are left alone.
+    fn touch_session(&self, session_id: SessionId, session: Session) -> Result<Session> {
+        let max_lifetime = match self.expiry {
+            SessionExpiry::Fixed => return Ok(session),
+            SessionExpiry::Sliding { max_lifetime } => max_lifetime,
+        };
+
+        let expires_at = match session.expires_at() {
+            Some(expires_at) if !session.is_impersonation() => expires_at,
+            _ => return Ok(session),
+        };
+
+        // Only refresh once enough of the TTL has been used up
+        let now = Utc::now();
+        let threshold = now + self.session_ttl - self.session_ttl / REFRESH_FRACTION;
+        if expires_at >= threshold {
+            return Ok(session);
+        }
+
+        let new_expires_at = (now + self.session_ttl).min(session.created_at() + max_lifetime);
+        if new_expires_at <= expires_at {
+            return Ok(session);
+        }
+
+        debug!(
+            "Extending session ID {} to expire at {}",
+            session_id, new_expires_at,
+        );
+
+        // Another request may have already extended it
+        let id: i64 = session_id.into();
+        let updated = diesel::update(
+            sessions::table
+                .filter(sessions::session_id.eq(id))
+                .filter(sessions::expires_at.lt(new_expires_at)),
+        )
+        .set(sessions::expires_at.eq(new_expires_at))
+        .returning(SESSION_COLUMNS)
+        .get_result::<Session>(&*self.conn.get()?)
+        .optional()?;
+
+        Ok(updated.unwrap_or(session))
+    }
+
     pub async fn end_session(&self, session_id: SessionId, user_id: UserId) -> Result<()> {
         debug!("Ending session ID {} for user ID {}", session_id, user_id);
 
@@ -145,15 +436,89 @@ impl SessionManager {
         let rows = diesel::delete(sessions::table)
             .filter(sessions::session_id.eq(session))
             .filter(sessions::user_id.eq(user))
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
-        if rows_to_result(rows) {
+        if rows_to_result(rows)? {
             Ok(())
         } else {
             Err(Error::InvalidSession)
         }
     }
 
+    /// Removes the given session. Succeeds even if it doesn't exist.
+    pub async fn invalidate_session(&self, session_id: SessionId) -> Result<()> {
+        debug!("Invalidating session ID {}", session_id);
+
+        let id: i64 = session_id.into();
+        diesel::delete(sessions::table)
+            .filter(sessions::session_id.eq(id))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
+
+    /// Removes all sessions for the given user. Returns how many there were.
+    pub async fn invalidate_all_sessions(&self, user_id: UserId) -> Result<usize> {
+        debug!("Invalidating all sessions for user ID {}", user_id);
+
+        let id: i64 = user_id.into();
+        let rows = diesel::delete(sessions::table)
+            .filter(sessions::user_id.eq(id))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(rows)
+    }
+
+    /// Removes the user's sessions, including any where they were impersonating
+    /// someone else, and strips their identity from their login attempts.
+    ///
+    /// This includes failed attempts which weren't tied to the account, but were made
+    /// with its name, slug, or email. The attempts themselves are kept so totals stay
+    /// accurate, but the address and user agent are removed along with the identity.
+    pub async fn anonymize_user(&self, user: &User) -> Result<()> {
+        use login_attempts::dsl;
+
+        let user_id = user.id();
+        info!(
+            "Removing sessions and login identity for user ID {}",
+            user_id
+        );
+
+        let id: i64 = user_id.into();
+        let names: Vec<String> = [user.name(), user.slug(), user.email()]
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect();
+
+        let conn = &*self.conn.get()?;
+
+        diesel::delete(
+            sessions::table.filter(
+                sessions::user_id
+                    .eq(id)
+                    .or(sessions::impersonator_id.eq(id)),
+            ),
+        )
+        .execute(conn)?;
+
+        diesel::update(
+            login_attempts::table.filter(
+                dsl::user_id.eq(id).or(dsl::user_id
+                    .is_null()
+                    .and(lower_nullable(dsl::username_or_email).eq_any(names))),
+            ),
+        )
+        .set((
+            dsl::user_id.eq(None::<i64>),
+            dsl::username_or_email.eq(None::<String>),
+            dsl::remote_address.eq(None::<IpNetwork>),
+            dsl::user_agent.eq(None::<String>),
+        ))
+        .execute(conn)?;
+
+        Ok(())
+    }
+
     pub async fn end_other_sessions(
         &self,
         session_id: SessionId,
@@ -166,24 +531,58 @@ impl SessionManager {
 
         self.transaction(async {
             // Get sessions to invalidate
-            let (_, others) = self.get_sessions(session_id, user_id).await?;
+            let rows = self.get_active_session_rows(user_id).await?;
+            if !rows.iter().any(|(id, _)| *id == session_id) {
+                return Err(Error::InvalidSession);
+            }
 
-            let user: i64 = user_id.into();
-            let other_ids = others
-                .iter()
-                .map(|session| -> i64 { session.session_id().into() });
+            let (other_ids, others): (Vec<i64>, Vec<Session>) = rows
+                .into_iter()
+                .filter(|(id, _)| *id != session_id)
+                .map(|(id, session)| (id.to_i64(), session))
+                .unzip();
 
             // Remove from active sessions table
+            let user: i64 = user_id.into();
             diesel::delete(sessions::table)
                 .filter(sessions::session_id.eq_any(other_ids))
                 .filter(sessions::user_id.eq(user))
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
 
             Ok(others)
         })
         .await
     }
 
+    /// Gets all unexpired sessions for a user, along with their IDs, oldest first.
+    async fn get_active_session_rows(&self, user_id: UserId) -> Result<Vec<(SessionId, Session)>> {
+        use diesel::dsl::now;
+
+        debug!("Getting active sessions for user ID {}", user_id);
+
+        let id: i64 = user_id.into();
+        let rows = sessions::table
+            .filter(sessions::user_id.eq(id))
+            .filter(
+                sessions::expires_at
+                    .is_null()
+                    .or(sessions::expires_at.gt(now)),
+            )
+            .order_by((sessions::created_at.asc(), sessions::session_id.asc()))
+            .select((sessions::session_id, SESSION_COLUMNS))
+            .get_results::<(SessionId, Session)>(&*self.conn.get()?)?;
+
+        Ok(rows)
+    }
+
+    /// Gets all unexpired sessions for a user, oldest first.
+    pub async fn get_active_sessions(&self, user_id: UserId) -> Result<Vec<Session>> {
+        let rows = self.get_active_session_rows(user_id).await?;
+        let sessions = rows.into_iter().map(|(_, session)| session).collect();
+
+        Ok(sessions)
+    }
+
     pub async fn get_sessions(
         &self,
         session_id: SessionId,
@@ -194,30 +593,149 @@ impl SessionManager {
             user_id, session_id,
         );
 
-        // Get all sessions for a user
-        let id: i64 = user_id.into();
-        let mut sessions = sessions::table
-            .filter(sessions::user_id.eq(id))
-            .get_results::<Session>(&*self.conn)?;
+        let mut current = None;
+        let mut others = Vec::new();
 
         // Pick out the current session
-        let mut current = None;
-        for (idx, session) in sessions.iter().enumerate() {
-            if session.session_id() == session_id {
-                current = Some(idx);
-                break;
+        for (id, session) in self.get_active_session_rows(user_id).await? {
+            if id == session_id {
+                current = Some(session);
+            } else {
+                others.push(session);
             }
         }
 
         // Return an error if there is no current session
         match current {
+            Some(current) => Ok((current, others)),
             None => Err(Error::InvalidSession),
-            Some(idx) => {
-                let current = sessions.remove(idx);
+        }
+    }
+
+    /// Determines if a user has too many recent failed login attempts.
+    ///
+    /// Only failures after the most recent successful login are counted,
+    /// so logging in resets the count.
+    pub async fn is_locked_out(&self, user_id: UserId, now: DateTime<Utc>) -> Result<bool> {
+        use login_attempts::dsl;
+
+        debug!("Checking if user ID {} is locked out", user_id);
+
+        let id: i64 = user_id.into();
+        let last_success = login_attempts::table
+            .filter(dsl::user_id.eq(id))
+            .filter(dsl::success.eq(true))
+            .select(diesel::dsl::max(dsl::attempted_at))
+            .first::<Option<DateTime<Utc>>>(&*self.conn.get()?)?;
 
-                Ok((current, sessions))
+        let mut since = now - self.lockout.window;
+        if let Some(last_success) = last_success {
+            if last_success > since {
+                since = last_success;
             }
         }
+
+        let failures = login_attempts::table
+            .filter(dsl::user_id.eq(id))
+            .filter(dsl::success.eq(false))
+            .filter(dsl::attempted_at.gt(since))
+            .count()
+            .get_result::<i64>(&*self.conn.get()?)?;
+
+        if failures >= self.lockout.max_failures {
+            warn!(
+                "User ID {} is locked out ({} failed login attempts since {})",
+                user_id, failures, since,
+            );
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Checks if too many login attempts have come from the given address recently.
+    ///
+    /// IPv6 addresses are limited by /64 network, since a single host
+    /// is commonly assigned an entire block and can use any address in it.
+    pub async fn is_rate_limited(&self, address: IpAddr, now: DateTime<Utc>) -> Result<bool> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Bool, Inet};
+
+        debug!("Checking if {} is rate limited", address);
+
+        let prefix = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 64,
+        };
+
+        let network = IpNetwork::new(address, prefix)
+            .and_then(|network| IpNetwork::new(network.network(), prefix))
+            .expect("Prefix length is invalid");
+
+        let since = now - self.rate_limit.window;
+        let in_network = sql::<Bool>("remote_address <<= ").bind::<Inet, _>(network);
+        let attempts = login_attempts::table
+            .filter(login_attempts::attempted_at.gt(since))
+            .filter(in_network)
+            .count()
+            .get_result::<i64>(&*self.conn.get()?)?;
+
+        if attempts >= self.rate_limit.max_attempts {
+            warn!(
+                "Address {} is rate limited ({} login attempts since {})",
+                address, attempts, since,
+            );
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Reports how many networks failed logins for the user came from within the window.
+    ///
+    /// Addresses are grouped into /24 networks for IPv4 and /64 for IPv6,
+    /// since attackers can easily use many addresses within a single block.
+    pub async fn suspicious_activity(
+        &self,
+        user_id: UserId,
+        window: Duration,
+    ) -> Result<SuspicionReport> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::BigInt;
+
+        debug!(
+            "Checking for suspicious login activity on user ID {}",
+            user_id
+        );
+
+        let id: i64 = user_id.into();
+        let since = Utc::now() - window;
+        let (failures, distinct_networks) = login_attempts::table
+            .filter(login_attempts::user_id.eq(id))
+            .filter(login_attempts::success.eq(false))
+            .filter(login_attempts::attempted_at.gt(since))
+            .select(sql::<(BigInt, BigInt)>(
+                "COUNT(*), \
+                 COUNT(DISTINCT network(set_masklen(remote_address, \
+                    CASE family(remote_address) WHEN 4 THEN 24 ELSE 64 END)))",
+            ))
+            .get_result::<(i64, i64)>(&*self.conn.get()?)?;
+
+        let suspicious = distinct_networks > self.lockout.max_failure_networks;
+        if suspicious {
+            warn!(
+                "Failed logins for user ID {} came from {} different networks",
+                user_id, distinct_networks,
+            );
+        }
+
+        Ok(SuspicionReport::new(
+            failures,
+            distinct_networks,
+            suspicious,
+        ))
     }
 
     pub async fn get_login_attempt(
@@ -229,7 +747,7 @@ impl SessionManager {
         let id: i64 = login_attempt_id.into();
         let attempt = login_attempts::table
             .find(id)
-            .first::<LoginAttempt>(&*self.conn)?;
+            .first::<LoginAttempt>(&*self.conn.get()?)?;
 
         Ok(attempt)
     }
@@ -238,38 +756,341 @@ impl SessionManager {
         &self,
         user_id: UserId,
         since: DateTime<Tz>,
+        success: Option<bool>,
+        limit: u32,
+        offset: u32,
     ) -> Result<Vec<LoginAttempt>> {
         debug!(
-            "Getting login attempts for user ID {} since {}",
+            "Getting login attempts for user ID {} since {} (success {:?}, limit {}, offset {})",
             user_id,
             since.time(),
+            success,
+            limit,
+            offset,
         );
 
         let id: i64 = user_id.into();
-        let attempts = login_attempts::table
+        let mut query = login_attempts::table
             .filter(login_attempts::attempted_at.gt(since))
             .filter(login_attempts::user_id.eq(id))
+            .into_boxed();
+
+        if let Some(success) = success {
+            query = query.filter(login_attempts::success.eq(success));
+        }
+
+        let attempts = query
+            .order_by(login_attempts::attempted_at.desc())
+            .limit(limit.min(MAX_LOGIN_ATTEMPTS).into())
+            .offset(offset.into())
+            .get_results::<LoginAttempt>(&*self.conn.get()?)?;
+
+        Ok(attempts)
+    }
+
+    /// Gets every login attempt for a user, most recent first.
+    /// This has no limit, and is intended for exporting their data.
+    pub async fn get_all_user_login_attempts(&self, user_id: UserId) -> Result<Vec<LoginAttempt>> {
+        debug!("Getting all login attempts for user ID {}", user_id);
+
+        let id: i64 = user_id.into();
+        let attempts = login_attempts::table
+            .filter(login_attempts::user_id.eq(id))
+            .order_by((
+                login_attempts::attempted_at.desc(),
+                login_attempts::login_attempt_id.desc(),
+            ))
+            .get_results::<LoginAttempt>(&*self.conn.get()?)?;
+
+        Ok(attempts)
+    }
+
+    /// Gets the most recent login attempt for each of the given users.
+    /// Returned in the same order as the IDs, with `None` for users without any.
+    pub async fn get_latest_login_attempts(
+        &self,
+        ids: &[UserId],
+    ) -> Result<Vec<Option<LoginAttempt>>> {
+        debug!("Getting latest login attempts for user ids: {:?}", ids);
+
+        // Load
+        let mut result = {
+            let ids: Vec<_> = ids.iter().map(|id| id.to_i64()).collect();
+            login_attempts::table
+                .filter(login_attempts::user_id.eq_any(ids))
+                .distinct_on(login_attempts::user_id)
+                .order_by((
+                    login_attempts::user_id,
+                    login_attempts::attempted_at.desc(),
+                    login_attempts::login_attempt_id.desc(),
+                ))
+                .load::<LoginAttempt>(&*self.conn.get()?)?
+        };
+
+        // Add in nones where needed
+        let mut attempts = Vec::new();
+        for id in ids.iter().copied() {
+            let attempt = result
+                .iter()
+                .position(|attempt| attempt.user_id() == Some(id))
+                .map(|idx| result.swap_remove(idx));
+
+            attempts.push(attempt);
+        }
+
+        Ok(attempts)
+    }
+
+    /// Gets login attempts from the given remote address.
+    /// If `None`, matches attempts where the address is unknown.
+    pub async fn get_login_attempts_by_address<Tz: TimeZone>(
+        &self,
+        remote_address: Option<IpAddr>,
+        since: DateTime<Tz>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<LoginAttempt>> {
+        debug!(
+            "Getting login attempts from {} since {} (limit {}, offset {})",
+            display_address(remote_address),
+            since.time(),
+            limit,
+            offset,
+        );
+
+        let mut query = login_attempts::table
+            .filter(login_attempts::attempted_at.gt(since))
+            .into_boxed();
+
+        match remote_address {
+            Some(address) => {
+                let network = IpNetwork::from(address);
+                query = query.filter(login_attempts::remote_address.eq(network));
+            }
+            None => {
+                query = query.filter(login_attempts::remote_address.is_null());
+            }
+        }
+
+        let attempts = query
+            .order_by(login_attempts::attempted_at.desc())
+            .limit(limit.min(MAX_LOGIN_ATTEMPTS).into())
+            .offset(offset.into())
+            .get_results::<LoginAttempt>(&*self.conn.get()?)?;
+
+        Ok(attempts)
+    }
+
+    /// Gets login attempts from any address within the given network.
+    pub async fn get_login_attempts_in_network<Tz: TimeZone>(
+        &self,
+        network: IpNetwork,
+        since: DateTime<Tz>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<LoginAttempt>> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Bool, Inet};
+
+        debug!(
+            "Getting login attempts in network {} since {} (limit {}, offset {})",
+            network,
+            since.time(),
+            limit,
+            offset,
+        );
+
+        // Diesel has no operator for "is contained by or equals"
+        let in_network = sql::<Bool>("remote_address <<= ").bind::<Inet, _>(network);
+
+        let attempts = login_attempts::table
+            .filter(login_attempts::attempted_at.gt(since))
+            .filter(in_network)
             .order_by(login_attempts::attempted_at.desc())
-            .limit(100)
-            .get_results::<LoginAttempt>(&*self.conn)?;
+            .limit(limit.min(MAX_LOGIN_ATTEMPTS).into())
+            .offset(offset.into())
+            .get_results::<LoginAttempt>(&*self.conn.get()?)?;
 
         Ok(attempts)
     }
 
+    pub async fn count_login_attempts<Tz: TimeZone>(
+        &self,
+        user_id: UserId,
+        since: DateTime<Tz>,
+    ) -> Result<i64> {
+        debug!(
+            "Counting login attempts for user ID {} since {}",
+            user_id,
+            since.time(),
+        );
+
+        let id: i64 = user_id.into();
+        let count = login_attempts::table
+            .filter(login_attempts::attempted_at.gt(since))
+            .filter(login_attempts::user_id.eq(id))
+            .count()
+            .get_result::<i64>(&*self.conn.get()?)?;
+
+        Ok(count)
+    }
+
     pub async fn get_all_login_attempts<Tz: TimeZone>(
         &self,
         since: DateTime<Tz>,
+        limit: u32,
+        offset: u32,
     ) -> Result<Vec<LoginAttempt>> {
-        debug!("Getting all login attempts for since {}", since.time());
+        debug!(
+            "Getting all login attempts for since {} (limit {}, offset {})",
+            since.time(),
+            limit,
+            offset,
+        );
 
         let attempts = login_attempts::table
             .filter(login_attempts::attempted_at.gt(since))
             .order_by(login_attempts::attempted_at.desc())
-            .limit(100)
-            .get_results::<LoginAttempt>(&*self.conn)?;
+            .limit(limit.min(MAX_LOGIN_ATTEMPTS).into())
+            .offset(offset.into())
+            .get_results::<LoginAttempt>(&*self.conn.get()?)?;
+
+        Ok(attempts)
+    }
+
+    /// Gets login attempts older than the cursor, or the most recent ones if `None`.
+    ///
+    /// Unlike offsets, this doesn't need to scan all the attempts before the cursor,
+    /// so it stays fast for exporting the whole table.
+    pub async fn get_login_attempts_after(
+        &self,
+        cursor: Option<LoginAttemptCursor>,
+        limit: u32,
+    ) -> Result<Vec<LoginAttempt>> {
+        use login_attempts::dsl;
+
+        debug!(
+            "Getting login attempts after cursor {:?} (limit {})",
+            cursor, limit,
+        );
+
+        let mut query = login_attempts::table.into_boxed();
+
+        // Ties in time are broken by ID, so no attempts are skipped or repeated
+        if let Some(cursor) = cursor {
+            let attempted_at = cursor.attempted_at();
+            let id: i64 = cursor.login_attempt_id().into();
+
+            query = query.filter(
+                dsl::attempted_at.lt(attempted_at).or(dsl::attempted_at
+                    .eq(attempted_at)
+                    .and(dsl::login_attempt_id.lt(id))),
+            );
+        }
+
+        let attempts = query
+            .order_by((dsl::attempted_at.desc(), dsl::login_attempt_id.desc()))
+            .limit(limit.min(MAX_LOGIN_ATTEMPTS).into())
+            .get_results::<LoginAttempt>(&*self.conn.get()?)?;
 
         Ok(attempts)
     }
+
+    pub async fn login_stats<Tz: TimeZone>(&self, since: DateTime<Tz>) -> Result<LoginStats> {
+        use diesel::dsl::sql;
+        use diesel::sql_types::BigInt;
+
+        debug!("Getting login statistics since {}", since.time());
+
+        let (successful, failed, distinct_users) = login_attempts::table
+            .filter(login_attempts::attempted_at.gt(since))
+            .select(sql::<(BigInt, BigInt, BigInt)>(
+                "COUNT(*) FILTER (WHERE success), \
+                 COUNT(*) FILTER (WHERE NOT success), \
+                 COUNT(DISTINCT user_id)",
+            ))
+            .get_result::<(i64, i64, i64)>(&*self.conn.get()?)?;
+
+        Ok(LoginStats::new(successful, failed, distinct_users))
+    }
+
+    /// Deletes login attempts older than the given date, returning how many were removed.
+    ///
+    /// Attempts still referenced by a session are kept, as is each user's
+    /// most recent successful attempt, since lockouts are reset from it.
+    ///
+    /// Rows are deleted in batches, to avoid holding locks on the table
+    /// for long periods when there is a large backlog.
+    pub async fn purge_login_attempts(&self, older_than: DateTime<Utc>) -> Result<usize> {
+        use diesel::sql_types::{BigInt, Timestamptz};
+
+        info!("Purging login attempts older than {}", older_than);
+
+        let mut total = 0;
+        loop {
+            let rows = diesel::sql_query(
+                "DELETE FROM login_attempts WHERE login_attempt_id IN (
+                    SELECT login_attempt_id FROM login_attempts AS attempt
+                    WHERE attempted_at < $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM sessions
+                        WHERE sessions.login_attempt_id = attempt.login_attempt_id
+                    )
+                    AND NOT (success AND NOT EXISTS (
+                        SELECT 1 FROM login_attempts AS later
+                        WHERE later.user_id = attempt.user_id
+                        AND later.success
+                        AND later.attempted_at > attempt.attempted_at
+                    ))
+                    LIMIT $2
+                )",
+            )
+            .bind::<Timestamptz, _>(older_than)
+            .bind::<BigInt, _>(PURGE_BATCH_SIZE)
+            .execute(&*self.conn.get()?)?;
+
+            debug!("Purged batch of {} login attempts", rows);
+            total += rows;
+
+            if (rows as i64) < PURGE_BATCH_SIZE {
+                break;
+            }
+        }
+
+        Ok(total)
+    }
+
+    #[cfg(test)]
+    pub async fn set_login_attempt_time(
+        &self,
+        login_attempt_id: LoginAttemptId,
+        attempted_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let id: i64 = login_attempt_id.into();
+        diesel::update(login_attempts::table.find(id))
+            .set(login_attempts::attempted_at.eq(attempted_at))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub async fn set_session_times(
+        &self,
+        token: &str,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let (selector, _) = split_token(token).ok_or(Error::InvalidSession)?;
+        diesel::update(sessions::table.filter(sessions::token_selector.eq(selector)))
+            .set((
+                sessions::created_at.eq(created_at),
+                sessions::expires_at.eq(expires_at),
+            ))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
 }
 
 impl_async_transaction!(SessionManager);
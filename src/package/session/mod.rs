@@ -18,9 +18,13 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+mod events;
 mod manager;
 mod models;
+mod policy;
 
+pub use self::events::{LoginEvent, LoginEventListener};
 pub use self::manager::*;
+pub use self::policy::{LockoutPolicy, RateLimitPolicy, SessionExpiry};
 
 use self::models::*;
@@ -18,20 +18,31 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use crate::prelude::IpNetwork;
 use crate::schema::{login_attempts, sessions};
+use chrono::prelude::*;
 
 #[derive(Debug, Insertable)]
 #[table_name = "login_attempts"]
 pub struct NewLoginAttempt<'a> {
     pub user_id: Option<i64>,
     pub username_or_email: Option<&'a str>,
-    pub remote_address: Option<&'a str>,
+    pub remote_address: Option<IpNetwork>,
     pub success: bool,
+    pub credential_kind: &'static str,
+    pub idempotency_key: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
 }
 
 #[derive(Debug, Insertable)]
 #[table_name = "sessions"]
-pub struct NewSession {
+pub struct NewSession<'a> {
     pub user_id: i64,
-    pub login_attempt_id: i64,
+    pub login_attempt_id: Option<i64>,
+    pub impersonator_id: Option<i64>,
+    pub remote_address: Option<IpNetwork>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub token_selector: &'a str,
+    pub token_hash: &'a [u8],
+    pub user_agent: Option<&'a str>,
 }
@@ -0,0 +1,86 @@
+/*
+ * session/policy.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::Duration;
+
+/// Controls when an account is locked after repeated failed logins.
+#[derive(Debug, Copy, Clone)]
+pub struct LockoutPolicy {
+    /// How many failed attempts since the last successful login
+    /// before further attempts are refused.
+    pub max_failures: i64,
+
+    /// How far back failed attempts are counted.
+    pub window: Duration,
+
+    /// How many different networks an account's failed attempts can come from
+    /// before they are reported as suspicious. This does not lock the account.
+    pub max_failure_networks: i64,
+}
+
+impl Default for LockoutPolicy {
+    #[inline]
+    fn default() -> Self {
+        LockoutPolicy {
+            max_failures: 10,
+            window: Duration::minutes(15),
+            max_failure_networks: 5,
+        }
+    }
+}
+
+/// Controls how many login attempts can come from a single origin,
+/// regardless of which accounts they are for.
+#[derive(Debug, Copy, Clone)]
+pub struct RateLimitPolicy {
+    /// How many attempts from one address are allowed within the window.
+    pub max_attempts: i64,
+
+    /// How far back attempts are counted.
+    pub window: Duration,
+}
+
+impl Default for RateLimitPolicy {
+    #[inline]
+    fn default() -> Self {
+        RateLimitPolicy {
+            max_attempts: 60,
+            window: Duration::minutes(1),
+        }
+    }
+}
+
+/// Controls whether using a session extends it.
+#[derive(Debug, Copy, Clone)]
+pub enum SessionExpiry {
+    /// Sessions expire a fixed time after logging in.
+    Fixed,
+
+    /// Each use pushes the expiry forward to a full TTL away,
+    /// though never past `max_lifetime` after logging in.
+    Sliding { max_lifetime: Duration },
+}
+
+impl Default for SessionExpiry {
+    #[inline]
+    fn default() -> Self {
+        SessionExpiry::Fixed
+    }
+}
@@ -0,0 +1,80 @@
+/*
+ * totp/crypto.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::TotpKey;
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::aes::KeySize;
+use crypto::aes_gcm::AesGcm;
+use rand::{rngs::OsRng, RngCore};
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// The length of a new TOTP secret, in bytes.
+pub const SECRET_LEN: usize = 20;
+
+pub fn random_secret() -> [u8; SECRET_LEN] {
+    let mut bytes = [0; SECRET_LEN];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Encrypts the secret with AES-256-GCM.
+/// The output is the random nonce, followed by the ciphertext and its tag.
+pub fn encrypt_secret(key: &TotpKey, user_id: i64, secret: &[u8]) -> Vec<u8> {
+    let mut nonce = [0; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    // The user ID is authenticated too, so a secret can't be moved to another user
+    let aad = user_id.to_be_bytes();
+    let mut cipher = AesGcm::new(KeySize::KeySize256, &key.0, &nonce, &aad);
+    let mut output = vec![0; NONCE_LEN + secret.len() + TAG_LEN];
+
+    {
+        let (head, tag) = output.split_at_mut(NONCE_LEN + secret.len());
+        let (nonce_out, ciphertext) = head.split_at_mut(NONCE_LEN);
+
+        nonce_out.copy_from_slice(&nonce);
+        cipher.encrypt(secret, ciphertext, tag);
+    }
+
+    output
+}
+
+/// Decrypts a secret stored by `encrypt_secret()`.
+/// Returns `None` if it is malformed, or was encrypted with a different key.
+pub fn decrypt_secret(key: &TotpKey, user_id: i64, stored: &[u8]) -> Option<Vec<u8>> {
+    if stored.len() < NONCE_LEN + TAG_LEN {
+        return None;
+    }
+
+    let (nonce, rest) = stored.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+    let aad = user_id.to_be_bytes();
+    let mut cipher = AesGcm::new(KeySize::KeySize256, &key.0, nonce, &aad);
+    let mut secret = vec![0; ciphertext.len()];
+
+    if cipher.decrypt(ciphertext, &mut secret, tag) {
+        Some(secret)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,317 @@
+/*
+ * totp/manager.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::{
+    base32_encode, check_code, decrypt_secret, encrypt_secret, random_secret, time_step,
+    uri_encode, NewTotpLogin, NewUserTotp, TotpEnrollment, TotpLogin, TotpPolicy, UserTotp, DIGITS,
+    PERIOD,
+};
+use crate::manager_prelude::*;
+use crate::schema::{totp_logins, user_totp};
+use crate::token::{check_verifier, split_token, NewToken};
+use chrono::Duration;
+
+/// How long a user has to enter their code after giving their password.
+const PENDING_LOGIN_MINUTES: i64 = 5;
+
+/// How many wrong codes can be entered for one pending login.
+const MAX_CODE_FAILURES: i16 = 3;
+
+pub struct TotpManager {
+    conn: ConnectionPool,
+    policy: TotpPolicy,
+}
+
+impl TotpManager {
+    #[inline]
+    pub fn new(conn: &ConnectionPool, policy: TotpPolicy) -> Self {
+        debug!("Creating totp-manager service");
+
+        let conn = conn.clone();
+        TotpManager { conn, policy }
+    }
+
+    /// Generates a new secret for the user, replacing any unconfirmed one.
+    ///
+    /// Two-factor authentication is not enabled until `verify()` succeeds
+    /// with a code from the new secret. If it is already enabled,
+    /// it must be disabled before enrolling again.
+    pub async fn enroll(&self, user_id: UserId, account_name: &str) -> Result<TotpEnrollment> {
+        use self::user_totp::dsl;
+
+        info!("Enrolling user ID {} in two-factor authentication", user_id);
+
+        self.transaction(async {
+            let id: i64 = user_id.into();
+            let enabled = user_totp::table
+                .find(id)
+                .select(dsl::enabled)
+                .for_update()
+                .first::<bool>(&*self.conn.get()?)
+                .optional()?;
+
+            if enabled == Some(true) {
+                warn!("User ID {} already has two-factor enabled", user_id);
+
+                return Err(Error::InvalidArgument(
+                    "two-factor authentication is already enabled",
+                ));
+            }
+
+            let secret = random_secret();
+            let encrypted = encrypt_secret(&self.policy.key, id, &secret);
+            let model = NewUserTotp {
+                user_id: id,
+                totp_secret: &encrypted,
+            };
+
+            diesel::insert_into(user_totp::table)
+                .values(&model)
+                .on_conflict(dsl::user_id)
+                .do_update()
+                .set((
+                    dsl::totp_secret.eq(&encrypted),
+                    dsl::enabled.eq(false),
+                    dsl::last_used_step.eq(None::<i64>),
+                    dsl::created_at.eq(Utc::now()),
+                ))
+                .execute(&*self.conn.get()?)?;
+
+            let secret = base32_encode(&secret);
+            let issuer = uri_encode(&self.policy.issuer);
+            let uri = format!(
+                "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+                issuer,
+                uri_encode(account_name),
+                secret,
+                issuer,
+                DIGITS,
+                PERIOD,
+            );
+
+            Ok(TotpEnrollment::new(secret, uri))
+        })
+        .await
+    }
+
+    /// Checks a code from the user's authenticator app.
+    /// Returns `AuthenticationFailed` if it is wrong or has already been used.
+    ///
+    /// The first successful check after enrolling enables two-factor authentication.
+    pub async fn verify(&self, user_id: UserId, code: &str) -> Result<()> {
+        use self::user_totp::dsl;
+
+        debug!("Checking two-factor code for user ID {}", user_id);
+
+        self.transaction(async {
+            let id: i64 = user_id.into();
+            let record = user_totp::table
+                .find(id)
+                .select((dsl::totp_secret, dsl::enabled, dsl::last_used_step))
+                .for_update()
+                .first::<UserTotp>(&*self.conn.get()?)
+                .optional()?
+                .ok_or(Error::AuthenticationFailed)?;
+
+            let secret = match decrypt_secret(&self.policy.key, id, &record.totp_secret) {
+                Some(secret) => secret,
+                None => {
+                    error!(
+                        "Unable to decrypt two-factor secret for user ID {}",
+                        user_id
+                    );
+                    return Err(Error::AuthenticationFailed);
+                }
+            };
+
+            // Codes can't be reused, so only steps after the last one used are accepted
+            let current = time_step(Utc::now().timestamp());
+            let skew = i64::from(self.policy.skew);
+            let earliest = match record.last_used_step {
+                Some(last) => (current - skew).max(last + 1),
+                None => current - skew,
+            };
+
+            let step = (earliest..=current + skew)
+                .find(|&step| check_code(&secret, step, code))
+                .ok_or(Error::AuthenticationFailed)?;
+
+            if !record.enabled {
+                info!("Enabling two-factor authentication for user ID {}", user_id);
+            }
+
+            diesel::update(user_totp::table.find(id))
+                .set((dsl::enabled.eq(true), dsl::last_used_step.eq(step)))
+                .execute(&*self.conn.get()?)?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Determines if the user has confirmed two-factor authentication.
+    pub async fn is_enabled(&self, user_id: UserId) -> Result<bool> {
+        use self::user_totp::dsl;
+
+        debug!("Checking if user ID {} has two-factor enabled", user_id);
+
+        let id: i64 = user_id.into();
+        let enabled = user_totp::table
+            .find(id)
+            .select(dsl::enabled)
+            .first::<bool>(&*self.conn.get()?)
+            .optional()?;
+
+        Ok(enabled.unwrap_or(false))
+    }
+
+    /// Removes the user's secret. Succeeds even if they weren't enrolled.
+    pub async fn disable(&self, user_id: UserId) -> Result<()> {
+        info!(
+            "Disabling two-factor authentication for user ID {}",
+            user_id
+        );
+
+        let id: i64 = user_id.into();
+        diesel::delete(user_totp::table.find(id)).execute(&*self.conn.get()?)?;
+        diesel::delete(totp_logins::table)
+            .filter(totp_logins::dsl::user_id.eq(id))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
+
+    /// Records a login whose password was correct, but which still needs a code.
+    /// Returns the token used to finish it with `complete_login()`.
+    pub async fn create_login(
+        &self,
+        user_id: UserId,
+        login_attempt_id: LoginAttemptId,
+    ) -> Result<String> {
+        debug!(
+            "Creating pending two-factor login for user ID {} (login attempt ID {})",
+            user_id, login_attempt_id,
+        );
+
+        let token = NewToken::generate();
+        let model = NewTotpLogin {
+            selector: token.selector(),
+            user_id: user_id.into(),
+            login_attempt_id: login_attempt_id.into(),
+            token_hash: token.hash(),
+            expires_at: Utc::now() + Duration::minutes(PENDING_LOGIN_MINUTES),
+        };
+
+        diesel::insert_into(totp_logins::table)
+            .values(&model)
+            .execute(&*self.conn.get()?)?;
+
+        Ok(token.into_token())
+    }
+
+    /// Finishes a pending login by checking the user's code.
+    ///
+    /// Returns `InvalidSession` if the token is wrong, expired, or has had
+    /// too many wrong codes, and `AuthenticationFailed` if the code is wrong.
+    /// Once the code is accepted the token can't be used again.
+    pub async fn complete_login(
+        &self,
+        token: &str,
+        code: &str,
+    ) -> Result<(UserId, LoginAttemptId)> {
+        use self::totp_logins::dsl;
+
+        debug!("Completing pending two-factor login");
+
+        let (selector, verifier) = split_token(token).ok_or(Error::InvalidSession)?;
+        let record = totp_logins::table
+            .find(selector)
+            .select((dsl::user_id, dsl::login_attempt_id, dsl::token_hash))
+            .first::<TotpLogin>(&*self.conn.get()?)
+            .optional()?
+            .ok_or(Error::InvalidSession)?;
+
+        if !check_verifier(&record.token_hash, verifier) {
+            warn!("Pending two-factor login token mismatch");
+            return Err(Error::InvalidSession);
+        }
+
+        // Use up one of the allowed tries before checking the code, in a single
+        // statement, so concurrent guesses can't get any extra ones.
+        //
+        // This is outside of any transaction so it isn't rolled back
+        let reserved = diesel::update(
+            totp_logins::table
+                .find(selector)
+                .filter(dsl::failures.lt(MAX_CODE_FAILURES))
+                .filter(dsl::expires_at.gt(Utc::now())),
+        )
+        .set(dsl::failures.eq(dsl::failures + 1))
+        .execute(&*self.conn.get()?)?;
+
+        if reserved == 0 {
+            warn!(
+                "Pending two-factor login for user ID {} is no longer valid",
+                record.user_id,
+            );
+            return Err(Error::InvalidSession);
+        }
+
+        self.verify(record.user_id, code).await?;
+
+        // Only one request can use the token, even if several had valid codes
+        let deleted =
+            diesel::delete(totp_logins::table.find(selector)).execute(&*self.conn.get()?)?;
+
+        if deleted == 0 {
+            warn!("Pending two-factor login was already completed");
+            return Err(Error::InvalidSession);
+        }
+
+        Ok((record.user_id, record.login_attempt_id))
+    }
+
+    /// Generates the code for the user's secret, the given number of steps from now.
+    #[cfg(test)]
+    pub async fn generate_code(&self, user_id: UserId, offset: i64) -> Result<String> {
+        let id: i64 = user_id.into();
+        let encrypted = user_totp::table
+            .find(id)
+            .select(user_totp::dsl::totp_secret)
+            .first::<Vec<u8>>(&*self.conn.get()?)?;
+
+        let secret = decrypt_secret(&self.policy.key, id, &encrypted)
+            .expect("Unable to decrypt two-factor secret");
+        let step = time_step(Utc::now().timestamp()) + offset;
+
+        Ok(super::generate_code(&secret, step))
+    }
+}
+
+impl_async_transaction!(TotpManager);
+
+impl Debug for TotpManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TotpManager")
+            .field("conn", &"PgConnection { .. }")
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
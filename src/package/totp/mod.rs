@@ -0,0 +1,36 @@
+/*
+ * totp/mod.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+mod crypto;
+mod manager;
+mod models;
+mod otp;
+mod policy;
+
+#[cfg(test)]
+mod test;
+
+pub use self::manager::*;
+pub use self::models::TotpEnrollment;
+pub use self::policy::{TotpKey, TotpPolicy};
+
+use self::crypto::*;
+use self::models::*;
+use self::otp::*;
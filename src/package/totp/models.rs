@@ -0,0 +1,92 @@
+/*
+ * totp/models.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::prelude::{LoginAttemptId, UserId};
+use crate::schema::{totp_logins, user_totp};
+use chrono::prelude::*;
+use std::fmt::{self, Debug};
+
+/// A new secret for the user to add to their authenticator app.
+///
+/// It is not required at login until a code from it has been confirmed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TotpEnrollment {
+    secret: String,
+    uri: String,
+}
+
+impl TotpEnrollment {
+    #[inline]
+    pub fn new(secret: String, uri: String) -> Self {
+        TotpEnrollment { secret, uri }
+    }
+
+    /// The secret, encoded as base32 for manual entry.
+    #[inline]
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// The `otpauth://` URI, usually shown to the user as a QR code.
+    #[inline]
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+}
+
+impl Debug for TotpEnrollment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TotpEnrollment")
+            .field("secret", &"<secret>")
+            .field("uri", &"<secret>")
+            .finish()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "user_totp"]
+pub struct NewUserTotp<'a> {
+    pub user_id: i64,
+    pub totp_secret: &'a [u8],
+}
+
+#[derive(Debug, Queryable)]
+pub struct UserTotp {
+    pub totp_secret: Vec<u8>,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "totp_logins"]
+pub struct NewTotpLogin<'a> {
+    pub selector: &'a str,
+    pub user_id: i64,
+    pub login_attempt_id: i64,
+    pub token_hash: &'a [u8],
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct TotpLogin {
+    pub user_id: UserId,
+    pub login_attempt_id: LoginAttemptId,
+    pub token_hash: Vec<u8>,
+}
@@ -0,0 +1,109 @@
+/*
+ * totp/otp.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+// Time-based one-time passwords, as described in RFC 6238.
+//
+// These use the parameters authenticator apps assume by default,
+// HMAC-SHA1 with six-digit codes changing every 30 seconds.
+
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha1::Sha1;
+use crypto::util::fixed_time_eq;
+
+pub const DIGITS: u32 = 6;
+pub const PERIOD: i64 = 30;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Gets the time step for the given Unix timestamp.
+#[inline]
+pub fn time_step(timestamp: i64) -> i64 {
+    timestamp.div_euclid(PERIOD)
+}
+
+/// Generates the code for the given secret and time step, as in RFC 4226.
+pub fn generate_code(secret: &[u8], step: i64) -> String {
+    let mut hmac = Hmac::new(Sha1::new(), secret);
+    hmac.input(&step.to_be_bytes());
+
+    let result = hmac.result();
+    let hash = result.code();
+
+    // Dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        value % 10u32.pow(DIGITS),
+        width = DIGITS as usize,
+    )
+}
+
+/// Checks a code from a client against the one for the given step.
+#[inline]
+pub fn check_code(secret: &[u8], step: i64, code: &str) -> bool {
+    fixed_time_eq(generate_code(secret, step).as_bytes(), code.as_bytes())
+}
+
+/// Encodes bytes as unpadded base32, which is how authenticator apps expect secrets.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            output.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        output.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    output
+}
+
+/// Percent-encodes a string for use in an `otpauth://` URI.
+pub fn uri_encode(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                output.push(byte as char)
+            }
+            _ => output.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    output
+}
@@ -0,0 +1,58 @@
+/*
+ * totp/policy.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::fmt::{self, Debug};
+
+/// The key used to encrypt TOTP secrets in the database.
+///
+/// Its `Debug` implementation hides the value, so it isn't logged.
+#[derive(Copy, Clone)]
+pub struct TotpKey(pub [u8; 32]);
+
+impl Debug for TotpKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("TotpKey(<secret>)")
+    }
+}
+
+/// Controls two-factor authentication with time-based one-time passwords.
+#[derive(Debug, Clone)]
+pub struct TotpPolicy {
+    /// The key used to encrypt users' secrets. Changing this invalidates all enrollments.
+    pub key: TotpKey,
+
+    /// The name shown for this service in authenticator apps.
+    pub issuer: String,
+
+    /// How many time steps before or after the current one are accepted,
+    /// to allow for clocks being slightly off.
+    pub skew: u8,
+}
+
+impl TotpPolicy {
+    #[inline]
+    pub fn new(key: TotpKey) -> Self {
+        TotpPolicy {
+            key,
+            issuer: String::from("deepwell"),
+            skew: 1,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * totp/test.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::{
+    base32_encode, check_code, decrypt_secret, encrypt_secret, generate_code, time_step,
+    uri_encode, TotpKey,
+};
+
+// Test vectors from RFC 6238, appendix B
+const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+#[test]
+fn codes() {
+    // The RFC lists eight-digit codes, these are their last six digits
+    macro_rules! check {
+        ($timestamp:expr, $expected:expr) => {{
+            let step = time_step($timestamp);
+            assert_eq!(generate_code(RFC_SECRET, step), $expected, "Code mismatch");
+            assert!(check_code(RFC_SECRET, step, $expected), "Code not accepted");
+        }};
+    }
+
+    check!(59, "287082");
+    check!(1_111_111_109, "081804");
+    check!(1_111_111_111, "050471");
+    check!(1_234_567_890, "005924");
+    check!(2_000_000_000, "279037");
+
+    assert!(!check_code(RFC_SECRET, time_step(59), "287083"));
+    assert!(!check_code(RFC_SECRET, time_step(59), "28708"));
+    assert!(!check_code(RFC_SECRET, time_step(89), "287082"));
+}
+
+#[test]
+fn steps() {
+    assert_eq!(time_step(0), 0);
+    assert_eq!(time_step(29), 0);
+    assert_eq!(time_step(30), 1);
+    assert_eq!(time_step(-1), -1);
+}
+
+#[test]
+fn base32() {
+    assert_eq!(base32_encode(b""), "");
+    assert_eq!(base32_encode(b"f"), "MY");
+    assert_eq!(base32_encode(b"foo"), "MZXW6");
+    assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    assert_eq!(
+        base32_encode(RFC_SECRET),
+        "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+    );
+}
+
+#[test]
+fn uri() {
+    assert_eq!(uri_encode("deepwell"), "deepwell");
+    assert_eq!(uri_encode("Jane Doe"), "Jane%20Doe");
+    assert_eq!(uri_encode("a:b&c"), "a%3Ab%26c");
+}
+
+#[test]
+fn encryption() {
+    let key = TotpKey([7; 32]);
+    let stored = encrypt_secret(&key, 10, RFC_SECRET);
+    assert_ne!(&stored[12..32], RFC_SECRET, "Secret not encrypted");
+
+    let secret = decrypt_secret(&key, 10, &stored).expect("Unable to decrypt");
+    assert_eq!(secret, RFC_SECRET);
+
+    // Fresh nonce each time
+    assert_ne!(stored, encrypt_secret(&key, 10, RFC_SECRET));
+
+    // Wrong key, wrong user, or corrupted
+    assert_eq!(decrypt_secret(&TotpKey([8; 32]), 10, &stored), None);
+    assert_eq!(decrypt_secret(&key, 11, &stored), None);
+    assert_eq!(decrypt_secret(&key, 10, &stored[..20]), None);
+
+    let mut corrupted = stored.clone();
+    corrupted[15] ^= 1;
+    assert_eq!(decrypt_secret(&key, 10, &corrupted), None);
+}
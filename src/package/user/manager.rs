@@ -18,32 +18,152 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use super::models::{NewUser, NewUserVerification, UpdateUser};
+use super::models::{
+    EmailChange, NewEmailChange, NewUser, NewUserAuditEntry, NewUserVerification, UpdateUser,
+};
 use crate::manager_prelude::*;
-use crate::schema::{user_verification, users};
-use crate::utils::{lower, rand_alphanum, rows_to_result};
+use crate::schema::{
+    authors, email_changes, page_locks, parents, ratings, ratings_history, revisions,
+    role_membership, user_audit_log, user_verification, users, wiki_membership,
+};
+use crate::token::{check_verifier, split_token, NewToken};
+use crate::utils::{escape_like, rand_alphanum, rows_to_result};
+use chrono::Duration;
+use chrono_tz::Tz;
 use cow_utils::CowUtils;
 use diesel::pg::expression::dsl::any;
 use ref_map::*;
+use serde_json::Value as JsonValue;
+use wikidot_normalize::normalize;
+
+/// The most users which can be returned from a search.
+const MAX_SEARCH_RESULTS: u32 = 100;
+
+/// The longest allowed user name, in characters.
+///
+/// The `name` column is unbounded `TEXT`, so this is the only limit.
+const MAX_NAME_LENGTH: usize = 64;
+
+/// The longest allowed email, in characters. This is the limit from RFC 5321.
+const MAX_EMAIL_LENGTH: usize = 254;
+
+/// Converts a username into the normalized form used for lookups and uniqueness.
+fn name_to_slug(name: &str) -> String {
+    let mut slug = String::from(name);
+    normalize(&mut slug);
+    slug
+}
+
+/// Converts an email into the form which is stored and compared against.
+///
+/// This uses full Unicode lowercasing, since `LOWER()` in Postgres
+/// (which the column constraint uses) also folds non-ASCII characters.
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Checks that the timezone is an IANA name, such as `America/New_York`.
+/// An empty string clears it.
+fn check_timezone(timezone: &str) -> Result<()> {
+    if timezone.is_empty() || timezone.parse::<Tz>().is_ok() {
+        Ok(())
+    } else {
+        warn!("Invalid timezone '{}'", timezone);
+        Err(Error::InvalidTimezone(String::from(timezone)))
+    }
+}
+
+/// Checks that the locale is shaped like a BCP 47 language tag, such as `en-US`.
+/// An empty string clears it.
+fn check_locale(locale: &str) -> Result<()> {
+    if locale.is_empty() {
+        return Ok(());
+    }
+
+    let mut subtags = locale.split('-');
+    let valid_language = subtags
+        .next()
+        .map(|tag| (2..=3).contains(&tag.len()) && tag.bytes().all(|b| b.is_ascii_alphabetic()))
+        .unwrap_or(false);
+
+    let valid_rest = subtags
+        .all(|tag| (1..=8).contains(&tag.len()) && tag.bytes().all(|b| b.is_ascii_alphanumeric()));
+
+    if valid_language && valid_rest {
+        Ok(())
+    } else {
+        warn!("Invalid locale '{}'", locale);
+        Err(Error::InvalidLocale(String::from(locale)))
+    }
+}
+
+/// Checks that the name is not blank or too long.
+fn check_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        warn!("Invalid user name '{}'", name);
+        Err(Error::InvalidUserName(String::from(name)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that the email is shaped like `local@domain.tld`.
+///
+/// This isn't a full validation, that's only possible by sending mail to it.
+fn check_email(email: &str) -> Result<()> {
+    let email = email.trim();
+    let valid = match email.find('@') {
+        _ if email.chars().count() > MAX_EMAIL_LENGTH => false,
+        _ if email.chars().any(|c| c.is_whitespace() || c.is_control()) => false,
+        Some(idx) => {
+            let (local, domain) = (&email[..idx], &email[idx + 1..]);
+
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && domain.split('.').all(|label| !label.is_empty())
+        }
+        None => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        warn!("Invalid email '{}'", email);
+        Err(Error::InvalidEmail(String::from(email)))
+    }
+}
+
+/// Checks the name and email of a new user, before any database work is done.
+///
+/// Conflicts with existing users are checked separately when inserting.
+pub fn check_new_user(name: &str, email: &str) -> Result<()> {
+    check_name(name)?;
+    check_email(email)?;
+
+    Ok(())
+}
 
 pub struct UserManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
 }
 
 impl UserManager {
     #[inline]
-    pub fn new(conn: &Arc<PgConnection>) -> Self {
+    pub fn new(conn: &ConnectionPool) -> Self {
         debug!("Creating user-manager service");
 
-        let conn = Arc::clone(conn);
+        let conn = conn.clone();
         UserManager { conn }
     }
 
-    async fn check_conflicts(&self, name: Option<&str>, email: Option<&str>) -> Result<()> {
+    async fn check_conflicts(&self, slug: Option<&str>, email: Option<&str>) -> Result<()> {
         use self::users::dsl;
 
         // Disallow empty names and emails
-        if name == Some("") {
+        //
+        // Names which are entirely punctuation normalize to nothing.
+        if slug == Some("") {
             warn!("Disallowing empty username");
             return Err(Error::UserNameExists);
         }
@@ -54,19 +174,19 @@ impl UserManager {
         }
 
         // Compare against empty string to avoid conflicts with yourself
-        let name = name.unwrap_or("");
+        let slug = slug.unwrap_or("");
         let email = email.unwrap_or("");
 
         // Query table for conflicts
         let result = users::table
-            .filter(lower(users::name).eq(lower(name)))
-            .or_filter(users::email.eq(lower(email)))
-            .select((dsl::user_id, dsl::name, dsl::email))
-            .get_result::<(UserId, String, String)>(&*self.conn)
+            .filter(users::slug.eq(slug))
+            .or_filter(users::email.eq(email))
+            .select((dsl::user_id, dsl::slug, dsl::email))
+            .get_result::<(UserId, String, String)>(&*self.conn.get()?)
             .optional()?;
 
-        if let Some((user_id, conflict_name, conflict_email)) = result {
-            if name == conflict_name {
+        if let Some((user_id, conflict_slug, conflict_email)) = result {
+            if slug == conflict_slug {
                 warn!("Cannot create user, name conflicts with ID {}", user_id);
                 return Err(Error::UserNameExists);
             }
@@ -76,8 +196,9 @@ impl UserManager {
                 return Err(Error::UserEmailExists);
             }
 
-            // If there's a result then one of the email or name conflicts
-            unreachable!()
+            // If there's a result then one of the email or name should conflict
+            error!("Found conflicting user ID {}, but no fields match", user_id);
+            return Err(Error::UserNameExists);
         }
 
         // No conflicts
@@ -90,21 +211,21 @@ impl UserManager {
             name, email,
         );
 
-        self.check_conflicts(Some(name), Some(&email)).await?;
-
-        // Lowercase fields
-        let email = email.cow_to_ascii_lowercase();
+        let slug = name_to_slug(name);
+        let email = normalize_email(email);
+        self.check_conflicts(Some(&slug), Some(&email)).await?;
 
         // If not, insert into database
         let model = NewUser {
             name,
+            slug: &slug,
             email: &email,
         };
 
         let id = diesel::insert_into(users::table)
             .values(&model)
             .returning(users::dsl::user_id)
-            .get_result::<UserId>(&*self.conn)?;
+            .get_result::<UserId>(&*self.conn.get()?)?;
 
         Ok(id)
     }
@@ -115,7 +236,7 @@ impl UserManager {
         let id: i64 = id.into();
         let result = users::table
             .filter(users::user_id.eq(id))
-            .first::<User>(&*self.conn)
+            .first::<User>(&*self.conn.get()?)
             .optional()?;
 
         Ok(result)
@@ -130,7 +251,7 @@ impl UserManager {
             users::table
                 .filter(users::user_id.eq(any(ids)))
                 .order_by(users::user_id.asc())
-                .load::<User>(&*self.conn)?
+                .load::<User>(&*self.conn.get()?)?
         };
 
         // Add in nones where needed
@@ -155,119 +276,319 @@ impl UserManager {
         Ok(users)
     }
 
-    pub async fn get_id_from_email_or_name(&self, name_or_email: &str) -> Result<Option<UserId>> {
-        info!("Getting user ID for username or email '{}'", name_or_email);
+    /// Finds the user with the given name or email, along with which of the two it was.
+    ///
+    /// Names take precedence, so if one user's name normalizes to the same thing
+    /// as another user's email, the first user is returned.
+    ///
+    /// If no user is found, it is assumed to be an email if it has an `@` in it.
+    pub async fn resolve(
+        &self,
+        name_or_email: &str,
+        include_inactive: bool,
+    ) -> Result<(Option<User>, CredentialKind)> {
+        info!(
+            "Resolving user for username or email '{}' (include inactive: {})",
+            name_or_email, include_inactive,
+        );
 
-        let result = users::table
-            .filter(lower(users::name).eq(lower(name_or_email)))
-            .or_filter(users::email.eq(lower(name_or_email)))
-            .select(users::dsl::user_id)
-            .first::<UserId>(&*self.conn)
+        let slug = name_to_slug(name_or_email);
+        let email = normalize_email(name_or_email);
+        let mut query = users::table
+            .filter(users::slug.eq(&slug).or(users::email.eq(&email)))
+            .into_boxed();
+
+        if !include_inactive {
+            query = query.filter(users::deleted_at.is_null());
+        }
+
+        // Both are unique, so at most two users can match
+        let user = query
+            .order_by(users::slug.eq(&slug).desc())
+            .first::<User>(&*self.conn.get()?)
             .optional()?;
 
-        Ok(result)
+        let is_email = match user {
+            Some(ref user) => user.slug() != slug,
+            None => name_or_email.contains('@'),
+        };
+
+        let kind = if is_email {
+            CredentialKind::Email
+        } else {
+            CredentialKind::Username
+        };
+
+        Ok((user, kind))
     }
 
-    pub async fn get_from_email(&self, email: &str) -> Result<Option<User>> {
-        info!("Getting user for email '{}'", email);
+    /// Like `resolve()`, but only gets the user's ID.
+    pub async fn get_id_from_email_or_name(
+        &self,
+        name_or_email: &str,
+        include_inactive: bool,
+    ) -> Result<(Option<UserId>, CredentialKind)> {
+        let (user, kind) = self.resolve(name_or_email, include_inactive).await?;
+        let user_id = user.map(|user| user.id());
 
-        let result = users::table
-            .filter(users::email.eq(lower(email)))
-            .first::<User>(&*self.conn)
-            .optional()?;
+        Ok((user_id, kind))
+    }
+
+    pub async fn get_from_email(
+        &self,
+        email: &str,
+        include_inactive: bool,
+    ) -> Result<Option<User>> {
+        info!(
+            "Getting user for email '{}' (include inactive: {})",
+            email, include_inactive,
+        );
+
+        let email = normalize_email(email);
+        let mut query = users::table.filter(users::email.eq(&email)).into_boxed();
+
+        if !include_inactive {
+            query = query.filter(users::deleted_at.is_null());
+        }
+
+        let result = query.first::<User>(&*self.conn.get()?).optional()?;
 
         Ok(result)
     }
 
-    pub async fn get_from_name(&self, name: &str) -> Result<Option<User>> {
-        info!("Getting user for name '{}'", name);
+    pub async fn get_from_name(&self, name: &str, include_inactive: bool) -> Result<Option<User>> {
+        info!(
+            "Getting user for name '{}' (include inactive: {})",
+            name, include_inactive,
+        );
 
-        let result = users::table
-            .filter(lower(users::name).eq(lower(name)))
-            .first::<User>(&*self.conn)
-            .optional()?;
+        let slug = name_to_slug(name);
+        let mut query = users::table.filter(users::slug.eq(&slug)).into_boxed();
+
+        if !include_inactive {
+            query = query.filter(users::deleted_at.is_null());
+        }
+
+        let result = query.first::<User>(&*self.conn.get()?).optional()?;
 
         Ok(result)
     }
 
-    pub async fn edit(&self, id: UserId, changes: UserMetadata<'_>) -> Result<()> {
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: u32,
+        include_inactive: bool,
+    ) -> Result<Vec<User>> {
+        info!(
+            "Searching for users matching '{}' (limit {}, include inactive: {})",
+            query, limit, include_inactive,
+        );
+
+        let slug = name_to_slug(query);
+        if slug.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pattern = format!("%{}%", escape_like(&slug));
+        let mut query = users::table
+            .filter(users::slug.ilike(&pattern))
+            .into_boxed();
+
+        if !include_inactive {
+            query = query.filter(users::deleted_at.is_null());
+        }
+
+        let users = query
+            .order_by(users::name.asc())
+            .limit(limit.min(MAX_SEARCH_RESULTS).into())
+            .load::<User>(&*self.conn.get()?)?;
+
+        Ok(users)
+    }
+
+    pub async fn count(&self, include_inactive: bool) -> Result<i64> {
+        info!("Counting users (include inactive: {})", include_inactive);
+
+        let mut query = users::table.into_boxed();
+        if !include_inactive {
+            query = query.filter(users::deleted_at.is_null());
+        }
+
+        let count = query.count().get_result::<i64>(&*self.conn.get()?)?;
+
+        Ok(count)
+    }
+
+    /// Applies the given changes to a user, recording each changed field
+    /// in the user's audit log within the same transaction.
+    ///
+    /// Emails cannot be changed here, since the new address must be confirmed first.
+    /// See `create_email_change()`.
+    pub async fn edit(
+        &self,
+        id: UserId,
+        changes: UserMetadata<'_>,
+        changed_by: UserId,
+    ) -> Result<()> {
         use self::users::dsl;
+        use diesel::dsl::now;
 
         // Extract fields from metadata struct
         let UserMetadata {
             mut name,
-            mut email,
-            user_page,
-            website,
-            about,
+            email,
+            mut user_page,
+            mut website,
+            mut about,
             gender,
-            location,
+            mut location,
+            mut timezone,
+            mut locale,
         } = changes;
 
-        // Always run this to ensure the user exists
-        let user = self //
-            .get_from_id(id)
-            .await?
-            .ok_or(Error::UserNotFound)?;
+        // Rejected up front, so bad values never reach the database
+        if let Some(name) = name {
+            check_name(name)?;
+        }
+
+        if email.is_some() {
+            warn!("Cannot change email for user ID {} by editing", id);
 
-        // Set username / email to None if they are the same
-        if name == Some(user.name()) {
-            name = None;
+            return Err(Error::InvalidArgument(
+                "email can only be changed through an email change request",
+            ));
         }
 
-        if email == Some(user.email()) {
-            email = None;
+        if let Some(timezone) = timezone {
+            check_timezone(timezone)?;
         }
 
-        // Check if the username or email exists on another user
-        //
-        // This is why we erased unchanged usernames and emails,
-        // since otherwise this would trigger a false positive
-        // on the user itself.
-        self.check_conflicts(name, email).await?;
+        if let Some(locale) = locale {
+            check_locale(locale)?;
+        }
+
+        self.transaction(async {
+            // Always run this to ensure the user exists
+            let user = self //
+                .get_from_id(id)
+                .await?
+                .ok_or(Error::UserNotFound)?;
+
+            // Lowercase fields
+            let gender = gender.map(|s| s.cow_to_ascii_lowercase());
+            let mut gender = gender.ref_map(|s| s.as_ref());
+
+            // Set fields to None if they are the same,
+            // and note the ones which are actually changing.
+            let mut audit_entries = Vec::new();
+
+            macro_rules! diff {
+                ($field:ident) => {
+                    if $field == Some(user.$field()) {
+                        $field = None;
+                    }
+
+                    if let Some(value) = $field {
+                        audit_entries.push(NewUserAuditEntry {
+                            user_id: id.into(),
+                            field: stringify!($field),
+                            old_value: JsonValue::from(user.$field()),
+                            new_value: JsonValue::from(value),
+                            changed_by: changed_by.into(),
+                        });
+                    }
+                };
+            }
 
-        // Lowercase fields
-        let email = email.map(|s| s.cow_to_ascii_lowercase());
-        let email = email.ref_map(|s| s.as_ref());
+            diff!(name);
+            diff!(user_page);
+            diff!(website);
+            diff!(about);
+            diff!(gender);
+            diff!(location);
+            diff!(timezone);
+            diff!(locale);
+
+            // Only check the slug if it's actually changing,
+            // a user can change the display form of their name freely.
+            let slug = name.map(name_to_slug);
+            let conflict_slug = slug.as_deref().filter(|&slug| slug != user.slug());
+
+            // Check if the username exists on another user
+            //
+            // This is why we erased unchanged usernames,
+            // since otherwise this would trigger a false positive
+            // on the user itself.
+            self.check_conflicts(conflict_slug, None).await?;
+
+            // Prepare update model
+            let model = UpdateUser {
+                name,
+                slug: slug.as_deref(),
+                email: None,
+                is_verified: None,
+                user_page,
+                website,
+                about,
+                gender,
+                location,
+                timezone,
+                locale,
+                deleted_at: None,
+            };
 
-        let gender = gender.map(|s| s.cow_to_ascii_lowercase());
-        let gender = gender.ref_map(|s| s.as_ref());
+            info!("Editing user ID {}, data: {:?}", id, &model);
 
-        // Prepare update model
-        let is_verified = if email.is_some() { Some(false) } else { None };
-        let model = UpdateUser {
-            name,
-            email,
-            is_verified,
-            user_page,
-            website,
-            about,
-            gender,
-            location,
-            deleted_at: None,
-        };
+            if model.has_changes() {
+                let id: i64 = id.into();
+                diesel::update(dsl::users.filter(dsl::user_id.eq(id)))
+                    .set((&model, dsl::updated_at.eq(now)))
+                    .execute(&*self.conn.get()?)?;
 
-        info!("Editing user ID {}, data: {:?}", id, &model);
+                diesel::insert_into(user_audit_log::table)
+                    .values(&audit_entries)
+                    .execute(&*self.conn.get()?)?;
+            }
 
-        if model.has_changes() {
-            let id: i64 = id.into();
-            diesel::update(dsl::users.filter(dsl::user_id.eq(id)))
-                .set(&model)
-                .execute(&*self.conn)?;
-        }
+            Ok(())
+        })
+        .await
+    }
 
-        Ok(())
+    /// Gets all recorded changes to the given user's metadata, oldest first.
+    pub async fn get_audit_log(&self, id: UserId) -> Result<Vec<AuditEntry>> {
+        use self::user_audit_log::dsl;
+
+        debug!("Getting audit log for user ID {}", id);
+
+        let id: i64 = id.into();
+        let entries = user_audit_log::table
+            .filter(dsl::user_id.eq(id))
+            .select((
+                dsl::user_id,
+                dsl::field,
+                dsl::old_value,
+                dsl::new_value,
+                dsl::changed_by,
+                dsl::changed_at,
+            ))
+            .order_by(dsl::user_audit_log_entry_id.asc())
+            .get_results::<AuditEntry>(&*self.conn.get()?)?;
+
+        Ok(entries)
     }
 
     pub async fn verify(&self, id: UserId) -> Result<()> {
         use self::users::dsl;
+        use diesel::dsl::now;
 
         info!("Marking user ID {} as verified", id);
 
         let id: i64 = id.into();
         diesel::update(dsl::users.filter(dsl::user_id.eq(id)))
-            .set(dsl::is_verified.eq(true))
-            .execute(&*self.conn)?;
+            .set((dsl::is_verified.eq(true), dsl::updated_at.eq(now)))
+            .execute(&*self.conn.get()?)?;
 
         Ok(())
     }
@@ -279,7 +600,7 @@ impl UserManager {
             let user_id = user_verification::table
                 .filter(user_verification::token.eq(token))
                 .select(user_verification::dsl::user_id)
-                .first::<UserId>(&*self.conn)
+                .first::<UserId>(&*self.conn.get()?)
                 .optional()?;
 
             match user_id {
@@ -289,9 +610,9 @@ impl UserManager {
 
                     let rows = diesel::delete(user_verification::table)
                         .filter(user_verification::token.eq(token))
-                        .execute(&*self.conn)?;
+                        .execute(&*self.conn.get()?)?;
 
-                    if rows_to_result(rows) {
+                    if rows_to_result(rows)? {
                         Ok(())
                     } else {
                         Err(Error::InvalidVerificationToken)
@@ -313,11 +634,245 @@ impl UserManager {
 
         diesel::insert_into(user_verification::table)
             .values(&model)
-            .execute(&*self.conn)?;
+            .execute(&*self.conn.get()?)?;
 
         Ok(token)
     }
 
+    /// Replaces the user's verification token with a new one, unless
+    /// the current one was created less than `interval` ago.
+    ///
+    /// Returns `None` if no new token was issued.
+    pub async fn refresh_token(&self, id: UserId, interval: Duration) -> Result<Option<String>> {
+        info!("Refreshing verification token for user ID {}", id);
+
+        self.transaction(async {
+            let user_id: i64 = id.into();
+            let created_at = user_verification::table
+                .filter(user_verification::user_id.eq(user_id))
+                .select(user_verification::dsl::created_at)
+                .first::<DateTime<Utc>>(&*self.conn.get()?)
+                .optional()?;
+
+            if let Some(created_at) = created_at {
+                if Utc::now() - created_at < interval {
+                    debug!("Verification token was issued recently, not refreshing");
+                    return Ok(None);
+                }
+
+                diesel::delete(user_verification::table)
+                    .filter(user_verification::user_id.eq(user_id))
+                    .execute(&*self.conn.get()?)?;
+            }
+
+            self.create_token(id).await.map(Some)
+        })
+        .await
+    }
+
+    /// Stores a pending change of email for the user, which only takes effect
+    /// once the returned token is confirmed. Any earlier pending change is discarded.
+    pub async fn create_email_change(
+        &self,
+        id: UserId,
+        new_email: &str,
+        ttl: Duration,
+    ) -> Result<String> {
+        use self::email_changes::dsl;
+
+        info!(
+            "Requesting email change for user ID {} to '{}'",
+            id, new_email
+        );
+
+        check_email(new_email)?;
+        let email = normalize_email(new_email);
+
+        self.transaction(async {
+            self.get_from_id(id).await?.ok_or(Error::UserNotFound)?;
+            self.check_conflicts(None, Some(&email)).await?;
+
+            let user_id: i64 = id.into();
+            diesel::delete(email_changes::table)
+                .filter(dsl::user_id.eq(user_id))
+                .filter(dsl::redeemed_at.is_null())
+                .execute(&*self.conn.get()?)?;
+
+            let token = NewToken::generate();
+            let model = NewEmailChange {
+                selector: token.selector(),
+                user_id,
+                email: &email,
+                token_hash: token.hash(),
+                expires_at: Utc::now() + ttl,
+            };
+
+            diesel::insert_into(email_changes::table)
+                .values(&model)
+                .execute(&*self.conn.get()?)?;
+
+            Ok(token.into_token())
+        })
+        .await
+    }
+
+    /// Replaces the user's email with the pending one for the given token.
+    /// Since the token was sent to the new address, it is also marked as verified.
+    pub async fn confirm_email_change(&self, token: &str) -> Result<()> {
+        use self::email_changes::dsl;
+
+        debug!("Confirming email change token");
+
+        let (selector, verifier) = split_token(token).ok_or(Error::InvalidVerificationToken)?;
+
+        self.transaction(async {
+            let record = email_changes::table
+                .find(selector)
+                .select((
+                    dsl::user_id,
+                    dsl::email,
+                    dsl::token_hash,
+                    dsl::expires_at,
+                    dsl::redeemed_at,
+                ))
+                .for_update()
+                .first::<EmailChange>(&*self.conn.get()?)
+                .optional()?
+                .ok_or(Error::InvalidVerificationToken)?;
+
+            if !check_verifier(&record.token_hash, verifier) {
+                warn!("Email change token mismatch");
+                return Err(Error::InvalidVerificationToken);
+            }
+
+            if record.redeemed_at.is_some() {
+                warn!(
+                    "Email change token for user ID {} was already used",
+                    record.user_id,
+                );
+                return Err(Error::InvalidVerificationToken);
+            }
+
+            let now = Utc::now();
+            if record.expires_at <= now {
+                warn!(
+                    "Email change token for user ID {} has expired",
+                    record.user_id,
+                );
+                return Err(Error::InvalidVerificationToken);
+            }
+
+            // Another user may have taken the address since the request
+            self.check_conflicts(None, Some(&record.email)).await?;
+
+            info!(
+                "Changing email for user ID {} to '{}'",
+                record.user_id, record.email,
+            );
+
+            let model = UpdateUser {
+                email: Some(&record.email),
+                is_verified: Some(true),
+                ..Default::default()
+            };
+
+            let user_id: i64 = record.user_id.into();
+            diesel::update(users::table.find(user_id))
+                .set((&model, users::updated_at.eq(now)))
+                .execute(&*self.conn.get()?)?;
+
+            diesel::update(email_changes::table.find(selector))
+                .set(dsl::redeemed_at.eq(now))
+                .execute(&*self.conn.get()?)?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Permanently removes the user, along with their verification, pending email changes,
+    /// profile audit log, memberships, and page locks.
+    ///
+    /// Their contributions to the wiki can't be removed this way, so if there are any this
+    /// fails with `UserReferenced`, listing the tables which still refer to them.
+    /// Data kept by other services, such as passwords and sessions, must be removed first.
+    pub async fn delete(&self, id: UserId) -> Result<()> {
+        use diesel::dsl::{exists, select};
+
+        info!("Deleting user ID {}", id);
+
+        let user_id: i64 = id.into();
+
+        self.transaction(async {
+            let conn = &*self.conn.get()?;
+
+            macro_rules! referenced {
+                ($query:expr) => {
+                    select(exists($query)).get_result::<bool>(conn)?
+                };
+            }
+
+            let mut blocking = Vec::new();
+            let checks = [
+                (
+                    "revisions",
+                    referenced!(revisions::table.filter(revisions::user_id.eq(user_id))),
+                ),
+                (
+                    "authors",
+                    referenced!(authors::table.filter(authors::user_id.eq(user_id))),
+                ),
+                (
+                    "ratings",
+                    referenced!(ratings::table.filter(ratings::user_id.eq(user_id))),
+                ),
+                (
+                    "ratings_history",
+                    referenced!(ratings_history::table.filter(ratings_history::user_id.eq(user_id))),
+                ),
+                (
+                    "parents",
+                    referenced!(parents::table.filter(parents::parented_by.eq(user_id))),
+                ),
+                (
+                    "user_audit_log",
+                    referenced!(user_audit_log::table
+                        .filter(user_audit_log::changed_by.eq(user_id))
+                        .filter(user_audit_log::user_id.ne(user_id))),
+                ),
+            ];
+
+            for &(table, is_referenced) in &checks {
+                if is_referenced {
+                    blocking.push(table);
+                }
+            }
+
+            if !blocking.is_empty() {
+                let tables = blocking.join(", ");
+
+                warn!("Cannot delete user ID {}, still referenced by {}", id, tables);
+                return Err(Error::UserReferenced(tables));
+            }
+
+            diesel::delete(user_verification::table.find(user_id)).execute(conn)?;
+            diesel::delete(email_changes::table.filter(email_changes::user_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(user_audit_log::table.filter(user_audit_log::user_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(role_membership::table.filter(role_membership::user_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(wiki_membership::table.filter(wiki_membership::user_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(page_locks::table.filter(page_locks::user_id.eq(user_id)))
+                .execute(conn)?;
+            diesel::delete(users::table.find(user_id)).execute(conn)?;
+
+            Ok(())
+        })
+        .await
+    }
+
     pub async fn mark_inactive(&self, id: UserId, value: bool) -> Result<()> {
         use self::users::dsl;
         use diesel::dsl::now;
@@ -334,11 +889,12 @@ impl UserManager {
         // Set to NOW() or NULL
         if value {
             diesel::update(condition)
-                .set(dsl::deleted_at.eq(now))
-                .execute(&*self.conn)?;
+                .set((dsl::deleted_at.eq(now), dsl::updated_at.eq(now)))
+                .execute(&*self.conn.get()?)?;
         } else {
             let model = UpdateUser {
                 name: None,
+                slug: None,
                 email: None,
                 is_verified: None,
                 user_page: None,
@@ -346,14 +902,30 @@ impl UserManager {
                 about: None,
                 gender: None,
                 location: None,
+                timezone: None,
+                locale: None,
                 deleted_at: Some(None),
             };
 
-            diesel::update(condition).set(&model).execute(&*self.conn)?;
+            diesel::update(condition)
+                .set((&model, dsl::updated_at.eq(now)))
+                .execute(&*self.conn.get()?)?;
         }
 
         Ok(())
     }
+
+    #[cfg(test)]
+    pub async fn unverify(&self, id: UserId) -> Result<()> {
+        use self::users::dsl;
+
+        let id: i64 = id.into();
+        diesel::update(dsl::users.filter(dsl::user_id.eq(id)))
+            .set(dsl::is_verified.eq(false))
+            .execute(&*self.conn.get()?)?;
+
+        Ok(())
+    }
 }
 
 impl_async_transaction!(UserManager);
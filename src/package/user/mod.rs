@@ -20,6 +20,8 @@
 
 mod manager;
 mod models;
+mod policy;
 
 pub use self::manager::*;
 pub use self::models::*;
+pub use self::policy::VerificationPolicy;
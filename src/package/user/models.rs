@@ -18,13 +18,16 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use crate::schema::{user_verification, users};
+use crate::schema::{email_changes, user_audit_log, user_verification, users};
 use chrono::prelude::*;
+use deepwell_core::types::UserId;
+use serde_json::Value as JsonValue;
 
 #[derive(Debug, Insertable)]
 #[table_name = "users"]
 pub struct NewUser<'a> {
     pub name: &'a str,
+    pub slug: &'a str,
     pub email: &'a str,
 }
 
@@ -34,6 +37,7 @@ type Nullable<T> = Option<T>;
 #[table_name = "users"]
 pub struct UpdateUser<'a> {
     pub name: Option<&'a str>,
+    pub slug: Option<&'a str>,
     pub email: Option<&'a str>,
     pub is_verified: Option<bool>,
     pub user_page: Option<&'a str>,
@@ -41,12 +45,15 @@ pub struct UpdateUser<'a> {
     pub about: Option<&'a str>,
     pub gender: Option<&'a str>,
     pub location: Option<&'a str>,
+    pub timezone: Option<&'a str>,
+    pub locale: Option<&'a str>,
     pub deleted_at: Option<Nullable<DateTime<Utc>>>,
 }
 
 impl UpdateUser<'_> {
     pub fn has_changes(&self) -> bool {
         self.name.is_some()
+            || self.slug.is_some()
             || self.email.is_some()
             || self.is_verified.is_some()
             || self.user_page.is_some()
@@ -54,13 +61,44 @@ impl UpdateUser<'_> {
             || self.about.is_some()
             || self.gender.is_some()
             || self.location.is_some()
+            || self.timezone.is_some()
+            || self.locale.is_some()
             || self.deleted_at.is_some()
     }
 }
 
+#[derive(Debug, Insertable)]
+#[table_name = "user_audit_log"]
+pub struct NewUserAuditEntry {
+    pub user_id: i64,
+    pub field: &'static str,
+    pub old_value: JsonValue,
+    pub new_value: JsonValue,
+    pub changed_by: i64,
+}
+
 #[derive(Debug, Insertable)]
 #[table_name = "user_verification"]
 pub struct NewUserVerification<'a> {
     pub user_id: i64,
     pub token: &'a str,
 }
+
+#[derive(Debug, Insertable)]
+#[table_name = "email_changes"]
+pub struct NewEmailChange<'a> {
+    pub selector: &'a str,
+    pub user_id: i64,
+    pub email: &'a str,
+    pub token_hash: &'a [u8],
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Queryable)]
+pub struct EmailChange {
+    pub user_id: UserId,
+    pub email: String,
+    pub token_hash: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+    pub redeemed_at: Option<DateTime<Utc>>,
+}
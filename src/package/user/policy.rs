@@ -0,0 +1,50 @@
+/*
+ * user/policy.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::Duration;
+
+/// Controls how email verification is enforced.
+#[derive(Debug, Copy, Clone)]
+pub struct VerificationPolicy {
+    /// Whether a user must verify their email before they can log in.
+    pub require_before_login: bool,
+
+    /// Whether to issue a new verification token when a login is refused
+    /// because the user is not verified.
+    pub resend_token: bool,
+
+    /// The minimum time between automatically issued verification tokens.
+    pub resend_interval: Duration,
+
+    /// How long a token confirming a change of email remains valid.
+    pub email_change_ttl: Duration,
+}
+
+impl Default for VerificationPolicy {
+    #[inline]
+    fn default() -> Self {
+        VerificationPolicy {
+            require_before_login: false,
+            resend_token: true,
+            resend_interval: Duration::minutes(10),
+            email_change_ttl: Duration::days(1),
+        }
+    }
+}
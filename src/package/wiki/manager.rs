@@ -24,16 +24,16 @@ use crate::schema::{wiki_settings, wikis};
 use async_std::sync::RwLockWriteGuard;
 
 pub struct WikiManager {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
     wikis: RwLock<HashMap<WikiId, Wiki>>,
 }
 
 impl WikiManager {
-    pub fn new(conn: &Arc<PgConnection>) -> Result<Self> {
+    pub fn new(conn: &ConnectionPool) -> Result<Self> {
         debug!("Creating wiki-manager service");
 
-        let conn = Arc::clone(conn);
-        let values = wikis::table.load::<Wiki>(&*conn)?;
+        let conn = conn.clone();
+        let values = wikis::table.load::<Wiki>(&*conn.get()?)?;
 
         let wikis = {
             let mut map = HashMap::with_capacity(values.len());
@@ -62,7 +62,7 @@ impl WikiManager {
             let model = NewWiki { name, slug, domain };
             let wiki = diesel::insert_into(wikis::table)
                 .values(&model)
-                .get_result::<Wiki>(&*self.conn)?;
+                .get_result::<Wiki>(&*self.conn.get()?)?;
 
             let wiki_id = wiki.id();
             let mut guard = self.wikis.write().await;
@@ -76,7 +76,7 @@ impl WikiManager {
 
             diesel::insert_into(wiki_settings::table)
                 .values(&model)
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
 
             Ok((wiki_id, guard))
         })
@@ -120,7 +120,7 @@ impl WikiManager {
             let id: i64 = id.into();
             diesel::update(dsl::wikis.filter(dsl::wiki_id.eq(id)))
                 .set(&model)
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
         }
 
         Ok(())
@@ -132,7 +132,7 @@ impl WikiManager {
         let id: i64 = wiki_id.into();
         let result = wiki_settings::table
             .find(id)
-            .first::<WikiSettings>(&*self.conn)
+            .first::<WikiSettings>(&*self.conn.get()?)
             .optional()?;
 
         match result {
@@ -156,7 +156,7 @@ impl WikiManager {
             let id: i64 = wiki_id.into();
             diesel::update(dsl::wiki_settings.filter(dsl::wiki_id.eq(id)))
                 .set(&model)
-                .execute(&*self.conn)?;
+                .execute(&*self.conn.get()?)?;
         }
 
         Ok(())
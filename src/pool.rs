@@ -0,0 +1,332 @@
+/*
+ * pool.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::{Error, Result};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use std::cell::RefCell;
+use std::fmt::{self, Debug};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+type PgManager = ConnectionManager<PgConnection>;
+type PgPooled = PooledConnection<PgManager>;
+
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// How long to wait before the first retry, doubled on each one after.
+const RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The message Postgres gives errors with SQLSTATE 40P01 (`deadlock_detected`).
+///
+/// Diesel only reports the SQLSTATE through `DatabaseErrorKind`, which has no variant
+/// for deadlocks, and its error information can't be downcast to read the code. So
+/// deadlocks are recognized by this message, which Postgres only translates if
+/// `lc_messages` is set to another language. `ConnectionPool::new()` warns if it is.
+const DEADLOCK_MESSAGE: &str = "deadlock detected";
+
+thread_local! {
+    /// The connection for the transaction running on this thread, if any.
+    ///
+    /// Transactions block the thread until they finish, so any query made
+    /// on this thread in the meantime is part of it and must use the same
+    /// connection instead of checking out a new one.
+    #[allow(clippy::missing_const_for_thread_local)] // const initializers need a newer compiler
+    static CURRENT: RefCell<Option<(usize, Rc<PgPooled>)>> = RefCell::new(None);
+
+    /// Callbacks to run once the current transaction commits.
+    /// These are dropped without running if it is rolled back.
+    #[allow(clippy::missing_const_for_thread_local)]
+    static AFTER_COMMIT: RefCell<Vec<Box<dyn FnOnce()>>> = RefCell::new(Vec::new());
+}
+
+/// A pool of Postgres connections, shared between the managers.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    id: usize,
+    pool: Pool<PgManager>,
+    attempts: u32,
+}
+
+impl ConnectionPool {
+    pub fn new(database_url: &str, size: u32, attempts: u32) -> Result<Self> {
+        debug!(
+            "Creating Postgres connection pool with {} connections",
+            size
+        );
+
+        let manager = ConnectionManager::new(database_url);
+        let pool = Pool::builder().max_size(size).build(manager)?;
+        let id = NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed);
+
+        check_message_locale(&pool);
+
+        Ok(ConnectionPool { id, pool, attempts })
+    }
+
+    fn current(&self) -> Option<Rc<PgPooled>> {
+        CURRENT.with(|current| match *current.borrow() {
+            Some((id, ref conn)) if id == self.id => Some(Rc::clone(conn)),
+            _ => None,
+        })
+    }
+
+    /// Gets the connection for the current transaction, or checks one out from the pool.
+    pub fn get(&self) -> Result<ConnectionGuard> {
+        match self.current() {
+            Some(conn) => Ok(ConnectionGuard::Transaction(conn)),
+            None => Ok(ConnectionGuard::Pooled(self.pool.get()?)),
+        }
+    }
+
+    /// Like `get()`, but fails instead of waiting longer than `timeout` for a free connection.
+    pub fn get_timeout(&self, timeout: Duration) -> Result<ConnectionGuard> {
+        match self.current() {
+            Some(conn) => Ok(ConnectionGuard::Transaction(conn)),
+            None => Ok(ConnectionGuard::Pooled(self.pool.get_timeout(timeout)?)),
+        }
+    }
+
+    /// Runs the callback once the current transaction has committed,
+    /// or right away if there isn't one.
+    ///
+    /// If the transaction is rolled back, the callback is dropped instead.
+    /// This includes savepoints which are rolled back within a transaction.
+    pub fn after_commit<F: FnOnce() + 'static>(&self, f: F) {
+        if self.current().is_some() {
+            AFTER_COMMIT.with(|callbacks| callbacks.borrow_mut().push(Box::new(f)));
+        } else {
+            f();
+        }
+    }
+
+    /// Runs the closure in a transaction on a single connection.
+    /// Nested transactions become savepoints in the outer one.
+    pub fn transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        if let Some(conn) = self.current() {
+            // Forget callbacks added within a savepoint which is rolled back
+            let pending = AFTER_COMMIT.with(|callbacks| callbacks.borrow().len());
+            let result = conn.transaction(f);
+            if result.is_err() {
+                AFTER_COMMIT.with(|callbacks| callbacks.borrow_mut().truncate(pending));
+            }
+
+            return result;
+        }
+
+        let conn = Rc::new(self.pool.get()?);
+        let guard = CurrentGuard::new(self.id, &conn);
+
+        let result = conn.transaction(f);
+        guard.finish(result.is_ok());
+        result
+    }
+
+    /// Runs the closure in a read-only transaction which sees a single snapshot
+    /// of the database, so concurrent writes can't make its reads inconsistent.
+    ///
+    /// Nested in another transaction, this just uses the outer one.
+    pub fn snapshot_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Result<T>,
+    {
+        if self.current().is_some() {
+            return self.transaction(f);
+        }
+
+        let conn = Rc::new(self.pool.get()?);
+        let guard = CurrentGuard::new(self.id, &conn);
+
+        let result = conn
+            .build_transaction()
+            .repeatable_read()
+            .read_only()
+            .run(f);
+        guard.finish(result.is_ok());
+        result
+    }
+
+    /// Runs the closure in a transaction, running it again if Postgres
+    /// aborted the transaction because of a serialization failure or deadlock.
+    ///
+    /// Nested transactions are only run once, since it is the
+    /// outermost transaction which has to be retried.
+    pub fn retry_transaction<F, T>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        if self.current().is_some() {
+            return self.transaction(f);
+        }
+
+        let mut attempt = 1;
+        loop {
+            match self.transaction(&mut f) {
+                Err(ref error) if attempt < self.attempts && is_retryable(error) => {
+                    let delay = RETRY_DELAY * 2u32.pow(attempt - 1);
+
+                    warn!(
+                        "Transaction failed on attempt {} ({}), retrying in {} ms",
+                        attempt,
+                        error,
+                        delay.as_millis(),
+                    );
+
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn test_transaction<F>(&self, f: F)
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let conn = Rc::new(self.pool.get().expect("Unable to get connection"));
+        let _guard = CurrentGuard::new(self.id, &conn);
+
+        conn.test_transaction::<_, crate::Error, _>(f);
+    }
+}
+
+/// Whether the error means the transaction was aborted and can just be run again.
+fn is_retryable(error: &Error) -> bool {
+    use self::DatabaseErrorKind::{__Unknown, SerializationFailure};
+
+    match error {
+        Error::Database(DieselError::DatabaseError(SerializationFailure, _)) => true,
+        Error::Database(DieselError::DatabaseError(__Unknown, info)) => {
+            info.message() == DEADLOCK_MESSAGE
+        }
+        _ => false,
+    }
+}
+
+/// Warns if Postgres reports errors in a language other than English,
+/// since deadlocks wouldn't be recognized and retried. See `DEADLOCK_MESSAGE`.
+fn check_message_locale(pool: &Pool<PgManager>) {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Text;
+
+    let result = pool.get().map_err(Error::from).and_then(|conn| {
+        diesel::select(sql::<Text>("current_setting('lc_messages')"))
+            .get_result::<String>(&conn)
+            .map_err(Error::from)
+    });
+
+    match result {
+        Ok(locale) => {
+            let english = locale.is_empty()
+                || locale == "C"
+                || locale == "POSIX"
+                || locale.to_ascii_lowercase().starts_with("en");
+
+            if !english {
+                warn!(
+                    "Postgres lc_messages is '{}', deadlocked transactions will not be retried",
+                    locale,
+                );
+            }
+        }
+        Err(error) => warn!("Unable to check Postgres lc_messages: {}", error),
+    }
+}
+
+impl Debug for ConnectionPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.pool.state();
+
+        f.debug_struct("ConnectionPool")
+            .field("id", &self.id)
+            .field("connections", &state.connections)
+            .field("idle_connections", &state.idle_connections)
+            .field("attempts", &self.attempts)
+            .finish()
+    }
+}
+
+/// Unsets the current transaction's connection when dropped, even if unwinding.
+/// Any callbacks waiting for it to commit are dropped along with it.
+struct CurrentGuard;
+
+impl CurrentGuard {
+    fn new(id: usize, conn: &Rc<PgPooled>) -> Self {
+        CURRENT.with(|current| *current.borrow_mut() = Some((id, Rc::clone(conn))));
+        CurrentGuard
+    }
+
+    /// Ends the transaction, running its callbacks if it committed.
+    fn finish(self, committed: bool) {
+        let callbacks = AFTER_COMMIT.with(|callbacks| callbacks.replace(Vec::new()));
+        drop(self);
+
+        if committed {
+            for callback in callbacks {
+                callback();
+            }
+        }
+    }
+}
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = None);
+        AFTER_COMMIT.with(|callbacks| callbacks.borrow_mut().clear());
+    }
+}
+
+/// A database connection, either checked out for a single query or
+/// shared with the transaction running on this thread.
+pub enum ConnectionGuard {
+    Pooled(PgPooled),
+    Transaction(Rc<PgPooled>),
+}
+
+impl Deref for ConnectionGuard {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        match self {
+            ConnectionGuard::Pooled(conn) => conn,
+            ConnectionGuard::Transaction(conn) => conn,
+        }
+    }
+}
+
+impl Debug for ConnectionGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            ConnectionGuard::Pooled(_) => "Pooled",
+            ConnectionGuard::Transaction(_) => "Transaction",
+        };
+
+        write!(f, "ConnectionGuard::{}(PgConnection {{ .. }})", kind)
+    }
+}
@@ -3,7 +3,7 @@ table! {
         audit_log_entry_id -> Int8,
         audit_log_entry_type -> Text,
         created_at -> Timestamptz,
-        wiki_id -> Int8,
+        wiki_id -> Nullable<Int8>,
         user_id -> Nullable<Int8>,
         data -> Jsonb,
     }
@@ -18,6 +18,18 @@ table! {
     }
 }
 
+table! {
+    email_changes (selector) {
+        selector -> Text,
+        user_id -> Int8,
+        email -> Text,
+        token_hash -> Bytea,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        redeemed_at -> Nullable<Timestamptz>,
+    }
+}
+
 table! {
     files (file_id) {
         file_id -> Int8,
@@ -33,9 +45,12 @@ table! {
         login_attempt_id -> Int8,
         user_id -> Nullable<Int8>,
         username_or_email -> Nullable<Text>,
-        remote_address -> Nullable<Text>,
+        remote_address -> Nullable<Inet>,
         success -> Bool,
         attempted_at -> Timestamptz,
+        credential_kind -> Text,
+        idempotency_key -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
     }
 }
 
@@ -69,6 +84,30 @@ table! {
     }
 }
 
+table! {
+    password_resets (selector) {
+        selector -> Text,
+        user_id -> Int8,
+        token_hash -> Bytea,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        redeemed_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    password_history (password_history_id) {
+        password_history_id -> Int8,
+        user_id -> Int8,
+        hash -> Bytea,
+        salt -> Bytea,
+        logn -> Int2,
+        param_r -> Int4,
+        param_p -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     passwords (user_id) {
         user_id -> Int8,
@@ -132,7 +171,14 @@ table! {
     sessions (session_id) {
         session_id -> Int8,
         user_id -> Int8,
-        login_attempt_id -> Int8,
+        login_attempt_id -> Nullable<Int8>,
+        impersonator_id -> Nullable<Int8>,
+        created_at -> Timestamptz,
+        expires_at -> Nullable<Timestamptz>,
+        token_selector -> Text,
+        token_hash -> Bytea,
+        remote_address -> Nullable<Inet>,
+        user_agent -> Nullable<Text>,
     }
 }
 
@@ -144,6 +190,39 @@ table! {
     }
 }
 
+table! {
+    totp_logins (selector) {
+        selector -> Text,
+        user_id -> Int8,
+        login_attempt_id -> Int8,
+        token_hash -> Bytea,
+        failures -> Int2,
+        expires_at -> Timestamptz,
+    }
+}
+
+table! {
+    user_audit_log (user_audit_log_entry_id) {
+        user_audit_log_entry_id -> Int8,
+        user_id -> Int8,
+        field -> Text,
+        old_value -> Jsonb,
+        new_value -> Jsonb,
+        changed_by -> Int8,
+        changed_at -> Timestamptz,
+    }
+}
+
+table! {
+    user_totp (user_id) {
+        user_id -> Int8,
+        totp_secret -> Bytea,
+        enabled -> Bool,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     user_verification (user_id) {
         user_id -> Int8,
@@ -156,6 +235,7 @@ table! {
     users (user_id) {
         user_id -> Int8,
         name -> Text,
+        slug -> Text,
         email -> Text,
         is_verified -> Bool,
         is_special -> Bool,
@@ -165,8 +245,11 @@ table! {
         about -> Text,
         gender -> Text,
         location -> Text,
+        timezone -> Text,
+        locale -> Text,
         created_at -> Timestamptz,
         deleted_at -> Nullable<Timestamptz>,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -202,12 +285,15 @@ joinable!(audit_log -> users (user_id));
 joinable!(audit_log -> wikis (wiki_id));
 joinable!(authors -> pages (page_id));
 joinable!(authors -> users (user_id));
+joinable!(email_changes -> users (user_id));
 joinable!(files -> pages (page_id));
 joinable!(login_attempts -> users (user_id));
 joinable!(page_locks -> pages (page_id));
 joinable!(page_locks -> users (user_id));
 joinable!(pages -> wikis (wiki_id));
 joinable!(parents -> users (parented_by));
+joinable!(password_history -> users (user_id));
+joinable!(password_resets -> users (user_id));
 joinable!(passwords -> users (user_id));
 joinable!(ratings -> pages (page_id));
 joinable!(ratings -> users (user_id));
@@ -220,8 +306,10 @@ joinable!(role_membership -> users (user_id));
 joinable!(role_membership -> wikis (wiki_id));
 joinable!(roles -> wikis (wiki_id));
 joinable!(sessions -> login_attempts (login_attempt_id));
-joinable!(sessions -> users (user_id));
 joinable!(tag_history -> revisions (revision_id));
+joinable!(totp_logins -> login_attempts (login_attempt_id));
+joinable!(totp_logins -> users (user_id));
+joinable!(user_totp -> users (user_id));
 joinable!(user_verification -> users (user_id));
 joinable!(wiki_membership -> users (user_id));
 joinable!(wiki_membership -> wikis (wiki_id));
@@ -230,11 +318,14 @@ joinable!(wiki_settings -> wikis (wiki_id));
 allow_tables_to_appear_in_same_query!(
     audit_log,
     authors,
+    email_changes,
     files,
     login_attempts,
     page_locks,
     pages,
     parents,
+    password_history,
+    password_resets,
     passwords,
     ratings,
     ratings_history,
@@ -243,6 +334,9 @@ allow_tables_to_appear_in_same_query!(
     roles,
     sessions,
     tag_history,
+    totp_logins,
+    user_audit_log,
+    user_totp,
     user_verification,
     users,
     wiki_membership,
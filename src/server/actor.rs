@@ -0,0 +1,92 @@
+/*
+ * server/actor.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::manager_prelude::*;
+
+/// A user acting through a validated session.
+///
+/// This can only be obtained from `Server::get_actor()`, which checks the token
+/// with `Server::authenticate()`, so methods which take it can assume the caller
+/// has already been authenticated.
+#[derive(Debug)]
+pub struct Actor {
+    session: Session,
+    user: User,
+}
+
+impl Actor {
+    #[inline]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    #[inline]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    #[inline]
+    pub fn user_id(&self) -> UserId {
+        self.user.id()
+    }
+}
+
+impl Server {
+    /// Validates a client's session token and loads its user, for methods which require authentication.
+    /// Errors are the same as `authenticate()`.
+    pub async fn get_actor(&self, token: &str) -> Result<Actor> {
+        debug!("Getting actor for session token");
+
+        let (session, user) = self.authenticate(token).await?;
+
+        Ok(Actor { session, user })
+    }
+
+    /// Validates a client's session token and loads its user, for methods which require authentication.
+    /// The session and user are read in the same transaction.
+    ///
+    /// Returns `InvalidSession` if the token is wrong or the session has expired,
+    /// or `UserNotFound` if the user no longer exists or is inactive. If the
+    /// verification policy requires it, returns `AccountNotVerified` for users
+    /// who have not verified their email yet.
+    pub async fn authenticate(&self, token: &str) -> Result<(Session, User)> {
+        debug!("Authenticating session token");
+
+        self.transaction(async {
+            let session = self.session.validate_token(token).await?;
+            let user_id = session.user_id();
+            let user = match self.user.get_from_id(user_id).await? {
+                Some(user) if user.is_active() => user,
+                _ => {
+                    warn!("Session is for missing or inactive user ID {}", user_id);
+                    return Err(Error::UserNotFound);
+                }
+            };
+
+            if self.verification.require_before_login && !user.is_verified() {
+                warn!("User ID {} has not verified their email", user_id);
+                return Err(Error::AccountNotVerified(None));
+            }
+
+            Ok((session, user))
+        })
+        .await
+    }
+}
@@ -0,0 +1,34 @@
+/*
+ * server/audit.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::manager_prelude::*;
+
+impl Server {
+    /// Returns all audit log entries made by the given user since the given date.
+    /// Limited to 100 entries.
+    #[inline]
+    pub async fn get_audit_log_entries<Tz: TimeZone>(
+        &self,
+        user_id: UserId,
+        since: DateTime<Tz>,
+    ) -> Result<Vec<AuditLogEntry>> {
+        self.audit.get_entries(user_id, since).await
+    }
+}
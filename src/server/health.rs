@@ -0,0 +1,83 @@
+/*
+ * server/health.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::manager_prelude::*;
+use std::time::{Duration, Instant};
+
+/// How long to wait for a connection, and then for the query, before reporting unhealthy.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The outcome of `Server::health()`.
+#[derive(Debug, Clone)]
+pub struct HealthStatus {
+    pub database_reachable: bool,
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+impl HealthStatus {
+    #[inline]
+    pub fn healthy(&self) -> bool {
+        self.database_reachable
+    }
+}
+
+impl Server {
+    /// Checks that the database can be queried, and how long a round-trip takes.
+    ///
+    /// Unlike `ping()`, this never fails or waits indefinitely. If no connection
+    /// is available or the query takes too long, an unhealthy status is returned.
+    pub async fn health(&self) -> HealthStatus {
+        debug!("Checking database health");
+
+        let start = Instant::now();
+        let result = self.check_database(HEALTH_TIMEOUT);
+        let latency = start.elapsed();
+
+        match result {
+            Ok(()) => HealthStatus {
+                database_reachable: true,
+                latency,
+                error: None,
+            },
+            Err(error) => {
+                warn!("Database health check failed: {}", error);
+
+                HealthStatus {
+                    database_reachable: false,
+                    latency,
+                    error: Some(error.to_string()),
+                }
+            }
+        }
+    }
+
+    fn check_database(&self, timeout: Duration) -> Result<()> {
+        let conn = self.conn.get_timeout(timeout)?;
+        let statement_timeout = format!("SET LOCAL statement_timeout = {}", timeout.as_millis());
+
+        conn.transaction::<_, Error, _>(|| {
+            conn.execute(&statement_timeout)?;
+            conn.execute("SELECT 1")?;
+
+            Ok(())
+        })
+    }
+}
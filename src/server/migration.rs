@@ -0,0 +1,45 @@
+/*
+ * server/migration.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::manager_prelude::*;
+use crate::migration;
+
+impl Server {
+    /// Applies any migrations the database doesn't have yet.
+    /// Returns the names of the migrations which were run.
+    ///
+    /// Returns `DatabaseSchemaNewer` if the database has migrations this version doesn't know about.
+    pub async fn run_pending_migrations(&self) -> Result<Vec<String>> {
+        info!("Running pending database migrations");
+
+        let conn = self.conn.get()?;
+        let names = migration::run_pending(&conn)?;
+
+        Ok(names.into_iter().map(String::from).collect())
+    }
+
+    /// Lists every migration and whether it has been applied to the database.
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        debug!("Getting database migration status");
+
+        let conn = self.conn.get()?;
+        migration::status(&conn)
+    }
+}
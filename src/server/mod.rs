@@ -18,47 +18,77 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+mod actor;
+mod audit;
 mod author;
+mod health;
 mod lock;
+mod migration;
 mod page;
 mod password;
 mod rating;
 mod revision;
+mod self_test;
 mod session;
+mod totp;
 mod user;
 mod utils;
 mod wiki;
 
+pub use self::actor::Actor;
+pub use self::health::HealthStatus;
+pub use self::self_test::{SelfTestReport, SelfTestResult};
+pub use self::session::LoginResponse;
+pub use self::user::NewUserSpec;
+
 use crate::manager_prelude::*;
+use crate::package::audit::AuditLogManager;
 use crate::package::author::AuthorManager;
 use crate::package::lock::LockManager;
 use crate::package::page::PageManager;
 use crate::package::password::PasswordManager;
 use crate::package::rating::RatingManager;
-use crate::package::session::SessionManager;
+use crate::package::session::{LoginEventListener, SessionManager};
+use crate::package::totp::{TotpManager, TotpPolicy};
 use crate::package::user::UserManager;
 use crate::package::wiki::WikiManager;
+use chrono::Duration;
 use std::fmt::{self, Debug};
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Config<'a> {
     pub database_url: &'a str,
+    pub database_pool_size: u32,
+    pub transaction_attempts: u32,
     pub revisions_dir: PathBuf,
-    pub password_blacklist: Option<&'a Path>,
+    pub password_policy: PasswordPolicy,
+    pub password_cost: HashCost,
+    pub password_reset_ttl: Duration,
+    pub max_message_length: usize,
+    pub session_ttl: Duration,
+    pub session_expiry: SessionExpiry,
+    pub lockout: LockoutPolicy,
+    pub rate_limit: RateLimitPolicy,
+    pub verification: VerificationPolicy,
+    pub totp: TotpPolicy,
+    pub login_listener: Option<Arc<dyn LoginEventListener>>,
 }
 
 pub struct Server {
-    conn: Arc<PgConnection>,
+    conn: ConnectionPool,
+    audit: AuditLogManager,
     author: AuthorManager,
     lock: LockManager,
     page: PageManager,
     password: PasswordManager,
     rating: RatingManager,
     session: SessionManager,
+    totp: TotpManager,
     user: UserManager,
     wiki: WikiManager,
+    verification: VerificationPolicy,
 }
 
 impl Server {
@@ -67,38 +97,64 @@ impl Server {
 
         let Config {
             database_url,
+            database_pool_size,
+            transaction_attempts,
             revisions_dir,
-            password_blacklist,
+            password_policy,
+            password_cost,
+            password_reset_ttl,
+            max_message_length,
+            session_ttl,
+            session_expiry,
+            lockout,
+            rate_limit,
+            verification,
+            totp,
+            login_listener,
         } = config;
 
-        let conn = match PgConnection::establish(database_url) {
-            Ok(conn) => Arc::new(conn),
+        let pool = ConnectionPool::new(database_url, database_pool_size, transaction_attempts);
+        let conn = match pool {
+            Ok(conn) => conn,
             Err(error) => {
-                error!("Error establishing Postgres connection: {}", error);
+                error!("Error establishing Postgres connection pool: {}", error);
 
-                return Err(Error::DatabaseConnection(error));
+                return Err(error);
             }
         };
 
+        let audit = AuditLogManager::new(&conn);
         let author = AuthorManager::new(&conn);
         let lock = LockManager::new(&conn);
-        let page = PageManager::new(&conn, revisions_dir);
-        let password = PasswordManager::new(&conn, password_blacklist)?;
+        let page = PageManager::new(&conn, revisions_dir, max_message_length);
+        let password =
+            PasswordManager::new(&conn, password_policy, password_cost, password_reset_ttl);
         let rating = RatingManager::new(&conn);
-        let session = SessionManager::new(&conn);
+        let session = SessionManager::new(
+            &conn,
+            session_ttl,
+            session_expiry,
+            lockout,
+            rate_limit,
+            login_listener,
+        );
+        let totp = TotpManager::new(&conn, totp);
         let user = UserManager::new(&conn);
         let wiki = WikiManager::new(&conn)?;
 
         Ok(Server {
             conn,
+            audit,
             author,
             lock,
             page,
             password,
             rating,
             session,
+            totp,
             user,
             wiki,
+            verification,
         })
     }
 
@@ -106,14 +162,29 @@ impl Server {
     pub async fn ping(&self) -> Result<()> {
         debug!("Pinging database");
 
-        self.conn.execute("SELECT 1")?;
+        self.conn.get()?.execute("SELECT 1")?;
         Ok(())
     }
 
     #[cfg(test)]
     #[inline]
     pub fn test_transaction<F: FnOnce() -> Result<()>>(&self, f: F) {
-        self.conn.test_transaction::<_, Error, _>(f);
+        self.conn.test_transaction(f);
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub fn test_retry_transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        self.conn.retry_transaction(f)
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub fn test_after_commit<F: FnOnce() + 'static>(&self, f: F) {
+        self.conn.after_commit(f);
     }
 }
 
@@ -122,7 +193,7 @@ impl_async_transaction!(Server);
 impl Debug for Server {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("deepwell::Server")
-            .field("conn", &"PgConnection { .. }")
+            .field("conn", &self.conn)
             .field("page", &self.page)
             .field("user", &self.user)
             .field("wiki", &self.wiki)
@@ -25,14 +25,52 @@ impl Server {
     /// Sets or overwrites the given user's password.
     #[inline]
     pub fn set_user_password(&self, user_id: UserId, password: &str) -> Result<()> {
-        if password.is_empty() {
-            return Err(Error::NewPasswordInvalid("passwords may not be empty"));
-        }
-
         task::block_on(self.password.set(user_id, password))?;
         Ok(())
     }
 
+    /// Changes a user's password, after checking their current one.
+    /// Returns `AuthenticationFailed` if the old password doesn't match.
+    ///
+    /// If the token of the session the change was made from is given, all of the
+    /// user's other sessions are ended. Returns `InvalidSession` if that session
+    /// doesn't belong to the user.
+    pub async fn change_password(
+        &self,
+        user_id: UserId,
+        old_password: &str,
+        new_password: &str,
+        current_session: Option<&str>,
+    ) -> Result<()> {
+        info!("Changing password for user ID {}", user_id);
+
+        self.transaction(async {
+            self.password.check(user_id, old_password).await?;
+            self.password.set(user_id, new_password).await?;
+
+            if let Some(token) = current_session {
+                let (session_id, _) = self.session.resolve_token(token).await?;
+                self.session.end_other_sessions(session_id, user_id).await?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Issues a password reset token for the given user, to be sent to them by email.
+    #[inline]
+    pub async fn create_password_reset(&self, user_id: UserId) -> Result<String> {
+        self.password.create_reset_token(user_id).await
+    }
+
+    /// Sets a user's password from a reset token.
+    /// Returns `InvalidResetToken` if it is expired or was already used.
+    #[inline]
+    pub async fn redeem_reset_token(&self, token: &str, new_password: &str) -> Result<UserId> {
+        self.password.redeem_reset_token(token, new_password).await
+    }
+
     /// Validates the password for the given user.
     /// Returns `()` on success, authentication error on failure.
     #[inline]
@@ -40,4 +78,10 @@ impl Server {
         task::block_on(self.password.check(user_id, password))?;
         Ok(())
     }
+
+    #[cfg(test)]
+    #[inline]
+    pub async fn get_password_cost(&self, user_id: UserId) -> Result<Option<HashCost>> {
+        self.password.get_cost(user_id).await
+    }
 }
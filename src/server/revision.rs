@@ -56,7 +56,8 @@ impl Server {
         self.page.get_blame_by_id(page_id).await
     }
 
-    /// Get a diff for a given page between the two specified revisions.
+    /// Get a unified diff for a given page between the two specified revisions.
+    /// Both revisions must have changed the page.
     #[inline]
     pub async fn get_page_diff<S: Into<String>>(
         &self,
@@ -70,6 +71,116 @@ impl Server {
         self.page.get_diff(wiki_id, &slug, first, second).await
     }
 
+    /// Get the commit message, author, and time of a revision of a page.
+    /// The revision must have changed the page.
+    #[inline]
+    pub async fn get_page_revision<S: Into<String>>(
+        &self,
+        wiki_id: WikiId,
+        slug: S,
+        revision: Either<RevisionId, &GitHash>,
+    ) -> Result<RevisionInfo> {
+        let slug = normalize_slug(slug);
+
+        self.page.get_revision(wiki_id, &slug, revision).await
+    }
+
+    /// Marks a revision of a page with a name, such as `published`.
+    /// If the page already has a tag with that name, it is moved to this revision.
+    ///
+    /// Tag names can only contain ASCII letters, digits, `-`, `_`, and `.`.
+    #[inline]
+    pub async fn tag_page_revision<S: Into<String>>(
+        &self,
+        wiki_id: WikiId,
+        slug: S,
+        revision: Either<RevisionId, &GitHash>,
+        tag: &str,
+    ) -> Result<()> {
+        let slug = normalize_slug(slug);
+
+        self.page.tag_revision(wiki_id, &slug, revision, tag).await
+    }
+
+    /// Get the revision of a page with the given tag.
+    /// Returns `RevisionTagNotFound` if the page has no such tag.
+    #[inline]
+    pub async fn get_tagged_page_revision<S: Into<String>>(
+        &self,
+        wiki_id: WikiId,
+        slug: S,
+        tag: &str,
+    ) -> Result<RevisionInfo> {
+        let slug = normalize_slug(slug);
+
+        self.page.get_tagged_revision(wiki_id, &slug, tag).await
+    }
+
+    /// Get the most recent changes across all pages in a wiki, newest first.
+    #[inline]
+    pub async fn get_recent_changes(
+        &self,
+        wiki_id: WikiId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        self.page.get_recent_changes(wiki_id, limit, offset).await
+    }
+
+    /// Find lines containing the given text in the current version of a wiki's pages.
+    /// Only a few matches are returned from each page.
+    #[inline]
+    pub async fn search_content(
+        &self,
+        wiki_id: WikiId,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        self.page.search_content(wiki_id, query, false, limit).await
+    }
+
+    /// Like `search_content()`, but the query is a POSIX extended regular expression.
+    #[inline]
+    pub async fn search_content_regex(
+        &self,
+        wiki_id: WikiId,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        self.page
+            .search_content(wiki_id, pattern, true, limit)
+            .await
+    }
+
+    /// Get the revision history of a page, newest first.
+    /// Returns `PageNotFound` if the page has never existed.
+    #[inline]
+    pub async fn get_page_revisions<S: Into<String>>(
+        &self,
+        wiki_id: WikiId,
+        slug: S,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        let slug = normalize_slug(slug);
+
+        self.page.get_revisions(wiki_id, &slug, limit, offset).await
+    }
+
+    /// Get the most recent changes made by a user in a wiki, newest first.
+    #[inline]
+    pub async fn get_user_contributions(
+        &self,
+        wiki_id: WikiId,
+        user_id: UserId,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<RevisionInfo>> {
+        self.page
+            .get_contributions(wiki_id, user_id, limit, offset)
+            .await
+    }
+
     /// Overwrite the revision message for a given change.
     #[inline]
     pub async fn edit_revision(&self, revision_id: RevisionId, message: &str) -> Result<()> {
@@ -86,6 +197,17 @@ impl Server {
         self.page.undo(commit, revision).await
     }
 
+    /// Reverts a page to its contents as of the given revision.
+    /// This is committed as a new revision, and history is preserved.
+    #[inline]
+    pub async fn revert_revision(
+        &self,
+        commit: PageCommit<'_>,
+        revision: Either<RevisionId, &GitHash>,
+    ) -> Result<RevisionId> {
+        self.page.revert(commit, revision).await
+    }
+
     /// Performs git vacuum in the page repository.
     /// Returns the number of pruned objects.
     #[inline]
@@ -0,0 +1,222 @@
+/*
+ * server/self_test.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::manager_prelude::*;
+use crate::package::revision::{CommitInfo, GitStore, RevisionStore};
+use crate::utils::rand_alphanum;
+use async_std::fs;
+use diesel::result::Error as DieselError;
+use std::env;
+use std::time::{Duration, Instant};
+
+/// The outcome of running a single subsystem during a self-test.
+#[derive(Debug, Clone)]
+pub struct SelfTestResult {
+    pub subsystem: &'static str,
+    pub passed: bool,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+/// The outcome of `Server::self_test()`, with one result per subsystem in the order run.
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub results: Vec<SelfTestResult>,
+}
+
+impl SelfTestReport {
+    #[inline]
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    fn add<T>(&mut self, subsystem: &'static str, start: Instant, result: Result<T>) -> Option<T> {
+        let duration = start.elapsed();
+        let (passed, error, value) = match result {
+            Ok(value) => (true, None, Some(value)),
+            Err(error) => {
+                warn!("Self-test of {} failed: {}", subsystem, error);
+
+                (false, Some(error.to_string()), None)
+            }
+        };
+
+        self.results.push(SelfTestResult {
+            subsystem,
+            passed,
+            duration,
+            error,
+        });
+
+        value
+    }
+
+    fn skip(&mut self, subsystem: &'static str) {
+        self.results.push(SelfTestResult {
+            subsystem,
+            passed: false,
+            duration: Duration::from_secs(0),
+            error: Some("skipped due to earlier failure".into()),
+        });
+    }
+}
+
+impl Server {
+    /// Exercises each subsystem and reports which ones work, for post-deployment checks.
+    ///
+    /// All database changes are made in a transaction which is rolled back,
+    /// so nothing is reported to the login listener. The revision store is
+    /// created in a temporary directory which is removed afterwards.
+    pub async fn self_test(&self) -> Result<SelfTestReport> {
+        info!("Running server self-test");
+
+        let mut report = SelfTestReport::default();
+
+        // Always roll back, the report is filled in as a side effect
+        let result = self.transaction(async {
+            self.self_test_database(&mut report).await;
+
+            Err::<(), _>(Error::Database(DieselError::RollbackTransaction))
+        });
+
+        match result.await {
+            Err(Error::Database(DieselError::RollbackTransaction)) => (),
+            Err(error) => return Err(error),
+            Ok(()) => {
+                let error = Error::StaticMsg("self-test transaction was not rolled back");
+                report.add::<()>("rollback", Instant::now(), Err(error));
+            }
+        }
+
+        self.self_test_revision(&mut report).await;
+
+        Ok(report)
+    }
+
+    async fn self_test_database(&self, report: &mut SelfTestReport) {
+        macro_rules! run {
+            ($subsystem:expr, $future:expr) => {{
+                let start = Instant::now();
+                let result = $future.await;
+                report.add($subsystem, start, result)
+            }};
+        }
+
+        macro_rules! skip {
+            ($($subsystem:expr),*) => {{
+                $(
+                    report.skip($subsystem);
+                )*
+
+                return;
+            }};
+        }
+
+        if run!("database", self.ping()).is_none() {
+            skip!("user", "password", "session");
+        }
+
+        let name = format!("self-test-{}", rand_alphanum(16));
+        let email = format!("{}@example.com", name);
+        let password = rand_alphanum(32);
+
+        let user_id = match run!("user", self.user.create(&name, &email)) {
+            Some(user_id) => user_id,
+            None => skip!("password", "session"),
+        };
+
+        let password_check = async {
+            self.password.set(user_id, &password).await?;
+            self.password.check(user_id, &password).await
+        };
+
+        if run!("password", password_check).is_none() {
+            skip!("session");
+        }
+
+        let session_check = async {
+            let login_attempt_id = self
+                .session
+                .add_login_attempt(
+                    Some(user_id),
+                    None,
+                    CredentialKind::Id,
+                    None,
+                    None,
+                    false,
+                    None,
+                )
+                .await?;
+
+            let session = self
+                .session
+                .create_session(user_id, login_attempt_id)
+                .await?;
+
+            let validated = self.session.validate_token(session.token()).await?;
+            if validated.user_id() != user_id {
+                return Err(Error::InvalidSession);
+            }
+
+            Ok(())
+        };
+
+        run!("session", session_check);
+    }
+
+    async fn self_test_revision(&self, report: &mut SelfTestReport) {
+        const SLUG: &str = "self-test";
+        const CONTENT: &str = "DEEPWELL self-test page";
+
+        let directory = env::temp_dir().join(format!("deepwell-self-test-{}", rand_alphanum(8)));
+
+        let start = Instant::now();
+        if let Err(error) = fs::create_dir(&directory).await {
+            report.add::<()>("revision", start, Err(Error::from(error)));
+            return;
+        }
+
+        let revision_check = async {
+            let store = GitStore::new(&directory, "self-test.example.com");
+            let info = CommitInfo {
+                user_id: UserId::from_raw(0),
+                username: "self-test",
+                message: "Self-test commit",
+            };
+
+            store.initial_commit().await?;
+            store.commit(SLUG, Some(CONTENT), info).await?;
+
+            match store.get_page(SLUG).await? {
+                Some(ref content) if content == CONTENT => Ok(()),
+                _ => Err(Error::StaticMsg("page content mismatch after commit")),
+            }
+        };
+
+        let result = revision_check.await;
+        report.add("revision", start, result);
+
+        // Reported rather than returned, so the results so far aren't lost
+        let start = Instant::now();
+        if let Err(error) = fs::remove_dir_all(&directory).await {
+            report.add::<()>("revision cleanup", start, Err(Error::from(error)));
+        }
+    }
+}
@@ -19,6 +19,29 @@
  */
 
 use crate::manager_prelude::*;
+use crate::package::audit::AuditLogEntryType;
+use crate::package::user::check_new_user;
+use crate::utils::display_address;
+use chrono::Duration;
+use futures::stream::{self, Stream};
+use std::net::IpAddr;
+
+/// How long an impersonation session lasts before expiring.
+const IMPERSONATION_DURATION_MINUTES: i64 = 30;
+
+/// How many impersonation sessions a user can start per hour.
+const IMPERSONATION_LIMIT_PER_HOUR: i64 = 5;
+
+/// The result of registering a new user and logging them in.
+#[derive(Debug)]
+pub enum LoginResponse {
+    /// The user was logged in with this session.
+    Session(IssuedSession),
+
+    /// The verification policy requires the user to verify their email first.
+    /// Contains the verification token issued for them.
+    VerificationRequired(String),
+}
 
 macro_rules! wrap_login {
     ($future:expr) => {
@@ -36,26 +59,63 @@ macro_rules! wrap_login {
 
 impl Server {
     /// Attempts to login a user via user ID.
-    /// Returns the new session if successful, `AuthenticationFailed` otherwise.
+    /// Returns the new session and its token if successful, `AuthenticationFailed` otherwise.
+    ///
+    /// If the user has too many recent failed attempts, returns `AccountLocked`
+    /// without checking the password. If the user is inactive, returns `UserNotFound`.
+    ///
+    /// If the verification policy requires it and the user has not verified
+    /// their email, returns `AccountNotVerified` after checking the password.
+    /// This contains a new verification token if one was issued.
+    ///
+    /// If too many attempts have come from the remote address recently,
+    /// returns `RateLimited` without recording or checking anything.
+    ///
+    /// If the user has two-factor authentication enabled, returns `TwoFactorRequired`
+    /// after checking the password. This contains a token to pass to `try_login_totp()`
+    /// along with their code.
+    ///
+    /// The client's user agent, if given, is recorded with the attempt and session.
+    ///
+    /// Clients which retry a login can pass the same idempotency key each time,
+    /// so the attempt is only recorded once and isn't counted again for lockout
+    /// or rate limiting. Returns `InvalidArgument` if the key is empty or too long.
     pub async fn try_login_id(
         &self,
         user_id: UserId,
         password: &str,
-        remote_address: Option<&str>,
-    ) -> Result<Session> {
-        wrap_login!(self.try_login_id_internal(user_id, password, remote_address))
+        remote_address: Option<IpAddr>,
+        user_agent: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Result<IssuedSession> {
+        self.check_rate_limit(remote_address).await?;
+
+        let credential = (None, CredentialKind::Id);
+
+        wrap_login!(self.try_login_id_internal(
+            user_id,
+            credential,
+            password,
+            remote_address,
+            user_agent,
+            idempotency_key,
+        ))
     }
 
+    /// Logs in the given user, recording the credential they were found with.
     async fn try_login_id_internal(
         &self,
         user_id: UserId,
+        credential: (Option<&str>, CredentialKind),
         password: &str,
-        remote_address: Option<&str>,
-    ) -> Result<Session> {
+        remote_address: Option<IpAddr>,
+        user_agent: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Result<IssuedSession> {
         info!(
             "Trying to login user ID {} (from {})",
             user_id,
-            remote_address.unwrap_or("<unknown>"),
+            display_address(remote_address),
         );
 
         if password.is_empty() {
@@ -71,100 +131,450 @@ impl Server {
             return Err(Error::AuthenticationFailed);
         }
 
+        // Checked before this attempt is recorded, so it isn't counted
+        let locked_out = self.session.is_locked_out(user_id, Utc::now()).await?;
+
         // Outside of a transaction so it doesn't get rolled back
+        let (username_or_email, credential_kind) = credential;
         let login_attempt_id = self
             .session
-            .add_login_attempt(Some(user_id), None, remote_address, false)
+            .add_login_attempt(
+                Some(user_id),
+                username_or_email,
+                credential_kind,
+                remote_address,
+                user_agent,
+                false,
+                idempotency_key,
+            )
             .await?;
 
-        self.transaction(async {
-            self.password.check(user_id, password).await?;
+        if locked_out {
+            return Err(Error::AccountLocked);
+        }
 
-            let session = self
-                .session
-                .create_session(user_id, login_attempt_id)
-                .await?;
+        let result = self
+            .transaction(async {
+                self.check_active(user_id).await?;
+                self.password.check(user_id, password).await?;
+                self.check_verified(user_id).await?;
+                self.check_two_factor(user_id).await?;
 
-            Ok(session)
-        })
-        .await
+                let session = self
+                    .session
+                    .create_session(user_id, login_attempt_id)
+                    .await?;
+
+                Ok(session)
+            })
+            .await;
+
+        // Issued outside the transaction so it isn't rolled back
+        match result {
+            Err(Error::AccountNotVerified(_)) if self.verification.resend_token => {
+                let interval = self.verification.resend_interval;
+                let token = self.user.refresh_token(user_id, interval).await?;
+
+                Err(Error::AccountNotVerified(token))
+            }
+            Err(Error::TwoFactorRequired(_)) => {
+                let token = self.totp.create_login(user_id, login_attempt_id).await?;
+
+                Err(Error::TwoFactorRequired(Some(token)))
+            }
+            _ => result,
+        }
+    }
+
+    /// Refuses login attempts from addresses which have made too many recently.
+    ///
+    /// Attempts without an address are never limited, since otherwise
+    /// they would all share one limit and could lock each other out.
+    async fn check_rate_limit(&self, remote_address: Option<IpAddr>) -> Result<()> {
+        let address = match remote_address {
+            Some(address) => address,
+            None => return Ok(()),
+        };
+
+        if self.session.is_rate_limited(address, Utc::now()).await? {
+            Err(Error::RateLimited)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that the user exists and has not been marked inactive.
+    async fn check_active(&self, user_id: UserId) -> Result<()> {
+        let user = self
+            .user
+            .get_from_id(user_id)
+            .await?
+            .ok_or(Error::UserNotFound)?;
+
+        if user.is_active() {
+            Ok(())
+        } else {
+            warn!("User ID {} is inactive", user_id);
+            Err(Error::UserNotFound)
+        }
+    }
+
+    /// If the policy requires it, checks that the user has verified their email.
+    async fn check_verified(&self, user_id: UserId) -> Result<()> {
+        if !self.verification.require_before_login {
+            return Ok(());
+        }
+
+        let user = self
+            .user
+            .get_from_id(user_id)
+            .await?
+            .ok_or(Error::UserNotFound)?;
+
+        if user.is_verified() {
+            Ok(())
+        } else {
+            warn!("User ID {} has not verified their email", user_id);
+            Err(Error::AccountNotVerified(None))
+        }
+    }
+
+    /// Requires a code from users who have enabled two-factor authentication.
+    async fn check_two_factor(&self, user_id: UserId) -> Result<()> {
+        if self.totp.is_enabled(user_id).await? {
+            debug!("User ID {} requires a two-factor code", user_id);
+            Err(Error::TwoFactorRequired(None))
+        } else {
+            Ok(())
+        }
     }
 
     /// Attempts to login a user via username or email.
-    /// Returns the new session if successful, `AuthenticationFailed` otherwise.
+    /// Returns the new session and its token if successful, `AuthenticationFailed` otherwise.
+    ///
+    /// Rate limiting, idempotency keys, and other errors are the same as `try_login_id()`.
     pub async fn try_login(
         &self,
         name_or_email: &str,
         password: &str,
-        remote_address: Option<&str>,
-    ) -> Result<Session> {
-        wrap_login!(self.try_login_internal(name_or_email, password, remote_address))
+        remote_address: Option<IpAddr>,
+        user_agent: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Result<IssuedSession> {
+        self.check_rate_limit(remote_address).await?;
+
+        wrap_login!(self.try_login_internal(
+            name_or_email,
+            password,
+            remote_address,
+            user_agent,
+            idempotency_key,
+        ))
     }
 
     pub async fn try_login_internal(
         &self,
         name_or_email: &str,
         password: &str,
-        remote_address: Option<&str>,
-    ) -> Result<Session> {
+        remote_address: Option<IpAddr>,
+        user_agent: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Result<IssuedSession> {
         info!(
             "Trying to login user '{}' (from {})",
             name_or_email,
-            remote_address.unwrap_or("<unknown>"),
+            display_address(remote_address),
         );
 
         // Get associated user, if it exists
-        let user_id = self.user.get_id_from_email_or_name(name_or_email).await?;
+        //
+        // Inactive users are treated the same as ones which don't exist.
+        let (user_id, credential_kind) = self
+            .user
+            .get_id_from_email_or_name(name_or_email, false)
+            .await?;
 
         // Attempt login or fail
         match user_id {
             Some(id) => {
-                self.try_login_id_internal(id, password, remote_address)
-                    .await
+                let credential = (Some(name_or_email), credential_kind);
+
+                self.try_login_id_internal(
+                    id,
+                    credential,
+                    password,
+                    remote_address,
+                    user_agent,
+                    idempotency_key,
+                )
+                .await
             }
             None => {
                 self.session
-                    .add_login_attempt(None, Some(name_or_email), remote_address, false)
+                    .add_login_attempt(
+                        None,
+                        Some(name_or_email),
+                        credential_kind,
+                        remote_address,
+                        user_agent,
+                        false,
+                        idempotency_key,
+                    )
                     .await?;
 
+                // Empty passwords fail early for real users too
+                if password.is_empty() {
+                    return Err(Error::AuthenticationFailed);
+                }
+
+                self.password.check_decoy(password).await;
                 Err(Error::AuthenticationFailed)
             }
         }
     }
 
-    /// Validate a user's session to ensure they are logged in.
+    /// Finishes a login which returned `TwoFactorRequired`, using the token it
+    /// contained and a code from the user's authenticator app.
+    ///
+    /// Returns `AuthenticationFailed` if the code is wrong, and `InvalidSession` if the
+    /// token is wrong, expired, or too many wrong codes have been entered for it.
+    pub async fn try_login_totp(&self, token: &str, code: &str) -> Result<IssuedSession> {
+        wrap_login!(self.try_login_totp_internal(token, code))
+    }
+
+    async fn try_login_totp_internal(&self, token: &str, code: &str) -> Result<IssuedSession> {
+        info!("Trying to complete two-factor login");
+
+        let (user_id, login_attempt_id) = self.totp.complete_login(token, code).await?;
+
+        self.transaction(async {
+            self.check_active(user_id).await?;
+
+            let session = self
+                .session
+                .create_session(user_id, login_attempt_id)
+                .await?;
+
+            Ok(session)
+        })
+        .await
+    }
+
+    /// Creates a new user and logs them in, all in one transaction.
+    ///
+    /// If the verification policy requires verification before login, no session
+    /// is created and a verification token is returned instead.
+    pub async fn register_and_login(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+        remote_address: Option<IpAddr>,
+        user_agent: Option<&str>,
+    ) -> Result<(UserId, LoginResponse)> {
+        info!(
+            "Registering and logging in user '{}' (from {})",
+            name,
+            display_address(remote_address),
+        );
+
+        check_new_user(name, email)?;
+
+        self.transaction(async {
+            let user_id = self.user.create(name, email).await?;
+            self.password.set(user_id, password).await?;
+
+            if self.verification.require_before_login {
+                let token = self.user.create_token(user_id).await?;
+
+                return Ok((user_id, LoginResponse::VerificationRequired(token)));
+            }
+
+            let login_attempt_id = self
+                .session
+                .add_login_attempt(
+                    Some(user_id),
+                    None,
+                    CredentialKind::Id,
+                    remote_address,
+                    user_agent,
+                    true,
+                    None,
+                )
+                .await?;
+
+            let session = self
+                .session
+                .create_session(user_id, login_attempt_id)
+                .await?;
+
+            Ok((user_id, LoginResponse::Session(session)))
+        })
+        .await
+    }
+
+    /// Validate a client's session token to ensure they are logged in.
     /// Returns `()` if successful, `InvalidSession` otherwise.
     #[inline]
-    pub async fn check_session(&self, session_id: SessionId, user_id: UserId) -> Result<()> {
-        self.session.check_session(session_id, user_id).await
+    pub async fn check_session(&self, token: &str) -> Result<()> {
+        self.session.validate_token(token).await?;
+        Ok(())
+    }
+
+    /// Starts a short-lived session as the target user on behalf of a staff user.
+    ///
+    /// The staff user must be acting through a regular session, not an impersonated one,
+    /// and each use is recorded in the audit log. Callers are responsible for checking
+    /// that the staff user is permitted to do this.
+    pub async fn impersonate(&self, actor: &Actor, target_id: UserId) -> Result<IssuedSession> {
+        let admin_id = actor.user_id();
+
+        info!(
+            "User ID {} is impersonating user ID {}",
+            admin_id, target_id
+        );
+
+        self.transaction(async {
+            if actor.session().is_impersonation() {
+                warn!("Cannot impersonate from an impersonation session");
+                return Err(Error::ImpersonationNotAllowed);
+            }
+
+            if admin_id == target_id {
+                warn!("Cannot impersonate yourself");
+                return Err(Error::ImpersonationNotAllowed);
+            }
+
+            let target = self
+                .user
+                .get_from_id(target_id)
+                .await?
+                .ok_or(Error::UserNotFound)?;
+
+            if !target.is_active() {
+                warn!("Cannot impersonate inactive user ID {}", target_id);
+                return Err(Error::UserNotFound);
+            }
+
+            // Held until the entry is added, so parallel requests can't pass the limit
+            self.audit
+                .lock(AuditLogEntryType::ImpersonateUser, admin_id)
+                .await?;
+
+            let now = Utc::now();
+            let count = self
+                .audit
+                .count(
+                    AuditLogEntryType::ImpersonateUser,
+                    admin_id,
+                    now - Duration::hours(1),
+                )
+                .await?;
+
+            if count >= IMPERSONATION_LIMIT_PER_HOUR {
+                warn!(
+                    "User ID {} has impersonated too many users recently",
+                    admin_id
+                );
+                return Err(Error::RateLimited);
+            }
+
+            let expires_at = now + Duration::minutes(IMPERSONATION_DURATION_MINUTES);
+            let session = self
+                .session
+                .create_impersonation_session(admin_id, target_id, expires_at)
+                .await?;
+
+            let data = json!({
+                "target_user_id": target_id,
+                "expires_at": expires_at,
+            });
+
+            self.audit
+                .add(
+                    AuditLogEntryType::ImpersonateUser,
+                    None,
+                    Some(admin_id),
+                    &data,
+                )
+                .await?;
+
+            Ok(session)
+        })
+        .await
+    }
+
+    /// Gets the session a client's token refers to.
+    /// Returns `InvalidSession` if the token is wrong or the session has expired.
+    #[inline]
+    pub async fn validate_session_token(&self, token: &str) -> Result<Session> {
+        self.session.validate_token(token).await
     }
 
-    /// Deactivate a session currently logged in.
+    /// Deactivate the session a client's token refers to.
     /// Returns `()` if successful, `InvalidSession` if no such session was found.
+    pub async fn end_session(&self, token: &str) -> Result<()> {
+        let (session_id, session) = self.session.resolve_token(token).await?;
+
+        self.session
+            .end_session(session_id, session.user_id())
+            .await
+    }
+
+    /// Logs out of the session a client's token refers to.
+    /// Unlike `end_session()`, this succeeds if the session was already removed.
+    pub async fn logout(&self, token: &str) -> Result<()> {
+        match self.session.resolve_token(token).await {
+            Ok((session_id, _)) => self.session.invalidate_session(session_id).await,
+            Err(Error::InvalidSession) => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Ends every session for a user, such as after a password change.
+    /// Returns the number of sessions removed.
     #[inline]
-    pub async fn end_session(&self, session_id: SessionId, user_id: UserId) -> Result<()> {
-        self.session.end_session(session_id, user_id).await
+    pub async fn end_all_sessions(&self, user_id: UserId) -> Result<usize> {
+        self.session.invalidate_all_sessions(user_id).await
     }
 
-    /// Deactivates all sessions except the one currently logged in.
+    /// Deactivates all of the user's sessions except the one the token refers to.
     /// Returns a list of the sessions which were deactivated.
-    pub async fn end_other_sessions(
-        &self,
-        session_id: SessionId,
-        user_id: UserId,
-    ) -> Result<Vec<Session>> {
-        self.session.end_other_sessions(session_id, user_id).await
+    pub async fn end_other_sessions(&self, token: &str) -> Result<Vec<Session>> {
+        let (session_id, session) = self.session.resolve_token(token).await?;
+
+        self.session
+            .end_other_sessions(session_id, session.user_id())
+            .await
+    }
+
+    /// Gets all unexpired sessions for the given user, oldest first.
+    #[inline]
+    pub async fn get_active_sessions(&self, user_id: UserId) -> Result<Vec<Session>> {
+        self.session.get_active_sessions(user_id).await
     }
 
-    /// Get all sessions for the given user.
-    /// Returns the current session (the one passed in the argument) first,
+    /// Get all sessions for the user the token belongs to.
+    /// Returns the current session (the one the token refers to) first,
     /// and the other sessions in the list.
-    pub async fn get_sessions(
+    pub async fn get_sessions(&self, token: &str) -> Result<(Session, Vec<Session>)> {
+        let (session_id, session) = self.session.resolve_token(token).await?;
+
+        self.session
+            .get_sessions(session_id, session.user_id())
+            .await
+    }
+
+    /// Reports whether failed logins for a user within the window came from
+    /// an unusually high number of networks. This is only a signal for alerting,
+    /// and does not affect whether the user can log in.
+    #[inline]
+    pub async fn get_suspicious_activity(
         &self,
-        session_id: SessionId,
         user_id: UserId,
-    ) -> Result<(Session, Vec<Session>)> {
-        self.session.get_sessions(session_id, user_id).await
+        window: Duration,
+    ) -> Result<SuspicionReport> {
+        self.session.suspicious_activity(user_id, window).await
     }
 
     /// Fetch login attempt associated with the passed ID.
@@ -176,25 +586,191 @@ impl Server {
         self.session.get_login_attempt(login_attempt_id).await
     }
 
-    /// Returns all login attempts for a user since the given date.
-    /// Limited to 100 entries.
+    /// Returns login attempts for a user since the given date, most recent first.
+    /// If `success` is given, only returns attempts which did or did not succeed.
+    /// At most 1000 entries are returned at once.
     #[inline]
     pub async fn get_login_attempts<Tz: TimeZone>(
         &self,
         user_id: UserId,
         since: DateTime<Tz>,
+        success: Option<bool>,
+        limit: u32,
+        offset: u32,
     ) -> Result<Vec<LoginAttempt>> {
-        self.session.get_login_attempts(user_id, since).await
+        self.session
+            .get_login_attempts(user_id, since, success, limit, offset)
+            .await
+    }
+
+    /// Returns the most recent login attempt for each of the given users.
+    /// The results are in the same order as the IDs, with `None` for any
+    /// users which don't exist or have never tried to log in.
+    ///
+    /// At most 100 users can be requested at once.
+    pub async fn get_latest_login_attempts(
+        &self,
+        ids: &[UserId],
+    ) -> Result<Vec<Option<LoginAttempt>>> {
+        if ids.len() > 100 {
+            return Err(Error::RequestTooLarge(ids.len(), 100));
+        }
+
+        self.session.get_latest_login_attempts(ids).await
     }
 
-    /// Returns all login attempts for all users since the given date.
-    /// Limited to 100 entries.
+    /// Returns login attempts from a remote address since the given date, most recent first.
+    /// If `None`, returns attempts where the address was not recorded.
+    /// At most 1000 entries are returned at once.
+    #[inline]
+    pub async fn get_login_attempts_by_address<Tz: TimeZone>(
+        &self,
+        remote_address: Option<IpAddr>,
+        since: DateTime<Tz>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<LoginAttempt>> {
+        self.session
+            .get_login_attempts_by_address(remote_address, since, limit, offset)
+            .await
+    }
+
+    /// Returns login attempts from any address in a network since the given date,
+    /// most recent first. At most 1000 entries are returned at once.
+    #[inline]
+    pub async fn get_login_attempts_in_network<Tz: TimeZone>(
+        &self,
+        network: IpNetwork,
+        since: DateTime<Tz>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<LoginAttempt>> {
+        self.session
+            .get_login_attempts_in_network(network, since, limit, offset)
+            .await
+    }
+
+    /// Returns the number of login attempts for a user since the given date.
+    #[inline]
+    pub async fn count_login_attempts<Tz: TimeZone>(
+        &self,
+        user_id: UserId,
+        since: DateTime<Tz>,
+    ) -> Result<i64> {
+        self.session.count_login_attempts(user_id, since).await
+    }
+
+    /// Returns login attempts for all users since the given date, most recent first.
+    /// At most 1000 entries are returned at once.
     #[inline]
     pub async fn get_all_login_attempts<Tz: TimeZone>(
         &self,
         since: DateTime<Tz>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<LoginAttempt>> {
+        self.session
+            .get_all_login_attempts(since, limit, offset)
+            .await
+    }
+
+    /// Returns login attempts for all users older than the cursor, most recent first.
+    /// If the cursor is `None`, starts with the most recent attempt.
+    ///
+    /// Pass the cursor of the last attempt returned to get the next page.
+    /// Fewer than `limit` entries means there are no more. At most 1000
+    /// entries are returned at once.
+    #[inline]
+    pub async fn get_login_attempts_after(
+        &self,
+        cursor: Option<LoginAttemptCursor>,
+        limit: u32,
     ) -> Result<Vec<LoginAttempt>> {
-        self.session.get_all_login_attempts(since).await
+        self.session.get_login_attempts_after(cursor, limit).await
+    }
+
+    /// Streams login attempts for all users older than the cursor, most recent first,
+    /// in chunks of at most `chunk_size`. This pages through `get_login_attempts_after()`,
+    /// so only one chunk is held in memory at a time.
+    ///
+    /// The stream ends after the oldest attempt, or after the first error.
+    /// Dropping it stops fetching further chunks.
+    pub fn stream_login_attempts(
+        &self,
+        cursor: Option<LoginAttemptCursor>,
+        chunk_size: u32,
+    ) -> impl Stream<Item = Result<Vec<LoginAttempt>>> + '_ {
+        // Matches the limit in get_login_attempts_after(),
+        // otherwise a full chunk could look like the last one.
+        let chunk_size = chunk_size.clamp(1, 1000);
+
+        stream::unfold(Some(cursor), move |state| async move {
+            let cursor = state?;
+            let result = self.get_login_attempts_after(cursor, chunk_size).await;
+
+            match result {
+                Ok(attempts) if attempts.is_empty() => None,
+                Ok(attempts) => {
+                    // A short chunk means there are no more to fetch
+                    let next = if attempts.len() < chunk_size as usize {
+                        None
+                    } else {
+                        attempts.last().map(|attempt| Some(attempt.cursor()))
+                    };
+
+                    Some((Ok(attempts), next))
+                }
+                Err(error) => Some((Err(error), None)),
+            }
+        })
+    }
+
+    /// Returns the number of successful and failed login attempts since the given date,
+    /// and how many different users they were for.
+    #[inline]
+    pub async fn get_login_stats<Tz: TimeZone>(&self, since: DateTime<Tz>) -> Result<LoginStats> {
+        self.session.login_stats(since).await
+    }
+
+    /// Deletes login attempts older than the given date, returning how many were removed.
+    /// Intended to be run periodically.
+    ///
+    /// Attempts which are still needed, such as by an active session
+    /// or a user's most recent successful login, are kept.
+    #[inline]
+    pub async fn purge_login_attempts(&self, older_than: DateTime<Utc>) -> Result<usize> {
+        self.session.purge_login_attempts(older_than).await
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub async fn set_login_attempt_time(
+        &self,
+        login_attempt_id: LoginAttemptId,
+        attempted_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.session
+            .set_login_attempt_time(login_attempt_id, attempted_at)
+            .await
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub async fn set_session_times(
+        &self,
+        token: &str,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.session
+            .set_session_times(token, created_at, expires_at)
+            .await
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub fn password_decoy_checks(&self) -> usize {
+        self.password.decoy_checks()
     }
 }
 
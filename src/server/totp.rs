@@ -0,0 +1,58 @@
+/*
+ * server/totp.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::manager_prelude::*;
+
+impl Server {
+    /// Starts two-factor enrollment for the user, returning the new secret.
+    ///
+    /// Logins don't require a code until one from this secret is
+    /// confirmed with `verify_totp()`.
+    pub async fn enroll_totp(&self, id: UserId) -> Result<TotpEnrollment> {
+        let user = self
+            .user
+            .get_from_id(id)
+            .await?
+            .ok_or(Error::UserNotFound)?;
+
+        self.totp.enroll(id, user.name()).await
+    }
+
+    /// Checks a code from the user's authenticator app.
+    /// Returns `AuthenticationFailed` if it is wrong or was already used.
+    ///
+    /// After enrolling, this confirms the secret and enables two-factor authentication.
+    #[inline]
+    pub async fn verify_totp(&self, id: UserId, code: &str) -> Result<()> {
+        self.totp.verify(id, code).await
+    }
+
+    /// Turns off two-factor authentication for the user.
+    #[inline]
+    pub async fn disable_totp(&self, id: UserId) -> Result<()> {
+        self.totp.disable(id).await
+    }
+
+    #[cfg(test)]
+    #[inline]
+    pub async fn generate_totp_code(&self, id: UserId, offset: i64) -> Result<String> {
+        self.totp.generate_code(id, offset).await
+    }
+}
@@ -19,12 +19,37 @@
  */
 
 use crate::manager_prelude::*;
+use crate::package::user::check_new_user;
+
+/// The most users which can be created in one batch.
+const MAX_BATCH_USERS: usize = 100;
+
+/// The information needed to create a user, for `create_users()`.
+#[derive(Copy, Clone)]
+pub struct NewUserSpec<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub password: &'a str,
+}
+
+impl Debug for NewUserSpec<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NewUserSpec")
+            .field("name", &self.name)
+            .field("email", &self.email)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
 
 impl Server {
     /// Creates a new user with the given name and email. Returns its ID.
-    #[inline]
+    ///
+    /// The name must not be blank, and the email must look like an address.
     pub async fn create_user(&self, name: &str, email: &str, password: &str) -> Result<UserId> {
-        self.transaction(async {
+        check_new_user(name, email)?;
+
+        self.retry_transaction(|| async move {
             let user_id = self.user.create(name, email).await?;
             self.password.set(user_id, password).await?;
 
@@ -33,41 +58,192 @@ impl Server {
         .await
     }
 
+    /// Finds users whose normalized name contains the query, ordered by name.
+    /// At most 100 users are returned at once.
+    ///
+    /// Users marked inactive are not returned.
+    pub async fn search_users(&self, query: &str, limit: u32) -> Result<Vec<UserView>> {
+        let users = self.user.search(query, limit, false).await?;
+
+        Ok(users.into_iter().map(UserView::from).collect())
+    }
+
+    /// Finds users whose normalized name contains the query, including inactive users.
+    pub async fn search_any_users(&self, query: &str, limit: u32) -> Result<Vec<UserView>> {
+        let users = self.user.search(query, limit, true).await?;
+
+        Ok(users.into_iter().map(UserView::from).collect())
+    }
+
+    /// Returns the number of users, optionally including ones marked inactive.
+    #[inline]
+    pub async fn count_users(&self, include_inactive: bool) -> Result<i64> {
+        self.user.count(include_inactive).await
+    }
+
+    /// Creates several users at once. Returns their IDs in the same order.
+    ///
+    /// All of the users are created in one transaction, so if any of them
+    /// fail (such as a duplicate name), none are created. The names and emails
+    /// are checked, and the passwords are hashed, before the transaction starts.
+    ///
+    /// Rejects any requests with more than 100 users.
+    pub async fn create_users(&self, specs: &[NewUserSpec<'_>]) -> Result<Vec<UserId>> {
+        if specs.len() > MAX_BATCH_USERS {
+            return Err(Error::RequestTooLarge(specs.len(), MAX_BATCH_USERS));
+        }
+
+        info!("Creating {} users in a batch", specs.len());
+
+        for spec in specs {
+            check_new_user(spec.name, spec.email)?;
+        }
+
+        let hashes = specs
+            .iter()
+            .map(|spec| self.password.hash(spec.password))
+            .collect::<Result<Vec<_>>>()?;
+
+        let hashes = &hashes;
+        self.retry_transaction(|| async move {
+            let mut user_ids = Vec::with_capacity(specs.len());
+
+            for (spec, hash) in specs.iter().zip(hashes) {
+                let user_id = self.user.create(spec.name, spec.email).await?;
+                self.password.set_hash(user_id, hash).await?;
+                user_ids.push(user_id);
+            }
+
+            Ok(user_ids)
+        })
+        .await
+    }
+
     /// Edits data attached to a user with the given ID.
+    /// Each changed field is recorded in the user's audit log, along with who changed it.
+    ///
+    /// Emails are changed with `request_email_change()` instead,
+    /// so this returns `InvalidArgument` if one is given.
+    #[inline]
+    pub async fn edit_user(
+        &self,
+        id: UserId,
+        changes: UserMetadata<'_>,
+        changed_by: UserId,
+    ) -> Result<()> {
+        self.user.edit(id, changes, changed_by).await
+    }
+
+    /// Gets all recorded changes to a user's metadata, oldest first.
     #[inline]
-    pub async fn edit_user(&self, id: UserId, changes: UserMetadata<'_>) -> Result<()> {
-        self.user.edit(id, changes).await
+    pub async fn get_user_audit_log(&self, id: UserId) -> Result<Vec<AuditEntry>> {
+        self.user.get_audit_log(id).await
+    }
+
+    /// Gathers everything stored about a user into one structure, such as for
+    /// a data access request. See `UserExport` for what it contains.
+    ///
+    /// All the reads see the same snapshot of the database,
+    /// so the parts are consistent with each other.
+    pub async fn export_user_data(&self, id: UserId) -> Result<UserExport> {
+        info!("Exporting data for user ID {}", id);
+
+        self.snapshot_transaction(async {
+            let user = self
+                .user
+                .get_from_id(id)
+                .await?
+                .ok_or(Error::UserNotFound)?;
+
+            let login_attempts = self.session.get_all_user_login_attempts(id).await?;
+            let sessions = self.session.get_active_sessions(id).await?;
+            let audit_log = self.user.get_audit_log(id).await?;
+
+            Ok(UserExport::new(
+                Utc::now(),
+                user,
+                login_attempts,
+                sessions,
+                audit_log,
+            ))
+        })
+        .await
     }
 
-    /// Get the model for a user from its ID.
+    /// Gets the public view of a user from its ID.
+    pub async fn get_user_from_id(&self, id: UserId) -> Result<Option<UserView>> {
+        let user = self.user.get_from_id(id).await?;
+
+        Ok(user.map(UserView::from))
+    }
+
+    /// Gets the full model for a user from its ID.
+    ///
+    /// This includes columns which aren't meant for clients, such as the email.
+    /// Use `get_user_from_id()` for anything which is sent to them.
     #[inline]
-    pub async fn get_user_from_id(&self, id: UserId) -> Result<Option<User>> {
+    pub async fn get_user_model_from_id(&self, id: UserId) -> Result<Option<User>> {
         self.user.get_from_id(id).await
     }
 
-    /// Gets the models for users from their IDs.
+    /// Gets the public views of users from their IDs.
     /// Results are returned in the same order as the IDs, and any missing
     /// users give `None` instead.
     ///
     /// Rejects any requests with more than 100 IDs.
-    pub async fn get_users_from_ids(&self, ids: &[UserId]) -> Result<Vec<Option<User>>> {
+    pub async fn get_users_from_ids(&self, ids: &[UserId]) -> Result<Vec<Option<UserView>>> {
         if ids.len() > 100 {
             return Err(Error::RequestTooLarge(ids.len(), 100));
         }
 
-        self.user.get_from_ids(ids).await
+        let users = self.user.get_from_ids(ids).await?;
+
+        Ok(users
+            .into_iter()
+            .map(|user| user.map(UserView::from))
+            .collect())
     }
 
     /// Gets the model for a user from its name.
+    /// Names are compared in their normalized form.
+    ///
+    /// Users marked inactive are not returned.
     #[inline]
     pub async fn get_user_from_name(&self, name: &str) -> Result<Option<User>> {
-        self.user.get_from_name(name).await
+        self.user.get_from_name(name, false).await
+    }
+
+    /// Gets the model for a user from its name, including inactive users.
+    #[inline]
+    pub async fn get_any_user_from_name(&self, name: &str) -> Result<Option<User>> {
+        self.user.get_from_name(name, true).await
+    }
+
+    /// Gets the model for a user from either its name or email, along with which one matched.
+    /// This is the same lookup used by `try_login()`.
+    ///
+    /// If a name and a different user's email both match, the name takes precedence.
+    /// Both are compared in their normalized forms.
+    ///
+    /// Users marked inactive are not returned.
+    pub async fn resolve_user(&self, credential: &str) -> Result<Option<(User, CredentialKind)>> {
+        let (user, kind) = self.user.resolve(credential, false).await?;
+
+        Ok(user.map(|user| (user, kind)))
     }
 
     /// Gets the model for a user from its email.
+    ///
+    /// Users marked inactive are not returned.
     #[inline]
     pub async fn get_user_from_email(&self, email: &str) -> Result<Option<User>> {
-        self.user.get_from_email(email).await
+        self.user.get_from_email(email, false).await
+    }
+
+    /// Gets the model for a user from its email, including inactive users.
+    #[inline]
+    pub async fn get_any_user_from_email(&self, email: &str) -> Result<Option<User>> {
+        self.user.get_from_email(email, true).await
     }
 
     /// Marks a user as verified.
@@ -89,15 +265,66 @@ impl Server {
         self.user.create_token(id).await
     }
 
+    /// Requests that the user's email be changed, returning a token to send to the new address.
+    /// The current email remains in effect until the change is confirmed.
+    pub async fn request_email_change(&self, id: UserId, new_email: &str) -> Result<String> {
+        let ttl = self.verification.email_change_ttl;
+
+        self.user.create_email_change(id, new_email, ttl).await
+    }
+
+    /// Applies a pending email change from its token.
+    /// Returns `InvalidVerificationToken` if it is expired or was already used.
+    #[inline]
+    pub async fn confirm_email_change(&self, token: &str) -> Result<()> {
+        self.user.confirm_email_change(token).await
+    }
+
     /// Marks the user as "inactive", effectively deleting them.
     #[inline]
     pub async fn mark_user_inactive(&self, id: UserId) -> Result<()> {
         self.user.mark_inactive(id, true).await
     }
 
+    /// Permanently deletes the user and their account data, such as for an erasure request.
+    /// Succeeds without doing anything if the user doesn't exist.
+    ///
+    /// Sessions, passwords, and two-factor secrets are removed, and the user is detached
+    /// from their login attempts and audit log entries. If they have contributed to the
+    /// wiki, such as by editing or rating pages, returns `UserReferenced` instead.
+    pub async fn delete_user(&self, id: UserId) -> Result<()> {
+        info!("Hard deleting user ID {}", id);
+
+        self.transaction(async {
+            let user = match self.user.get_from_id(id).await? {
+                Some(user) => user,
+                None => {
+                    debug!("User ID {} is already gone", id);
+                    return Ok(());
+                }
+            };
+
+            // Needs the name and email, so it runs before the user is removed
+            self.session.anonymize_user(&user).await?;
+            self.password.remove_all(id).await?;
+            self.totp.disable(id).await?;
+            self.audit.anonymize_user(id).await?;
+            self.user.delete(id).await?;
+
+            Ok(())
+        })
+        .await
+    }
+
     /// Marks the user as "active" again, effectively un-deleting them.
     #[inline]
     pub async fn mark_user_active(&self, id: UserId) -> Result<()> {
         self.user.mark_inactive(id, false).await
     }
+
+    #[cfg(test)]
+    #[inline]
+    pub async fn unverify_user(&self, id: UserId) -> Result<()> {
+        self.user.unverify(id).await
+    }
 }
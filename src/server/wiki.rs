@@ -32,9 +32,13 @@ impl Server {
         let domain = to_lowercase(domain);
 
         let (id, guard) = self.wiki.create(name, &slug, &domain).await?;
-        let wiki = guard
-            .get(&id)
-            .expect("Can't find wiki object after inserting");
+        let wiki = match guard.get(&id) {
+            Some(wiki) => wiki,
+            None => {
+                error!("Can't find wiki object after inserting (ID {})", id);
+                return Err(Error::WikiNotFound);
+            }
+        };
 
         self.page.add_store(&wiki).await?;
 
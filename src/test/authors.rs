@@ -28,14 +28,14 @@ async fn author() {
 
     let user_id_1 = create_user(server).await;
     let user_1 = server
-        .get_user_from_id(user_id_1)
+        .get_user_model_from_id(user_id_1)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
 
     let user_id_2 = create_user(server).await;
     let user_2 = server
-        .get_user_from_id(user_id_2)
+        .get_user_model_from_id(user_id_2)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
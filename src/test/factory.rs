@@ -20,6 +20,7 @@
 
 use crate::prelude::*;
 use crate::utils::rand_alphanum;
+use chrono::Duration;
 use std::env;
 use std::ops::Deref;
 use tempfile::TempDir;
@@ -39,26 +40,48 @@ impl Deref for ServerWrap {
     }
 }
 
+#[inline]
 pub async fn create_server() -> ServerWrap {
+    create_server_with(|_| ()).await
+}
+
+pub async fn create_server_with<F>(f: F) -> ServerWrap
+where
+    F: FnOnce(&mut Config),
+{
     color_backtrace::install();
 
     let database_url = &env::var("DATABASE_TEST_URL").expect("No DATABASE_TEST_URL specified!");
     let temp_dir = TempDir::new().expect("Unable to create temp dir");
     let revisions_dir = temp_dir.path().into();
 
-    let config = Config {
+    let mut config = Config {
         database_url,
+        database_pool_size: 2,
+        transaction_attempts: 3,
         revisions_dir,
-        password_blacklist: None,
+        password_policy: PasswordPolicy::default(),
+        password_cost: HashCost::default(),
+        password_reset_ttl: Duration::hours(1),
+        max_message_length: 200,
+        session_ttl: Duration::days(1),
+        session_expiry: SessionExpiry::default(),
+        lockout: LockoutPolicy::default(),
+        rate_limit: RateLimitPolicy::default(),
+        verification: VerificationPolicy::default(),
+        totp: TotpPolicy::new(TotpKey([0x42; 32])),
+        login_listener: None,
     };
 
+    f(&mut config);
+
     let server = Server::new(config).expect("Unable to create deepwell server");
 
     ServerWrap { server, temp_dir }
 }
 
 // User
-pub async fn create_user_full(server: &Server, password: &str) -> (UserId, String, String) {
+pub fn generate_username() -> (String, String) {
     let username = {
         let mut chars = rand_alphanum(16);
         chars.insert_str(0, "user_");
@@ -67,6 +90,12 @@ pub async fn create_user_full(server: &Server, password: &str) -> (UserId, Strin
 
     let email = format!("{}@example.com", username);
 
+    (username, email)
+}
+
+pub async fn create_user_full(server: &Server, password: &str) -> (UserId, String, String) {
+    let (username, email) = generate_username();
+
     println!("Creating test user '{}'", username);
     let id = server
         .create_user(&username, &email, password)
@@ -0,0 +1,75 @@
+/*
+ * test/health.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+#[tokio::test]
+async fn health() {
+    let server = &create_server().await;
+    let status = server.health().await;
+
+    assert!(status.healthy(), "Database unhealthy: {:?}", status.error);
+    assert!(status.error.is_none());
+}
+
+#[tokio::test]
+async fn health_pool_exhausted() {
+    let server = Arc::new(
+        create_server_with(|config| {
+            config.database_pool_size = 1;
+        })
+        .await,
+    );
+
+    // Hold the only connection in another thread
+    let (started_tx, started_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel();
+    let handle = {
+        let server = Arc::clone(&server);
+
+        thread::spawn(move || {
+            server.test_retry_transaction(|| {
+                started_tx.send(()).unwrap();
+                release_rx.recv().unwrap();
+                Ok(())
+            })
+        })
+    };
+
+    started_rx.recv().expect("Transaction thread didn't start");
+
+    let status = server.health().await;
+    assert!(
+        !status.healthy(),
+        "Database healthy with no free connections"
+    );
+    assert!(status.error.is_some());
+
+    release_tx.send(()).unwrap();
+    handle
+        .join()
+        .expect("Transaction thread panicked")
+        .expect("Transaction failed");
+
+    let status = server.health().await;
+    assert!(status.healthy(), "Database unhealthy: {:?}", status.error);
+}
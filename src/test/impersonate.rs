@@ -0,0 +1,198 @@
+/*
+ * test/impersonate.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use chrono::prelude::*;
+use chrono::Duration;
+
+#[tokio::test]
+async fn impersonation() {
+    let server = &create_server().await;
+    let (admin_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let target_id = create_user(server).await;
+    let other_id = create_user(server).await;
+    let start = Utc::now() - Duration::minutes(1);
+
+    let session = server
+        .try_login_id(admin_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let actor = server
+        .get_actor(session.token())
+        .await
+        .expect("Unable to get actor");
+
+    // Impersonate the target
+    let impersonation = server
+        .impersonate(&actor, target_id)
+        .await
+        .expect("Unable to impersonate user");
+
+    assert_eq!(impersonation.user_id(), target_id);
+    assert_eq!(impersonation.impersonator_id(), Some(admin_id));
+    assert_eq!(impersonation.login_attempt_id(), None);
+    assert!(impersonation.is_impersonation());
+    assert!(impersonation.expires_at().is_some());
+    assert!(!session.is_impersonation());
+
+    server
+        .check_session(impersonation.token())
+        .await
+        .expect("Impersonation session was invalid");
+
+    // Check the audit log entry
+    let entries = server
+        .get_audit_log_entries(admin_id, start)
+        .await
+        .expect("Unable to get audit log entries");
+
+    assert_eq!(entries.len(), 1);
+
+    let entry = &entries[0];
+    let data = entry.data();
+    assert_eq!(entry.entry_type(), "impersonate_user");
+    assert_eq!(entry.user_id(), Some(admin_id));
+    assert_eq!(entry.wiki_id(), None);
+    assert_eq!(data["target_user_id"], target_id.to_i64());
+
+    // Cannot escalate from an impersonation session
+    let impersonated_actor = server
+        .get_actor(impersonation.token())
+        .await
+        .expect("Unable to get impersonated actor");
+
+    assert_eq!(impersonated_actor.user_id(), target_id);
+
+    let error = server
+        .impersonate(&impersonated_actor, other_id)
+        .await
+        .expect_err("Allowed impersonation from an impersonated session");
+
+    match error {
+        Error::ImpersonationNotAllowed => (),
+        _ => panic!("Error wasn't impersonation not allowed"),
+    }
+
+    // Cannot impersonate yourself
+    let error = server
+        .impersonate(&actor, admin_id)
+        .await
+        .expect_err("Allowed self-impersonation");
+
+    match error {
+        Error::ImpersonationNotAllowed => (),
+        _ => panic!("Error wasn't impersonation not allowed"),
+    }
+
+    // Requires a valid session
+    let error = server
+        .get_actor("not-a-valid-token")
+        .await
+        .expect_err("Got actor with invalid session");
+
+    match error {
+        Error::InvalidSession => (),
+        _ => panic!("Error wasn't invalid session"),
+    }
+
+    // Rate limited after too many uses
+    for _ in 0..4 {
+        server
+            .impersonate(&actor, other_id)
+            .await
+            .expect("Unable to impersonate user");
+    }
+
+    let error = server
+        .impersonate(&actor, other_id)
+        .await
+        .expect_err("Impersonation wasn't rate limited");
+
+    match error {
+        Error::RateLimited => (),
+        _ => panic!("Error wasn't rate limited"),
+    }
+
+    // Only successful impersonations are logged
+    let entries = server
+        .get_audit_log_entries(admin_id, start)
+        .await
+        .expect("Unable to get audit log entries");
+
+    assert_eq!(entries.len(), 5);
+}
+
+#[tokio::test]
+async fn impersonation_concurrent() {
+    use async_std::task;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    const THREADS: usize = 8;
+
+    // Enough connections that all of them can run at once
+    let server = create_server_with(|config| {
+        config.database_pool_size = THREADS as u32;
+    })
+    .await;
+
+    let server = Arc::new(server);
+    let (admin_id, _, _) = create_user_full(&server, "blackmoonhowls").await;
+    let target_id = create_user(&server).await;
+
+    let session = server
+        .try_login_id(admin_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let actor = server
+        .get_actor(session.token())
+        .await
+        .expect("Unable to get actor");
+
+    // Parallel requests still can't go over the limit
+    let actor = Arc::new(actor);
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let actor = Arc::clone(&actor);
+            let barrier = Arc::clone(&barrier);
+
+            thread::spawn(move || {
+                barrier.wait();
+
+                task::block_on(server.impersonate(&actor, target_id))
+            })
+        })
+        .collect();
+
+    let mut succeeded = 0;
+    for handle in handles {
+        match handle.join().expect("Thread panicked") {
+            Ok(_) => succeeded += 1,
+            Err(Error::RateLimited) => (),
+            Err(error) => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    assert_eq!(succeeded, 5);
+}
@@ -30,13 +30,13 @@ async fn locks() {
     let other_user_id = create_user(server).await;
 
     let user = server
-        .get_user_from_id(user_id)
+        .get_user_model_from_id(user_id)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
 
     let other_user = server
-        .get_user_from_id(other_user_id)
+        .get_user_model_from_id(other_user_id)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
@@ -19,11 +19,19 @@
  */
 
 use super::prelude::*;
+use crate::utils::rand_alphanum;
 use chrono::prelude::*;
+use chrono::Duration;
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration as StdDuration;
 
-const IP_ADDRESS_1: Option<&str> = Some("alpha-beta.local");
-const IP_ADDRESS_2: Option<&str> = Some("1.1.1.1");
-const IP_ADDRESS_3: Option<&str> = None;
+const IP_ADDRESS_1: Option<IpAddr> = Some(IpAddr::V6(Ipv6Addr::LOCALHOST));
+const IP_ADDRESS_2: Option<IpAddr> = Some(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)));
+const IP_ADDRESS_3: Option<IpAddr> = None;
 
 macro_rules! check_err {
     ($error:expr) => {
@@ -49,27 +57,27 @@ async fn logins() {
 
     // Login
     let error = server
-        .try_login_id(user_id, "letmein", IP_ADDRESS_2)
+        .try_login_id(user_id, "letmein", IP_ADDRESS_2, None, None)
         .await
         .expect_err("Allowed invalid login");
 
     check_err!(error);
 
     let error = server
-        .try_login_id(user_id, "backmonhowl", IP_ADDRESS_1)
+        .try_login_id(user_id, "backmonhowl", IP_ADDRESS_1, None, None)
         .await
         .expect_err("Allowed invalid login");
 
     check_err!(error);
 
     server
-        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_3)
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_3, None, None)
         .await
         .expect("Unable to login");
 
     // Check all login attempts
     let mut attempts = server
-        .get_login_attempts(user_id, start_time())
+        .get_login_attempts(user_id, start_time(), None, 100, 0)
         .await
         .expect("Unable to get login attempts");
 
@@ -85,6 +93,10 @@ async fn logins() {
 
     assert_eq!(first.user_id(), Some(user_id));
     assert_eq!(first.username_or_email(), None);
+    assert_eq!(
+        first.credential_kind().expect("Invalid credential kind"),
+        CredentialKind::Id,
+    );
     assert_eq!(first.remote_address(), IP_ADDRESS_2);
     assert_eq!(first.success(), false);
 
@@ -97,4 +109,1135 @@ async fn logins() {
     assert_eq!(third.username_or_email(), None);
     assert_eq!(third.remote_address(), IP_ADDRESS_3);
     assert_eq!(third.success(), true);
+
+    // Paginate through login attempts
+    let count = server
+        .count_login_attempts(user_id, start_time())
+        .await
+        .expect("Unable to count login attempts");
+
+    assert_eq!(count, 3);
+
+    let page = server
+        .get_login_attempts(user_id, start_time(), None, 2, 1)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].login_attempt_id(), second.login_attempt_id());
+    assert_eq!(page[1].login_attempt_id(), first.login_attempt_id());
+
+    let page = server
+        .get_login_attempts(user_id, start_time(), None, 2, 3)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert!(page.is_empty());
+
+    // Filter by success
+    let failures = server
+        .get_login_attempts(user_id, start_time(), Some(false), 100, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(failures.len(), 2);
+    assert!(failures.iter().all(|attempt| !attempt.success()));
+
+    let successes = server
+        .get_login_attempts(user_id, start_time(), Some(true), 100, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(successes.len(), 1);
+    assert_eq!(successes[0].login_attempt_id(), third.login_attempt_id());
+}
+
+#[tokio::test]
+async fn logins_by_address() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // Documentation-only range, not used by other tests
+    let address_1 = Some(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7)));
+    let address_2 = Some(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 231)));
+
+    for remote_address in &[address_1, None, address_2, address_1] {
+        server
+            .try_login_id(user_id, "letmein", *remote_address, None, None)
+            .await
+            .expect_err("Allowed invalid login");
+    }
+
+    // Exact address
+    let attempts = server
+        .get_login_attempts_by_address(address_1, start_time(), 100, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 2);
+    for attempt in &attempts {
+        assert_eq!(attempt.remote_address(), address_1);
+        assert_eq!(attempt.user_id(), Some(user_id));
+    }
+
+    // No address matches unknown ones
+    let attempts = server
+        .get_login_attempts_by_address(None, start_time(), 100, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert!(!attempts.is_empty());
+    assert!(attempts
+        .iter()
+        .all(|attempt| attempt.remote_address().is_none()));
+
+    // Network range
+    let network: IpNetwork = "198.51.100.0/24".parse().unwrap();
+    let attempts = server
+        .get_login_attempts_in_network(network, start_time(), 100, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 3);
+    for attempt in &attempts {
+        let address = attempt.remote_address().expect("No remote address");
+        assert!(network.contains(address));
+        assert_eq!(attempt.remote_network().map(|net| net.prefix()), Some(32));
+    }
+
+    let network: IpNetwork = "198.51.100.0/25".parse().unwrap();
+    let attempts = server
+        .get_login_attempts_in_network(network, start_time(), 100, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 2);
+}
+
+#[tokio::test]
+async fn lockout() {
+    let server = &create_server_with(|config| {
+        config.lockout.max_failures = 3;
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    macro_rules! fail_login {
+        () => {{
+            let error = server
+                .try_login_id(user_id, "letmein", IP_ADDRESS_1, None, None)
+                .await
+                .expect_err("Allowed invalid login");
+
+            check_err!(error);
+        }};
+    }
+
+    // Successful logins reset the failure count
+    fail_login!();
+    fail_login!();
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, None, None)
+        .await
+        .expect("Unable to login");
+
+    fail_login!();
+    fail_login!();
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, None, None)
+        .await
+        .expect("Unable to login");
+
+    // Too many failures lock the account, even with the right password
+    fail_login!();
+    fail_login!();
+    fail_login!();
+
+    let error = server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, None, None)
+        .await
+        .expect_err("Allowed login to locked account");
+
+    match error {
+        Error::AccountLocked => (),
+        _ => panic!("Error wasn't account locked"),
+    }
+}
+
+#[tokio::test]
+async fn login_purge() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let old_time = {
+        let date = NaiveDate::from_ymd(1999, 6, 1).and_hms(12, 0, 0);
+        DateTime::<Utc>::from_utc(date, Utc)
+    };
+    let cutoff = {
+        let date = NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, 0);
+        DateTime::<Utc>::from_utc(date, Utc)
+    };
+
+    // Create attempts, ending with a successful login
+    let session_1 = server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, None, None)
+        .await
+        .expect("Unable to login");
+
+    server
+        .try_login_id(user_id, "letmein", IP_ADDRESS_1, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let session_2 = server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_2, None, None)
+        .await
+        .expect("Unable to login");
+
+    server
+        .try_login_id(user_id, "letmein", IP_ADDRESS_2, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    server
+        .logout(session_1.token())
+        .await
+        .expect("Unable to logout");
+
+    // Move them all into the past, keeping their order
+    let attempts = server
+        .get_login_attempts(user_id, start_time(), None, 10, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 4);
+
+    for (i, attempt) in attempts.iter().rev().enumerate() {
+        let attempted_at = old_time + Duration::minutes(i as i64);
+
+        server
+            .set_login_attempt_time(attempt.login_attempt_id(), attempted_at)
+            .await
+            .expect("Unable to change login attempt time");
+    }
+
+    // Only the most recent success remains
+    let purged = server
+        .purge_login_attempts(cutoff)
+        .await
+        .expect("Unable to purge login attempts");
+
+    assert_eq!(purged, 3);
+
+    let attempts = server
+        .get_login_attempts(user_id, old_time - Duration::days(1), None, 10, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 1);
+    assert_eq!(
+        Some(attempts[0].login_attempt_id()),
+        session_2.login_attempt_id(),
+    );
+
+    // Still kept once its session is gone
+    server
+        .logout(session_2.token())
+        .await
+        .expect("Unable to logout");
+
+    let purged = server
+        .purge_login_attempts(cutoff)
+        .await
+        .expect("Unable to purge login attempts");
+
+    assert_eq!(purged, 0);
+}
+
+#[tokio::test]
+async fn login_stats() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let since = Utc::now() - Duration::minutes(1);
+
+    // Other tests run concurrently, so only check lower bounds
+    let before = server
+        .get_login_stats(since)
+        .await
+        .expect("Unable to get login stats");
+
+    for password in &["letmein", "backmonhowl"] {
+        let error = server
+            .try_login_id(user_id, password, IP_ADDRESS_1, None, None)
+            .await
+            .expect_err("Allowed invalid login");
+
+        check_err!(error);
+    }
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, None, None)
+        .await
+        .expect("Unable to login");
+
+    let after = server
+        .get_login_stats(since)
+        .await
+        .expect("Unable to get login stats");
+
+    assert!(after.successful() > before.successful());
+    assert!(after.failed() >= before.failed() + 2);
+    assert!(after.distinct_users() >= 1);
+
+    // Nothing in the future
+    let stats = server
+        .get_login_stats(Utc::now() + Duration::days(1))
+        .await
+        .expect("Unable to get login stats");
+
+    assert_eq!(stats, LoginStats::new(0, 0, 0));
+}
+
+#[tokio::test]
+async fn login_suspicious_activity() {
+    let server = &create_server_with(|config| {
+        config.lockout.max_failure_networks = 2;
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let window = Duration::minutes(5);
+
+    macro_rules! fail_from {
+        ($address:expr) => {{
+            let address = $address.map(|address: &str| address.parse().unwrap());
+            let error = server
+                .try_login_id(user_id, "letmein", address, None, None)
+                .await
+                .expect_err("Allowed invalid login");
+
+            check_err!(error);
+        }};
+    }
+
+    // Addresses in the same block count as one network,
+    // and unknown addresses aren't counted at all
+    fail_from!(Some("203.0.113.5"));
+    fail_from!(Some("203.0.113.71"));
+    fail_from!(Some("2001:db8::1"));
+    fail_from!(Some("2001:db8::ffff"));
+    fail_from!(None);
+
+    let report = server
+        .get_suspicious_activity(user_id, window)
+        .await
+        .expect("Unable to check suspicious activity");
+
+    assert_eq!(report, SuspicionReport::new(5, 2, false));
+
+    // Successful logins aren't counted, nor do they block anything
+    server
+        .try_login_id(
+            user_id,
+            "blackmoonhowls",
+            Some("203.0.113.9".parse().unwrap()),
+            None,
+            None,
+        )
+        .await
+        .expect("Unable to login");
+
+    fail_from!(Some("192.0.2.200"));
+
+    let report = server
+        .get_suspicious_activity(user_id, window)
+        .await
+        .expect("Unable to check suspicious activity");
+
+    assert_eq!(report, SuspicionReport::new(6, 3, true));
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_2, None, None)
+        .await
+        .expect("Suspicious activity blocked login");
+}
+
+#[tokio::test]
+async fn login_rate_limit() {
+    let server = &create_server_with(|config| {
+        config.rate_limit.max_attempts = 3;
+    })
+    .await;
+
+    let (user_id_1, username_1, _) = create_user_full(server, "blackmoonhowls").await;
+    let (user_id_2, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let address: Option<IpAddr> = Some("192.0.2.77".parse().unwrap());
+
+    macro_rules! rate_limited {
+        ($result:expr) => {
+            match $result {
+                Err(Error::RateLimited) => (),
+                Err(error) => panic!("Unexpected error: {}", error),
+                Ok(_) => panic!("Rate limited login was allowed"),
+            }
+        };
+    }
+
+    // Attempts for any account count towards the limit
+    for &user_id in &[user_id_1, user_id_2, user_id_1] {
+        let error = server
+            .try_login_id(user_id, "letmein", address, None, None)
+            .await
+            .expect_err("Allowed invalid login");
+
+        check_err!(error);
+    }
+
+    rate_limited!(
+        server
+            .try_login_id(user_id_2, "blackmoonhowls", address, None, None)
+            .await
+    );
+    rate_limited!(
+        server
+            .try_login(&username_1, "blackmoonhowls", address, None, None)
+            .await
+    );
+
+    // Other addresses and unknown ones are unaffected
+    server
+        .try_login_id(
+            user_id_1,
+            "blackmoonhowls",
+            Some("192.0.2.78".parse().unwrap()),
+            None,
+            None,
+        )
+        .await
+        .expect("Unable to login from other address");
+
+    for _ in 0..4 {
+        server
+            .try_login_id(user_id_1, "blackmoonhowls", None, None, None)
+            .await
+            .expect("Unable to login without address");
+    }
+
+    // IPv6 addresses in the same /64 share a limit
+    for suffix in 1..=3 {
+        let address = format!("2001:db8:5::{}", suffix).parse().unwrap();
+
+        server
+            .try_login_id(user_id_2, "blackmoonhowls", Some(address), None, None)
+            .await
+            .expect("Unable to login");
+    }
+
+    let address = "2001:db8:5::ffff".parse().unwrap();
+    rate_limited!(
+        server
+            .try_login_id(user_id_2, "blackmoonhowls", Some(address), None, None)
+            .await
+    );
+
+    let address = "2001:db8:6::1".parse().unwrap();
+    server
+        .try_login_id(user_id_2, "blackmoonhowls", Some(address), None, None)
+        .await
+        .expect("Unable to login from other network");
+}
+
+#[derive(Debug)]
+struct ChannelListener {
+    sender: Mutex<Sender<LoginEvent>>,
+    release: Option<Mutex<Receiver<()>>>,
+}
+
+impl LoginEventListener for ChannelListener {
+    fn on_login_attempt(&self, event: LoginEvent) {
+        // Blocks until the test drops its end of the channel
+        if let Some(ref release) = self.release {
+            let _ = release.lock().unwrap().recv();
+        }
+
+        let _ = self.sender.lock().unwrap().send(event);
+    }
+}
+
+fn listener() -> (Arc<dyn LoginEventListener>, Receiver<LoginEvent>) {
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+
+    (
+        Arc::new(ChannelListener {
+            sender,
+            release: None,
+        }),
+        receiver,
+    )
+}
+
+/// Like `listener()`, but it blocks on each event until the returned sender is dropped.
+fn blocked_listener() -> (
+    Arc<dyn LoginEventListener>,
+    Receiver<LoginEvent>,
+    Sender<()>,
+) {
+    let (sender, receiver) = mpsc::channel();
+    let (release_sender, release) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let release = Some(Mutex::new(release));
+
+    (
+        Arc::new(ChannelListener { sender, release }),
+        receiver,
+        release_sender,
+    )
+}
+
+#[tokio::test]
+async fn login_events() {
+    use async_std::task;
+
+    let (listener, receiver) = listener();
+    let server = &create_server_with(|config| config.login_listener = Some(listener)).await;
+    let (user_id, username, _) = create_user_full(server, "blackmoonhowls").await;
+
+    macro_rules! next_event {
+        () => {
+            receiver
+                .recv_timeout(StdDuration::from_secs(5))
+                .expect("No login event received")
+        };
+    }
+
+    // Failed login
+    server
+        .try_login_id(user_id, "letmein", IP_ADDRESS_2, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let event = next_event!();
+    assert_eq!(event.user_id, Some(user_id));
+    assert_eq!(event.remote_address, IP_ADDRESS_2);
+    assert!(!event.success);
+
+    // Successful login
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, None, None)
+        .await
+        .expect("Unable to login");
+
+    let recorded = next_event!();
+    let succeeded = next_event!();
+    let login_attempt_id = session.session().login_attempt_id();
+
+    assert_eq!(Some(recorded.login_attempt_id), login_attempt_id);
+    assert!(!recorded.success);
+    assert_eq!(Some(succeeded.login_attempt_id), login_attempt_id);
+    assert_eq!(succeeded.user_id, Some(user_id));
+    assert_eq!(succeeded.remote_address, IP_ADDRESS_1);
+    assert_eq!(succeeded.attempted_at, recorded.attempted_at);
+    assert!(succeeded.success);
+
+    // Unknown user
+    server
+        .try_login("nonexistent-user-for-events", "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let event = next_event!();
+    assert_eq!(event.user_id, None);
+    assert_eq!(
+        event.username_or_email,
+        Some(String::from("nonexistent-user-for-events")),
+    );
+    assert_eq!(event.credential_kind, CredentialKind::Username);
+    assert!(!event.success);
+
+    // Known user, by name
+    server
+        .try_login(&username, "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let event = next_event!();
+    assert_eq!(event.user_id, Some(user_id));
+
+    // Attempts which are rolled back aren't reported
+    server.test_transaction(|| {
+        let result = task::block_on(server.try_login_id(user_id, "letmein", None, None, None));
+        assert!(result.is_err(), "Allowed invalid login");
+        Ok(())
+    });
+
+    server
+        .try_login_id(user_id, "letmein", IP_ADDRESS_2, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let event = next_event!();
+    assert_eq!(event.remote_address, IP_ADDRESS_2);
+    assert!(receiver.try_recv().is_err(), "Extra login event received");
+}
+
+#[tokio::test]
+async fn login_events_slow_listener() {
+    use async_std::task;
+
+    let (listener, receiver, release) = blocked_listener();
+    let server = create_server_with(|config| config.login_listener = Some(listener)).await;
+    let server = Arc::new(server);
+    let (user_id, _, _) = create_user_full(&server, "blackmoonhowls").await;
+
+    // Logged in on another thread, so a login stuck on the listener fails
+    // the test instead of hanging it
+    let (done_sender, done) = mpsc::channel();
+    let handle = {
+        let server = Arc::clone(&server);
+
+        thread::spawn(move || {
+            for _ in 0..3 {
+                task::block_on(server.try_login_id(user_id, "blackmoonhowls", None, None, None))
+                    .expect("Unable to login");
+            }
+
+            let _ = done_sender.send(());
+        })
+    };
+
+    done.recv_timeout(StdDuration::from_secs(60))
+        .expect("Logins waited for the listener");
+
+    handle.join().expect("Thread panicked");
+    assert!(receiver.try_recv().is_err(), "Listener wasn't blocked");
+
+    // Each login is reported twice, once released
+    drop(release);
+    for _ in 0..6 {
+        receiver
+            .recv_timeout(StdDuration::from_secs(10))
+            .expect("No login event received");
+    }
+}
+
+#[tokio::test]
+async fn login_attempts_cursor() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // Other tests add attempts concurrently, so these are moved
+    // to a time nothing else uses, all sharing one timestamp.
+    let attempted_at = {
+        let date = NaiveDate::from_ymd(2000, 6, 15).and_hms_micro(12, 34, 56, 789_012);
+        DateTime::<Utc>::from_utc(date, Utc)
+    };
+
+    for _ in 0..5 {
+        server
+            .try_login_id(user_id, "letmein", None, None, None)
+            .await
+            .expect_err("Allowed invalid login");
+    }
+
+    let attempts = server
+        .get_login_attempts(user_id, start_time(), None, 10, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 5);
+
+    let mut expected = Vec::new();
+    for attempt in &attempts {
+        server
+            .set_login_attempt_time(attempt.login_attempt_id(), attempted_at)
+            .await
+            .expect("Unable to change login attempt time");
+
+        expected.push(attempt.login_attempt_id());
+    }
+
+    expected.sort();
+    expected.reverse();
+
+    // Start just after them and page through two at a time
+    let start = LoginAttemptCursor::new(
+        attempted_at + Duration::microseconds(1),
+        LoginAttemptId::from_raw(0),
+    );
+
+    let mut cursor = Some(start);
+    let mut found = Vec::new();
+    loop {
+        let page = server
+            .get_login_attempts_after(cursor, 2)
+            .await
+            .expect("Unable to get login attempts");
+
+        assert!(page.len() <= 2, "Too many attempts returned");
+
+        let mut done = page.len() < 2;
+        for attempt in &page {
+            if attempt.attempted_at() == attempted_at {
+                found.push(attempt.login_attempt_id());
+            } else {
+                assert!(
+                    attempt.attempted_at() < attempted_at,
+                    "Attempt out of order"
+                );
+                done = true;
+            }
+        }
+
+        if done {
+            break;
+        }
+
+        // Round trip through the client's form
+        let encoded = page.last().unwrap().cursor().to_string();
+        cursor = Some(LoginAttemptCursor::try_from(encoded.as_str()).expect("Invalid cursor"));
+    }
+
+    assert_eq!(
+        found, expected,
+        "Attempts skipped, repeated, or out of order"
+    );
+
+    // Nothing is older than the epoch
+    let epoch = LoginAttemptCursor::new(Utc.timestamp(0, 0), LoginAttemptId::from_raw(0));
+    let attempts = server
+        .get_login_attempts_after(Some(epoch), 10)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert!(attempts.is_empty());
+}
+
+#[tokio::test]
+async fn login_decoy() {
+    let server = &create_server().await;
+    let (_, username, email) = create_user_full(server, "blackmoonhowls").await;
+
+    macro_rules! login {
+        ($name:expr, $password:expr) => {{
+            let error = server
+                .try_login($name, $password, None, None, None)
+                .await
+                .expect_err("Invalid login succeeded");
+
+            check_err!(error);
+            server.password_decoy_checks()
+        }};
+    }
+
+    // Real users are checked against their own password
+    assert_eq!(server.password_decoy_checks(), 0);
+    assert_eq!(login!(&username, "letmein"), 0);
+    assert_eq!(login!(&email, "letmein"), 0);
+
+    // Missing users are checked against the decoy, failing the same way
+    assert_eq!(login!("nonexistent-user-for-decoy", "letmein"), 1);
+    assert_eq!(login!("nonexistent-user@example.com", "blackmoonhowls"), 2);
+
+    // Empty passwords fail early either way
+    assert_eq!(login!(&username, ""), 2);
+    assert_eq!(login!("nonexistent-user-for-decoy", ""), 2);
+}
+
+#[tokio::test]
+async fn login_credential_kind() {
+    let server = &create_server().await;
+    let (user_id, username, email) = create_user_full(server, "blackmoonhowls").await;
+    let address = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 160));
+
+    macro_rules! login {
+        ($name:expr) => {
+            server
+                .try_login($name, "letmein", Some(address), None, None)
+                .await
+                .expect_err("Allowed invalid login")
+        };
+    }
+
+    login!(&username);
+    login!(&email.to_uppercase());
+    login!("nonexistent-user-for-kinds");
+    login!("nonexistent-user-for-kinds@example.com");
+
+    server
+        .try_login_id(user_id, "letmein", Some(address), None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let mut attempts = server
+        .get_login_attempts_by_address(Some(address), Utc::now() - Duration::hours(1), 10, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    attempts.reverse();
+
+    let kinds: Vec<_> = attempts
+        .iter()
+        .map(|attempt| {
+            (
+                attempt.user_id(),
+                attempt.username_or_email().map(String::from),
+                attempt.credential_kind().expect("Invalid credential kind"),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        kinds,
+        [
+            (Some(user_id), Some(username), CredentialKind::Username),
+            (
+                Some(user_id),
+                Some(email.to_uppercase()),
+                CredentialKind::Email
+            ),
+            (
+                None,
+                Some(String::from("nonexistent-user-for-kinds")),
+                CredentialKind::Username,
+            ),
+            (
+                None,
+                Some(String::from("nonexistent-user-for-kinds@example.com")),
+                CredentialKind::Email,
+            ),
+            (Some(user_id), None, CredentialKind::Id),
+        ],
+    );
+}
+
+#[tokio::test]
+async fn login_latest_attempts() {
+    let server = &create_server().await;
+    let (user_id_1, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let (user_id_2, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let (user_id_3, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let missing_id = UserId::from_raw(i64::MAX);
+
+    macro_rules! latest {
+        ($ids:expr) => {
+            server
+                .get_latest_login_attempts($ids)
+                .await
+                .expect("Unable to get latest login attempts")
+                .into_iter()
+                .map(|attempt| attempt.map(|attempt| (attempt.user_id(), attempt.success())))
+                .collect::<Vec<_>>()
+        };
+    }
+
+    assert_eq!(latest!(&[]), []);
+    assert_eq!(latest!(&[user_id_1, missing_id]), [None, None]);
+
+    server
+        .try_login_id(user_id_1, "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    server
+        .try_login_id(user_id_1, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    server
+        .try_login_id(user_id_3, "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    // Most recent attempt, in the order requested
+    assert_eq!(
+        latest!(&[user_id_3, missing_id, user_id_1, user_id_2]),
+        [
+            Some((Some(user_id_3), false)),
+            None,
+            Some((Some(user_id_1), true)),
+            None,
+        ],
+    );
+
+    // Like get_users_from_ids(), each attempt is only returned once
+    assert_eq!(
+        latest!(&[user_id_1, user_id_1]),
+        [Some((Some(user_id_1), true)), None],
+    );
+
+    let ids = vec![user_id_1; 101];
+    match server.get_latest_login_attempts(&ids).await {
+        Err(Error::RequestTooLarge(101, 100)) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed oversized request"),
+    }
+}
+
+#[tokio::test]
+async fn login_idempotency_key() {
+    use async_std::task;
+    use std::sync::Barrier;
+
+    let server = Arc::new(create_server().await);
+    let (user_id, username, _) = create_user_full(&server, "blackmoonhowls").await;
+    let key = format!("retry-{}", rand_alphanum(16));
+
+    macro_rules! count {
+        () => {
+            server
+                .get_login_attempts(user_id, start_time(), None, 100, 0)
+                .await
+                .expect("Unable to get login attempts")
+                .len()
+        };
+    }
+
+    // Without a key, each attempt is recorded
+    for _ in 0..2 {
+        server
+            .try_login_id(user_id, "letmein", None, None, None)
+            .await
+            .expect_err("Allowed invalid login");
+    }
+
+    assert_eq!(count!(), 2);
+
+    // Retries with the same key get the original attempt
+    for _ in 0..2 {
+        server
+            .try_login(&username, "letmein", None, None, Some(&key))
+            .await
+            .expect_err("Allowed invalid login");
+    }
+
+    assert_eq!(count!(), 3);
+
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, Some(&key))
+        .await
+        .expect("Unable to login");
+
+    assert_eq!(count!(), 3);
+
+    let attempt_id = session.login_attempt_id().expect("No login attempt");
+    let attempt = server
+        .get_login_attempt(attempt_id)
+        .await
+        .expect("Unable to get login attempt");
+
+    assert_eq!(attempt.idempotency_key(), Some(key.as_str()));
+    assert!(attempt.success());
+
+    // Concurrent retries only insert once
+    const THREADS: usize = 4;
+    let key = Arc::new(format!("retry-{}", rand_alphanum(16)));
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let key = Arc::clone(&key);
+            let barrier = Arc::clone(&barrier);
+
+            thread::spawn(move || {
+                barrier.wait();
+
+                task::block_on(server.try_login_id(
+                    user_id,
+                    "blackmoonhowls",
+                    None,
+                    None,
+                    Some(&key),
+                ))
+                .expect("Unable to login")
+                .login_attempt_id()
+            })
+        })
+        .collect();
+
+    let ids: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("Thread panicked"))
+        .collect();
+
+    assert!(ids.iter().all(|&id| id == ids[0]), "Attempt IDs differ");
+    assert_eq!(count!(), 4);
+
+    // Keys are limited in length
+    let long_key = "a".repeat(129);
+    for key in &[long_key.as_str(), ""] {
+        match server
+            .try_login_id(user_id, "blackmoonhowls", None, None, Some(key))
+            .await
+        {
+            Err(Error::InvalidArgument(_)) => (),
+            Err(error) => panic!("Unexpected error: {}", error),
+            Ok(_) => panic!("Allowed invalid idempotency key"),
+        }
+    }
+
+    assert_eq!(count!(), 4);
+}
+
+#[tokio::test]
+async fn login_attempts_stream() {
+    use futures::stream::StreamExt;
+
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // Moved to their own time, like in login_attempts_cursor
+    let attempted_at = {
+        let date = NaiveDate::from_ymd(2000, 7, 15).and_hms_micro(12, 34, 56, 789_012);
+        DateTime::<Utc>::from_utc(date, Utc)
+    };
+
+    let mut expected = Vec::new();
+    for _ in 0..5 {
+        server
+            .try_login_id(user_id, "letmein", None, None, None)
+            .await
+            .expect_err("Allowed invalid login");
+    }
+
+    let attempts = server
+        .get_login_attempts(user_id, start_time(), None, 10, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    for attempt in &attempts {
+        server
+            .set_login_attempt_time(attempt.login_attempt_id(), attempted_at)
+            .await
+            .expect("Unable to change login attempt time");
+
+        expected.push(attempt.login_attempt_id());
+    }
+
+    expected.sort();
+    expected.reverse();
+
+    let start = LoginAttemptCursor::new(
+        attempted_at + Duration::microseconds(1),
+        LoginAttemptId::from_raw(0),
+    );
+
+    // Stop reading once past them, older attempts are left unfetched
+    let mut stream = Box::pin(server.stream_login_attempts(Some(start), 2));
+    let mut found = Vec::new();
+    let mut chunks = 0;
+
+    'outer: while let Some(chunk) = stream.next().await {
+        let chunk = chunk.expect("Unable to get login attempts");
+        assert!(!chunk.is_empty(), "Empty chunk streamed");
+        assert!(chunk.len() <= 2, "Chunk too large");
+        chunks += 1;
+
+        for attempt in chunk {
+            if attempt.attempted_at() != attempted_at {
+                break 'outer;
+            }
+
+            found.push(attempt.login_attempt_id());
+        }
+    }
+
+    drop(stream);
+
+    assert_eq!(
+        found, expected,
+        "Attempts skipped, repeated, or out of order"
+    );
+    assert!(chunks >= 3, "Attempts not split into chunks");
+
+    // Nothing is older than the epoch
+    let epoch = LoginAttemptCursor::new(Utc.timestamp(0, 0), LoginAttemptId::from_raw(0));
+    let chunks: Vec<_> = server.stream_login_attempts(Some(epoch), 2).collect().await;
+    assert!(chunks.is_empty());
+}
+
+#[tokio::test]
+async fn login_user_agent() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let user_agent = "Mozilla/5.0 (X11; Linux x86_64; rv:74.0) Gecko/20100101 Firefox/74.0";
+
+    macro_rules! login {
+        ($user_agent:expr) => {
+            server
+                .try_login_id(user_id, "blackmoonhowls", IP_ADDRESS_1, $user_agent, None)
+                .await
+                .expect("Unable to login")
+        };
+    }
+
+    macro_rules! attempt {
+        ($session:expr) => {{
+            let login_attempt_id = $session
+                .session()
+                .login_attempt_id()
+                .expect("No login attempt for session");
+
+            server
+                .get_login_attempt(login_attempt_id)
+                .await
+                .expect("Unable to get login attempt")
+        }};
+    }
+
+    // Stored on both the attempt and the session
+    let session = login!(Some(user_agent));
+    assert_eq!(session.session().user_agent(), Some(user_agent));
+    assert_eq!(attempt!(session).user_agent(), Some(user_agent));
+
+    let sessions = server
+        .get_active_sessions(user_id)
+        .await
+        .expect("Unable to get active sessions");
+
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].user_agent(), Some(user_agent));
+
+    // Failed attempts keep it too
+    server
+        .try_login(
+            "blackmoonhowls",
+            "letmein",
+            IP_ADDRESS_1,
+            Some(user_agent),
+            None,
+        )
+        .await
+        .expect_err("Allowed invalid login");
+
+    let attempts = server
+        .get_login_attempts(user_id, start_time(), None, 10, 0)
+        .await
+        .expect("Unable to get login attempts");
+
+    assert!(attempts
+        .iter()
+        .all(|attempt| attempt.user_agent() == Some(user_agent)));
+
+    // Long values are truncated, without splitting a character
+    let long_user_agent = "é".repeat(300);
+    let session = login!(Some(&long_user_agent));
+    let stored = attempt!(session)
+        .user_agent()
+        .map(String::from)
+        .expect("User agent not stored");
+
+    assert_eq!(stored.len(), 512);
+    assert!(long_user_agent.starts_with(&stored));
+    assert_eq!(session.session().user_agent(), Some(stored.as_str()));
+
+    // Missing or blank values stay absent
+    for &user_agent in &[None, Some(""), Some("   ")] {
+        let session = login!(user_agent);
+        assert_eq!(session.session().user_agent(), None);
+        assert_eq!(attempt!(session).user_agent(), None);
+    }
 }
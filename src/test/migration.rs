@@ -0,0 +1,93 @@
+/*
+ * test/migration.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::migration::{self, MIGRATIONS};
+use diesel::prelude::*;
+use std::{env, fs};
+
+#[test]
+fn migrations_embedded() {
+    let mut names = fs::read_dir("migrations")
+        .expect("Unable to read migrations directory")
+        .map(|entry| entry.expect("Unable to read directory entry"))
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().into_string().expect("Name isn't UTF-8"))
+        .collect::<Vec<_>>();
+
+    names.sort();
+
+    let embedded = MIGRATIONS
+        .iter()
+        .map(|migration| migration.name())
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, embedded, "Embedded migrations don't match directory");
+}
+
+#[tokio::test]
+async fn migrations_applied() {
+    let server = &create_server().await;
+
+    let statuses = server
+        .migration_status()
+        .await
+        .expect("Unable to get migration status");
+
+    assert_eq!(statuses.len(), MIGRATIONS.len());
+
+    for status in &statuses {
+        assert!(status.applied(), "Migration {} not applied", status.name());
+    }
+
+    let applied = server
+        .run_pending_migrations()
+        .await
+        .expect("Unable to run pending migrations");
+
+    assert!(applied.is_empty(), "Migrations were run twice");
+}
+
+#[test]
+fn migrations_schema_newer() {
+    let database_url = env::var("DATABASE_TEST_URL").expect("No DATABASE_TEST_URL specified!");
+    let conn = PgConnection::establish(&database_url).expect("Unable to connect to database");
+
+    conn.test_transaction::<_, Error, _>(|| {
+        diesel::sql_query(
+            "INSERT INTO __diesel_schema_migrations (version) VALUES ('99990101000000')",
+        )
+        .execute(&conn)?;
+
+        match migration::status(&conn) {
+            Err(Error::DatabaseSchemaNewer(version)) => assert_eq!(version, "99990101000000"),
+            Err(error) => panic!("Unexpected error: {}", error),
+            Ok(_) => panic!("Unknown migration was accepted"),
+        }
+
+        match migration::run_pending(&conn) {
+            Err(Error::DatabaseSchemaNewer(_)) => (),
+            Err(error) => panic!("Unexpected error: {}", error),
+            Ok(_) => panic!("Ran migrations on newer schema"),
+        }
+
+        Ok(())
+    });
+}
@@ -24,12 +24,18 @@ extern crate tokio;
 
 mod authors;
 mod factory;
+mod health;
+mod impersonate;
 mod lock;
 mod login;
+mod migration;
 mod page;
 mod password;
+mod pool;
+mod self_test;
 mod session;
 mod tags;
+mod totp;
 mod user;
 mod verify;
 mod wiki;
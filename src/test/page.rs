@@ -161,3 +161,236 @@ async fn pages() {
     let objects = server.revision_vacuum(wiki_id).await.unwrap();
     assert_eq!(objects, 0, "Pruned objects found");
 }
+
+#[tokio::test]
+async fn page_messages() {
+    let server = &create_server().await;
+
+    // Setup
+    let user = server
+        .get_user_from_name("unknown")
+        .await
+        .expect("Unable to get user")
+        .expect("Default user not found");
+
+    let wiki_id = create_wiki(server).await;
+
+    macro_rules! last_message {
+        () => {{
+            let changes = server
+                .get_recent_changes(wiki_id, 1, 0)
+                .await
+                .expect("Unable to get recent changes");
+
+            changes[0].message().to_string()
+        }};
+    }
+
+    // Control characters are stripped
+    let commit = PageCommit {
+        wiki_id,
+        slug: "scp-xxxx",
+        message: "new\x1b[2J page\x07\r\nsecond line\0",
+        user: &user,
+    };
+
+    server
+        .create_page(commit, "contents", &[], "SCP-XXXX", "")
+        .await
+        .expect("Unable to create page");
+
+    let message = last_message!();
+    assert!(message.ends_with("\n\nnew[2J page\nsecond line"));
+
+    // Long messages are truncated
+    let long_message = "a".repeat(300);
+    let commit = PageCommit {
+        wiki_id,
+        slug: "scp-xxxx",
+        message: &long_message,
+        user: &user,
+    };
+
+    server
+        .edit_page(commit, Some("new contents"), None, None)
+        .await
+        .expect("Unable to edit page");
+
+    let message = last_message!();
+    assert!(message.ends_with(&long_message[..200]));
+    assert!(!message.ends_with(&long_message[..201]));
+
+    // Extremely long messages are rejected
+    let long_message = "a".repeat(10000);
+    let commit = PageCommit {
+        wiki_id,
+        slug: "scp-xxxx",
+        message: &long_message,
+        user: &user,
+    };
+
+    let result = server
+        .edit_page(commit, Some("newer contents"), None, None)
+        .await;
+
+    match result {
+        Err(Error::MessageTooLong) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Over-long commit message was accepted"),
+    }
+
+    let contents = server
+        .get_page_contents(wiki_id, "scp-xxxx")
+        .await
+        .expect("Unable to get page contents");
+
+    assert_eq!(contents.as_deref(), Some("new contents"));
+}
+
+#[tokio::test]
+async fn page_revision() {
+    let server = &create_server().await;
+
+    // Setup
+    let user = server
+        .get_user_from_name("unknown")
+        .await
+        .expect("Unable to get user")
+        .expect("Default user not found");
+
+    let wiki_id = create_wiki(server).await;
+
+    let commit = PageCommit {
+        wiki_id,
+        slug: "scp-xxxx",
+        message: "",
+        user: &user,
+    };
+
+    let (_, first) = server
+        .create_page(commit, "contents", &[], "SCP-XXXX", "")
+        .await
+        .expect("Unable to create page");
+
+    let commit = PageCommit {
+        wiki_id,
+        slug: "scp-xxxx",
+        message: "fixed typo",
+        user: &user,
+    };
+
+    let second = server
+        .edit_page(commit, Some("content"), None, None)
+        .await
+        .expect("Unable to edit page");
+
+    macro_rules! revision {
+        ($revision_id:expr) => {
+            server
+                .get_page_revision(wiki_id, "scp-xxxx", Left($revision_id))
+                .await
+                .expect("Unable to get revision")
+        };
+    }
+
+    // Empty summaries leave only the generated line
+    let revision = revision!(first);
+    assert!(!revision.message().contains('\n'));
+    assert!(revision.message().contains(" created page ID "));
+    assert_eq!(revision.user_id(), Some(user.id()));
+    assert_eq!(revision.username(), user.name());
+
+    let revision = revision!(second);
+    assert!(revision.message().ends_with("\n\nfixed typo"));
+    assert_eq!(revision.slugs(), &["scp-xxxx"]);
+
+    // Same as listed in the history
+    let revisions = server
+        .get_page_revisions(wiki_id, "scp-xxxx", 10, 0)
+        .await
+        .expect("Unable to get revisions");
+
+    assert_eq!(revisions[0], revision);
+}
+
+#[tokio::test]
+async fn page_revert() {
+    let server = &create_server().await;
+
+    // Setup
+    let user = server
+        .get_user_from_name("unknown")
+        .await
+        .expect("Unable to get user")
+        .expect("Default user not found");
+
+    let wiki_id = create_wiki(server).await;
+
+    macro_rules! commit {
+        ($slug:expr, $message:expr) => {
+            PageCommit {
+                wiki_id,
+                slug: $slug,
+                message: $message,
+                user: &user,
+            }
+        };
+    }
+
+    let (_, original) = server
+        .create_page(commit!("scp-xxxx", "new page"), "good content", &[], "", "")
+        .await
+        .expect("Unable to create page");
+
+    let (_, other) = server
+        .create_page(
+            commit!("scp-yyyy", "other page"),
+            "other content",
+            &[],
+            "",
+            "",
+        )
+        .await
+        .expect("Unable to create page");
+
+    server
+        .edit_page(commit!("scp-xxxx", "lol"), Some("vandalism"), None, None)
+        .await
+        .expect("Unable to edit page");
+
+    // Revert to the first version
+    server
+        .revert_revision(commit!("scp-xxxx", "revert vandalism"), Left(original))
+        .await
+        .expect("Unable to revert page");
+
+    let contents = server
+        .get_page_contents(wiki_id, "scp-xxxx")
+        .await
+        .expect("Unable to get page contents");
+
+    assert_eq!(contents.as_deref(), Some("good content"));
+
+    let revisions = server
+        .get_page_revisions(wiki_id, "scp-xxxx", 10, 0)
+        .await
+        .expect("Unable to get page revisions");
+
+    assert_eq!(revisions.len(), 3);
+    assert!(
+        revisions[0].message().contains(" reverted page ID "),
+        "Revert recorded as a different change: {:?}",
+        revisions[0].message(),
+    );
+
+    // Cannot revert to a revision of a different page
+    let result = server
+        .revert_revision(commit!("scp-xxxx", "bad revert"), Left(other))
+        .await;
+
+    match result {
+        Err(Error::RevisionPageMismatch) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Reverted to a revision of another page"),
+    }
+}
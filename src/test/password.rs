@@ -19,6 +19,7 @@
  */
 
 use super::prelude::*;
+use chrono::Duration;
 
 #[tokio::test]
 async fn passwords() {
@@ -85,3 +86,342 @@ async fn password_default() {
     bad_password!(4, "blackmoon");
     bad_password!(5, "blackmoon");
 }
+
+#[tokio::test]
+async fn password_policy() {
+    let server = &create_server_with(|config| {
+        config.password_policy.min_length = 10;
+        config.password_policy.require_digit = true;
+    })
+    .await;
+
+    macro_rules! weak_password {
+        ($result:expr, $reason:expr) => {
+            match $result {
+                Err(Error::PasswordTooWeak(reason)) => assert_eq!(reason, $reason),
+                Err(error) => panic!("Unexpected error: {}", error),
+                Ok(_) => panic!("Weak password was accepted"),
+            }
+        };
+    }
+
+    // Weak passwords abort user creation
+    let (username, email) = generate_username();
+    let result = server.create_user(&username, &email, "blackmon1").await;
+    weak_password!(result, WeakPasswordReason::TooShort(10));
+
+    let result = server
+        .create_user(&username, &email, "blackmoonhowls")
+        .await;
+    weak_password!(result, WeakPasswordReason::MissingDigit);
+
+    let user = server
+        .get_user_from_name(&username)
+        .await
+        .expect("Unable to get user");
+
+    assert!(user.is_none(), "User created with weak password");
+
+    // Changing to a weak password leaves the old one
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls1").await;
+
+    let result = server.set_user_password(user_id, "letmein");
+    weak_password!(result, WeakPasswordReason::TooShort(10));
+
+    server
+        .validate_user_password(user_id, "blackmoonhowls1")
+        .expect("Password doesn't match");
+}
+
+#[tokio::test]
+async fn password_change() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let session_1 = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let session_2 = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    // Wrong old password
+    let error = server
+        .change_password(user_id, "letmein", "rustybirb1", Some(session_1.token()))
+        .await
+        .expect_err("Changed password with wrong old password");
+
+    match error {
+        Error::AuthenticationFailed => (),
+        _ => panic!("Error wasn't authentication failed"),
+    }
+
+    server
+        .validate_user_password(user_id, "blackmoonhowls")
+        .expect("Password doesn't match");
+
+    server
+        .check_session(session_2.token())
+        .await
+        .expect("Session was invalid");
+
+    // Weak new password
+    let error = server
+        .change_password(user_id, "blackmoonhowls", "letmein", None)
+        .await
+        .expect_err("Changed to weak password");
+
+    match error {
+        Error::PasswordTooWeak(_) => (),
+        _ => panic!("Error wasn't password too weak"),
+    }
+
+    // Another user's session
+    let (other_id, _, _) = create_user_full(server, "rustybirb1").await;
+    let other_session = server
+        .try_login_id(other_id, "rustybirb1", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let error = server
+        .change_password(
+            user_id,
+            "blackmoonhowls",
+            "rustybirb1",
+            Some(other_session.token()),
+        )
+        .await
+        .expect_err("Changed password from another user's session");
+
+    match error {
+        Error::InvalidSession => (),
+        _ => panic!("Error wasn't invalid session"),
+    }
+
+    server
+        .validate_user_password(user_id, "blackmoonhowls")
+        .expect("Password doesn't match");
+
+    server
+        .check_session(session_2.token())
+        .await
+        .expect("Session was invalid");
+
+    // Successful change, ending other sessions
+    server
+        .change_password(
+            user_id,
+            "blackmoonhowls",
+            "rustybirb1",
+            Some(session_1.token()),
+        )
+        .await
+        .expect("Unable to change password");
+
+    server
+        .validate_user_password(user_id, "rustybirb1")
+        .expect("Password doesn't match");
+
+    server
+        .check_session(session_1.token())
+        .await
+        .expect("Session was invalid");
+
+    server
+        .check_session(session_2.token())
+        .await
+        .expect_err("Other session still valid");
+
+    // Without a session
+    server
+        .change_password(user_id, "rustybirb1", "blackmoonhowls", None)
+        .await
+        .expect("Unable to change password");
+
+    server
+        .check_session(session_1.token())
+        .await
+        .expect("Session was invalid");
+}
+
+#[tokio::test]
+async fn password_history() {
+    let server = &create_server_with(|config| {
+        config.password_policy.history_size = 2;
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    macro_rules! change {
+        ($old:expr, $new:expr) => {
+            server.change_password(user_id, $old, $new, None).await
+        };
+    }
+
+    macro_rules! reused {
+        ($result:expr) => {
+            match $result {
+                Err(Error::PasswordReused) => (),
+                Err(error) => panic!("Unexpected error: {}", error),
+                Ok(_) => panic!("Reused password was accepted"),
+            }
+        };
+    }
+
+    // The current password counts as a recent one
+    reused!(change!("blackmoonhowls", "blackmoonhowls"));
+
+    change!("blackmoonhowls", "rustybirb1").expect("Unable to change password");
+    reused!(change!("rustybirb1", "blackmoonhowls"));
+
+    server
+        .validate_user_password(user_id, "rustybirb1")
+        .expect("Password doesn't match");
+
+    // Older passwords fall out of the history
+    change!("rustybirb1", "scp-3000-eel").expect("Unable to change password");
+    reused!(change!("scp-3000-eel", "rustybirb1"));
+
+    change!("scp-3000-eel", "blackmoonhowls").expect("Unable to change old password");
+}
+
+#[tokio::test]
+async fn password_history_disabled() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    server
+        .change_password(user_id, "blackmoonhowls", "blackmoonhowls", None)
+        .await
+        .expect("Unable to reuse password");
+}
+
+#[tokio::test]
+async fn password_reset() {
+    let server = &create_server().await;
+    let user_id = create_user(server).await;
+
+    let token = server
+        .create_password_reset(user_id)
+        .await
+        .expect("Unable to create reset token");
+
+    // Invalid tokens
+    for token in &["", "abc", &token[..32], &token.to_ascii_lowercase()] {
+        let error = server
+            .redeem_reset_token(token, "blackmoonhowls")
+            .await
+            .expect_err("Redeemed invalid reset token");
+
+        match error {
+            Error::InvalidResetToken => (),
+            _ => panic!("Error wasn't invalid reset token"),
+        }
+    }
+
+    // A rejected password doesn't use up the token
+    server
+        .redeem_reset_token(&token, "letmein")
+        .await
+        .expect_err("Reset to a weak password");
+
+    let reset_id = server
+        .redeem_reset_token(&token, "blackmoonhowls")
+        .await
+        .expect("Unable to redeem reset token");
+
+    assert_eq!(user_id, reset_id);
+
+    server
+        .validate_user_password(user_id, "blackmoonhowls")
+        .expect("Password doesn't match");
+
+    // Tokens are single-use
+    let error = server
+        .redeem_reset_token(&token, "rustybirb1")
+        .await
+        .expect_err("Redeemed reset token twice");
+
+    match error {
+        Error::InvalidResetToken => (),
+        _ => panic!("Error wasn't invalid reset token"),
+    }
+
+    server
+        .validate_user_password(user_id, "blackmoonhowls")
+        .expect("Password doesn't match");
+}
+
+#[tokio::test]
+async fn password_reset_expiry() {
+    let server = &create_server_with(|config| {
+        config.password_reset_ttl = Duration::zero();
+    })
+    .await;
+
+    let user_id = create_user(server).await;
+
+    let token = server
+        .create_password_reset(user_id)
+        .await
+        .expect("Unable to create reset token");
+
+    let error = server
+        .redeem_reset_token(&token, "blackmoonhowls")
+        .await
+        .expect_err("Redeemed expired reset token");
+
+    match error {
+        Error::InvalidResetToken => (),
+        _ => panic!("Error wasn't invalid reset token"),
+    }
+}
+
+#[tokio::test]
+async fn password_rehash() {
+    let old_cost = HashCost::new(4, 8, 1).unwrap();
+    let old_server = &create_server_with(|config| config.password_cost = old_cost).await;
+    let (user_id, username, _) = create_user_full(old_server, "blackmoonhowls").await;
+
+    macro_rules! cost {
+        ($server:expr) => {
+            $server
+                .get_password_cost(user_id)
+                .await
+                .expect("Unable to get password cost")
+                .expect("User has no password")
+        };
+    }
+
+    assert_eq!(cost!(old_server), old_cost);
+
+    // Logging in with a different configured cost still verifies, and upgrades the hash
+    let server = &create_server().await;
+    server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to log in with password hashed at a different cost");
+
+    assert_eq!(cost!(server), HashCost::default());
+
+    server
+        .validate_user_password(user_id, "blackmoonhowls")
+        .expect("Upgraded password doesn't match");
+
+    // The old server still accepts it, downgrading it back
+    old_server
+        .validate_user_password(user_id, "blackmoonhowls")
+        .expect("Password doesn't match at its original cost");
+
+    assert_eq!(cost!(old_server), old_cost);
+
+    // Failed logins don't touch the stored hash
+    let _ = server
+        .try_login(&username, "letmein", None, None, None)
+        .await;
+    assert_eq!(cost!(server), old_cost);
+}
@@ -0,0 +1,196 @@
+/*
+ * test/pool.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use diesel::result::DatabaseErrorKind::{self, SerializationFailure};
+use diesel::result::Error as DieselError;
+
+#[tokio::test]
+async fn pool_single_connection() {
+    // Queries inside a transaction must reuse its connection,
+    // otherwise this would wait forever for a second one.
+    let server = &create_server_with(|config| {
+        config.database_pool_size = 1;
+    })
+    .await;
+
+    let (_, username, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let session = server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    server
+        .get_actor(session.token())
+        .await
+        .expect("Unable to get actor");
+
+    // Failed transactions are rolled back
+    let error = server
+        .create_user(&username, "other@example.com", "blackmoonhowls")
+        .await
+        .expect_err("Created user with duplicate name");
+
+    match error {
+        Error::UserNameExists => (),
+        _ => panic!("Error wasn't user name exists"),
+    }
+
+    server.ping().await.expect("Unable to ping database");
+}
+
+#[tokio::test]
+async fn pool_retry() {
+    let server = &create_server_with(|config| {
+        config.transaction_attempts = 3;
+    })
+    .await;
+
+    macro_rules! serialization_failure {
+        () => {
+            Error::Database(DieselError::DatabaseError(
+                SerializationFailure,
+                Box::new(String::from("could not serialize access")),
+            ))
+        };
+    }
+
+    // Succeeds after retrying
+    let mut attempts = 0;
+    let result = server.test_retry_transaction(|| {
+        attempts += 1;
+
+        if attempts < 3 {
+            Err(serialization_failure!())
+        } else {
+            Ok(attempts)
+        }
+    });
+
+    assert_eq!(result.expect("Transaction wasn't retried"), 3);
+
+    // Gives up after the configured number of attempts
+    let mut attempts = 0;
+    let result = server.test_retry_transaction(|| -> Result<()> {
+        attempts += 1;
+
+        Err(serialization_failure!())
+    });
+
+    match result.expect_err("Transaction succeeded") {
+        Error::Database(DieselError::DatabaseError(SerializationFailure, _)) => (),
+        error => panic!("Unexpected error: {}", error),
+    }
+
+    assert_eq!(attempts, 3);
+
+    // Deadlocks have no kind of their own, but are retried too
+    let mut attempts = 0;
+    let result = server.test_retry_transaction(|| {
+        attempts += 1;
+
+        if attempts < 2 {
+            Err(Error::Database(DieselError::DatabaseError(
+                DatabaseErrorKind::__Unknown,
+                Box::new(String::from("deadlock detected")),
+            )))
+        } else {
+            Ok(attempts)
+        }
+    });
+
+    assert_eq!(result.expect("Transaction wasn't retried"), 2);
+
+    // Other errors are not retried
+    let mut attempts = 0;
+    let result = server.test_retry_transaction(|| -> Result<()> {
+        attempts += 1;
+
+        Err(Error::UserNotFound)
+    });
+
+    match result.expect_err("Transaction succeeded") {
+        Error::UserNotFound => (),
+        error => panic!("Unexpected error: {}", error),
+    }
+
+    assert_eq!(attempts, 1);
+}
+
+#[tokio::test]
+async fn pool_after_commit() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let server = &create_server().await;
+    let ran = Rc::new(RefCell::new(Vec::new()));
+
+    macro_rules! push {
+        ($value:expr) => {{
+            let ran = Rc::clone(&ran);
+            server.test_after_commit(move || ran.borrow_mut().push($value));
+        }};
+    }
+
+    // Runs right away outside of a transaction
+    push!(1);
+    assert_eq!(*ran.borrow(), [1]);
+
+    // Waits until the transaction commits
+    server
+        .test_retry_transaction(|| {
+            push!(2);
+            assert_eq!(*ran.borrow(), [1]);
+            Ok(())
+        })
+        .expect("Transaction failed");
+
+    assert_eq!(*ran.borrow(), [1, 2]);
+
+    // Dropped if the transaction is rolled back
+    server.test_transaction(|| {
+        push!(3);
+        Ok(())
+    });
+
+    let _ = server.test_retry_transaction(|| -> Result<()> {
+        push!(4);
+        Err(Error::UserNotFound)
+    });
+
+    assert_eq!(*ran.borrow(), [1, 2]);
+
+    // Or if its savepoint is
+    server
+        .test_retry_transaction(|| {
+            push!(5);
+
+            let _ = server.test_retry_transaction(|| -> Result<()> {
+                push!(6);
+                Err(Error::UserNotFound)
+            });
+
+            Ok(())
+        })
+        .expect("Transaction failed");
+
+    assert_eq!(*ran.borrow(), [1, 2, 5]);
+}
@@ -0,0 +1,83 @@
+/*
+ * test/self_test.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[tokio::test]
+async fn self_test() {
+    let server = &create_server().await;
+
+    let report = server.self_test().await.expect("Unable to run self-test");
+
+    for result in &report.results {
+        assert!(
+            result.passed,
+            "Self-test of {} failed: {:?}",
+            result.subsystem, result.error,
+        );
+    }
+
+    let subsystems: Vec<_> = report.results.iter().map(|r| r.subsystem).collect();
+    assert_eq!(
+        subsystems,
+        ["database", "user", "password", "session", "revision"],
+    );
+    assert!(report.passed());
+
+    // Server still works afterwards
+    server.ping().await.expect("Unable to ping after self-test");
+    create_user(server).await;
+}
+
+#[derive(Debug)]
+struct ChannelListener(Mutex<Sender<LoginEvent>>);
+
+impl LoginEventListener for ChannelListener {
+    fn on_login_attempt(&self, event: LoginEvent) {
+        let _ = self.0.lock().unwrap().send(event);
+    }
+}
+
+#[tokio::test]
+async fn self_test_events() {
+    let (sender, receiver) = mpsc::channel();
+    let listener = Arc::new(ChannelListener(Mutex::new(sender)));
+    let server = &create_server_with(|config| config.login_listener = Some(listener)).await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let report = server.self_test().await.expect("Unable to run self-test");
+    assert!(report.passed());
+
+    // The self-test's login was rolled back, so the first event is this one
+    server
+        .try_login_id(user_id, "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let event = receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("No login event received");
+
+    assert_eq!(event.user_id, Some(user_id));
+    assert!(receiver.try_recv().is_err(), "Extra login event received");
+}
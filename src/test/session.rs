@@ -19,6 +19,9 @@
  */
 
 use super::prelude::*;
+use chrono::prelude::*;
+use chrono::Duration;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 macro_rules! check_err {
     ($error:expr) => {
@@ -29,6 +32,12 @@ macro_rules! check_err {
     };
 }
 
+/// Drops the fractional part of a timestamp, so it survives the database unchanged.
+fn whole_seconds(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.with_nanosecond(0)
+        .expect("Unable to truncate timestamp")
+}
+
 #[tokio::test]
 async fn session_internal() {
     let server = &create_server().await;
@@ -37,51 +46,51 @@ async fn session_internal() {
 
     // Login with user ID
     let session_1 = server
-        .try_login_id(user_id, password, None)
+        .try_login_id(user_id, password, None, None, None)
         .await
         .expect("Unable to login");
 
     assert_eq!(user_id, session_1.user_id());
 
     server
-        .check_session(session_1.session_id(), session_1.user_id())
+        .check_session(session_1.token())
         .await
         .expect("Session was invalid");
 
     // Login with username
     let session_2 = server
-        .try_login(&username, password, None)
+        .try_login(&username, password, None, None, None)
         .await
         .expect("Unable to login");
 
     assert_eq!(user_id, session_2.user_id());
 
     server
-        .check_session(session_2.session_id(), session_2.user_id())
+        .check_session(session_2.token())
         .await
         .expect("Session was invalid");
 
     // Login with email
     let session_3 = server
-        .try_login(&email, password, None)
+        .try_login(&email, password, None, None, None)
         .await
         .expect("Unable to login");
 
     assert_eq!(user_id, session_3.user_id());
 
     server
-        .check_session(session_3.session_id(), session_3.user_id())
+        .check_session(session_3.token())
         .await
         .expect("Session was invalid");
 
     // Invalidate session 1
     server
-        .end_session(session_1.session_id(), session_1.user_id())
+        .end_session(session_1.token())
         .await
         .expect("Unable to end session");
 
     let error = server
-        .check_session(session_1.session_id(), session_1.user_id())
+        .check_session(session_1.token())
         .await
         .expect_err("Session still valid");
 
@@ -89,12 +98,12 @@ async fn session_internal() {
 
     // Invalidate session 2
     server
-        .end_session(session_2.session_id(), session_2.user_id())
+        .end_session(session_2.token())
         .await
         .expect("Unable to end session");
 
     let error = server
-        .check_session(session_2.session_id(), session_2.user_id())
+        .check_session(session_2.token())
         .await
         .expect_err("Session still valid");
 
@@ -102,7 +111,7 @@ async fn session_internal() {
 
     // Invalidate invalid session
     let error = server
-        .end_session(session_1.session_id(), session_1.user_id())
+        .end_session(session_1.token())
         .await
         .expect_err("Unable to end session");
 
@@ -110,12 +119,12 @@ async fn session_internal() {
 
     // Invalidate session 3
     server
-        .end_session(session_3.session_id(), session_3.user_id())
+        .end_session(session_3.token())
         .await
         .expect("Unable to end session");
 
     let error = server
-        .check_session(session_3.session_id(), session_3.user_id())
+        .check_session(session_3.token())
         .await
         .expect_err("Session still valid");
 
@@ -129,58 +138,566 @@ async fn session_end_other() {
 
     // Create multiple sessions
     let session_1 = server
-        .try_login_id(user_id, "blackmoonhowls", None)
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
         .await
         .expect("Unable to login");
 
     server
-        .check_session(session_1.session_id(), session_1.user_id())
+        .check_session(session_1.token())
         .await
         .expect("Session was invalid");
 
     let session_2 = server
-        .try_login_id(user_id, "blackmoonhowls", None)
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
         .await
         .expect("Unable to login");
 
     server
-        .check_session(session_2.session_id(), session_2.user_id())
+        .check_session(session_2.token())
         .await
         .expect("Session was invalid");
 
     let session_3 = server
-        .try_login_id(user_id, "blackmoonhowls", None)
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
         .await
         .expect("Unable to login");
 
     server
-        .check_session(session_3.session_id(), session_3.user_id())
+        .check_session(session_3.token())
         .await
         .expect("Session was invalid");
 
     // Invalidate all other sessions
     server
-        .end_other_sessions(session_1.session_id(), session_1.user_id())
+        .end_other_sessions(session_1.token())
         .await
         .expect("Unable to end all other sessions");
 
     // Check sessions for validity
     server
-        .check_session(session_1.session_id(), session_1.user_id())
+        .check_session(session_1.token())
         .await
         .expect("Session was invalid");
 
     let error = server
-        .check_session(session_2.session_id(), session_2.user_id())
+        .check_session(session_2.token())
         .await
         .expect_err("Session still valid");
 
     check_err!(error);
 
     let error = server
-        .check_session(session_3.session_id(), session_3.user_id())
+        .check_session(session_3.token())
         .await
         .expect_err("Session still valid");
 
     check_err!(error);
 }
+
+#[tokio::test]
+async fn session_actor() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    // Valid session
+    let actor = server
+        .get_actor(session.token())
+        .await
+        .expect("Unable to get actor");
+
+    assert_eq!(actor.user_id(), user_id);
+    assert_eq!(actor.user().id(), user_id);
+    assert_eq!(
+        actor.session().login_attempt_id(),
+        session.login_attempt_id()
+    );
+
+    // Bad tokens
+    for token in &["", "not-a-valid-token", &session.token()[1..]] {
+        let error = server
+            .get_actor(token)
+            .await
+            .expect_err("Got actor for bad token");
+
+        check_err!(error);
+    }
+
+    // Inactive user
+    server
+        .mark_user_inactive(user_id)
+        .await
+        .expect("Unable to mark user inactive");
+
+    match server.get_actor(session.token()).await {
+        Err(Error::UserNotFound) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Got actor for inactive user"),
+    }
+
+    server
+        .mark_user_active(user_id)
+        .await
+        .expect("Unable to mark user active");
+
+    // Ended session
+    server
+        .end_session(session.token())
+        .await
+        .expect("Unable to end session");
+
+    let error = server
+        .get_actor(session.token())
+        .await
+        .expect_err("Got actor for ended session");
+
+    check_err!(error);
+}
+
+#[tokio::test]
+async fn session_authenticate() {
+    let server = &create_server_with(|config| {
+        config.verification.require_before_login = true;
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    server
+        .verify_user(user_id)
+        .await
+        .expect("Unable to verify user");
+
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    // Valid token
+    let (authenticated, user) = server
+        .authenticate(session.token())
+        .await
+        .expect("Unable to authenticate");
+
+    assert_eq!(authenticated.login_attempt_id(), session.login_attempt_id());
+    assert_eq!(authenticated.user_id(), user_id);
+    assert_eq!(user.id(), user_id);
+
+    // Wrong token
+    let error = server
+        .authenticate("not-a-valid-token")
+        .await
+        .expect_err("Authenticated with invalid token");
+
+    check_err!(error);
+
+    // Inactive user
+    server
+        .mark_user_inactive(user_id)
+        .await
+        .expect("Unable to mark user inactive");
+
+    match server.authenticate(session.token()).await {
+        Err(Error::UserNotFound) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Authenticated inactive user"),
+    }
+
+    server
+        .mark_user_active(user_id)
+        .await
+        .expect("Unable to mark user active");
+
+    // Unverified user
+    server
+        .unverify_user(user_id)
+        .await
+        .expect("Unable to mark user unverified");
+
+    match server.authenticate(session.token()).await {
+        Err(Error::AccountNotVerified(None)) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Authenticated unverified user"),
+    }
+
+    // Actors go through the same checks
+    match server.get_actor(session.token()).await {
+        Err(Error::AccountNotVerified(None)) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Got actor for unverified user"),
+    }
+
+    server
+        .verify_user(user_id)
+        .await
+        .expect("Unable to verify user");
+
+    // Expired session
+    let now = Utc::now();
+    server
+        .set_session_times(
+            session.token(),
+            now - Duration::days(2),
+            now - Duration::days(1),
+        )
+        .await
+        .expect("Unable to change session times");
+
+    let error = server
+        .authenticate(session.token())
+        .await
+        .expect_err("Authenticated with expired session");
+
+    check_err!(error);
+}
+
+#[tokio::test]
+async fn session_expiry() {
+    // Sessions last for the configured TTL
+    {
+        let server = &create_server().await;
+        let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+        let session = server
+            .try_login_id(user_id, "blackmoonhowls", None, None, None)
+            .await
+            .expect("Unable to login");
+
+        let expires_at = session.expires_at().expect("Session has no expiry");
+        assert!(expires_at > session.created_at());
+        assert!(!session.is_expired(Utc::now()));
+        assert!(session.is_expired(expires_at));
+
+        server
+            .check_session(session.token())
+            .await
+            .expect("Session was invalid");
+    }
+
+    // Sessions which have expired are invalid
+    {
+        let server = &create_server_with(|config| {
+            config.session_ttl = Duration::zero();
+        })
+        .await;
+
+        let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+        let session = server
+            .try_login_id(user_id, "blackmoonhowls", None, None, None)
+            .await
+            .expect("Unable to login");
+
+        assert!(session.is_expired(Utc::now()));
+
+        let error = server
+            .check_session(session.token())
+            .await
+            .expect_err("Expired session still valid");
+
+        check_err!(error);
+
+        let error = server
+            .validate_session_token(session.token())
+            .await
+            .expect_err("Expired session token still valid");
+
+        check_err!(error);
+    }
+}
+
+#[tokio::test]
+async fn session_logout() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // Logout is idempotent
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    server
+        .logout(session.token())
+        .await
+        .expect("Unable to logout");
+
+    let error = server
+        .check_session(session.token())
+        .await
+        .expect_err("Session still valid");
+
+    check_err!(error);
+
+    server
+        .logout(session.token())
+        .await
+        .expect("Unable to logout of ended session");
+
+    // Logout everywhere
+    let mut sessions = Vec::new();
+    for _ in 0..3 {
+        let session = server
+            .try_login_id(user_id, "blackmoonhowls", None, None, None)
+            .await
+            .expect("Unable to login");
+
+        sessions.push(session);
+    }
+
+    let count = server
+        .end_all_sessions(user_id)
+        .await
+        .expect("Unable to end all sessions");
+
+    assert_eq!(count, 3);
+
+    for session in &sessions {
+        let error = server
+            .check_session(session.token())
+            .await
+            .expect_err("Session still valid");
+
+        check_err!(error);
+    }
+
+    let count = server
+        .end_all_sessions(user_id)
+        .await
+        .expect("Unable to end all sessions");
+
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn session_token() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let session_1 = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let session_2 = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    assert_ne!(session_1.token(), session_2.token());
+
+    // Valid tokens
+    for issued in &[&session_1, &session_2] {
+        let session = server
+            .validate_session_token(issued.token())
+            .await
+            .expect("Session token invalid");
+
+        assert_eq!(session.login_attempt_id(), issued.login_attempt_id());
+        assert_eq!(session.user_id(), user_id);
+    }
+
+    // Invalid tokens
+    let wrong_verifier = {
+        let mut token = String::from(session_1.token());
+        let last = if token.ends_with('a') { "b" } else { "a" };
+        token.pop();
+        token.push_str(last);
+        token
+    };
+
+    let mixed = format!("{}{}", &session_1.token()[..16], &session_2.token()[16..]);
+
+    for token in &["", "abc", &session_1.token()[..32], &wrong_verifier, &mixed] {
+        let error = server
+            .validate_session_token(token)
+            .await
+            .expect_err("Invalid session token accepted");
+
+        check_err!(error);
+    }
+
+    // Ended sessions
+    server
+        .logout(session_1.token())
+        .await
+        .expect("Unable to logout");
+
+    let error = server
+        .validate_session_token(session_1.token())
+        .await
+        .expect_err("Session token still valid after logout");
+
+    check_err!(error);
+
+    server
+        .validate_session_token(session_2.token())
+        .await
+        .expect("Session token invalid");
+}
+
+#[tokio::test]
+async fn session_active() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let addresses = [
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10))),
+        Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+        None,
+    ];
+
+    let mut issued = Vec::new();
+    for &address in &addresses {
+        let session = server
+            .try_login_id(user_id, "blackmoonhowls", address, None, None)
+            .await
+            .expect("Unable to login");
+
+        issued.push(session);
+    }
+
+    let sessions = server
+        .get_active_sessions(user_id)
+        .await
+        .expect("Unable to get active sessions");
+
+    assert_eq!(sessions.len(), 3);
+
+    for (i, session) in sessions.iter().enumerate() {
+        assert_eq!(session.login_attempt_id(), issued[i].login_attempt_id());
+        assert_eq!(session.remote_address(), addresses[i]);
+    }
+
+    assert!(sessions[0].created_at() <= sessions[1].created_at());
+    assert!(sessions[1].created_at() <= sessions[2].created_at());
+
+    // Ended sessions are no longer listed
+    server
+        .logout(issued[1].token())
+        .await
+        .expect("Unable to logout");
+
+    let sessions = server
+        .get_active_sessions(user_id)
+        .await
+        .expect("Unable to get active sessions");
+
+    let ids: Vec<_> = sessions
+        .iter()
+        .map(|session| session.login_attempt_id())
+        .collect();
+    assert_eq!(
+        ids,
+        vec![issued[0].login_attempt_id(), issued[2].login_attempt_id()],
+    );
+}
+
+#[tokio::test]
+async fn session_sliding() {
+    let server = &create_server_with(|config| {
+        config.session_ttl = Duration::hours(1);
+        config.session_expiry = SessionExpiry::Sliding {
+            max_lifetime: Duration::hours(3),
+        };
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    macro_rules! validate {
+        () => {
+            server
+                .validate_session_token(session.token())
+                .await
+                .expect("Session token invalid")
+        };
+    }
+
+    macro_rules! set_times {
+        ($created_at:expr, $expires_at:expr) => {
+            server
+                .set_session_times(session.token(), $created_at, $expires_at)
+                .await
+                .expect("Unable to set session times")
+        };
+    }
+
+    // Expiry barely moved, so it isn't written
+    let validated = validate!();
+    assert_eq!(validated.expires_at(), session.expires_at());
+
+    // Most of the TTL is left, so still no refresh
+    let now = whole_seconds(Utc::now());
+    let expires_at = now + Duration::minutes(55);
+    set_times!(now - Duration::minutes(5), expires_at);
+
+    let validated = validate!();
+    assert_eq!(validated.expires_at(), Some(expires_at));
+
+    // Half the TTL is used up, so it's pushed forward a full TTL
+    let now = Utc::now();
+    set_times!(now - Duration::minutes(30), now + Duration::minutes(30));
+
+    let validated = validate!();
+    let expires_at = validated.expires_at().expect("Session has no expiry");
+    assert!(expires_at >= now + Duration::minutes(59));
+    assert!(expires_at <= Utc::now() + Duration::hours(1));
+
+    // Near the end of its lifetime, it can't be extended past the maximum
+    let now = Utc::now();
+    set_times!(now - Duration::minutes(170), now + Duration::minutes(5));
+
+    let validated = validate!();
+    assert_eq!(
+        validated.expires_at(),
+        Some(validated.created_at() + Duration::hours(3)),
+    );
+
+    // Expired sessions are not revived
+    let now = Utc::now();
+    set_times!(now - Duration::hours(2), now - Duration::minutes(1));
+
+    let error = server
+        .validate_session_token(session.token())
+        .await
+        .expect_err("Expired session token still valid");
+
+    check_err!(error);
+}
+
+#[tokio::test]
+async fn session_fixed() {
+    let server = &create_server_with(|config| {
+        config.session_ttl = Duration::hours(1);
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let now = whole_seconds(Utc::now());
+    let expires_at = now + Duration::minutes(10);
+    server
+        .set_session_times(session.token(), now - Duration::minutes(50), expires_at)
+        .await
+        .expect("Unable to set session times");
+
+    let validated = server
+        .validate_session_token(session.token())
+        .await
+        .expect("Session token invalid");
+
+    assert_eq!(validated.expires_at(), Some(expires_at));
+}
@@ -32,7 +32,7 @@ async fn tags() {
 
     let user_2_id = create_user(server).await;
     let user_2 = server
-        .get_user_from_id(user_2_id)
+        .get_user_model_from_id(user_2_id)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
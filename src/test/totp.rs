@@ -0,0 +1,255 @@
+/*
+ * test/totp.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+
+const PASSWORD: &str = "correcthorsebatterystaple";
+
+#[tokio::test]
+async fn totp_enroll() {
+    let server = &create_server().await;
+    let (user_id, username, _) = create_user_full(server, PASSWORD).await;
+
+    let enrollment = server
+        .enroll_totp(user_id)
+        .await
+        .expect("Unable to enroll user");
+
+    assert_eq!(enrollment.secret().len(), 32);
+    assert!(enrollment.uri().starts_with("otpauth://totp/deepwell:"));
+    assert!(enrollment.uri().contains(&username));
+    assert!(enrollment.uri().contains(enrollment.secret()));
+
+    // Not required until confirmed
+    server
+        .try_login_id(user_id, PASSWORD, None, None, None)
+        .await
+        .expect("Unconfirmed enrollment required a code");
+
+    // Enrolling again replaces the secret
+    let enrollment_2 = server
+        .enroll_totp(user_id)
+        .await
+        .expect("Unable to enroll user again");
+
+    assert_ne!(enrollment.secret(), enrollment_2.secret());
+
+    match server.verify_totp(user_id, "000000").await {
+        Err(Error::AuthenticationFailed) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => {
+            // Possible but unlikely, try another
+            server
+                .verify_totp(user_id, "000001")
+                .await
+                .expect_err("Allowed invalid code");
+        }
+    }
+
+    let code = server.generate_totp_code(user_id, 0).await.unwrap();
+    server
+        .verify_totp(user_id, &code)
+        .await
+        .expect("Unable to confirm enrollment");
+
+    // Can't enroll again while enabled
+    server
+        .enroll_totp(user_id)
+        .await
+        .expect_err("Allowed enrolling while enabled");
+
+    // Codes can't be reused
+    match server.verify_totp(user_id, &code).await {
+        Err(Error::AuthenticationFailed) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed code reuse"),
+    }
+
+    // Disabling removes the requirement
+    server
+        .disable_totp(user_id)
+        .await
+        .expect("Unable to disable two-factor");
+
+    server
+        .try_login_id(user_id, PASSWORD, None, None, None)
+        .await
+        .expect("Disabled two-factor still required a code");
+}
+
+#[tokio::test]
+async fn totp_login() {
+    let server = &create_server().await;
+    let (user_id, username, _) = create_user_full(server, PASSWORD).await;
+
+    server.enroll_totp(user_id).await.unwrap();
+    let code = server.generate_totp_code(user_id, -1).await.unwrap();
+    server
+        .verify_totp(user_id, &code)
+        .await
+        .expect("Previous step not accepted");
+
+    macro_rules! start_login {
+        () => {
+            match server
+                .try_login(&username, PASSWORD, None, None, None)
+                .await
+            {
+                Err(Error::TwoFactorRequired(Some(token))) => token,
+                Err(error) => panic!("Unexpected error: {}", error),
+                Ok(_) => panic!("Logged in without a code"),
+            }
+        };
+    }
+
+    // Wrong password doesn't reach two-factor
+    match server
+        .try_login(&username, "wrongpassword", None, None, None)
+        .await
+    {
+        Err(Error::AuthenticationFailed) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed invalid login"),
+    }
+
+    // Complete login
+    let token = start_login!();
+    let code = server.generate_totp_code(user_id, 0).await.unwrap();
+
+    match server.try_login_totp("not a token", &code).await {
+        Err(Error::InvalidSession) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed invalid token"),
+    }
+
+    let session = server
+        .try_login_totp(&token, &code)
+        .await
+        .expect("Unable to complete login");
+
+    assert_eq!(session.session().user_id(), user_id);
+
+    // Token and code are both used up
+    match server.try_login_totp(&token, &code).await {
+        Err(Error::InvalidSession) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed token reuse"),
+    }
+
+    let token = start_login!();
+    match server.try_login_totp(&token, &code).await {
+        Err(Error::AuthenticationFailed) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed code reuse"),
+    }
+
+    // Too many wrong codes invalidates the token
+    let code = server.generate_totp_code(user_id, 1).await.unwrap();
+    server.try_login_totp(&token, "bad").await.unwrap_err();
+    server.try_login_totp(&token, "bad").await.unwrap_err();
+
+    match server.try_login_totp(&token, &code).await {
+        Err(Error::InvalidSession) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed too many attempts"),
+    }
+
+    // Steps outside the allowed skew are refused
+    let token = start_login!();
+    let code = server.generate_totp_code(user_id, 2).await.unwrap();
+    server
+        .try_login_totp(&token, &code)
+        .await
+        .expect_err("Allowed code outside skew");
+
+    // The next step is still fine
+    let code = server.generate_totp_code(user_id, 1).await.unwrap();
+    server
+        .try_login_totp(&token, &code)
+        .await
+        .expect("Unable to login with next step");
+}
+
+#[tokio::test]
+async fn totp_login_concurrent() {
+    use async_std::task;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    const THREADS: usize = 8;
+
+    // Enough connections that all of them can run at once
+    let server = create_server_with(|config| {
+        config.database_pool_size = THREADS as u32;
+    })
+    .await;
+
+    let server = Arc::new(server);
+    let (user_id, _, _) = create_user_full(&server, PASSWORD).await;
+
+    server.enroll_totp(user_id).await.unwrap();
+    let code = server.generate_totp_code(user_id, 0).await.unwrap();
+    server.verify_totp(user_id, &code).await.unwrap();
+
+    let token = match server
+        .try_login_id(user_id, PASSWORD, None, None, None)
+        .await
+    {
+        Err(Error::TwoFactorRequired(Some(token))) => token,
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Logged in without a code"),
+    };
+
+    // Concurrent guesses only get the usual number of tries
+    let token = Arc::new(token);
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let token = Arc::clone(&token);
+            let barrier = Arc::clone(&barrier);
+
+            thread::spawn(move || {
+                barrier.wait();
+
+                task::block_on(server.try_login_totp(&token, "bad"))
+                    .expect_err("Allowed invalid code")
+            })
+        })
+        .collect();
+
+    let mut checked = 0;
+    for handle in handles {
+        match handle.join().expect("Thread panicked") {
+            Error::AuthenticationFailed => checked += 1,
+            Error::InvalidSession => (),
+            error => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    assert_eq!(checked, 3);
+
+    let code = server.generate_totp_code(user_id, 1).await.unwrap();
+    match server.try_login_totp(&token, &code).await {
+        Err(Error::InvalidSession) => (),
+        Err(error) => panic!("Unexpected error: {}", error),
+        Ok(_) => panic!("Allowed too many attempts"),
+    }
+}
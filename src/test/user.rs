@@ -19,6 +19,9 @@
  */
 
 use super::prelude::*;
+use crate::utils::rand_alphanum;
+use chrono::prelude::*;
+use chrono::Duration;
 
 macro_rules! check_err {
     ($error:expr, $expected:pat) => {
@@ -35,7 +38,7 @@ async fn users() {
 
     let user_id_1 = create_user(server).await;
     let original_user = server
-        .get_user_from_id(user_id_1)
+        .get_user_model_from_id(user_id_1)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
@@ -48,10 +51,12 @@ async fn users() {
         about: Some("Test user who writes for a test function to test"),
         gender: Some("FEMALE"),
         location: Some("Earth"),
+        timezone: None,
+        locale: None,
     };
 
     server
-        .edit_user(user_id_1, metadata)
+        .edit_user(user_id_1, metadata, user_id_1)
         .await
         .expect("Unable to edit user");
 
@@ -79,10 +84,12 @@ async fn users() {
         about: Some("test user 2"),
         gender: Some("non-binary"),
         location: Some("Earth"),
+        timezone: None,
+        locale: None,
     };
 
     server
-        .edit_user(user_id_2, metadata)
+        .edit_user(user_id_2, metadata, user_id_2)
         .await
         .expect("Unable to edit second user");
 
@@ -105,7 +112,10 @@ async fn users() {
         .await
         .expect("Unable to get multiple users");
 
-    assert_eq!(users, vec![Some(user_1), None, Some(user_2)]);
+    assert_eq!(
+        users,
+        vec![Some(UserView::from(user_1)), None, Some(user_2)],
+    );
 
     let error = server
         .get_users_from_ids(&vec![invalid; 198])
@@ -123,10 +133,12 @@ async fn users() {
         about: None,
         gender: None,
         location: None,
+        timezone: None,
+        locale: None,
     };
 
     server
-        .edit_user(user_id_1, metadata)
+        .edit_user(user_id_1, metadata, user_id_1)
         .await
         .expect("Unable to reset user's name");
 }
@@ -140,13 +152,13 @@ async fn users_conflict() {
 
     // Get original users
     let user_1 = server
-        .get_user_from_id(user_id_1)
+        .get_user_model_from_id(user_id_1)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
 
     let user_2 = server
-        .get_user_from_id(user_id_2)
+        .get_user_model_from_id(user_id_2)
         .await
         .expect("Unable to get user")
         .expect("Created user not found");
@@ -157,9 +169,9 @@ async fn users_conflict() {
             user_id_1,
             UserMetadata {
                 name: Some("conflictTest joe"),
-                email: Some("joe@example.net"),
                 ..UserMetadata::default()
             },
+            user_id_1,
         )
         .await
         .expect("Unable to edit user initially");
@@ -169,13 +181,16 @@ async fn users_conflict() {
             user_id_2,
             UserMetadata {
                 name: Some("conflictTest jim"),
-                email: Some("jim@example.net"),
                 ..UserMetadata::default()
             },
+            user_id_2,
         )
         .await
         .expect("Unable to edit user initially");
 
+    change_email(server, user_id_1, "joe@example.net").await;
+    change_email(server, user_id_2, "jim@example.net").await;
+
     // Check conflicts with username
     let error = server
         .edit_user(
@@ -184,6 +199,22 @@ async fn users_conflict() {
                 name: Some("conflictTest jim"),
                 ..UserMetadata::default()
             },
+            user_id_1,
+        )
+        .await
+        .expect_err("Conflicted username edit succeeded");
+
+    check_err!(error, Error::UserNameExists);
+
+    // Check conflicts with username differing only by case
+    let error = server
+        .edit_user(
+            user_id_1,
+            UserMetadata {
+                name: Some("CONFLICTTEST JIM"),
+                ..UserMetadata::default()
+            },
+            user_id_1,
         )
         .await
         .expect_err("Conflicted username edit succeeded");
@@ -198,58 +229,1180 @@ async fn users_conflict() {
                 name: Some("conflictTest joe"),
                 ..UserMetadata::default()
             },
+            user_id_1,
         )
         .await
         .expect("Unable to set username to equivalent value");
 
     // Check conflicts with email
     let error = server
+        .request_email_change(user_id_1, "jim@example.net")
+        .await
+        .expect_err("Conflicted email change succeeded");
+
+    check_err!(error, Error::UserEmailExists);
+
+    // Check conflicts with email differing only by case
+    let error = server
+        .request_email_change(user_id_1, "Jim@Example.net")
+        .await
+        .expect_err("Conflicted email change succeeded");
+
+    check_err!(error, Error::UserEmailExists);
+
+    // Set them both to the original values
+    server
         .edit_user(
             user_id_1,
             UserMetadata {
-                email: Some("jim@example.net"),
+                name: Some(user_1.name()),
                 ..UserMetadata::default()
             },
+            user_id_1,
         )
         .await
-        .expect_err("Conflicted username edit succeeded");
+        .expect("Unable to edit user to original");
+
+    server
+        .edit_user(
+            user_id_2,
+            UserMetadata {
+                name: Some(user_2.name()),
+                ..UserMetadata::default()
+            },
+            user_id_2,
+        )
+        .await
+        .expect("Unable to edit user to original");
+
+    change_email(server, user_id_1, user_1.email()).await;
+    change_email(server, user_id_2, user_2.email()).await;
+}
+
+async fn change_email(server: &Server, user_id: UserId, email: &str) {
+    let token = server
+        .request_email_change(user_id, email)
+        .await
+        .expect("Unable to request email change");
+
+    server
+        .confirm_email_change(&token)
+        .await
+        .expect("Unable to confirm email change");
+}
+
+#[tokio::test]
+async fn users_normalized() {
+    let server = &create_server().await;
+
+    let user_id = server
+        .create_user("Big Cheese Horace", "horace@example.net", "blackmoonhowls")
+        .await
+        .expect("Unable to create user");
+
+    let user = server
+        .get_user_model_from_id(user_id)
+        .await
+        .expect("Unable to get user")
+        .expect("Created user not found");
+
+    assert_eq!(user.name(), "Big Cheese Horace");
+    assert_eq!(user.slug(), "big-cheese-horace");
+
+    // Lookups by name
+    for name in &[
+        "big-cheese-horace",
+        "BIG CHEESE HORACE",
+        "-Big Cheese--Horace!",
+    ] {
+        let found = server
+            .get_user_from_name(name)
+            .await
+            .expect("Unable to get user by username")
+            .expect("No such user with this name");
+
+        assert_eq!(found.id(), user_id);
+    }
+
+    // Names which normalize to an existing user
+    let error = server
+        .create_user("big cheese-horace", "horace2@example.net", "blackmoonhowls")
+        .await
+        .expect_err("Created user with conflicting normalized name");
+
+    check_err!(error, Error::UserNameExists);
+
+    let error = server
+        .create_user("!?!", "horace3@example.net", "blackmoonhowls")
+        .await
+        .expect_err("Created user with empty normalized name");
+
+    check_err!(error, Error::UserNameExists);
+
+    // Changing only the display form of the name
+    server
+        .edit_user(
+            user_id,
+            UserMetadata {
+                name: Some("big cheese horace"),
+                ..UserMetadata::default()
+            },
+            user_id,
+        )
+        .await
+        .expect("Unable to change name display form");
+
+    let user = server
+        .get_user_from_name("Big Cheese Horace")
+        .await
+        .expect("Unable to get user by username")
+        .expect("No such user with this name");
+
+    assert_eq!(user.name(), "big cheese horace");
+    assert_eq!(user.slug(), "big-cheese-horace");
+}
+
+#[tokio::test]
+async fn users_email() {
+    let server = &create_server().await;
+
+    let user_id = server
+        .create_user("Jenny Email", " Jenny@Example.NET", "blackmoonhowls")
+        .await
+        .expect("Unable to create user");
+
+    let user = server
+        .get_user_from_email("jenny@example.net")
+        .await
+        .expect("Unable to get user by email")
+        .expect("No such user with this email");
+
+    assert_eq!(user.id(), user_id);
+    assert_eq!(user.email(), "jenny@example.net");
+
+    // Mixed-case lookups
+    for email in &[
+        "JENNY@EXAMPLE.NET",
+        "jenny@Example.net",
+        "Jenny@Example.NET ",
+    ] {
+        let found = server
+            .get_user_from_email(email)
+            .await
+            .expect("Unable to get user by email")
+            .expect("No such user with this email");
+
+        assert_eq!(found.id(), user_id);
+    }
+
+    // Uniqueness is on the normalized form
+    let error = server
+        .create_user("Jenny Email 2", "jenny@EXAMPLE.net", "blackmoonhowls")
+        .await
+        .expect_err("Created user with conflicting email");
 
     check_err!(error, Error::UserEmailExists);
 
-    // Try changing to same email
+    // Edits can't change the email, it must go through a change request
+    server
+        .verify_user(user_id)
+        .await
+        .expect("Unable to mark user as verified");
+
+    for email in &["jenny2@example.net", "JENNY@example.net"] {
+        let error = server
+            .edit_user(
+                user_id,
+                UserMetadata {
+                    email: Some(email),
+                    about: Some("changed"),
+                    ..UserMetadata::default()
+                },
+                user_id,
+            )
+            .await
+            .expect_err("Changed email by editing");
+
+        check_err!(error, Error::InvalidArgument(_));
+    }
+
+    let user = server
+        .get_user_model_from_id(user_id)
+        .await
+        .expect("Unable to get user")
+        .expect("Created user not found");
+
+    assert_eq!(user.email(), "jenny@example.net");
+    assert_ne!(user.about(), "changed");
+    assert!(user.is_verified());
+
+    let log = server
+        .get_user_audit_log(user_id)
+        .await
+        .expect("Unable to get audit log");
+
+    assert!(log.is_empty(), "Rejected edit was logged");
+}
+
+#[tokio::test]
+async fn users_count() {
+    let server = &create_server().await;
+
+    // Other tests run concurrently, so only check lower bounds
+    let total = server
+        .count_users(true)
+        .await
+        .expect("Unable to count users");
+    let active = server
+        .count_users(false)
+        .await
+        .expect("Unable to count users");
+    assert!(total >= active);
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let new_total = server
+        .count_users(true)
+        .await
+        .expect("Unable to count users");
+    assert!(new_total > total);
+
+    server
+        .mark_user_inactive(user_id)
+        .await
+        .expect("Unable to mark user as inactive");
+
+    let total = server
+        .count_users(true)
+        .await
+        .expect("Unable to count users");
+    let active = server
+        .count_users(false)
+        .await
+        .expect("Unable to count users");
+    assert!(total > active);
+}
+
+#[tokio::test]
+async fn users_inactive() {
+    let server = &create_server().await;
+    let (user_id, username, email) = create_user_full(server, "blackmoonhowls").await;
+
+    server
+        .mark_user_inactive(user_id)
+        .await
+        .expect("Unable to mark user as inactive");
+
+    // Lookups exclude inactive users by default
+    let user = server
+        .get_user_from_name(&username)
+        .await
+        .expect("Unable to get user by name");
+
+    assert!(user.is_none());
+
+    let user = server
+        .get_user_from_email(&email)
+        .await
+        .expect("Unable to get user by email");
+
+    assert!(user.is_none());
+
+    // Unless asked for explicitly
+    let user = server
+        .get_any_user_from_name(&username)
+        .await
+        .expect("Unable to get user by name")
+        .expect("Inactive user not found");
+
+    assert_eq!(user.id(), user_id);
+    assert!(!user.is_active());
+
+    let user = server
+        .get_any_user_from_email(&email)
+        .await
+        .expect("Unable to get user by email")
+        .expect("Inactive user not found");
+
+    assert_eq!(user.id(), user_id);
+
+    // Cannot log in
+    let error = server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Logged in as inactive user");
+
+    check_err!(error, Error::AuthenticationFailed);
+
+    let error = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Logged in as inactive user");
+
+    check_err!(error, Error::UserNotFound);
+
+    // Reactivated users can be found again
+    server
+        .mark_user_active(user_id)
+        .await
+        .expect("Unable to mark user as active");
+
+    let user = server
+        .get_user_from_name(&username)
+        .await
+        .expect("Unable to get user by name")
+        .expect("Reactivated user not found");
+
+    assert_eq!(user.id(), user_id);
+
+    server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+}
+
+#[tokio::test]
+async fn users_search() {
+    let server = &create_server().await;
+    let tag = rand_alphanum(12).to_ascii_lowercase();
+
+    macro_rules! create {
+        ($name:expr) => {{
+            let name = format!("Searchable {} {}", tag, $name);
+            let email = format!("{}-{}@example.com", tag, $name);
+
+            server
+                .create_user(&name, &email, "blackmoonhowls")
+                .await
+                .expect("Unable to create user")
+        }};
+    }
+
+    macro_rules! search {
+        ($method:ident, $query:expr, $limit:expr) => {{
+            server
+                .$method($query, $limit)
+                .await
+                .expect("Unable to search users")
+                .iter()
+                .map(|user| user.id)
+                .collect::<Vec<_>>()
+        }};
+    }
+
+    let beta = create!("beta");
+    let alpha = create!("alpha");
+    let gamma = create!("gamma");
+
+    server
+        .mark_user_inactive(gamma)
+        .await
+        .expect("Unable to mark user as inactive");
+
+    // Substring matching on the normalized name, ordered by name
+    let query = format!("{} ", tag.to_ascii_uppercase());
+    assert_eq!(search!(search_users, &query, 10), vec![alpha, beta]);
+    assert_eq!(search!(search_users, &query, 1), vec![alpha]);
+    assert_eq!(
+        search!(search_any_users, &query, 10),
+        vec![alpha, beta, gamma]
+    );
+
+    let query = format!("{} bet", tag);
+    assert_eq!(search!(search_users, &query, 10), vec![beta]);
+
+    // Wildcards are not interpreted
+    //
+    // Underscores are kept by normalization, unlike other punctuation.
+    assert_eq!(search!(search_users, &format!("{}_alpha", tag), 10), vec![]);
+    assert_eq!(search!(search_users, "%", 10), vec![]);
+
+    // No matches
+    assert_eq!(search!(search_users, "", 10), vec![]);
+    assert_eq!(search!(search_users, &format!("{} delta", tag), 10), vec![]);
+}
+
+#[tokio::test]
+async fn users_batch() {
+    let server = &create_server().await;
+
+    let names: Vec<_> = (0..3).map(|_| generate_username()).collect();
+    let specs: Vec<_> = names
+        .iter()
+        .map(|(name, email)| NewUserSpec {
+            name,
+            email,
+            password: "blackmoonhowls",
+        })
+        .collect();
+
+    // IDs are in the same order as the specs
+    let user_ids = server
+        .create_users(&specs)
+        .await
+        .expect("Unable to create users");
+
+    assert_eq!(user_ids.len(), 3);
+
+    for (user_id, (name, _)) in user_ids.iter().zip(&names) {
+        let user = server
+            .get_user_from_id(*user_id)
+            .await
+            .expect("Unable to get user")
+            .expect("Created user not found");
+
+        assert_eq!(&user.name, name);
+    }
+
+    server
+        .try_login_id(user_ids[1], "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    // Failures roll back the whole batch
+    let (name_1, email_1) = generate_username();
+    let (name_2, email_2) = generate_username();
+
+    let specs = [
+        NewUserSpec {
+            name: &name_1,
+            email: &email_1,
+            password: "blackmoonhowls",
+        },
+        NewUserSpec {
+            name: &name_1,
+            email: &email_2,
+            password: "blackmoonhowls",
+        },
+    ];
+
+    let error = server
+        .create_users(&specs)
+        .await
+        .expect_err("Created users with duplicate names");
+
+    check_err!(error, Error::UserNameExists);
+
+    let user = server
+        .get_user_from_name(&name_1)
+        .await
+        .expect("Unable to get user");
+
+    assert!(user.is_none(), "User from failed batch was created");
+
+    let specs = [
+        NewUserSpec {
+            name: &name_1,
+            email: &email_1,
+            password: "blackmoonhowls",
+        },
+        NewUserSpec {
+            name: &name_2,
+            email: &email_2,
+            password: "a",
+        },
+    ];
+
+    let error = server
+        .create_users(&specs)
+        .await
+        .expect_err("Created user with weak password");
+
+    check_err!(error, Error::PasswordTooWeak(_));
+
+    let user = server
+        .get_user_from_name(&name_1)
+        .await
+        .expect("Unable to get user");
+
+    assert!(user.is_none(), "User from failed batch was created");
+
+    // Batch size is limited
+    let specs = vec![specs[0]; 101];
+    let error = server
+        .create_users(&specs)
+        .await
+        .expect_err("Created over 100 users");
+
+    check_err!(error, Error::RequestTooLarge(101, 100));
+}
+
+#[tokio::test]
+async fn users_resolve() {
+    let server = &create_server().await;
+    let (user_id, username, email) = create_user_full(server, "blackmoonhowls").await;
+
+    macro_rules! resolve {
+        ($credential:expr) => {
+            server
+                .resolve_user($credential)
+                .await
+                .expect("Unable to resolve user")
+                .map(|(user, kind)| (user.id(), kind))
+        };
+    }
+
+    assert_eq!(
+        resolve!(&username),
+        Some((user_id, CredentialKind::Username)),
+    );
+    assert_eq!(
+        resolve!(&username.to_uppercase()),
+        Some((user_id, CredentialKind::Username)),
+    );
+    assert_eq!(resolve!(&email), Some((user_id, CredentialKind::Email)));
+    assert_eq!(
+        resolve!(&format!(" {} ", email.to_uppercase())),
+        Some((user_id, CredentialKind::Email)),
+    );
+    assert_eq!(resolve!("nonexistent-user-for-resolve"), None);
+    assert_eq!(resolve!("nonexistent-user-for-resolve@example.com"), None);
+
+    // A name which looks like another user's email wins over it
+    let (_, other_email) = generate_username();
+    let email_user_id = server
+        .create_user(
+            &format!("{} email", rand_alphanum(8)),
+            &other_email,
+            "blackmoonhowls",
+        )
+        .await
+        .expect("Unable to create user");
+
+    assert_eq!(
+        resolve!(&other_email),
+        Some((email_user_id, CredentialKind::Email)),
+    );
+
+    let name_user_id = server
+        .create_user(&other_email, &format!("name-{}", email), "blackmoonhowls")
+        .await
+        .expect("Unable to create user");
+
+    assert_eq!(
+        resolve!(&other_email),
+        Some((name_user_id, CredentialKind::Username)),
+    );
+
+    // Logins use the same precedence
+    server
+        .try_login(&other_email, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    let attempts = server
+        .get_login_attempts(
+            name_user_id,
+            Utc::now() - Duration::hours(1),
+            Some(true),
+            10,
+            0,
+        )
+        .await
+        .expect("Unable to get login attempts");
+
+    assert_eq!(attempts.len(), 1);
+}
+
+#[tokio::test]
+async fn users_timestamps() {
+    let server = &create_server().await;
+    let (user_id, username, _) = create_user_full(server, "blackmoonhowls").await;
+
+    macro_rules! get_user {
+        () => {
+            server
+                .get_user_model_from_id(user_id)
+                .await
+                .expect("Unable to get user")
+                .expect("Created user not found")
+        };
+    }
+
+    let user = get_user!();
+    assert_eq!(user.created_at(), user.updated_at());
+
+    // Changes bump the time
     server
         .edit_user(
-            user_id_2,
+            user_id,
             UserMetadata {
-                email: Some("jim@example.net"),
+                about: Some("timestamps"),
                 ..UserMetadata::default()
             },
+            user_id,
         )
         .await
-        .expect("Unable to set email to equivalent value");
+        .expect("Unable to edit user");
 
-    // Set them both to the original values
+    let edited = get_user!();
+    assert_eq!(edited.created_at(), user.created_at());
+    assert!(edited.updated_at() > user.updated_at());
+
+    // Edits which don't change anything don't
     server
         .edit_user(
-            user_id_1,
+            user_id,
             UserMetadata {
-                name: Some(user_1.name()),
-                email: Some(user_1.email()),
+                about: Some("timestamps"),
                 ..UserMetadata::default()
             },
+            user_id,
         )
         .await
-        .expect("Unable to edit user to original");
+        .expect("Unable to edit user");
+
+    assert_eq!(get_user!().updated_at(), edited.updated_at());
 
     server
+        .verify_user(user_id)
+        .await
+        .expect("Unable to verify user");
+
+    let verified = get_user!();
+    assert!(verified.updated_at() > edited.updated_at());
+
+    // Carried by the other getters too
+    let user = server
+        .get_user_from_name(&username)
+        .await
+        .expect("Unable to get user")
+        .expect("Created user not found");
+
+    assert_eq!(user.updated_at(), verified.updated_at());
+
+    // Public views only have the creation time
+    let users = server
+        .search_users(&username, 10)
+        .await
+        .expect("Unable to search users");
+
+    let found = users
+        .iter()
+        .find(|user| user.id == user_id)
+        .expect("User not found in search");
+
+    assert_eq!(found.created_at, verified.created_at());
+}
+
+#[tokio::test]
+async fn users_invalid() {
+    let server = &create_server().await;
+    let (name, email) = generate_username();
+
+    // Names
+    let long_name = "a".repeat(65);
+    for &bad_name in &["", "   ", "\t\n", &long_name] {
+        let error = server
+            .create_user(bad_name, &email, "blackmoonhowls")
+            .await
+            .expect_err("Created user with invalid name");
+
+        check_err!(error, Error::InvalidUserName(_));
+    }
+
+    // Emails
+    let long_email = format!("{}@example.com", "a".repeat(250));
+    for &bad_email in &[
+        "",
+        "not-an-email",
+        "@example.com",
+        "user@",
+        "user@localhost",
+        "user@example.",
+        "user@.example.com",
+        "user@example..com",
+        "user@host@example.com",
+        "user name@example.com",
+        &long_email,
+    ] {
+        let error = server
+            .create_user(&name, bad_email, "blackmoonhowls")
+            .await
+            .expect_err("Created user with invalid email");
+
+        check_err!(error, Error::InvalidEmail(_));
+    }
+
+    let user = server
+        .get_user_from_name(&name)
+        .await
+        .expect("Unable to get user");
+
+    assert!(user.is_none(), "User with invalid fields was created");
+
+    // Checked in batches too, before anything is created
+    let (other_name, other_email) = generate_username();
+    let specs = [
+        NewUserSpec {
+            name: &other_name,
+            email: &other_email,
+            password: "blackmoonhowls",
+        },
+        NewUserSpec {
+            name: &name,
+            email: "not-an-email",
+            password: "blackmoonhowls",
+        },
+    ];
+
+    let error = server
+        .create_users(&specs)
+        .await
+        .expect_err("Created users with invalid email");
+
+    check_err!(error, Error::InvalidEmail(_));
+
+    let user = server
+        .get_user_from_name(&other_name)
+        .await
+        .expect("Unable to get user");
+
+    assert!(user.is_none(), "User from failed batch was created");
+
+    // And when registering
+    let error = server
+        .register_and_login(" ", &email, "blackmoonhowls", None, None)
+        .await
+        .expect_err("Registered user with invalid name");
+
+    check_err!(error, Error::InvalidUserName(_));
+
+    // Boundaries are allowed
+    let max_name = format!("{}{}", name, "a".repeat(64 - name.len()));
+    let user_id = server
+        .create_user(&max_name, &email, "blackmoonhowls")
+        .await
+        .expect("Unable to create user with longest name");
+
+    // Changes are checked the same way
+    let error = server
         .edit_user(
-            user_id_2,
+            user_id,
             UserMetadata {
-                name: Some(user_2.name()),
-                email: Some(user_2.email()),
+                name: Some(""),
                 ..UserMetadata::default()
             },
+            user_id,
         )
         .await
-        .expect("Unable to edit user initially");
+        .expect_err("Changed to invalid name");
+
+    check_err!(error, Error::InvalidUserName(_));
+
+    let error = server
+        .request_email_change(user_id, "not-an-email")
+        .await
+        .expect_err("Requested change to invalid email");
+
+    check_err!(error, Error::InvalidEmail(_));
+}
+
+#[tokio::test]
+async fn users_audit_log() {
+    let server = &create_server().await;
+    let (user_id, username, _) = create_user_full(server, "blackmoonhowls").await;
+    let (admin_id, admin_name, _) = create_user_full(server, "blackmoonhowls").await;
+
+    let log = server
+        .get_user_audit_log(user_id)
+        .await
+        .expect("Unable to get audit log");
+
+    assert!(log.is_empty(), "New user has audit log entries");
+
+    // Only fields which differ are logged
+    server
+        .edit_user(
+            user_id,
+            UserMetadata {
+                website: Some("https://example.com"),
+                about: Some(""),
+                gender: Some("Female"),
+                ..UserMetadata::default()
+            },
+            admin_id,
+        )
+        .await
+        .expect("Unable to edit user");
+
+    let log = server
+        .get_user_audit_log(user_id)
+        .await
+        .expect("Unable to get audit log");
+
+    assert_eq!(log.len(), 2, "Wrong number of audit log entries");
+    assert_eq!(log[0].field(), "website");
+    assert_eq!(log[0].old_value(), &json!(""));
+    assert_eq!(log[0].new_value(), &json!("https://example.com"));
+    assert_eq!(log[1].field(), "gender");
+    assert_eq!(log[1].new_value(), &json!("female"));
+
+    for entry in &log {
+        assert_eq!(entry.user_id(), user_id);
+        assert_eq!(entry.changed_by(), admin_id);
+    }
+
+    // Failed edits aren't logged
+    let error = server
+        .edit_user(
+            user_id,
+            UserMetadata {
+                name: Some(&admin_name),
+                ..UserMetadata::default()
+            },
+            user_id,
+        )
+        .await
+        .expect_err("Conflicted username edit succeeded");
+
+    check_err!(error, Error::UserNameExists);
+
+    // Neither are edits with no changes
+    server
+        .edit_user(
+            user_id,
+            UserMetadata {
+                name: Some(&username),
+                gender: Some("female"),
+                ..UserMetadata::default()
+            },
+            user_id,
+        )
+        .await
+        .expect("Unable to edit user");
+
+    let log = server
+        .get_user_audit_log(user_id)
+        .await
+        .expect("Unable to get audit log");
+
+    assert_eq!(log.len(), 2, "Unchanged fields were logged");
+}
+
+#[tokio::test]
+async fn users_timezone_locale() {
+    let server = &create_server().await;
+    let user_id = create_user(server).await;
+
+    macro_rules! get_user {
+        () => {
+            server
+                .get_user_model_from_id(user_id)
+                .await
+                .expect("Unable to get user")
+                .expect("Created user not found")
+        };
+    }
+
+    macro_rules! edit {
+        ($timezone:expr, $locale:expr) => {
+            server
+                .edit_user(
+                    user_id,
+                    UserMetadata {
+                        timezone: $timezone,
+                        locale: $locale,
+                        ..UserMetadata::default()
+                    },
+                    user_id,
+                )
+                .await
+        };
+    }
+
+    let user = get_user!();
+    assert_eq!(user.timezone(), "");
+    assert_eq!(user.locale(), "");
+
+    edit!(Some("America/New_York"), Some("en-US")).expect("Unable to set timezone and locale");
+
+    let user = get_user!();
+    assert_eq!(user.timezone(), "America/New_York");
+    assert_eq!(user.locale(), "en-US");
+
+    // Invalid values are refused, and nothing is changed
+    let error = edit!(Some("Mars/Olympus_Mons"), Some("fr")).expect_err("Allowed invalid timezone");
+    check_err!(error, Error::InvalidTimezone(_));
+
+    for &locale in &["e", "english", "en_US", "en-", "en-toolongsubtag"] {
+        let error = edit!(None, Some(locale)).expect_err("Allowed invalid locale");
+        check_err!(error, Error::InvalidLocale(_));
+    }
+
+    let user = get_user!();
+    assert_eq!(user.timezone(), "America/New_York");
+    assert_eq!(user.locale(), "en-US");
+
+    // Other valid forms
+    edit!(Some("UTC"), Some("zh-Hant-TW")).expect("Unable to change timezone and locale");
+
+    let user = get_user!();
+    assert_eq!(user.timezone(), "UTC");
+    assert_eq!(user.locale(), "zh-Hant-TW");
+
+    let log = server
+        .get_user_audit_log(user_id)
+        .await
+        .expect("Unable to get audit log");
+
+    let fields: Vec<_> = log.iter().map(|entry| entry.field()).collect();
+    assert_eq!(fields, vec!["timezone", "locale", "timezone", "locale"]);
+
+    // Empty strings clear them
+    edit!(Some(""), Some("")).expect("Unable to clear timezone and locale");
+
+    let user = get_user!();
+    assert_eq!(user.timezone(), "");
+    assert_eq!(user.locale(), "");
+}
+
+#[tokio::test]
+async fn users_export() {
+    let server = &create_server().await;
+    let (user_id, _, email) = create_user_full(server, "blackmoonhowls").await;
+
+    server
+        .try_login_id(user_id, "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    let session = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login");
+
+    server
+        .edit_user(
+            user_id,
+            UserMetadata {
+                about: Some("exported"),
+                ..UserMetadata::default()
+            },
+            user_id,
+        )
+        .await
+        .expect("Unable to edit user");
+
+    let export = server
+        .export_user_data(user_id)
+        .await
+        .expect("Unable to export user data");
+
+    assert_eq!(export.user().id(), user_id);
+    assert_eq!(export.user().email(), email.to_lowercase());
+    assert_eq!(export.user().about(), "exported");
+    assert_eq!(export.login_attempts().len(), 2);
+    assert!(export.login_attempts()[0].success());
+    assert!(!export.login_attempts()[1].success());
+    assert_eq!(export.sessions().len(), 1);
+    assert_eq!(
+        export.sessions()[0].login_attempt_id(),
+        session.login_attempt_id(),
+    );
+    assert_eq!(export.audit_log().len(), 1);
+
+    // Check the documented shape, and that no secrets are included
+    let json = serde_json::to_value(&export).expect("Unable to serialize export");
+    let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+    keys.sort();
+
+    assert_eq!(
+        keys,
+        vec![
+            "audit_log",
+            "exported_at",
+            "login_attempts",
+            "sessions",
+            "user"
+        ],
+    );
+
+    let text = json.to_string();
+    assert!(!text.contains(session.token()), "Session token exported");
+    assert!(!text.contains("hash"), "Hash exported");
+    assert!(!text.contains("password"), "Password exported");
+
+    let error = server
+        .export_user_data(UserId::from_raw(-1))
+        .await
+        .expect_err("Exported missing user");
+
+    check_err!(error, Error::UserNotFound);
+}
+
+#[tokio::test]
+async fn users_delete() {
+    let server = &create_server().await;
+    let (user_id, username, email) = create_user_full(server, "blackmoonhowls").await;
+    let (admin_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let address = Some("192.0.2.150".parse().unwrap());
+    let user_agent = Some("Mozilla/5.0 (X11; Linux x86_64)");
+
+    // Give the user data in most of the tables which refer to them
+    server
+        .try_login_id(user_id, "letmein", address, user_agent, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", address, user_agent, None)
+        .await
+        .expect("Unable to login");
+
+    // Attempts with their email while inactive aren't tied to the user
+    server
+        .mark_user_inactive(user_id)
+        .await
+        .expect("Unable to mark user inactive");
+
+    server
+        .try_login(&email, "blackmoonhowls", address, user_agent, None)
+        .await
+        .expect_err("Inactive user could log in");
+
+    server
+        .mark_user_active(user_id)
+        .await
+        .expect("Unable to mark user active");
+
+    // Someone else's attempt from the same address
+    let other_name = format!("{}-other", username);
+    server
+        .try_login(&other_name, "blackmoonhowls", address, user_agent, None)
+        .await
+        .expect_err("Unknown user could log in");
+
+    server
+        .enroll_totp(user_id)
+        .await
+        .expect("Unable to enroll in two-factor");
+
+    server
+        .request_email_change(user_id, &format!("{}-new@example.com", username))
+        .await
+        .expect("Unable to request email change");
+
+    server
+        .edit_user(
+            user_id,
+            UserMetadata {
+                about: Some("to be deleted"),
+                ..UserMetadata::default()
+            },
+            admin_id,
+        )
+        .await
+        .expect("Unable to edit user");
+
+    // The admin's edit blocks deleting them, but not the user it was for
+    let error = server
+        .delete_user(admin_id)
+        .await
+        .expect_err("Deleted user with references");
+
+    match error {
+        Error::UserReferenced(ref tables) => assert_eq!(tables, "user_audit_log"),
+        _ => panic!("Unexpected error: {}", error),
+    }
+
+    let attempt_ids: Vec<_> = server
+        .get_login_attempts_by_address(address, Utc::now() - Duration::hours(1), 10, 0)
+        .await
+        .expect("Unable to get login attempts")
+        .iter()
+        .map(|attempt| attempt.login_attempt_id())
+        .collect();
+
+    assert_eq!(attempt_ids.len(), 4);
+
+    server
+        .delete_user(user_id)
+        .await
+        .expect("Unable to delete user");
+
+    let user = server
+        .get_user_from_id(user_id)
+        .await
+        .expect("Unable to get user");
+
+    assert!(user.is_none(), "User still exists");
+
+    // Login attempts are kept, but not who they were for or where they came from
+    let mut anonymized = 0;
+    for &attempt_id in &attempt_ids {
+        let attempt = server
+            .get_login_attempt(attempt_id)
+            .await
+            .expect("Unable to get login attempt");
+
+        assert_eq!(attempt.user_id(), None);
+
+        if attempt.username_or_email() == Some(other_name.as_str()) {
+            assert_eq!(attempt.remote_address(), address);
+            assert_eq!(attempt.user_agent(), user_agent);
+        } else {
+            assert_eq!(attempt.username_or_email(), None);
+            assert_eq!(attempt.remote_address(), None);
+            assert_eq!(attempt.user_agent(), None);
+            anonymized += 1;
+        }
+    }
+
+    assert_eq!(anonymized, 3);
+
+    let error = server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Deleted user could log in");
+
+    check_err!(error, Error::AuthenticationFailed);
+
+    // Already gone
+    server
+        .delete_user(user_id)
+        .await
+        .expect("Deleting a missing user failed");
+
+    server
+        .delete_user(admin_id)
+        .await
+        .expect("Unable to delete admin");
+
+    // Contributions can't be removed
+    let (author_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+    let author = server
+        .get_user_model_from_id(author_id)
+        .await
+        .unwrap()
+        .expect("Created user not found");
+
+    let wiki_id = create_wiki(server).await;
+    let commit = PageCommit {
+        wiki_id,
+        slug: "deleted-author",
+        message: "new page",
+        user: &author,
+    };
+
+    server
+        .create_page(commit, "contents", &[], "", "")
+        .await
+        .expect("Unable to create page");
+
+    let error = server
+        .delete_user(author_id)
+        .await
+        .expect_err("Deleted user with references");
+
+    match error {
+        Error::UserReferenced(ref tables) => assert!(tables.contains("revisions")),
+        _ => panic!("Unexpected error: {}", error),
+    }
+
+    // Nothing was removed
+    server
+        .try_login_id(author_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login after failed delete");
 }
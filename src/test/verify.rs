@@ -19,6 +19,7 @@
  */
 
 use super::prelude::*;
+use chrono::Duration;
 
 #[tokio::test]
 async fn verify() {
@@ -32,7 +33,7 @@ async fn verify() {
         .expect("Couldn't find user")
         .expect("Created user not found");
 
-    assert_eq!(user.is_verified(), false, "User is verified on creation");
+    assert_eq!(user.is_verified, false, "User is verified on creation");
 
     let token = server
         .new_verification_token(user_id)
@@ -50,7 +51,7 @@ async fn verify() {
         .expect("Couldn't find user")
         .expect("Created user not found");
 
-    assert_eq!(user.is_verified(), true, "User is not verified after token");
+    assert_eq!(user.is_verified, true, "User is not verified after token");
 
     // Test verify_user
     let user_id = create_user(server).await;
@@ -60,7 +61,7 @@ async fn verify() {
         .expect("Couldn't find user")
         .expect("Created user not found");
 
-    assert_eq!(user.is_verified(), false, "User is verified on creation");
+    assert_eq!(user.is_verified, false, "User is verified on creation");
 
     server
         .verify_user(user_id)
@@ -73,5 +74,300 @@ async fn verify() {
         .expect("Couldn't find user")
         .expect("Created user not found");
 
-    assert_eq!(user.is_verified(), true, "User is not verified after token");
+    assert_eq!(user.is_verified, true, "User is not verified after token");
+}
+
+#[tokio::test]
+async fn verify_not_required() {
+    let server = &create_server().await;
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // Unverified users can login
+    server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login unverified user");
+}
+
+#[tokio::test]
+async fn verify_required() {
+    let server = &create_server_with(|config| {
+        config.verification.require_before_login = true;
+        config.verification.resend_token = false;
+    })
+    .await;
+
+    let (user_id, _, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // Wrong password is still an authentication failure
+    let error = server
+        .try_login_id(user_id, "letmein", None, None, None)
+        .await
+        .expect_err("Allowed invalid login");
+
+    match error {
+        Error::AuthenticationFailed => (),
+        _ => panic!("Error wasn't authentication failed"),
+    }
+
+    // Unverified users cannot login
+    let error = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Allowed unverified login");
+
+    match error {
+        Error::AccountNotVerified(None) => (),
+        _ => panic!("Error wasn't account not verified (without token)"),
+    }
+
+    // Verified users can
+    server
+        .verify_user(user_id)
+        .await
+        .expect("Unable to verify user directly");
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login verified user");
+}
+
+#[tokio::test]
+async fn verify_required_resend() {
+    let server = &create_server_with(|config| {
+        config.verification.require_before_login = true;
+        config.verification.resend_token = true;
+    })
+    .await;
+
+    let (user_id, username, _) = create_user_full(server, "blackmoonhowls").await;
+
+    // First refused login issues a token
+    let error = server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Allowed unverified login");
+
+    let token = match error {
+        Error::AccountNotVerified(Some(token)) => token,
+        _ => panic!("Error wasn't account not verified (with token)"),
+    };
+
+    // Another attempt right afterwards is rate limited
+    let error = server
+        .try_login(&username, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Allowed unverified login");
+
+    match error {
+        Error::AccountNotVerified(None) => (),
+        _ => panic!("Error wasn't account not verified (without token)"),
+    }
+
+    // The issued token works
+    server
+        .verify_token(&token)
+        .await
+        .expect("Unable to verify user with token");
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login verified user");
+}
+
+#[tokio::test]
+async fn register_not_required() {
+    let server = &create_server().await;
+    let (username, email) = generate_username();
+
+    let (user_id, response) = server
+        .register_and_login(&username, &email, "blackmoonhowls", None, None)
+        .await
+        .expect("Unable to register user");
+
+    let session = match response {
+        LoginResponse::Session(session) => session,
+        _ => panic!("Registration didn't return a session"),
+    };
+
+    assert_eq!(session.user_id(), user_id);
+
+    server
+        .check_session(session.token())
+        .await
+        .expect("Registration session is invalid");
+
+    let attempt_id = session.login_attempt_id().expect("No login attempt");
+    let attempt = server
+        .get_login_attempt(attempt_id)
+        .await
+        .expect("Unable to get login attempt");
+
+    assert!(attempt.success(), "Login attempt not marked successful");
+
+    // The password was set
+    server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login registered user");
+
+    // Name conflicts roll back the whole registration
+    let (_, other_email) = generate_username();
+    let error = server
+        .register_and_login(&username, &other_email, "blackmoonhowls", None, None)
+        .await
+        .expect_err("Allowed conflicting registration");
+
+    match error {
+        Error::UserNameExists => (),
+        _ => panic!("Error wasn't user name exists"),
+    }
+}
+
+#[tokio::test]
+async fn register_required() {
+    let server = &create_server_with(|config| {
+        config.verification.require_before_login = true;
+    })
+    .await;
+
+    let (username, email) = generate_username();
+    let (user_id, response) = server
+        .register_and_login(&username, &email, "blackmoonhowls", None, None)
+        .await
+        .expect("Unable to register user");
+
+    let token = match response {
+        LoginResponse::VerificationRequired(token) => token,
+        _ => panic!("Registration returned a session without verification"),
+    };
+
+    // Cannot login until verified
+    let error = server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect_err("Allowed unverified login");
+
+    match error {
+        Error::AccountNotVerified(_) => (),
+        _ => panic!("Error wasn't account not verified"),
+    }
+
+    server
+        .verify_token(&token)
+        .await
+        .expect("Unable to verify user with token");
+
+    server
+        .try_login_id(user_id, "blackmoonhowls", None, None, None)
+        .await
+        .expect("Unable to login verified user");
+}
+
+#[tokio::test]
+async fn email_change() {
+    let server = &create_server().await;
+    let (user_id, _, old_email) = create_user_full(server, "blackmoonhowls").await;
+    let (_, new_email) = generate_username();
+
+    let token = server
+        .request_email_change(user_id, &new_email)
+        .await
+        .expect("Unable to request email change");
+
+    // The old address still applies until confirmation
+    let user = server
+        .get_user_from_email(&old_email)
+        .await
+        .expect("Couldn't find user")
+        .expect("User not found by old email");
+
+    assert_eq!(user.id(), user_id);
+
+    let user = server
+        .get_user_from_email(&new_email)
+        .await
+        .expect("Couldn't find user");
+
+    assert!(user.is_none(), "User found by unconfirmed email");
+
+    server
+        .confirm_email_change(&token)
+        .await
+        .expect("Unable to confirm email change");
+
+    let user = server
+        .get_user_from_email(&new_email)
+        .await
+        .expect("Couldn't find user")
+        .expect("User not found by new email");
+
+    assert_eq!(user.id(), user_id);
+    assert!(user.is_verified(), "User not verified after email change");
+
+    let user = server
+        .get_user_from_email(&old_email)
+        .await
+        .expect("Couldn't find user");
+
+    assert!(user.is_none(), "User still found by old email");
+
+    // Tokens are single-use
+    let error = server
+        .confirm_email_change(&token)
+        .await
+        .expect_err("Confirmed email change twice");
+
+    match error {
+        Error::InvalidVerificationToken => (),
+        _ => panic!("Error wasn't invalid verification token"),
+    }
+
+    // Addresses in use can't be requested
+    let (_, _, other_email) = create_user_full(server, "blackmoonhowls").await;
+    let error = server
+        .request_email_change(user_id, &other_email)
+        .await
+        .expect_err("Requested email of another user");
+
+    match error {
+        Error::UserEmailExists => (),
+        _ => panic!("Error wasn't user email exists"),
+    }
+}
+
+#[tokio::test]
+async fn email_change_expiry() {
+    let server = &create_server_with(|config| {
+        config.verification.email_change_ttl = Duration::zero();
+    })
+    .await;
+
+    let (user_id, _, old_email) = create_user_full(server, "blackmoonhowls").await;
+    let (_, new_email) = generate_username();
+
+    let token = server
+        .request_email_change(user_id, &new_email)
+        .await
+        .expect("Unable to request email change");
+
+    let error = server
+        .confirm_email_change(&token)
+        .await
+        .expect_err("Confirmed expired email change");
+
+    match error {
+        Error::InvalidVerificationToken => (),
+        _ => panic!("Error wasn't invalid verification token"),
+    }
+
+    let user = server
+        .get_user_model_from_id(user_id)
+        .await
+        .expect("Couldn't find user")
+        .expect("Created user not found");
+
+    assert_eq!(user.email(), old_email.to_lowercase());
 }
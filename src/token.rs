@@ -0,0 +1,103 @@
+/*
+ * token.rs
+ *
+ * deepwell - Database management and migrations service
+ * Copyright (C) 2019-2020 Ammon Smith
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+// Secret tokens handed out to clients, such as for sessions and password resets.
+//
+// A token is a public selector, used to find the row, followed by
+// a secret verifier, which is only stored hashed. This way lookups
+// are by an indexed column, but the secret itself is still compared
+// in constant time.
+
+use crate::utils::rand_alphanum;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+use std::fmt::{self, Debug};
+
+const SELECTOR_LEN: usize = 16;
+const VERIFIER_LEN: usize = 48;
+
+pub type TokenHash = [u8; 32];
+
+/// A freshly generated token, along with the hash to store for it.
+pub struct NewToken {
+    token: String,
+    hash: TokenHash,
+}
+
+impl NewToken {
+    pub fn generate() -> Self {
+        let token = rand_alphanum(SELECTOR_LEN + VERIFIER_LEN);
+        let hash = hash_verifier(&token[SELECTOR_LEN..]);
+
+        NewToken { token, hash }
+    }
+
+    #[inline]
+    pub fn selector(&self) -> &str {
+        &self.token[..SELECTOR_LEN]
+    }
+
+    #[inline]
+    pub fn hash(&self) -> &[u8] {
+        &self.hash
+    }
+
+    #[inline]
+    pub fn into_token(self) -> String {
+        self.token
+    }
+}
+
+impl Debug for NewToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NewToken")
+            .field("selector", &self.selector())
+            .field("token", &"<secret>")
+            .finish()
+    }
+}
+
+/// Splits a token from a client into its selector and verifier.
+/// Returns `None` if it is malformed.
+pub fn split_token(token: &str) -> Option<(&str, &str)> {
+    if token.len() != SELECTOR_LEN + VERIFIER_LEN || !token.is_ascii() {
+        return None;
+    }
+
+    Some(token.split_at(SELECTOR_LEN))
+}
+
+/// Checks the verifier from a client against the stored hash.
+#[inline]
+pub fn check_verifier(stored: &[u8], verifier: &str) -> bool {
+    fixed_time_eq(stored, &hash_verifier(verifier))
+}
+
+/// Verifiers are long random strings, unlike passwords,
+/// so a single round of SHA-256 is sufficient.
+fn hash_verifier(verifier: &str) -> TokenHash {
+    let mut hasher = Sha256::new();
+    let mut hash = [0; 32];
+
+    hasher.input(verifier.as_bytes());
+    hasher.result(&mut hash);
+    hash
+}
@@ -18,17 +18,51 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use diesel::sql_types::Text;
+use crate::{Error, Result};
+use diesel::sql_types::{Nullable, Text};
+use std::net::IpAddr;
 
 sql_function!(fn lower(val: Text) -> Text);
 sql_function!(fn upper(val: Text) -> Text);
+sql_function!(#[sql_name = "lower"] fn lower_nullable(val: Nullable<Text>) -> Nullable<Text>);
 
-pub fn rows_to_result(rows_deleted: usize) -> bool {
+pub fn rows_to_result(rows_deleted: usize) -> Result<bool> {
     match rows_deleted {
-        0 => false,
-        1 => true,
-        _ => panic!("Multiple rows deleted in primary key removal"),
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => {
+            error!(
+                "Multiple rows ({}) deleted in primary key removal",
+                rows_deleted
+            );
+
+            Err(Error::StaticMsg(
+                "multiple rows deleted in primary key removal",
+            ))
+        }
+    }
+}
+
+pub fn display_address(address: Option<IpAddr>) -> String {
+    match address {
+        Some(address) => address.to_string(),
+        None => "<unknown>".into(),
+    }
+}
+
+/// Escapes the wildcard characters in a string for use in a `LIKE` pattern.
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for ch in value.chars() {
+        if let '\\' | '%' | '_' = ch {
+            escaped.push('\\');
+        }
+
+        escaped.push(ch);
     }
+
+    escaped
 }
 
 pub fn rand_alphanum(len: usize) -> String {